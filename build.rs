@@ -0,0 +1,14 @@
+fn main() {
+    // the server of --grpc, and a client for its tests, with the protoc of
+    // protoc-bin-vendored so that none needs to be installed
+    #[cfg(feature = "grpc")]
+    {
+        let mut config = tonic_prost_build::Config::new();
+        config.protoc_executable(protoc_bin_vendored::protoc_bin_path().unwrap());
+        tonic_prost_build::configure()
+            .client_mod_attribute("json2jsonl", "#[cfg(test)]")
+            .compile_with_config(config, &["proto/json2jsonl.proto"], &["proto"])
+            .unwrap();
+    }
+    println!("cargo::rerun-if-changed=build.rs");
+}
@@ -0,0 +1,68 @@
+/*
+ * The C API of json2jsonl, in the library built with
+ * `cargo build --release --features json2jsonl-capi` (libjson2jsonl.so).
+ *
+ * Functions return a negative value on failure, and the message of the
+ * error is then available from j2j_last_error() on the same thread.
+ */
+#ifndef JSON2JSONL_H
+#define JSON2JSONL_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The conversion options. A NULL pointer stands for the defaults, all zero. */
+typedef struct j2j_options {
+    /* accept JSON5 syntax: single-quoted strings, comments and a trailing comma */
+    bool json5;
+    /* strip // and block comments, both between and inside elements */
+    bool comments;
+    /* accept (and drop) a comma before a closing ] or } */
+    bool trailing_commas;
+    /* reject elements nested deeper than this, 0 for no limit */
+    uint32_t max_depth;
+} j2j_options;
+
+/*
+ * Convert the JSON array read from in_fd (gzipped or not) to JSONL written
+ * to out_fd, and return the number of records, or -1 on failure. The
+ * descriptors are left open.
+ */
+int64_t j2j_convert_fd(int in_fd, int out_fd, const j2j_options *options);
+
+/* A conversion fed with chunks of the input. */
+typedef struct j2j_converter j2j_converter;
+
+/* Start a conversion fed with j2j_converter_push(), to be freed with j2j_converter_free(). */
+j2j_converter *j2j_converter_new(const j2j_options *options);
+
+/*
+ * Convert the len bytes at chunk, the next ones of the input. The JSONL
+ * records of the elements they complete (possibly none) are at *out, for
+ * *out_len bytes, until the next call. Returns 0, or -1 on failure.
+ */
+int j2j_converter_push(j2j_converter *converter, const uint8_t *chunk, size_t len,
+                       const uint8_t **out, size_t *out_len);
+
+/*
+ * Convert the rest of the input once all of it has been pushed, like
+ * j2j_converter_push(). Fails if the array isn't complete.
+ */
+int j2j_converter_finish(j2j_converter *converter, const uint8_t **out, size_t *out_len);
+
+/* Free a converter, and the records it returned. */
+void j2j_converter_free(j2j_converter *converter);
+
+/* The message of the last error on this thread, or NULL if there was none. */
+const char *j2j_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
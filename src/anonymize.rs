@@ -0,0 +1,283 @@
+use crate::template;
+use anyhow::{Context, bail};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+/// What to do with a field.
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    /// remove it
+    Drop,
+    /// replace it with the hex SHA-256 of the salt and its value, the same
+    /// for the same value, so that records can still be joined on it
+    Hash,
+    /// replace all but the last 4 characters with `*`
+    MaskLast4,
+    /// keep only the year and month of a date, `YYYY-MM`
+    Month,
+    /// replace it with this value
+    Replace(Value),
+}
+
+/// Anonymizes the records by a set of rules, one per field, for --anonymize.
+///
+/// The rules are a YAML file, with the action for each dotted path into the
+/// records under `fields`, and an optional `salt` for the hashes:
+///
+/// ```yaml
+/// salt: change me
+/// fields:
+///   ssn: drop
+///   user.email: hash
+///   card: mask-last-4
+///   birth_date: month
+///   name: { replace: REDACTED }
+/// ```
+///
+/// or the same as a TOML file, with a `[fields]` table, if its name ends
+/// with `.toml`.
+///
+/// A value that an action can't apply to, such as a date that isn't one,
+/// becomes null rather than going through as it is.
+#[derive(Debug, Clone)]
+pub struct Anonymizer {
+    salt: String,
+    rules: Vec<(Vec<String>, Action)>,
+}
+
+impl Anonymizer {
+    /// Read the rules at `path`, TOML if it ends with `.toml` and YAML
+    /// otherwise.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Self::parse_toml(&text),
+            _ => Self::parse(&text),
+        }
+        .with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// Parse YAML rules in the format of [`Anonymizer`].
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        Self::from_value(serde_yaml::from_str(text)?)
+    }
+
+    /// Parse TOML rules in the format of [`Anonymizer`].
+    pub fn parse_toml(text: &str) -> anyhow::Result<Self> {
+        Self::from_value(to_json(toml::Value::Table(text.parse()?)))
+    }
+
+    fn from_value(value: Value) -> anyhow::Result<Self> {
+        let Value::Object(entries) = value else {
+            bail!("expected `salt` and `fields`");
+        };
+        let mut salt = String::new();
+        let mut rules = vec![];
+        for (key, value) in entries {
+            match (key.as_str(), value) {
+                ("salt", Value::String(s)) => salt = s,
+                ("fields", Value::Object(fields)) => {
+                    for (path, action) in fields {
+                        let action = match action {
+                            Value::String(action) => match action.as_str() {
+                                "drop" => Action::Drop,
+                                "hash" => Action::Hash,
+                                "mask-last-4" => Action::MaskLast4,
+                                "month" => Action::Month,
+                                _ => bail!(
+                                    "{path}: unknown action `{action}` (drop, hash, \
+                                     mask-last-4, month, or a `replace` with a value)"
+                                ),
+                            },
+                            Value::Object(mut t) if t.len() == 1 && t.contains_key("replace") => {
+                                Action::Replace(t.remove("replace").unwrap())
+                            }
+                            _ => bail!("{path}: expected an action"),
+                        };
+                        rules.push((path.split('.').map(String::from).collect(), action));
+                    }
+                }
+                (key, _) => bail!("unexpected `{key}`, expected `salt` or `fields`"),
+            }
+        }
+        Ok(Self { salt, rules })
+    }
+
+    /// Apply the rules to `record`, in place.
+    pub fn apply(&self, record: &mut Value) {
+        for (path, action) in &self.rules {
+            if *action == Action::Drop {
+                template::remove(record, path);
+            } else if let Some(value) = template::lookup_mut(record, path) {
+                *value = self.anonymize(action, value);
+            }
+        }
+    }
+
+    fn anonymize(&self, action: &Action, value: &Value) -> Value {
+        if let Action::Replace(replacement) = action {
+            return replacement.clone();
+        }
+        let text = match value {
+            Value::String(s) => s.clone(),
+            Value::Null => return Value::Null,
+            value => value.to_string(),
+        };
+        match action {
+            Action::Drop | Action::Replace(_) => Value::Null,
+            Action::Hash => {
+                let mut sha256 = Sha256::new();
+                sha256.update(self.salt.as_bytes());
+                sha256.update(text.as_bytes());
+                Value::String(format!("{:x}", sha256.finalize()))
+            }
+            Action::MaskLast4 => {
+                let len = text.chars().count();
+                let masked = (text.chars().enumerate())
+                    .map(|(i, c)| if len > 4 && i >= len - 4 { c } else { '*' })
+                    .collect();
+                Value::String(masked)
+            }
+            Action::Month => {
+                let b = text.as_bytes();
+                let date = b.len() >= 7
+                    && b[..4].iter().all(u8::is_ascii_digit)
+                    && b[4] == b'-'
+                    && b[5..7].iter().all(u8::is_ascii_digit)
+                    && value.is_string();
+                match date {
+                    true => Value::String(text[..7].to_string()),
+                    false => Value::Null,
+                }
+            }
+        }
+    }
+}
+
+fn to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(n) => n.into(),
+        toml::Value::Float(f) => f.into(),
+        toml::Value::Boolean(b) => b.into(),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(values) => Value::Array(values.into_iter().map(to_json).collect()),
+        toml::Value::Table(t) => Value::Object(
+            t.into_iter()
+                .map(|(k, v)| (k, to_json(v)))
+                .collect::<Map<_, _>>(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const RULES: &str = "\
+salt: pepper
+fields:
+  ssn: drop
+  user.email: hash
+  card: mask-last-4
+  birth_date: month
+  name: { replace: REDACTED }
+  user.id: { replace: [0, { x: null }] }
+";
+
+    fn sha256(text: &str) -> String {
+        format!("{:x}", Sha256::digest(text.as_bytes()))
+    }
+
+    fn record() -> Value {
+        json!({
+            "ssn": "123-45-6789",
+            "user": {"email": "a@b.c", "id": 42},
+            "card": "4111111111111111",
+            "birth_date": "1990-07-14",
+            "name": "Ada",
+            "other": 1,
+        })
+    }
+
+    fn anonymized() -> Value {
+        json!({
+            "user": {"email": sha256("peppera@b.c"), "id": [0, {"x": null}]},
+            "card": "************1111",
+            "birth_date": "1990-07",
+            "name": "REDACTED",
+            "other": 1,
+        })
+    }
+
+    #[test]
+    fn yaml_rules() {
+        let anonymizer = Anonymizer::parse(RULES).unwrap();
+        let mut record = record();
+        anonymizer.apply(&mut record);
+        assert_eq!(record, anonymized());
+    }
+
+    #[test]
+    fn values_the_actions_dont_apply_to() {
+        let anonymizer = Anonymizer::parse(RULES).unwrap();
+        let mut record = json!({"card": "123", "birth_date": "July 1990", "user": {"email": null}});
+        anonymizer.apply(&mut record);
+        assert_eq!(
+            record,
+            json!({"card": "***", "birth_date": null, "user": {"email": null}})
+        );
+    }
+
+    #[test]
+    fn files_by_extension() {
+        let dir = std::env::temp_dir().join(format!("json2jsonl-anonymize-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let toml = "\
+salt = \"pepper\"
+
+[fields]
+ssn = \"drop\"
+\"user.email\" = \"hash\"
+card = \"mask-last-4\"
+birth_date = \"month\"
+name = { replace = \"REDACTED\" }
+\"user.id\" = { replace = [0, { x = {} }] }
+";
+        for (name, text) in [
+            ("rules.yaml", RULES),
+            ("rules.yml", RULES),
+            ("rules.toml", toml),
+        ] {
+            fs::write(dir.join(name), text).unwrap();
+            let anonymizer = Anonymizer::load(&dir.join(name)).unwrap();
+            let mut record = record();
+            anonymizer.apply(&mut record);
+            let mut expected = anonymized();
+            if name == "rules.toml" {
+                // there is no null in TOML
+                expected["user"]["id"][1]["x"] = json!({});
+            }
+            assert_eq!(record, expected, "{name}");
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_rules() {
+        for rules in [
+            "fields: { a: erase }",
+            "fields: { a: 1 }",
+            "fields: { a: { replace: 1, with: 2 } }",
+            "pepper: x",
+            "- drop",
+            "fields: [",
+        ] {
+            assert!(Anonymizer::parse(rules).is_err(), "{rules}");
+        }
+    }
+}
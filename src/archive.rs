@@ -0,0 +1,241 @@
+use anyhow::Context;
+use flate2::read::MultiGzDecoder;
+use glob::Pattern;
+use std::io::{Read, Seek};
+
+/// Archive formats of the input.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Kind {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+/// The name of the input, without the query string of a URL.
+fn file_name(input: &str) -> &str {
+    match input.split_once("://") {
+        Some(_) => input.split(['?', '#']).next().unwrap_or(input),
+        None => input,
+    }
+}
+
+/// Is the input an archive, judging from its name?
+pub fn kind(input: &str) -> Option<Kind> {
+    let name = file_name(input);
+    if name.ends_with(".tar") {
+        Some(Kind::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Kind::TarGz)
+    } else if name.ends_with(".zip") {
+        Some(Kind::Zip)
+    } else {
+        None
+    }
+}
+
+/// Passes the members matching a pattern on to the callback, with their
+/// path and (uncompressed) size.
+struct Members<'a, F> {
+    input: &'a str,
+    pattern: &'a Pattern,
+    f: F,
+    matched: bool,
+}
+
+impl<'a, F: FnMut(&str, u64, &mut dyn Read) -> anyhow::Result<()>> Members<'a, F> {
+    fn new(input: &'a str, pattern: &'a Pattern, f: F) -> Self {
+        Self {
+            input,
+            pattern,
+            f,
+            matched: false,
+        }
+    }
+
+    fn visit(
+        &mut self,
+        path: &str,
+        is_file: bool,
+        size: u64,
+        member: &mut dyn Read,
+    ) -> anyhow::Result<()> {
+        if !is_file || !self.pattern.matches(path) {
+            return Ok(());
+        }
+        self.matched = true;
+        (self.f)(path, size, member).with_context(|| format!("in member {path}"))
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        if !self.matched {
+            anyhow::bail!(
+                "no member of {} matches {}",
+                self.input,
+                self.pattern.as_str()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Call `f` on each regular file of the archive read from `rd` whose path
+/// matches `pattern`, in the order of the archive.
+///
+/// Members are streamed from the archive, nothing is extracted to disk. Zip
+/// members are found from their local headers, which only works for
+/// archives whose writer recorded the member sizes there; use
+/// [`for_each_zip_member`] when the archive can be seeked.
+pub fn for_each_member(
+    kind: Kind,
+    input: &str,
+    mut rd: impl Read,
+    pattern: &Pattern,
+    f: impl FnMut(&str, u64, &mut dyn Read) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut members = Members::new(input, pattern, f);
+    let rd: Box<dyn Read + '_> = match kind {
+        Kind::Tar => Box::new(rd),
+        Kind::TarGz => Box::new(MultiGzDecoder::new(rd)),
+        Kind::Zip => {
+            while let Some(mut file) =
+                zip::read::read_zipfile_from_stream(&mut rd).context("reading zip archive")?
+            {
+                let path = file.name()?.into_owned();
+                members.visit(&path, file.is_file(), file.size(), &mut file)?;
+            }
+            return members.finish();
+        }
+    };
+    let mut archive = tar::Archive::new(rd);
+    for entry in archive.entries().context("reading tar archive")? {
+        let mut entry = entry.context("reading tar archive")?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+        let is_file = entry.header().entry_type().is_file();
+        members.visit(&path, is_file, entry.size(), &mut entry)?;
+    }
+    members.finish()
+}
+
+/// Call `f` on each regular file of the zip archive `rd` whose path matches
+/// `pattern`, using the central directory at the end of the archive.
+pub fn for_each_zip_member(
+    input: &str,
+    rd: impl Read + Seek,
+    pattern: &Pattern,
+    f: impl FnMut(&str, u64, &mut dyn Read) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut members = Members::new(input, pattern, f);
+    let mut archive = zip::ZipArchive::new(rd).context("reading zip archive")?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).context("reading zip archive")?;
+        let path = file.name()?.into_owned();
+        members.visit(&path, file.is_file(), file.size(), &mut file)?;
+    }
+    members.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    type Found = Vec<(String, u64, String)>;
+
+    /// The path, size and contents of the members matching `pattern`, read
+    /// with `read`.
+    fn members(
+        pattern: &str,
+        read: impl FnOnce(
+            &Pattern,
+            &mut dyn FnMut(&str, u64, &mut dyn Read) -> anyhow::Result<()>,
+        ) -> anyhow::Result<()>,
+    ) -> anyhow::Result<Found> {
+        let mut found = vec![];
+        read(
+            &Pattern::new(pattern).unwrap(),
+            &mut |path, size, member| {
+                let mut contents = String::new();
+                member.read_to_string(&mut contents)?;
+                found.push((path.to_string(), size, contents));
+                Ok(())
+            },
+        )?;
+        Ok(found)
+    }
+
+    fn tar() -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, contents) in [("a.json", "[1]"), ("b.txt", "x"), ("dir/c.json", "[2,3]")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(kind("a.tar"), Some(Kind::Tar));
+        assert_eq!(kind("a.tgz"), Some(Kind::TarGz));
+        assert_eq!(kind("https://host/a.zip?token=1"), Some(Kind::Zip));
+        assert_eq!(kind("a.zip.json"), None);
+    }
+
+    #[test]
+    fn tar_members() {
+        let tar = tar();
+        let found = members("*.json", |pattern, f| {
+            for_each_member(Kind::Tar, "in.tar", &tar[..], pattern, f)
+        });
+        assert_eq!(
+            found.unwrap(),
+            [
+                ("a.json".into(), 3, "[1]".into()),
+                ("dir/c.json".into(), 5, "[2,3]".into()),
+            ]
+        );
+        let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::fast());
+        gz.write_all(&tar).unwrap();
+        let gz = gz.finish().unwrap();
+        let found = members("b.*", |pattern, f| {
+            for_each_member(Kind::TarGz, "in.tgz", &gz[..], pattern, f)
+        });
+        assert_eq!(found.unwrap(), [("b.txt".into(), 1, "x".into())]);
+        let err = members("*.csv", |pattern, f| {
+            for_each_member(Kind::Tar, "in.tar", &tar[..], pattern, f)
+        });
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "no member of in.tar matches *.csv"
+        );
+    }
+
+    #[test]
+    fn zip_members() {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        let options = zip::write::SimpleFileOptions::default();
+        zip.add_directory("dir/", options).unwrap();
+        for (path, contents) in [("dir/a.json", "[1]"), ("b.json", "[2]"), ("c.txt", "x")] {
+            zip.start_file(path, options).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        let zip = zip.finish().unwrap().into_inner();
+        let expected = [
+            ("dir/a.json".to_string(), 3, "[1]".to_string()),
+            ("b.json".to_string(), 3, "[2]".to_string()),
+        ];
+        // from the local headers, and from the central directory
+        let found = members("*.json", |pattern, f| {
+            for_each_member(Kind::Zip, "in.zip", &zip[..], pattern, f)
+        });
+        assert_eq!(found.unwrap(), expected);
+        let found = members("*.json", |pattern, f| {
+            for_each_zip_member("in.zip", std::io::Cursor::new(&zip), pattern, f)
+        });
+        assert_eq!(found.unwrap(), expected);
+    }
+}
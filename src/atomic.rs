@@ -0,0 +1,79 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A file written under a temporary name next to its final path, and renamed
+/// to it once complete, so that there is never a partial file at the final
+/// path. The temporary file is removed if dropped before [`persist`].
+///
+/// [`persist`]: AtomicFile::persist
+pub struct AtomicFile {
+    tmp: PathBuf,
+    path: PathBuf,
+    done: bool,
+}
+
+impl AtomicFile {
+    /// Create the temporary file for `path`, which gets the permissions of
+    /// the file at `like` if there is one (usually `path` itself).
+    pub fn create(path: &Path, like: &Path) -> io::Result<(Self, File)> {
+        let mut name = OsString::from(path.file_name().unwrap_or_default());
+        name.push(format!(".tmp.{}", std::process::id()));
+        let tmp = path.with_file_name(name);
+        let file = File::create(&tmp)?;
+        let atomic = Self {
+            tmp,
+            path: path.to_path_buf(),
+            done: false,
+        };
+        if let Ok(meta) = fs::metadata(like) {
+            file.set_permissions(meta.permissions())?;
+        }
+        Ok((atomic, file))
+    }
+
+    /// Rename the temporary file to the final path, once everything has
+    /// been written to it.
+    pub fn persist(mut self) -> io::Result<()> {
+        fs::rename(&self.tmp, &self.path)?;
+        self.done = true;
+        Ok(())
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn persist_or_drop() {
+        let dir = std::env::temp_dir().join(format!("json2jsonl-atomic-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.jsonl");
+        fs::write(&path, "old\n").unwrap();
+        let (atomic, mut file) = AtomicFile::create(&path, &path).unwrap();
+        file.write_all(b"new\n").unwrap();
+        // the old file until renamed
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old\n");
+        let tmp = dir.join(format!("out.jsonl.tmp.{}", std::process::id()));
+        assert!(tmp.exists());
+        atomic.persist().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        let (atomic, mut file) = AtomicFile::create(&path, &path).unwrap();
+        file.write_all(b"partial").unwrap();
+        drop(atomic);
+        assert!(!tmp.exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
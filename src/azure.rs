@@ -0,0 +1,134 @@
+use anyhow::{Context, bail};
+use std::io::{self, Write};
+
+/// Size of the blocks the blob is uploaded in. A blob has at most 50,000
+/// blocks, which makes for 400 GB.
+const BLOCK: usize = 8 * 1024 * 1024;
+
+const VERSION: &str = "2021-08-06";
+
+/// Is the argument an Azure blob, as `az://container/path` or a
+/// `https://<account>.blob.core.windows.net/...` URL?
+pub fn is_uri(s: &str) -> bool {
+    s.starts_with("az://")
+        || s.strip_prefix("https://")
+            .and_then(|s| s.split('/').next())
+            .is_some_and(|host| host.ends_with(".blob.core.windows.net"))
+}
+
+/// The HTTPS URL of the blob, with the SAS token from
+/// `AZURE_STORAGE_SAS_TOKEN` if the URL doesn't carry one already.
+///
+/// `az://` URIs are resolved in the account `AZURE_STORAGE_ACCOUNT`, or
+/// against `AZURE_STORAGE_BLOB_ENDPOINT` (e.g. an Azurite emulator).
+fn url(uri: &str) -> anyhow::Result<String> {
+    let env = |name| std::env::var(name).ok().filter(|v| !v.is_empty());
+    let mut url = match uri.strip_prefix("az://") {
+        Some(path) => {
+            if !path
+                .split_once('/')
+                .is_some_and(|(c, p)| !c.is_empty() && !p.is_empty())
+            {
+                bail!("expected az://container/path, got {uri:?}");
+            }
+            let endpoint = match env("AZURE_STORAGE_BLOB_ENDPOINT") {
+                Some(endpoint) => endpoint.trim_end_matches('/').to_string(),
+                None => {
+                    let account = env("AZURE_STORAGE_ACCOUNT")
+                        .context("az:// URIs need AZURE_STORAGE_ACCOUNT to be set")?;
+                    format!("https://{account}.blob.core.windows.net")
+                }
+            };
+            format!("{endpoint}/{path}")
+        }
+        None => uri.to_string(),
+    };
+    if !url.contains('?')
+        && let Some(sas) = env("AZURE_STORAGE_SAS_TOKEN")
+    {
+        url.push('?');
+        url.push_str(sas.trim_start_matches('?'));
+    }
+    Ok(url)
+}
+
+/// The URL of the blob, with the headers to send to read it.
+pub fn download(uri: &str) -> anyhow::Result<(String, Vec<(String, String)>)> {
+    Ok((
+        url(uri)?,
+        vec![("x-ms-version".to_string(), VERSION.to_string())],
+    ))
+}
+
+/// Append a query parameter to `url`.
+fn with_param(url: &str, param: &str) -> String {
+    let sep = if url.contains('?') { '&' } else { '?' };
+    format!("{url}{sep}{param}")
+}
+
+/// Writes a block blob, sent block by block as data comes.
+///
+/// The blob only changes once [`Upload::finish`] commits the blocks.
+pub struct Upload {
+    agent: ureq::Agent,
+    url: String,
+    buf: Vec<u8>,
+    blocks: Vec<String>,
+}
+
+impl Upload {
+    pub fn create(uri: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            agent: ureq::Agent::new_with_defaults(),
+            url: url(uri)?,
+            buf: Vec::with_capacity(BLOCK),
+            blocks: vec![],
+        })
+    }
+
+    fn put_block(&mut self) -> anyhow::Result<()> {
+        // IDs must be base64 strings of the same length: decimal digits are
+        // part of the alphabet, and 8 of them make valid base64
+        let id = format!("{:08}", self.blocks.len());
+        let url = with_param(&self.url, &format!("comp=block&blockid={id}"));
+        self.agent
+            .put(&url)
+            .header("x-ms-version", VERSION)
+            .send(&self.buf[..])?;
+        self.blocks.push(id);
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Upload the remaining data and commit the blob.
+    pub fn finish(&mut self) -> anyhow::Result<()> {
+        if !self.buf.is_empty() {
+            self.put_block()?;
+        }
+        let mut list = String::from(r#"<?xml version="1.0" encoding="utf-8"?><BlockList>"#);
+        for id in &self.blocks {
+            list.push_str(&format!("<Latest>{id}</Latest>"));
+        }
+        list.push_str("</BlockList>");
+        self.agent
+            .put(&with_param(&self.url, "comp=blocklist"))
+            .header("x-ms-version", VERSION)
+            .send(list.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Write for Upload {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = data.len().min(BLOCK - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        if self.buf.len() == BLOCK {
+            self.put_block().map_err(io::Error::other)?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use std::io::{self, Write};
+
+/// Input of a block at most, so that even stored uncompressed it fits in
+/// the 64KiB a block can take.
+const MAX_INPUT: usize = 0xFF00;
+
+/// The empty block ending a BGZF file.
+const EOF: &[u8] = b"\x1F\x8B\x08\x04\0\0\0\0\0\xFF\x06\0BC\x02\0\x1B\0\x03\0\0\0\0\0\0\0\0\0";
+
+/// Compresses to BGZF, the blocked gzip of `bgzip` and htslib: a series of
+/// gzip members of at most 64KiB, each giving its size in an extra field,
+/// so that readers can index the file and decompress any block on its own.
+///
+/// Blocks end with a record, at a call to [`end_record`](Self::end_record),
+/// except within records too large for a single block.
+pub struct BgzfWriter<W: Write> {
+    inner: W,
+    /// input of the block being built
+    buf: Vec<u8>,
+    /// where the record being written starts in `buf`
+    record_start: usize,
+}
+
+impl<W: Write> BgzfWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(MAX_INPUT),
+            record_start: 0,
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Mark the end of a record, which the next block may start after.
+    pub fn end_record(&mut self) -> io::Result<()> {
+        self.record_start = self.buf.len();
+        if self.buf.len() == MAX_INPUT {
+            self.write_block(MAX_INPUT)?;
+        }
+        Ok(())
+    }
+
+    /// Write the first `len` bytes of `buf` as a block.
+    fn write_block(&mut self, len: usize) -> io::Result<()> {
+        let data = &self.buf[..len];
+        let mut deflated = deflate(data, Compression::default())?;
+        if deflated.len() > MAX_INPUT {
+            deflated = deflate(data, Compression::none())?;
+        }
+        let mut crc = Crc::new();
+        crc.update(data);
+        let size = (deflated.len() + 25) as u16;
+        let mut header = *b"\x1F\x8B\x08\x04\0\0\0\0\0\xFF\x06\0BC\x02\0\0\0";
+        header[16..].copy_from_slice(&size.to_le_bytes());
+        self.inner.write_all(&header)?;
+        self.inner.write_all(&deflated)?;
+        self.inner.write_all(&crc.sum().to_le_bytes())?;
+        self.inner.write_all(&(len as u32).to_le_bytes())?;
+        self.buf.drain(..len);
+        self.record_start = self.record_start.saturating_sub(len);
+        Ok(())
+    }
+
+    /// Write the last block and the end of file marker, and flush the
+    /// output.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.write_block(self.buf.len())?;
+        }
+        self.inner.write_all(EOF)?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        while self.buf.len() > MAX_INPUT {
+            // end the block before the record if there is one before it,
+            // or else split the record
+            match self.record_start {
+                0 => self.write_block(MAX_INPUT)?,
+                start => self.write_block(start)?,
+            }
+        }
+        Ok(buf.len())
+    }
+
+    // blocks only end with records, there is nothing to flush before then
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn deflate(data: &[u8], level: Compression) -> io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), level);
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::{GzDecoder, MultiGzDecoder};
+    use std::io::Read;
+
+    /// The blocks of a BGZF file, decompressed one by one.
+    fn blocks(mut bgzf: &[u8]) -> Vec<Vec<u8>> {
+        let mut blocks = vec![];
+        while !bgzf.is_empty() {
+            assert_eq!(&bgzf[12..14], b"BC");
+            let size = u16::from_le_bytes([bgzf[16], bgzf[17]]) as usize + 1;
+            let mut block = vec![];
+            GzDecoder::new(&bgzf[..size])
+                .read_to_end(&mut block)
+                .unwrap();
+            blocks.push(block);
+            bgzf = &bgzf[size..];
+        }
+        blocks
+    }
+
+    fn bgzf(records: &[String]) -> Vec<u8> {
+        let mut w = BgzfWriter::new(vec![]);
+        for record in records {
+            w.write_all(record.as_bytes()).unwrap();
+            w.end_record().unwrap();
+        }
+        w.finish().unwrap();
+        w.inner
+    }
+
+    #[test]
+    fn blocks_end_at_records() {
+        let mut state = 1u64;
+        let records: Vec<String> = (0..300)
+            .map(|i| {
+                // hardly compressible, so that blocks fill up
+                let noise: String = (0..200)
+                    .map(|_| {
+                        state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                        format!("{:x}", state >> 60)
+                    })
+                    .collect();
+                format!("{{\"i\":{i},\"noise\":\"{noise}\"}}\n")
+            })
+            .collect();
+        let out = bgzf(&records);
+        assert!(out.ends_with(EOF));
+        let mut all = vec![];
+        MultiGzDecoder::new(&out[..]).read_to_end(&mut all).unwrap();
+        assert_eq!(all, records.concat().as_bytes());
+
+        let blocks = blocks(&out);
+        assert!(blocks.len() > 2);
+        assert_eq!(blocks.last().unwrap(), b"");
+        for block in &blocks[..blocks.len() - 1] {
+            assert!(block.len() <= MAX_INPUT && block.ends_with(b"\n"));
+        }
+        assert_eq!(blocks.concat(), records.concat().as_bytes());
+    }
+
+    #[test]
+    fn large_records_split() {
+        let records = [
+            "[1]\n".to_string(),
+            format!("\"{}\"\n", "x".repeat(150_000)),
+        ];
+        let blocks = blocks(&bgzf(&records));
+        let sizes: Vec<_> = blocks.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [4, MAX_INPUT, MAX_INPUT, 150_003 - 2 * MAX_INPUT, 0]);
+    }
+}
@@ -0,0 +1,34 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// Tell the kernel that `file` is going to be read sequentially, so that it
+/// reads ahead more aggressively. Does nothing outside Linux.
+pub fn advise_sequential(file: &File) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    fadvise(file, libc::POSIX_FADV_SEQUENTIAL)?;
+    #[cfg(not(target_os = "linux"))]
+    let _ = file;
+    Ok(())
+}
+
+/// Evict the pages of the file at `path` from the page cache, once it has
+/// been read and won't be needed again soon. Does nothing outside Linux.
+pub fn drop_cache(path: &Path) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    fadvise(&File::open(path)?, libc::POSIX_FADV_DONTNEED)?;
+    #[cfg(not(target_os = "linux"))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn fadvise(file: &File, advice: libc::c_int) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: the descriptor is valid for as long as `file` is borrowed
+    match unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, advice) } {
+        0 => Ok(()),
+        e => Err(io::Error::from_raw_os_error(e)),
+    }
+}
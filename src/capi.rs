@@ -0,0 +1,304 @@
+//! The C API, declared in `include/json2jsonl.h`.
+//!
+//! Functions return a negative value on failure, and the message of the
+//! error is then available from [`j2j_last_error`] on the same thread.
+
+use crate::gzip::MaybeGzip;
+use crate::push;
+use crate::scan::{self, Scanner};
+use std::cell::RefCell;
+use std::ffi::{CString, c_char, c_int};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::mem::ManuallyDrop;
+use std::os::fd::FromRawFd;
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::default();
+}
+
+fn set_error(e: anyhow::Error) {
+    let msg = format!("{e:#}").replace('\0', " ");
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(msg).unwrap_or_default());
+}
+
+/// The conversion options, `j2j_options` in C. A null pointer stands for
+/// the defaults, all zero.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Options {
+    pub json5: bool,
+    pub comments: bool,
+    pub trailing_commas: bool,
+    /// 0 for no limit
+    pub max_depth: u32,
+}
+
+impl Options {
+    /// # Safety
+    ///
+    /// `options` must be null or point to valid options.
+    unsafe fn from_ptr(options: *const Options) -> Self {
+        // SAFETY: guaranteed by the caller
+        unsafe { options.as_ref() }.copied().unwrap_or_default()
+    }
+
+    fn max_depth(&self) -> usize {
+        match self.max_depth {
+            0 => usize::MAX,
+            n => n as usize,
+        }
+    }
+}
+
+fn convert_fd(input: &File, output: &File, opts: Options) -> anyhow::Result<u64> {
+    let mut rd = BufReader::new(MaybeGzip::new(input)?);
+    scan::skip_bom(&mut rd)?;
+    let mut scanner = Scanner::new(rd)
+        .json5(opts.json5)
+        .comments(opts.comments)
+        .trailing_commas(opts.trailing_commas)
+        .max_depth(opts.max_depth());
+    let mut w = BufWriter::new(output);
+    let mut record = vec![];
+    let mut records = 0;
+    while scanner.read_record_into(&mut record)? {
+        w.write_all(&record)?;
+        w.write_all(b"\n")?;
+        records += 1;
+    }
+    w.flush()?;
+    Ok(records)
+}
+
+/// Convert the JSON array read from `in_fd` (gzipped or not) to JSONL
+/// written to `out_fd`, and return the number of records, or -1 on failure.
+/// The descriptors are left open.
+///
+/// # Safety
+///
+/// `in_fd` and `out_fd` must be open file descriptors, and `options` null
+/// or valid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn j2j_convert_fd(
+    in_fd: c_int,
+    out_fd: c_int,
+    options: *const Options,
+) -> i64 {
+    // SAFETY: guaranteed by the caller, and the files aren't closed
+    let (input, output, opts) = unsafe {
+        (
+            ManuallyDrop::new(File::from_raw_fd(in_fd)),
+            ManuallyDrop::new(File::from_raw_fd(out_fd)),
+            Options::from_ptr(options),
+        )
+    };
+    match convert_fd(&input, &output, opts) {
+        Ok(records) => records as i64,
+        Err(e) => {
+            set_error(e);
+            -1
+        }
+    }
+}
+
+/// A conversion fed with chunks of the input, `j2j_converter` in C.
+pub struct Converter {
+    inner: push::Converter,
+    /// the records returned by the last call
+    out: Vec<u8>,
+}
+
+/// Start a conversion fed with [`j2j_converter_push`], to be freed with
+/// [`j2j_converter_free`].
+///
+/// # Safety
+///
+/// `options` must be null or valid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn j2j_converter_new(options: *const Options) -> *mut Converter {
+    // SAFETY: guaranteed by the caller
+    let opts = unsafe { Options::from_ptr(options) };
+    let inner = push::Converter::new()
+        .json5(opts.json5)
+        .comments(opts.comments)
+        .trailing_commas(opts.trailing_commas)
+        .max_depth(opts.max_depth());
+    Box::into_raw(Box::new(Converter { inner, out: vec![] }))
+}
+
+/// Point `out` and `out_len` to the records of the last call, or return -1.
+fn output(
+    converter: &mut Converter,
+    res: anyhow::Result<()>,
+    out: *mut *const u8,
+    out_len: *mut usize,
+) -> c_int {
+    if let Err(e) = res {
+        set_error(e);
+        return -1;
+    }
+    // SAFETY: checked to be valid by the caller
+    unsafe {
+        *out = converter.out.as_ptr();
+        *out_len = converter.out.len();
+    }
+    0
+}
+
+/// Convert the `len` bytes at `chunk`, the next ones of the input. The JSONL
+/// records of the elements they complete (possibly none) are at `*out`, for
+/// `*out_len` bytes, until the next call. Returns 0, or -1 on failure.
+///
+/// # Safety
+///
+/// `converter` must come from [`j2j_converter_new`], `chunk` point to `len`
+/// readable bytes, and `out` and `out_len` be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn j2j_converter_push(
+    converter: *mut Converter,
+    chunk: *const u8,
+    len: usize,
+    out: *mut *const u8,
+    out_len: *mut usize,
+) -> c_int {
+    // SAFETY: guaranteed by the caller
+    let (converter, chunk) = unsafe {
+        let chunk = match len {
+            0 => &[][..],
+            len => std::slice::from_raw_parts(chunk, len),
+        };
+        (&mut *converter, chunk)
+    };
+    converter.out.clear();
+    let res = converter.inner.push(chunk, &mut converter.out);
+    output(converter, res, out, out_len)
+}
+
+/// Convert the rest of the input once all of it has been pushed, like
+/// [`j2j_converter_push`]. Fails if the array isn't complete.
+///
+/// # Safety
+///
+/// As for [`j2j_converter_push`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn j2j_converter_finish(
+    converter: *mut Converter,
+    out: *mut *const u8,
+    out_len: *mut usize,
+) -> c_int {
+    // SAFETY: guaranteed by the caller
+    let converter = unsafe { &mut *converter };
+    converter.out.clear();
+    let res = converter.inner.finish(&mut converter.out);
+    output(converter, res, out, out_len)
+}
+
+/// Free a converter, and the records it returned.
+///
+/// # Safety
+///
+/// `converter` must be null or come from [`j2j_converter_new`], and not be
+/// used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn j2j_converter_free(converter: *mut Converter) {
+    if !converter.is_null() {
+        // SAFETY: guaranteed by the caller
+        drop(unsafe { Box::from_raw(converter) });
+    }
+}
+
+/// The message of the last error on this thread, valid until the next call
+/// that fails, or null if there was none.
+#[unsafe(no_mangle)]
+pub extern "C" fn j2j_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match last.borrow().as_bytes() {
+        [] => ptr::null(),
+        _ => last.borrow().as_ptr(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spill::TempFile;
+    use std::ffi::CStr;
+    use std::io::{Read, Seek, SeekFrom};
+    use std::os::fd::AsRawFd;
+
+    /// Push `chunks` and finish, returning the records of each call.
+    fn push(options: *const Options, chunks: &[&str]) -> Result<Vec<String>, String> {
+        let converter = unsafe { j2j_converter_new(options) };
+        let (mut out, mut out_len) = (ptr::null(), 0);
+        let mut records = vec![];
+        let mut step = |res: c_int, out: *const u8, out_len: usize| {
+            if res != 0 {
+                let e = unsafe { CStr::from_ptr(j2j_last_error()) };
+                return Err(e.to_string_lossy().into_owned());
+            }
+            let out = unsafe { std::slice::from_raw_parts(out, out_len) };
+            records.push(String::from_utf8(out.to_vec()).unwrap());
+            Ok(())
+        };
+        let res = (chunks.iter())
+            .try_for_each(|chunk| {
+                let (ptr, len) = (chunk.as_ptr(), chunk.len());
+                let res =
+                    unsafe { j2j_converter_push(converter, ptr, len, &mut out, &mut out_len) };
+                step(res, out, out_len)
+            })
+            .and_then(|()| {
+                let res = unsafe { j2j_converter_finish(converter, &mut out, &mut out_len) };
+                step(res, out, out_len)
+            });
+        unsafe { j2j_converter_free(converter) };
+        res.map(|()| records)
+    }
+
+    #[test]
+    fn converter() {
+        // an element is complete once what follows it is read
+        let records = push(ptr::null(), &[r#"[{"a":1},{"b""#, r#":[2]}"#, "", "]"]);
+        let (a, b) = ("{\"a\":1}\n", "{\"b\":[2]}\n");
+        assert_eq!(records.unwrap(), [a, "", "", b, ""]);
+        let opts = Options {
+            json5: true,
+            ..Options::default()
+        };
+        let records = push(&opts, &["[{a: 'x'}, // one\n", "]"]).unwrap();
+        assert_eq!(records.concat(), "{\"a\":\"x\"}\n");
+    }
+
+    #[test]
+    fn errors() {
+        assert!(push(ptr::null(), &[r#"[{"a":1}"#]).is_err());
+        assert!(push(ptr::null(), &[r#"[{"a":}]"#]).is_err());
+        let opts = Options {
+            max_depth: 1,
+            ..Options::default()
+        };
+        assert!(
+            push(&opts, &[r#"[{"a":[1]}]"#])
+                .unwrap_err()
+                .contains("deeper")
+        );
+        assert!(push(&opts, &[r#"[{"a":1}]"#]).is_ok());
+    }
+
+    #[test]
+    fn convert_fd() {
+        let (input, output) = (TempFile::new().unwrap(), TempFile::new().unwrap());
+        input.file().write_all(br#"[{"a":1}, [2], 3]"#).unwrap();
+        input.file().seek(SeekFrom::Start(0)).unwrap();
+        let (in_fd, out_fd) = (input.file().as_raw_fd(), output.file().as_raw_fd());
+        assert_eq!(unsafe { j2j_convert_fd(in_fd, out_fd, ptr::null()) }, 3);
+        let mut records = String::new();
+        output.file().seek(SeekFrom::Start(0)).unwrap();
+        output.file().read_to_string(&mut records).unwrap();
+        assert_eq!(records, "{\"a\":1}\n[2]\n3\n");
+        // at the end of the input, this time
+        assert_eq!(unsafe { j2j_convert_fd(in_fd, out_fd, ptr::null()) }, -1);
+        assert!(!j2j_last_error().is_null());
+    }
+}
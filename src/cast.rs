@@ -0,0 +1,498 @@
+use crate::datetime::{self, DateTime};
+use crate::template;
+use crate::tz::Zone;
+use anyhow::{Context, bail};
+use serde_json::Value;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A type to cast a field to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    Float,
+    Bool,
+    String,
+    /// an RFC 3339 timestamp
+    Datetime,
+}
+
+/// What to do with a value that doesn't cast.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnError {
+    /// The record is invalid (see --skip-invalid and --dead-letter)
+    Fail,
+    /// Set the value to null
+    Null,
+    /// Leave the value as it is
+    Keep,
+    /// Remove the field
+    Drop,
+}
+
+impl Type {
+    fn name(self) -> &'static str {
+        match self {
+            Type::Int => "int",
+            Type::Float => "float",
+            Type::Bool => "bool",
+            Type::String => "string",
+            Type::Datetime => "datetime",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Rule {
+    field: String,
+    path: Vec<String>,
+    to: Type,
+    /// `strftime` formats of the timestamps, tried in order
+    formats: Vec<String>,
+    /// the time zones to convert timestamps from (if naive) and to
+    zones: Option<Arc<(Zone, Zone)>>,
+    /// the strings for true and for false, in place of those of [`parse_bool`]
+    words: Option<Arc<(Vec<String>, Vec<String>)>>,
+    on_error: OnError,
+    /// values that didn't cast
+    failed: AtomicU64,
+}
+
+/// Casts fields of the records to other types, by a specification file for
+/// --cast.
+///
+/// The file is a JSON object from each dotted path into the records to the
+/// type to cast it to, `int`, `float`, `bool`, `string` or `datetime`, or to
+/// an object with the `type`, what to do `on_error`: `fail` (the default),
+/// `null`, `keep` or `drop`, and for a `datetime` the `formats` to read it by
+/// (see [`datetime::parse`]) and the time zones to convert it from (if naive)
+/// and to, `tz_from` and `tz_to` (see [`Zone::load`]):
+///
+/// ```json
+/// {"age": "int", "price": {"type": "float", "on_error": "null"},
+///  "created": {"type": "datetime", "formats": ["%d/%m/%Y %H:%M"]}}
+/// ```
+///
+/// Strings are cast to numbers, integers of any size included, and booleans
+/// (`true`, `false`, `yes`, `no`, `1`, `0`, ignoring case) by their text,
+/// and numbers to timestamps as seconds since the Unix epoch. Timestamps
+/// without an offset are taken to be in UTC. Null, and missing fields, are
+/// left as they are.
+#[derive(Debug, Default)]
+pub struct Casts {
+    rules: Vec<Rule>,
+}
+
+impl Casts {
+    /// Read the specification at `path`.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// Parse a specification in the format of [`Casts`].
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let Value::Object(spec) = serde_json::from_str(text)? else {
+            bail!("expected an object from field paths to types");
+        };
+        let mut rules = vec![];
+        for (field, rule) in spec {
+            let (mut to, mut on_error, mut formats) = (None, OnError::Fail, vec![]);
+            let (mut tz_from, mut tz_to) = (None, None);
+            match rule {
+                Value::String(name) => to = Some(name),
+                Value::Object(rule) => {
+                    for (key, value) in rule {
+                        match (key.as_str(), value) {
+                            ("type", Value::String(name)) => to = Some(name),
+                            ("on_error", Value::String(name)) => {
+                                on_error = clap::ValueEnum::from_str(&name, false).map_err(|_| {
+                                    anyhow::anyhow!(
+                                        "{field}: unknown on_error `{name}` (fail, null, keep or drop)"
+                                    )
+                                })?;
+                            }
+                            ("formats", Value::Array(values)) => {
+                                for value in values {
+                                    let Value::String(format) = value else {
+                                        bail!("{field}: expected formats as strings");
+                                    };
+                                    formats.push(format);
+                                }
+                            }
+                            ("tz_from", Value::String(zone)) => {
+                                tz_from = Some(Zone::load(&zone).context(field.clone())?);
+                            }
+                            ("tz_to", Value::String(zone)) => {
+                                tz_to = Some(Zone::load(&zone).context(field.clone())?);
+                            }
+                            (key, _) => bail!("{field}: unexpected `{key}`"),
+                        }
+                    }
+                }
+                _ => bail!("{field}: expected a type"),
+            }
+            let to = match to.as_deref() {
+                Some("int") => Type::Int,
+                Some("float") => Type::Float,
+                Some("bool") => Type::Bool,
+                Some("string") => Type::String,
+                Some("datetime") => Type::Datetime,
+                Some(to) => {
+                    bail!("{field}: unknown type `{to}` (int, float, bool, string or datetime)")
+                }
+                None => bail!("{field}: expected a `type`"),
+            };
+            rules.push(Rule {
+                path: field.split('.').map(String::from).collect(),
+                field,
+                to,
+                formats,
+                zones: zones(tz_from, tz_to),
+                words: None,
+                on_error,
+                failed: AtomicU64::new(0),
+            });
+        }
+        Ok(Self { rules })
+    }
+
+    /// Also cast `fields` to timestamps, read by the first of `formats` (see
+    /// [`datetime::parse`]) that they match, or else as RFC 3339 timestamps,
+    /// and converted to the second of `zones`, the naive ones being in the
+    /// first.
+    pub fn add_datetimes(
+        &mut self,
+        fields: &[String],
+        formats: &[String],
+        zones: Option<(Zone, Zone)>,
+        on_error: OnError,
+    ) {
+        let zones = zones.map(Arc::new);
+        for field in fields {
+            self.rules.push(Rule {
+                field: field.clone(),
+                path: field.split('.').map(String::from).collect(),
+                to: Type::Datetime,
+                formats: formats.to_vec(),
+                zones: zones.clone(),
+                words: None,
+                on_error,
+                failed: AtomicU64::new(0),
+            });
+        }
+    }
+
+    /// Also cast `fields` to booleans, strings being true if they are one of
+    /// `truthy` and false if they are one of `falsy`, ignoring case.
+    pub fn add_bools(
+        &mut self,
+        fields: &[String],
+        truthy: &[String],
+        falsy: &[String],
+        on_error: OnError,
+    ) {
+        let words = Arc::new((truthy.to_vec(), falsy.to_vec()));
+        for field in fields {
+            self.rules.push(Rule {
+                field: field.clone(),
+                path: field.split('.').map(String::from).collect(),
+                to: Type::Bool,
+                formats: vec![],
+                zones: None,
+                words: Some(words.clone()),
+                on_error,
+                failed: AtomicU64::new(0),
+            });
+        }
+    }
+
+    /// Cast the fields of `record`, in place. Fails on the first value that
+    /// doesn't cast, for a field to `fail` on errors.
+    pub fn apply(&self, record: &mut Value) -> anyhow::Result<()> {
+        for rule in &self.rules {
+            let Some(value) = template::lookup_mut(record, &rule.path) else {
+                continue;
+            };
+            if let Some(cast) = cast(value, rule) {
+                *value = cast;
+                continue;
+            }
+            match rule.on_error {
+                OnError::Fail => bail!("can't cast {} {value} to {}", rule.field, rule.to.name()),
+                OnError::Null => *value = Value::Null,
+                OnError::Keep => (),
+                OnError::Drop => template::remove(record, &rule.path),
+            }
+            rule.failed.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Write the number of values of each field that didn't cast, if any.
+    pub fn write_report(&self, mut w: impl Write) -> io::Result<()> {
+        for rule in &self.rules {
+            let failed = rule.failed.load(Ordering::Relaxed);
+            if failed > 0 {
+                let done = match rule.on_error {
+                    OnError::Fail => unreachable!(),
+                    OnError::Null => "set to null",
+                    OnError::Keep => "kept as they were",
+                    OnError::Drop => "dropped",
+                };
+                writeln!(
+                    w,
+                    "{failed} values of {} didn't cast to {}, {done}",
+                    rule.field,
+                    rule.to.name()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A boolean by its conventional text: `true`, `yes` and `1`, or `false`,
+/// `no` and `0`, ignoring case.
+pub fn parse_bool(s: &str) -> Option<bool> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "true" | "yes" | "1" => Some(true),
+        "false" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// `value` cast by `rule`, if it can be.
+fn cast(value: &Value, rule: &Rule) -> Option<Value> {
+    if value.is_null() {
+        return Some(Value::Null);
+    }
+    let formats = &rule.formats;
+    let timestamp = |mut time: DateTime| {
+        match rule.zones.as_deref() {
+            Some((from, to)) => time = to.convert(time, from),
+            // naive times are in UTC
+            None => _ = time.offset.get_or_insert(0),
+        }
+        Some(Value::String(time.to_rfc3339()))
+    };
+    match (rule.to, value) {
+        (Type::Int, Value::Number(n)) if n.is_i64() || n.is_u64() => Some(value.clone()),
+        (Type::Int, Value::Number(n)) => integer(&n.to_string()).or_else(|| int(n.as_f64()?)),
+        (Type::Int, Value::String(s)) => {
+            integer(s.trim()).or_else(|| int(s.trim().parse().ok()?))
+        }
+        (Type::Float, Value::Number(n)) => Some(n.as_f64()?.into()),
+        (Type::Float, Value::String(s)) => {
+            let f: f64 = s.trim().parse().ok()?;
+            f.is_finite().then(|| f.into())
+        }
+        (Type::Bool, Value::Bool(_)) => Some(value.clone()),
+        (Type::Bool, Value::String(s)) => match rule.words.as_deref() {
+            Some((truthy, falsy)) => {
+                let is = |words: &[String]| words.iter().any(|w| w.eq_ignore_ascii_case(s.trim()));
+                (is(truthy) || is(falsy)).then(|| is(truthy).into())
+            }
+            None => parse_bool(s).map(Value::Bool),
+        },
+        (Type::Bool, Value::Number(n)) => match n.as_f64()? {
+            0.0 => Some(false.into()),
+            1.0 => Some(true.into()),
+            _ => None,
+        },
+        (Type::String, Value::String(_)) => Some(value.clone()),
+        (Type::String, value) => Some(Value::String(value.to_string())),
+        (Type::Datetime, Value::String(s)) => {
+            let s = s.trim();
+            let parsed = (formats.iter()).find_map(|format| datetime::parse(s, format));
+            timestamp(parsed.or_else(|| DateTime::parse_rfc3339(s))?)
+        }
+        (Type::Datetime, Value::Number(n)) => {
+            let text = n.to_string();
+            let parsed = (formats.iter()).find_map(|format| datetime::parse(&text, format));
+            timestamp(parsed.or_else(|| DateTime::from_unix(n.as_f64()?))?)
+        }
+        _ => None,
+    }
+}
+
+/// The time zones to convert timestamps from and to, if either is set.
+fn zones(from: Option<Zone>, to: Option<Zone>) -> Option<Arc<(Zone, Zone)>> {
+    if from.is_none() && to.is_none() {
+        return None;
+    }
+    let utc = || Zone::load("UTC").unwrap();
+    Some(Arc::new((from.unwrap_or_else(utc), to.unwrap_or_else(utc))))
+}
+
+/// The integer written `s`, digits after an optional sign, of any size.
+fn integer(s: &str) -> Option<Value> {
+    let (sign, digits) = match s.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", s.strip_prefix('+').unwrap_or(s)),
+    };
+    if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    // JSON has no leading zeros, nor -0
+    let text = match digits.trim_start_matches('0') {
+        "" => "0".to_string(),
+        digits => format!("{sign}{digits}"),
+    };
+    text.parse().ok().map(Value::Number)
+}
+
+/// `f` as an integer, if it is one.
+fn int(f: f64) -> Option<Value> {
+    (f.fract() == 0.0 && f.abs() < 9.2e18).then(|| (f as i64).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `record` cast by the specification `spec`.
+    fn cast(spec: &str, record: &str) -> anyhow::Result<String> {
+        let mut record: Value = serde_json::from_str(record)?;
+        Casts::parse(spec)?.apply(&mut record)?;
+        Ok(record.to_string())
+    }
+
+    #[test]
+    fn ints() {
+        let spec = r#"{"a": "int", "b": "int", "c": "int", "d": "int", "e": "int", "f": "int"}"#;
+        let record = r#"{"a": " -0012 ", "b": "99999999999999999999", "c": "2.0", "d": 3.0,
+            "e": -123456789012345678901234, "f": null}"#;
+        assert_eq!(
+            cast(spec, record).unwrap(),
+            r#"{"a":-12,"b":99999999999999999999,"c":2,"d":3,"e":-123456789012345678901234,"f":null}"#
+        );
+        for value in [r#""12a""#, r#""1.5""#, r#""""#, r#""--1""#, "true", "1e400"] {
+            let record = format!(r#"{{"a": {value}}}"#);
+            assert!(cast(r#"{"a": "int"}"#, &record).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn floats_bools_and_strings() {
+        let spec = r#"{"f": "float", "b": "bool", "n": "bool", "s": "string", "t": "string"}"#;
+        let record = r#"{"f": " 1.5 ", "b": "Yes", "n": 0, "s": 12, "t": {"x": [1]}}"#;
+        assert_eq!(
+            cast(spec, record).unwrap(),
+            r#"{"f":1.5,"b":true,"n":false,"s":"12","t":"{\"x\":[1]}"}"#
+        );
+        assert!(cast(r#"{"f": "float"}"#, r#"{"f": "NaN"}"#).is_err());
+        assert!(cast(r#"{"b": "bool"}"#, r#"{"b": 2}"#).is_err());
+    }
+
+    #[test]
+    fn datetimes() {
+        let spec = r#"{"a": {"type": "datetime", "formats": ["%d/%m/%Y %H:%M"]},
+            "b": "datetime", "c": "datetime"}"#;
+        let record = r#"{"a": "02/01/2024 03:04", "b": "2024-01-02T03:04:05+02:00", "c": 0}"#;
+        assert_eq!(
+            cast(spec, record).unwrap(),
+            r#"{"a":"2024-01-02T03:04:00Z","b":"2024-01-02T03:04:05+02:00","c":"1970-01-01T00:00:00Z"}"#
+        );
+    }
+
+    #[test]
+    fn datetime_fields() {
+        let mut casts = Casts::default();
+        let fields = ["t".to_string(), "u.v".to_string()];
+        let formats = ["%d/%m/%Y %H:%M".to_string(), "%s".to_string()];
+        casts.add_datetimes(&fields, &formats, None, OnError::Null);
+        let mut record = serde_json::json!({"t": "01/03/2024 12:30", "u": {"v": "1709296200"}});
+        casts.apply(&mut record).unwrap();
+        assert_eq!(
+            record.to_string(),
+            r#"{"t":"2024-03-01T12:30:00Z","u":{"v":"2024-03-01T12:30:00Z"}}"#
+        );
+        // RFC 3339 without a format for it, and numbers as Unix time
+        let mut record = serde_json::json!({"t": "2024-03-01T12:30:00+01:00", "u": {"v": 0}});
+        casts.apply(&mut record).unwrap();
+        assert_eq!(
+            record.to_string(),
+            r#"{"t":"2024-03-01T12:30:00+01:00","u":{"v":"1970-01-01T00:00:00Z"}}"#
+        );
+        let mut record = serde_json::json!({"t": "yesterday"});
+        casts.apply(&mut record).unwrap();
+        assert_eq!(record.to_string(), r#"{"t":null}"#);
+    }
+
+    #[test]
+    fn time_zones() {
+        let mut casts = Casts::default();
+        let zones = (Zone::load("+05:30").unwrap(), Zone::load("-02:00").unwrap());
+        casts.add_datetimes(&["t".to_string()], &[], Some(zones), OnError::Fail);
+        let mut record = serde_json::json!({"t": "2024-03-01T12:30:00"});
+        casts.apply(&mut record).unwrap();
+        assert_eq!(record.to_string(), r#"{"t":"2024-03-01T05:00:00-02:00"}"#);
+        let mut record = serde_json::json!({"t": "2024-03-01T12:30:00Z"});
+        casts.apply(&mut record).unwrap();
+        assert_eq!(record.to_string(), r#"{"t":"2024-03-01T10:30:00-02:00"}"#);
+    }
+
+    #[test]
+    fn bool_words() {
+        let mut casts = Casts::default();
+        let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        let fields = words(&["a", "b", "c", "d", "e"]);
+        casts.add_bools(
+            &fields,
+            &words(&["Y", "on"]),
+            &words(&["n", "off"]),
+            OnError::Keep,
+        );
+        let mut record =
+            serde_json::json!({"a": " y ", "b": "OFF", "c": 1, "d": "yes", "e": false});
+        casts.apply(&mut record).unwrap();
+        assert_eq!(
+            record.to_string(),
+            r#"{"a":true,"b":false,"c":true,"d":"yes","e":false}"#
+        );
+        let mut report = vec![];
+        casts.write_report(&mut report).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "1 values of d didn't cast to bool, kept as they were\n"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let spec = r#"{"a": {"type": "int", "on_error": "null"}, "b": {"type": "int", "on_error": "keep"},
+            "c": {"type": "int", "on_error": "drop"}, "x.y": "int", "z": "int"}"#;
+        let casts = Casts::parse(spec).unwrap();
+        let mut record = serde_json::json!({"a": "x", "b": "x", "c": "x", "x": {"y": "7"}});
+        casts.apply(&mut record).unwrap();
+        assert_eq!(record.to_string(), r#"{"a":null,"b":"x","x":{"y":7}}"#);
+        let mut report = vec![];
+        casts.write_report(&mut report).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "1 values of a didn't cast to int, set to null\n\
+             1 values of b didn't cast to int, kept as they were\n\
+             1 values of c didn't cast to int, dropped\n"
+        );
+        let err = cast(r#"{"a": "int"}"#, r#"{"a": "x"}"#).unwrap_err();
+        assert_eq!(err.to_string(), r#"can't cast a "x" to int"#);
+    }
+
+    #[test]
+    fn invalid_specifications() {
+        for spec in [
+            "[]",
+            r#"{"a": "date"}"#,
+            r#"{"a": {"on_error": "null"}}"#,
+            r#"{"a": {"type": "int", "on_error": "ignore"}}"#,
+            r#"{"a": {"type": "int", "unit": "s"}}"#,
+        ] {
+            assert!(Casts::parse(spec).is_err(), "{spec}");
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::Context;
+use serde_json::{Value, json};
+use std::fs;
+use std::path::Path;
+
+/// How far a conversion got, so that it can be resumed from there.
+///
+/// Checkpoints are only taken between two elements, once the records of
+/// the previous ones have been flushed to the output.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Offset in the input of the byte following the last element converted
+    pub input_offset: u64,
+    /// Number of elements of the input array converted (or skipped)
+    pub elements: u64,
+    /// Number of records written
+    pub records: u64,
+    /// Size of the output up to the last record
+    pub output_len: u64,
+}
+
+impl Checkpoint {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let read = || -> anyhow::Result<Self> {
+            let v: Value = serde_json::from_slice(&fs::read(path)?)?;
+            let field = |name: &str| {
+                v.get(name)
+                    .and_then(Value::as_u64)
+                    .with_context(|| format!("missing field {name:?}"))
+            };
+            Ok(Self {
+                input_offset: field("input_offset")?,
+                elements: field("elements")?,
+                records: field("records")?,
+                output_len: field("output_len")?,
+            })
+        };
+        read().with_context(|| format!("reading checkpoint {}", path.display()))
+    }
+
+    /// Write the checkpoint to `path`, replacing the previous one atomically,
+    /// so that a run killed in the middle leaves a usable checkpoint.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let v = json!({
+            "input_offset": self.input_offset,
+            "elements": self.elements,
+            "records": self.records,
+            "output_len": self.output_len,
+        });
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, v.to_string() + "\n")
+            .and_then(|()| fs::rename(&tmp, path))
+            .with_context(|| format!("writing checkpoint {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_load() {
+        let path = std::env::temp_dir().join(format!("json2jsonl-cp-{}.json", std::process::id()));
+        let cp = Checkpoint {
+            input_offset: 12,
+            elements: 3,
+            records: 4,
+            output_len: 40,
+        };
+        cp.save(&path).unwrap();
+        assert_eq!(Checkpoint::load(&path).unwrap(), cp);
+        fs::write(&path, r#"{"input_offset":1}"#).unwrap();
+        let err = Checkpoint::load(&path).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err.root_cause()),
+            "missing field \"elements\""
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
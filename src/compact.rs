@@ -0,0 +1,58 @@
+use serde_json::value::RawValue;
+
+/// Check that `src` is a single valid JSON value. Unlike parsing it into
+/// [`serde::de::IgnoredAny`], this doesn't allocate.
+pub fn validate(src: &[u8]) -> serde_json::Result<()> {
+    serde_json::from_slice::<&RawValue>(src).map(|_| ())
+}
+
+/// Append the valid JSON value `src` to `out` without its insignificant
+/// whitespace, i.e. outside of strings.
+///
+/// `src` is not validated: this only tracks strings and their escapes, so
+/// that an element checked by the parser can be copied as is otherwise.
+pub fn compact(src: &[u8], out: &mut Vec<u8>) {
+    out.reserve(src.len());
+    let mut in_str = false;
+    let mut escape = false;
+    // start of the bytes not copied yet
+    let mut from = 0;
+    for (i, &c) in src.iter().enumerate() {
+        if in_str {
+            if escape {
+                escape = false;
+            } else if c == b'\\' {
+                escape = true;
+            } else if c == b'"' {
+                in_str = false;
+            }
+        } else if c == b'"' {
+            in_str = true;
+        } else if matches!(c, b' ' | b'\t' | b'\n' | b'\r') {
+            out.extend_from_slice(&src[from..i]);
+            from = i + 1;
+        }
+    }
+    out.extend_from_slice(&src[from..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compacts() {
+        let src =
+            b"{ \"a b\" : [ 1.50 ,\n\t1e5 ],\r\n \"c\": \"\\\" x\\\\\" , \"d\":\"\\u00e9\\/\" }";
+        assert!(validate(src).is_ok());
+        let mut out = b"prefix".to_vec();
+        compact(src, &mut out);
+        // numbers and escapes as they were
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"prefix{"a b":[1.50,1e5],"c":"\" x\\","d":"\u00e9\/"}"#
+        );
+        assert!(validate(b"[1,]").is_err());
+        assert!(validate(b"1 2").is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use anyhow::{Context, bail};
+use clap::Command;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::Value;
+
+/// The config file read when none is given: `json2jsonl/config.toml` in
+/// `$XDG_CONFIG_HOME`, or else in `~/.config`.
+pub fn default_path() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("json2jsonl").join("config.toml"))
+}
+
+/// Turn the config file at `path` into arguments for `cmd`, to go before
+/// those of the command line.
+///
+/// Each key is the long name of a flag (with `-` or `_` between words) and
+/// its value that of the flag: `threads = 4`, `mmap = true`, or an array for
+/// a flag that can be given several times. A count such as `verbose = 2`
+/// repeats the flag. Flags set from the environment are left to it.
+pub fn args(cmd: &Command, path: &Path) -> anyhow::Result<Vec<OsString>> {
+    let text = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let table: toml::Table = text
+        .parse()
+        .with_context(|| format!("parsing {}", path.display()))?;
+    let mut args = vec![];
+    for (key, value) in &table {
+        let name = key.replace('_', "-");
+        let Some(arg) = cmd
+            .get_arguments()
+            .find(|a| a.get_long() == Some(name.as_str()))
+        else {
+            bail!("{}: unknown flag `{key}`", path.display());
+        };
+        if name == "config" || name == "no-config" {
+            bail!(
+                "{}: `{key}` can't be set from a config file",
+                path.display()
+            );
+        }
+        if arg.get_env().is_some_and(|var| env::var_os(var).is_some()) {
+            continue;
+        }
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let takes_value = arg.get_action().takes_values();
+            match value {
+                Value::Boolean(false) if !takes_value => (),
+                Value::Boolean(true) if !takes_value => args.push(format!("--{name}").into()),
+                Value::Integer(n) if !takes_value => {
+                    (0..*n).for_each(|_| args.push(format!("--{name}").into()))
+                }
+                Value::String(s) if takes_value => args.push(format!("--{name}={s}").into()),
+                Value::Integer(_) | Value::Float(_) | Value::Boolean(_) if takes_value => {
+                    args.push(format!("--{name}={value}").into())
+                }
+                _ => bail!("{}: invalid value for `{key}`: {value}", path.display()),
+            }
+        }
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction};
+
+    fn command() -> Command {
+        Command::new("json2jsonl")
+            .arg(Arg::new("threads").long("threads"))
+            .arg(Arg::new("mmap").long("mmap").action(ArgAction::SetTrue))
+            .arg(Arg::new("fsync").long("fsync").action(ArgAction::SetTrue))
+            .arg(Arg::new("verbose").long("verbose").action(ArgAction::Count))
+            .arg(
+                Arg::new("drop-field")
+                    .long("drop-field")
+                    .action(ArgAction::Append),
+            )
+            .arg(Arg::new("config").long("config"))
+    }
+
+    /// The arguments from a config file of `text`.
+    fn config(name: &str, text: &str) -> anyhow::Result<Vec<String>> {
+        let path = env::temp_dir().join(format!(
+            "json2jsonl-config-{}-{name}.toml",
+            std::process::id()
+        ));
+        fs::write(&path, text).unwrap();
+        let args = super::args(&command(), &path);
+        fs::remove_file(&path).unwrap();
+        Ok(args?
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect())
+    }
+
+    #[test]
+    fn flags() {
+        let args = config(
+            "flags",
+            "threads = 4\nmmap = true\nfsync = false\nverbose = 2\ndrop_field = [\"a\", \"b.c\"]\n",
+        );
+        assert_eq!(
+            args.unwrap(),
+            [
+                "--drop-field=a",
+                "--drop-field=b.c",
+                "--mmap",
+                "--threads=4",
+                "--verbose",
+                "--verbose",
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let err = |text| format!("{:#}", config("errors", text).unwrap_err());
+        assert!(err("nope = 1").ends_with(": unknown flag `nope`"));
+        assert!(err("config = \"x\"").ends_with(": `config` can't be set from a config file"));
+        assert!(err("mmap = \"yes\"").ends_with(": invalid value for `mmap`: \"yes\""));
+        assert!(err("threads =").contains("parsing "));
+    }
+}
@@ -0,0 +1,46 @@
+/// What to do with raw control characters (U+0000 to U+001F) inside
+/// strings, which JSON requires to be escaped.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, clap::ValueEnum)]
+pub enum ControlChars {
+    /// Reject the record
+    Error,
+    /// Replace them by their escape sequence
+    Escape,
+    /// Remove them
+    Strip,
+}
+
+/// Rewrite the control characters in the strings of the element according
+/// to `policy`.
+pub fn replace(buf: &mut Vec<u8>, policy: ControlChars) {
+    if policy == ControlChars::Error || !buf.iter().any(|&c| c < 0x20) {
+        return;
+    }
+    let mut out = Vec::with_capacity(buf.len() + 16);
+    let mut quote = None;
+    let mut escape = false;
+    for &c in buf.iter() {
+        match quote {
+            Some(_) if c < 0x20 => {
+                if policy == ControlChars::Escape {
+                    match c {
+                        b'\n' => out.extend_from_slice(b"\\n"),
+                        b'\r' => out.extend_from_slice(b"\\r"),
+                        b'\t' => out.extend_from_slice(b"\\t"),
+                        _ => out.extend_from_slice(format!("\\u{c:04x}").as_bytes()),
+                    }
+                }
+                escape = false;
+                continue;
+            }
+            Some(_) if escape => escape = false,
+            Some(_) if c == b'\\' => escape = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == b'"' || c == b'\'' => quote = Some(c),
+            None => (),
+        }
+        out.push(c);
+    }
+    *buf = out;
+}
@@ -0,0 +1,154 @@
+use crate::spill::OVERHEAD;
+use crate::template;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// The tally of the values of a field.
+#[derive(Debug, Clone, Default)]
+struct Field {
+    name: String,
+    path: Vec<String>,
+    counts: HashMap<String, u64>,
+    /// records without the field
+    missing: u64,
+    /// records with a value past the limit of distinct ones
+    other: u64,
+}
+
+/// Counts of the distinct values of some fields of the records, for a quick
+/// profile of the data.
+#[derive(Debug, Clone)]
+pub struct ValueCounts {
+    fields: Vec<Field>,
+    /// distinct values counted at most for each field
+    limit: usize,
+    /// bytes of the values counted, roughly, and at most
+    bytes: usize,
+    max_bytes: usize,
+    records: u64,
+}
+
+impl ValueCounts {
+    /// Count the values of `fields`, dotted paths into the records like
+    /// those of a template, up to `limit` distinct values for each.
+    pub fn new(fields: &[String], limit: usize) -> Self {
+        Self {
+            fields: (fields.iter())
+                .map(|name| Field {
+                    name: name.clone(),
+                    path: name.split('.').map(String::from).collect(),
+                    ..Field::default()
+                })
+                .collect(),
+            limit,
+            bytes: 0,
+            max_bytes: usize::MAX,
+            records: 0,
+        }
+    }
+
+    /// Count about `max_bytes` of distinct values at most, for all the
+    /// fields: those past them are other values, like past the limit.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Count the values of `record`. Strings are counted as they are and
+    /// other values as JSON.
+    pub fn add(&mut self, record: &Value) {
+        self.records += 1;
+        for field in &mut self.fields {
+            let json;
+            let text = match template::lookup(record, &field.path) {
+                None => {
+                    field.missing += 1;
+                    continue;
+                }
+                Some(Value::String(s)) => s.as_str(),
+                Some(value) => {
+                    json = value.to_string();
+                    &json
+                }
+            };
+            if let Some(n) = field.counts.get_mut(text) {
+                *n += 1;
+            } else if field.counts.len() < self.limit
+                && self.bytes + text.len() + OVERHEAD <= self.max_bytes
+            {
+                self.bytes += text.len() + OVERHEAD;
+                field.counts.insert(text.to_string(), 1);
+            } else {
+                field.other += 1;
+            }
+        }
+    }
+
+    /// Write the table of the counts of each field, most frequent values
+    /// first.
+    pub fn write_table(&self, mut w: impl Write) -> io::Result<()> {
+        for (i, field) in self.fields.iter().enumerate() {
+            if i > 0 {
+                writeln!(w)?;
+            }
+            let mut counts: Vec<_> = field.counts.iter().collect();
+            counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+            let distinct = match field.other {
+                0 => format!("{}", counts.len()),
+                _ => format!("over {}", counts.len()),
+            };
+            writeln!(
+                w,
+                "{} ({distinct} distinct values in {} records):",
+                field.name, self.records
+            )?;
+            let width = (counts.first().map_or(0, |&(_, &n)| n))
+                .max(field.missing)
+                .max(field.other)
+                .to_string()
+                .len();
+            for (value, n) in counts {
+                writeln!(w, "  {n:>width$}  {value}")?;
+            }
+            if field.other > 0 {
+                writeln!(w, "  {:>width$}  (other values)", field.other)?;
+            }
+            if field.missing > 0 {
+                writeln!(w, "  {:>width$}  (missing)", field.missing)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn table(mut counts: ValueCounts) -> String {
+        for kind in ["a", "b", "a", "c"] {
+            counts.add(&json!({ "kind": kind }));
+        }
+        counts.add(&json!({}));
+        let mut table = vec![];
+        counts.write_table(&mut table).unwrap();
+        String::from_utf8(table).unwrap()
+    }
+
+    #[test]
+    fn limit() {
+        assert_eq!(
+            table(ValueCounts::new(&["kind".into()], 2)),
+            "kind (over 2 distinct values in 5 records):\n  \
+             2  a\n  1  b\n  1  (other values)\n  1  (missing)\n"
+        );
+    }
+
+    #[test]
+    fn max_bytes() {
+        let counts = ValueCounts::new(&["kind".into()], 100).max_bytes(2 * (1 + OVERHEAD));
+        assert_eq!(table(counts), table(ValueCounts::new(&["kind".into()], 2)));
+    }
+}
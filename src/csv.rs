@@ -0,0 +1,176 @@
+use anyhow::Context;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+
+/// Call `f` with each JSONL record read from `rd`, which must be objects,
+/// skipping blank lines.
+fn for_each_record(
+    mut rd: impl BufRead,
+    mut f: impl FnMut(Map<String, Value>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut line = vec![];
+    let mut lineno = 0;
+    loop {
+        line.clear();
+        if rd.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        lineno += 1;
+        let record = line.trim_ascii();
+        if record.is_empty() {
+            continue;
+        }
+        match serde_json::from_slice(record) {
+            Ok(Value::Object(record)) => f(record)?,
+            Ok(_) => anyhow::bail!("the record on line {lineno} is not an object"),
+            Err(e) => return Err(e).with_context(|| format!("invalid record on line {lineno}")),
+        }
+    }
+}
+
+/// The union of the keys of the JSONL records read from `rd`, in the order
+/// they first appear, for the header of [`write`].
+pub fn header(rd: impl BufRead) -> anyhow::Result<Vec<String>> {
+    let mut keys = vec![];
+    let mut seen = HashSet::new();
+    for_each_record(rd, |record| {
+        for (key, _) in record {
+            if !seen.contains(&key) {
+                seen.insert(key.clone());
+                keys.push(key);
+            }
+        }
+        Ok(())
+    })?;
+    Ok(keys)
+}
+
+/// Write the JSONL records read from `rd` to `w` as CSV (RFC 4180), with
+/// `header` for the columns, and return the number of records.
+///
+/// Strings are written as they are and other values as JSON, nested arrays
+/// and objects included; null and missing fields leave the cell empty, and
+/// fields not in `header` are dropped.
+pub fn write(rd: impl BufRead, mut w: impl Write, header: &[String]) -> anyhow::Result<u64> {
+    write_row(&mut w, header.iter().map(|key| key.as_bytes()))?;
+    let mut records = 0;
+    let mut json = vec![];
+    for_each_record(rd, |record| {
+        let mut cells = vec![];
+        for key in header {
+            cells.push(match record.get(key) {
+                None | Some(Value::Null) => (0, 0),
+                Some(Value::String(s)) => {
+                    json.extend_from_slice(s.as_bytes());
+                    (json.len() - s.len(), json.len())
+                }
+                Some(value) => {
+                    let start = json.len();
+                    serde_json::to_writer(&mut json, value)?;
+                    (start, json.len())
+                }
+            });
+        }
+        write_row(&mut w, cells.iter().map(|&(start, end)| &json[start..end]))?;
+        json.clear();
+        records += 1;
+        Ok(())
+    })?;
+    w.flush()?;
+    Ok(records)
+}
+
+fn write_row<'a>(w: &mut impl Write, cells: impl Iterator<Item = &'a [u8]>) -> std::io::Result<()> {
+    for (i, cell) in cells.enumerate() {
+        if i > 0 {
+            w.write_all(b",")?;
+        }
+        if cell
+            .iter()
+            .any(|c| matches!(c, b',' | b'"' | b'\r' | b'\n'))
+        {
+            w.write_all(b"\"")?;
+            for part in cell.split_inclusive(|&c| c == b'"') {
+                w.write_all(part)?;
+                if part.ends_with(b"\"") {
+                    w.write_all(b"\"")?;
+                }
+            }
+            w.write_all(b"\"")?;
+        } else {
+            w.write_all(cell)?;
+        }
+    }
+    w.write_all(b"\r\n")
+}
+
+/// The rows of the CSV (RFC 4180) `text`, as lists of cells, taking LF as
+/// well as CRLF line endings and skipping blank lines.
+pub fn read(text: &str) -> anyhow::Result<Vec<Vec<String>>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut cell = String::new();
+    let mut chars = text.chars().peekable();
+    let mut lineno = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if cell.is_empty() => loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        cell.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => {
+                        lineno += (c == '\n') as usize;
+                        cell.push(c);
+                    }
+                    None => anyhow::bail!("unterminated quoted cell on line {lineno}"),
+                }
+            },
+            ',' => row.push(std::mem::take(&mut cell)),
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\n' => {
+                lineno += 1;
+                row.push(std::mem::take(&mut cell));
+                if row != [""] {
+                    rows.push(std::mem::take(&mut row));
+                }
+                row.clear();
+            }
+            c => cell.push(c),
+        }
+    }
+    row.push(cell);
+    if row != [""] {
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn union_of_keys() {
+        let input = "{\"a\":1,\"b\":\"x\"}\n\n{\"c\":[1,2],\"a\":null}\n{\"b\":\"say \\\"hi\\\", twice\",\"d\":{}}\n";
+        let header = header(input.as_bytes()).unwrap();
+        assert_eq!(header, ["a", "b", "c", "d"]);
+        let mut out = vec![];
+        assert_eq!(write(input.as_bytes(), &mut out, &header[..3]).unwrap(), 3);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "a,b,c\r\n1,x,\r\n,,\"[1,2]\"\r\n,\"say \"\"hi\"\", twice\",\r\n"
+        );
+    }
+
+    #[test]
+    fn not_objects() {
+        let e = header(&b"{}\n[1]\n"[..]).unwrap_err();
+        assert_eq!(e.to_string(), "the record on line 2 is not an object");
+        let e = write(&b"{\"a\":}\n"[..], std::io::sink(), &[]).unwrap_err();
+        assert_eq!(e.to_string(), "invalid record on line 1");
+    }
+}
@@ -0,0 +1,425 @@
+/// A point in time as read: its wall-clock time, and its offset from UTC if
+/// it had one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    /// seconds from 1970-01-01T00:00:00 to the wall-clock time
+    pub local: i64,
+    pub nanos: u32,
+    /// seconds east of UTC, `None` for a naive time
+    pub offset: Option<i32>,
+}
+
+/// Days since 1970-01-01 of a date of the proleptic Gregorian calendar
+/// (Howard Hinnant's algorithm).
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// The date `days` days after 1970-01-01, as (year, month, day).
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + (month <= 2) as i64, month, day)
+}
+
+/// The number of days in `month` of `year`.
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl DateTime {
+    /// The time `secs` seconds after the Unix epoch, in UTC.
+    pub fn from_unix(secs: f64) -> Option<Self> {
+        // RFC 3339 years are 0000 to 9999
+        if !(-62_167_219_200.0..253_402_300_800.0).contains(&secs) {
+            return None;
+        }
+        let whole = secs.floor();
+        Some(Self {
+            local: whole as i64,
+            nanos: ((secs - whole) * 1e9).round().min(999_999_999.0) as u32,
+            offset: Some(0),
+        })
+    }
+
+    /// The time `millis` milliseconds after the Unix epoch, in UTC, exactly.
+    pub fn from_unix_millis(millis: i64) -> Option<Self> {
+        Self::from_unix(millis.div_euclid(1000) as f64).map(|t| Self {
+            nanos: millis.rem_euclid(1000) as u32 * 1_000_000,
+            ..t
+        })
+    }
+
+    /// Seconds since the Unix epoch, taking a naive time to be in UTC.
+    pub fn unix(&self) -> i64 {
+        self.local - self.offset.unwrap_or(0) as i64
+    }
+
+    /// Make a time of day on a date, checking that both are valid.
+    pub fn from_parts(
+        (year, month, day): (i64, u32, u32),
+        (hour, minute, second): (u32, u32, u32),
+        nanos: u32,
+        offset: Option<i32>,
+    ) -> Option<Self> {
+        let valid = (0..=9999).contains(&year)
+            && (1..=12).contains(&month)
+            && (1..=days_in_month(year, month)).contains(&day)
+            && hour < 24
+            && minute < 60
+            // a leap second, 23:59:60, is the first second of the next day
+            && second <= 60;
+        valid.then(|| Self {
+            local: days_from_civil(year, month, day) * 86400
+                + (hour * 3600 + minute * 60 + second) as i64,
+            nanos,
+            offset,
+        })
+    }
+
+    /// Parse an RFC 3339 timestamp such as `2024-03-01T12:30:00Z` or
+    /// `2024-03-01T12:30:00.5+01:00`, also taking a space or a lowercase
+    /// `t` between the date and the time, a time without an offset (naive)
+    /// and a date alone (midnight, naive).
+    pub fn parse_rfc3339(s: &str) -> Option<Self> {
+        let s = s.as_bytes();
+        let num = |range: std::ops::Range<usize>| -> Option<u32> {
+            let digits = s.get(range)?;
+            (digits.iter().all(u8::is_ascii_digit))
+                .then(|| digits.iter().fold(0, |n, d| n * 10 + (d - b'0') as u32))
+        };
+        let date = (num(0..4)? as i64, num(5..7)?, num(8..10)?);
+        if s.get(4) != Some(&b'-') || s.get(7) != Some(&b'-') {
+            return None;
+        }
+        if s.len() == 10 {
+            return Self::from_parts(date, (0, 0, 0), 0, None);
+        }
+        if !matches!(s.get(10), Some(b'T' | b't' | b' ')) || s.get(13) != Some(&b':') {
+            return None;
+        }
+        let (hour, minute) = (num(11..13)?, num(14..16)?);
+        let (second, mut i) = match s.get(16) {
+            Some(b':') => (num(17..19)?, 19),
+            _ => (0, 16),
+        };
+        let mut nanos = 0;
+        if s.get(i) == Some(&b'.') {
+            let start = i + 1;
+            i = start;
+            while s.get(i).is_some_and(u8::is_ascii_digit) {
+                i += 1;
+            }
+            if i == start {
+                return None;
+            }
+            let digits = &s[start..i.min(start + 9)];
+            nanos = digits.iter().fold(0, |n, d| n * 10 + (d - b'0') as u32)
+                * 10u32.pow(9 - digits.len() as u32);
+        }
+        let offset = match s.get(i) {
+            None => None,
+            Some(b'Z' | b'z') if i + 1 == s.len() => Some(0),
+            Some(&sign @ (b'+' | b'-')) if s.len() == i + 6 && s[i + 3] == b':' => {
+                let offset = (num(i + 1..i + 3)? * 3600 + num(i + 4..i + 6)? * 60) as i32;
+                Some(if sign == b'-' { -offset } else { offset })
+            }
+            _ => return None,
+        };
+        Self::from_parts(date, (hour, minute, second), nanos, offset)
+    }
+
+    /// Format as RFC 3339, with as many digits of fractional seconds as
+    /// needed and the offset if any: `2024-03-01T12:30:00.5+01:00`.
+    pub fn to_rfc3339(&self) -> String {
+        let (days, secs) = (self.local.div_euclid(86400), self.local.rem_euclid(86400));
+        let (year, month, day) = civil_from_days(days);
+        let mut s = format!(
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        );
+        if self.nanos > 0 {
+            let fraction = format!("{:09}", self.nanos);
+            s.push('.');
+            s.push_str(fraction.trim_end_matches('0'));
+        }
+        match self.offset {
+            None => (),
+            Some(0) => s.push('Z'),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let offset = offset.unsigned_abs();
+                s += &format!("{sign}{:02}:{:02}", offset / 3600, offset / 60 % 60);
+            }
+        }
+        s
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+/// Reads the fields of a date and time out of a string, for [`parse`].
+struct Reader<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    /// A number of `min` to `max` digits.
+    fn number(&mut self, min: usize, max: usize) -> Option<u32> {
+        let start = self.pos;
+        while self.pos - start < max && self.s.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+        let digits = &self.s[start..self.pos];
+        (digits.len() >= min).then(|| digits.iter().fold(0, |n, d| n * 10 + (d - b'0') as u32))
+    }
+
+    /// One of `words`, ignoring case, in full or by its first 3 letters.
+    fn word(&mut self, words: &[&str]) -> Option<usize> {
+        let rest = &self.s[self.pos..];
+        for (i, word) in words.iter().enumerate() {
+            let word = word.as_bytes();
+            for word in [word, &word[..word.len().min(3)]] {
+                if rest
+                    .get(..word.len())
+                    .is_some_and(|r| r.eq_ignore_ascii_case(word))
+                {
+                    self.pos += word.len();
+                    return Some(i);
+                }
+            }
+        }
+        None
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        let found = self.s.get(self.pos) == Some(&c);
+        self.pos += found as usize;
+        found
+    }
+}
+
+/// Parse `s` by a `strftime` format: `%Y` (year), `%y` (2 digits, 1969 to
+/// 2068), `%m`, `%d` or `%e`, `%H`, `%I` with `%p` (AM or PM), `%M`, `%S`,
+/// `%f` (fractional seconds, after the dot), `%b` or `%B` (the name of the
+/// month), `%z` (`Z`, `+HHMM` or `+HH:MM`), `%s` (seconds since the Unix
+/// epoch), `%F` for `%Y-%m-%d`, `%T` for `%H:%M:%S`, and `%%`. Other
+/// characters are matched as they are, and fields left out are those of
+/// 1970-01-01T00:00:00.
+pub fn parse(s: &str, format: &str) -> Option<DateTime> {
+    let format = format.replace("%F", "%Y-%m-%d").replace("%T", "%H:%M:%S");
+    let mut r = Reader {
+        s: s.as_bytes(),
+        pos: 0,
+    };
+    let (mut year, mut month, mut day) = (1970, 1, 1);
+    let (mut hour, mut minute, mut second, mut nanos) = (0, 0, 0, 0);
+    let (mut pm, mut offset, mut epoch) = (None, None, None);
+    let mut spec = format.bytes();
+    while let Some(c) = spec.next() {
+        if c != b'%' {
+            if !r.eat(c) {
+                return None;
+            }
+            continue;
+        }
+        match spec.next()? {
+            b'Y' => year = r.number(4, 4)? as i64,
+            b'y' => {
+                let y = r.number(2, 2)? as i64;
+                year = if y < 69 { 2000 + y } else { 1900 + y };
+            }
+            b'm' => month = r.number(1, 2)?,
+            b'd' => day = r.number(1, 2)?,
+            b'e' => {
+                r.eat(b' ');
+                day = r.number(1, 2)?;
+            }
+            b'H' => hour = r.number(1, 2)?,
+            b'I' => {
+                hour = r.number(1, 2)?;
+                if !(1..=12).contains(&hour) {
+                    return None;
+                }
+            }
+            b'p' => pm = Some(r.word(&["am", "pm"])? == 1),
+            b'M' => minute = r.number(2, 2)?,
+            b'S' => second = r.number(2, 2)?,
+            b'f' => {
+                let start = r.pos;
+                let n = r.number(1, 9)?;
+                nanos = n * 10u32.pow(9 - (r.pos - start) as u32);
+                // digits past the nanoseconds are dropped
+                r.number(0, usize::MAX);
+            }
+            b'b' | b'B' | b'h' => month = r.word(&MONTHS)? as u32 + 1,
+            b'z' => {
+                if r.eat(b'Z') || r.eat(b'z') {
+                    offset = Some(0);
+                    continue;
+                }
+                let sign = if r.eat(b'-') {
+                    -1
+                } else if r.eat(b'+') {
+                    1
+                } else {
+                    return None;
+                };
+                let hours = r.number(2, 2)?;
+                r.eat(b':');
+                let minutes = r.number(2, 2)?;
+                offset = Some(sign * (hours * 3600 + minutes * 60) as i32);
+            }
+            b's' => {
+                let start = r.pos;
+                r.eat(b'-');
+                r.number(1, 19)?;
+                if r.eat(b'.') {
+                    r.number(1, usize::MAX)?;
+                }
+                let text = std::str::from_utf8(&r.s[start..r.pos]).ok()?;
+                epoch = Some(text.parse::<f64>().ok()?);
+            }
+            b'%' if r.eat(b'%') => (),
+            _ => return None,
+        }
+    }
+    if r.pos < r.s.len() {
+        return None;
+    }
+    if let Some(secs) = epoch {
+        return DateTime::from_unix(secs);
+    }
+    if let Some(pm) = pm {
+        hour = hour % 12 + if pm { 12 } else { 0 };
+    }
+    DateTime::from_parts((year, month, day), (hour, minute, second), nanos, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rfc3339(s: &str, format: &str) -> Option<String> {
+        parse(s, format).map(|t| t.to_rfc3339())
+    }
+
+    #[test]
+    fn calendar() {
+        for days in [-719_468, -1, 0, 59, 11_016, 19_783, 2_932_896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+        assert_eq!(civil_from_days(19_783), (2024, 3, 1));
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(1900, 2), 28);
+        assert_eq!(days_in_month(2000, 2), 29);
+    }
+
+    #[test]
+    fn rfc3339_timestamps() {
+        for (s, formatted) in [
+            ("2024-03-01T12:30:00Z", "2024-03-01T12:30:00Z"),
+            (
+                "2024-03-01t12:30:00.500+01:00",
+                "2024-03-01T12:30:00.5+01:00",
+            ),
+            ("2024-03-01 12:30-05:30", "2024-03-01T12:30:00-05:30"),
+            ("2024-03-01", "2024-03-01T00:00:00"),
+            (
+                "2024-03-01T12:30:00.1234567891z",
+                "2024-03-01T12:30:00.123456789Z",
+            ),
+        ] {
+            assert_eq!(
+                DateTime::parse_rfc3339(s).unwrap().to_rfc3339(),
+                formatted,
+                "{s}"
+            );
+        }
+        for s in [
+            "2023-02-29",
+            "2024-03-01T24:00:00Z",
+            "2024-03-01T12:30:00+0100",
+            "2024-3-1",
+        ] {
+            assert_eq!(DateTime::parse_rfc3339(s), None, "{s}");
+        }
+        let t = DateTime::parse_rfc3339("2024-03-01T01:00:00+02:00").unwrap();
+        assert_eq!(t.unix(), 1_709_247_600);
+        assert_eq!(
+            DateTime::from_unix_millis(-1).unwrap().to_rfc3339(),
+            "1969-12-31T23:59:59.999Z"
+        );
+        assert_eq!(DateTime::from_unix(1e12), None);
+    }
+
+    #[test]
+    fn formats() {
+        let cases = [
+            ("01/03/2024 12:30", "%d/%m/%Y %H:%M", "2024-03-01T12:30:00"),
+            (
+                "1 Mar 24 7:05:09 pm",
+                "%e %b %y %I:%M:%S %p",
+                "2024-03-01T19:05:09",
+            ),
+            ("December 31, 1999", "%B %d, %Y", "1999-12-31T00:00:00"),
+            (
+                "2024-03-01T12:30:00.25+0130",
+                "%FT%T.%f%z",
+                "2024-03-01T12:30:00.25+01:30",
+            ),
+            ("1709296200.5", "%s", "2024-03-01T12:30:00.5Z"),
+            ("10%", "%H%%", "1970-01-01T10:00:00"),
+        ];
+        for (s, format, formatted) in cases {
+            assert_eq!(
+                rfc3339(s, format).as_deref(),
+                Some(formatted),
+                "{s} by {format}"
+            );
+        }
+        for (s, format) in [
+            ("31/02/2024", "%d/%m/%Y"),
+            ("13:00 pm", "%I:%M %p"),
+            ("2024-03-01 extra", "%F"),
+            ("2024", "%Y-%m"),
+            ("12", "%q"),
+        ] {
+            assert_eq!(rfc3339(s, format), None, "{s} by {format}");
+        }
+    }
+}
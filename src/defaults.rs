@@ -0,0 +1,91 @@
+use serde_json::{Map, Value};
+
+/// Fills in the fields missing from the records with default values, for
+/// --default.
+#[derive(Debug)]
+pub struct Defaults {
+    fields: Vec<(Vec<String>, Value)>,
+}
+
+impl Defaults {
+    /// Default each dotted path of `fields`, like those of a template, to
+    /// its value.
+    pub fn new(fields: &[(String, Value)]) -> Self {
+        Self {
+            fields: (fields.iter())
+                .map(|(field, value)| (field.split('.').map(String::from).collect(), value.clone()))
+                .collect(),
+        }
+    }
+
+    /// Add the fields missing from `record`, and the objects on their paths.
+    /// Fields that are there, null included, are left as they are, as are
+    /// the paths that go through something other than an object.
+    pub fn apply(&self, record: &mut Value) {
+        for (path, default) in &self.fields {
+            let Some((last, parents)) = path.split_last() else {
+                continue;
+            };
+            let mut value = &mut *record;
+            for key in parents {
+                let Value::Object(entries) = value else {
+                    break;
+                };
+                value = (entries.entry(key.clone())).or_insert_with(|| Value::Object(Map::new()));
+            }
+            if let Value::Object(entries) = value {
+                entries
+                    .entry(last.clone())
+                    .or_insert_with(|| default.clone());
+            }
+        }
+    }
+}
+
+/// Parse `FIELD=VALUE`, the value being JSON, or else a string.
+pub fn parse(s: &str) -> Result<(String, Value), String> {
+    match s.split_once('=') {
+        Some((field, value)) if !field.is_empty() => {
+            let value = serde_json::from_str(value).unwrap_or_else(|_| value.into());
+            Ok((field.to_string(), value))
+        }
+        _ => Err(format!("expected FIELD=VALUE, got {s:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn defaults() {
+        let fields = ["n=0", "s=unknown", "a.b.c=[1]", "x.y=true"].map(|s| parse(s).unwrap());
+        let defaults = Defaults::new(&fields);
+        let mut record = json!({"n": null, "x": 5});
+        defaults.apply(&mut record);
+        assert_eq!(
+            record,
+            json!({"n": null, "x": 5, "s": "unknown", "a": {"b": {"c": [1]}}})
+        );
+        let mut record = json!({"s": "", "a": {"d": 1}, "x": {}});
+        defaults.apply(&mut record);
+        assert_eq!(
+            record,
+            json!({"s": "", "a": {"d": 1, "b": {"c": [1]}}, "x": {"y": true}, "n": 0})
+        );
+        let mut record = json!([1]);
+        defaults.apply(&mut record);
+        assert_eq!(record, json!([1]));
+    }
+
+    #[test]
+    fn parsed() {
+        assert_eq!(parse("a=1").unwrap(), ("a".to_string(), json!(1)));
+        assert_eq!(parse("a=\"1\"").unwrap(), ("a".to_string(), json!("1")));
+        assert_eq!(parse("a=b=c").unwrap(), ("a".to_string(), json!("b=c")));
+        assert_eq!(parse("a=").unwrap(), ("a".to_string(), json!("")));
+        assert_eq!(parse("=1").unwrap_err(), "expected FIELD=VALUE, got \"=1\"");
+        assert!(parse("a").is_err());
+    }
+}
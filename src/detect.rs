@@ -0,0 +1,226 @@
+use crate::scan;
+use serde::de::IgnoredAny;
+
+/// Bytes of the input looked at, from its beginning.
+pub const SAMPLE_LEN: usize = 64 * 1024;
+
+/// The compressed or archive format of an input starting with `head`, for
+/// those that aren't read as they are (gzip is).
+pub fn container(head: &[u8]) -> Option<&'static str> {
+    if head.starts_with(b"\x28\xB5\x2F\xFD") {
+        Some("zstd")
+    } else if head.starts_with(b"\xFD7zXZ\x00") {
+        Some("xz")
+    } else if head.starts_with(b"BZh") {
+        Some("bzip2")
+    } else if head.starts_with(b"PK\x03\x04") {
+        Some("zip")
+    } else if head.get(257..262) == Some(b"ustar") {
+        Some("tar")
+    } else {
+        None
+    }
+}
+
+/// The overall shape of the input.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Shape {
+    /// Nothing but whitespace
+    Empty,
+    /// A JSON array
+    Array,
+    /// One JSON value per line, already JSONL
+    Lines,
+    /// A GeoJSON FeatureCollection
+    GeoJson,
+    /// A single value that isn't an array, of this kind (see [`scan::kind`])
+    Single(&'static str),
+    /// Not JSON at all
+    NotJson,
+}
+
+/// What the beginning of an input looks like, and which syntax beyond JSON
+/// it uses there.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub shape: Shape,
+    /// `//` or `/* */` comments
+    pub comments: bool,
+    /// a comma before a closing `]` or `}`
+    pub trailing_commas: bool,
+    /// single-quoted strings or unquoted keys, as in JSON5
+    pub json5: bool,
+    /// NaN or Infinity
+    pub nonfinite: bool,
+}
+
+impl Report {
+    /// Look at `sample`, the beginning of the input as UTF-8, past any byte
+    /// order mark. The sample may end anywhere.
+    pub fn new(sample: &[u8]) -> Self {
+        let mut report = Self {
+            shape: shape(sample),
+            comments: false,
+            trailing_commas: false,
+            json5: false,
+            nonfinite: false,
+        };
+        report.scan(sample);
+        report
+    }
+
+    /// Go over the tokens of `s`, noting the syntax beyond JSON.
+    fn scan(&mut self, s: &[u8]) {
+        // last significant byte outside of strings and comments
+        let mut last = None;
+        let mut i = 0;
+        while i < s.len() {
+            let c = s[i];
+            match c {
+                b'"' | b'\'' => {
+                    self.json5 |= c == b'\'';
+                    i += 1;
+                    while i < s.len() && s[i] != c {
+                        i += if s[i] == b'\\' { 2 } else { 1 };
+                    }
+                    i += 1;
+                    last = Some(b'"');
+                    continue;
+                }
+                b'/' if matches!(s.get(i + 1), Some(b'/' | b'*')) => {
+                    self.comments = true;
+                    let end: &[u8] = if s[i + 1] == b'/' { b"\n" } else { b"*/" };
+                    i = memchr::memmem::find(&s[i + 2..], end)
+                        .map_or(s.len(), |n| i + 2 + n + end.len());
+                    continue;
+                }
+                b'0'..=b'9' => {
+                    // numbers, along with their exponents
+                    while i < s.len() && (s[i].is_ascii_alphanumeric() || b".+-".contains(&s[i])) {
+                        i += 1;
+                    }
+                    last = Some(b'0');
+                    continue;
+                }
+                c if c.is_ascii_alphabetic() || c == b'_' || c == b'$' => {
+                    let start = i;
+                    while i < s.len() && (s[i].is_ascii_alphanumeric() || b"_$".contains(&s[i])) {
+                        i += 1;
+                    }
+                    match &s[start..i] {
+                        b"NaN" | b"Infinity" => self.nonfinite = true,
+                        b"true" | b"false" | b"null" => (),
+                        _ if matches!(last, Some(b'{' | b',')) => {
+                            let next = s[i..].iter().find(|c| !c.is_ascii_whitespace());
+                            self.json5 |= next == Some(&b':');
+                        }
+                        _ => (),
+                    }
+                    last = Some(b'a');
+                    continue;
+                }
+                b']' | b'}' if last == Some(b',') => self.trailing_commas = true,
+                _ => (),
+            }
+            if !c.is_ascii_whitespace() {
+                last = Some(c);
+            }
+            i += 1;
+        }
+    }
+}
+
+fn shape(sample: &[u8]) -> Shape {
+    let Some(start) = first_byte(sample) else {
+        return Shape::Empty;
+    };
+    let c = sample[start];
+    match c {
+        b'[' => return Shape::Array,
+        _ if scan::kind(c) == "not a JSON value" => return Shape::NotJson,
+        _ => (),
+    }
+    // another value on the next line, even cut short by the end of the
+    // sample, makes it JSONL
+    let sample = &sample[start..];
+    let mut values = serde_json::Deserializer::from_slice(sample).into_iter::<IgnoredAny>();
+    if let Some(Ok(_)) = values.next() {
+        let rest = &sample[values.byte_offset()..];
+        let next = rest.iter().position(|c| !c.is_ascii_whitespace());
+        if let Some(n) = next
+            && rest[..n].contains(&b'\n')
+            && !matches!(values.next(), Some(Err(e)) if !e.is_eof())
+        {
+            return Shape::Lines;
+        }
+    }
+    if c == b'{' && memchr::memmem::find(sample, b"\"FeatureCollection\"").is_some() {
+        return Shape::GeoJson;
+    }
+    Shape::Single(scan::kind(c))
+}
+
+/// The position of the first byte of `s` that isn't whitespace or in a
+/// comment, if any.
+fn first_byte(s: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    loop {
+        i += s[i..].iter().position(|c| !c.is_ascii_whitespace())?;
+        let end: &[u8] = match s.get(i..i + 2) {
+            Some(b"//") => b"\n",
+            Some(b"/*") => b"*/",
+            _ => return Some(i),
+        };
+        i += 2 + memchr::memmem::find(&s[i + 2..], end)? + end.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes() {
+        let shapes = [
+            (&b" \n"[..], Shape::Empty),
+            (b"/* c */ [1", Shape::Array),
+            (b"{\"a\":1}\n{\"a\":", Shape::Lines),
+            (b"1\n2\n", Shape::Lines),
+            (
+                b"{\"type\": \"FeatureCollection\", \"features\": [",
+                Shape::GeoJson,
+            ),
+            (b"{\"a\":1} x", Shape::Single("an object")),
+            (b"\"s\"", Shape::Single("a string")),
+            (b"a,b\n1,2", Shape::NotJson),
+        ];
+        for (sample, expected) in shapes {
+            assert_eq!(
+                shape(sample),
+                expected,
+                "{:?}",
+                String::from_utf8_lossy(sample)
+            );
+        }
+    }
+
+    #[test]
+    fn syntax() {
+        let report = Report::new(b"[{\"a\": 'x', // c\n b: [1e5, NaN,],}");
+        assert!(report.json5 && report.comments && report.trailing_commas && report.nonfinite);
+        // only in strings
+        let report = Report::new(b"[\"'//,]\", {\"k\": \"NaN\"}, true, 1e-5]");
+        assert!(!report.json5 && !report.comments && !report.trailing_commas);
+        assert!(!report.nonfinite);
+    }
+
+    #[test]
+    fn containers() {
+        assert_eq!(container(b"\x28\xB5\x2F\xFDxx"), Some("zstd"));
+        assert_eq!(container(b"PK\x03\x04"), Some("zip"));
+        let mut tar = vec![0; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(container(&tar), Some("tar"));
+        assert_eq!(container(b"\x1f\x8b"), None);
+    }
+}
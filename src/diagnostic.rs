@@ -0,0 +1,164 @@
+use std::fmt;
+
+/// Bytes shown at most on either side of the failure.
+const WIDTH: usize = 40;
+
+/// The bytes of an element around a parse error, to show with a caret under
+/// the byte at fault and a hint at the likely cause.
+#[derive(Debug, Clone)]
+pub struct Snippet {
+    /// the line of the failure, clipped around it
+    text: String,
+    /// column of the caret in `text`, in characters
+    caret: usize,
+    hint: Option<&'static str>,
+}
+
+impl Snippet {
+    /// The snippet for a failure at byte `at` of `element`, or at its end if
+    /// `at` is its length.
+    pub fn new(element: &[u8], at: usize) -> Self {
+        let at = at.min(element.len());
+        let line_start = (element[..at].iter().rposition(|&c| c == b'\n')).map_or(0, |i| i + 1);
+        let line_end =
+            (element[at..].iter().position(|&c| c == b'\n')).map_or(element.len(), |i| at + i);
+        let mut start = line_start.max(at.saturating_sub(WIDTH));
+        let mut end = line_end.min(at + WIDTH);
+        // don't cut a character in two
+        while start < at && is_continuation(element[start]) {
+            start += 1;
+        }
+        while end > at && end < element.len() && is_continuation(element[end]) {
+            end -= 1;
+        }
+        let mut text = String::new();
+        if start > line_start {
+            text.push_str("...");
+        }
+        push_escaped(&mut text, &element[start..at]);
+        let caret = text.chars().count();
+        push_escaped(&mut text, &element[at..end]);
+        if end < line_end {
+            text.push_str("...");
+        }
+        Self {
+            text,
+            caret,
+            hint: hint(element, at),
+        }
+    }
+
+    /// The snippet for `err`, from parsing `element`, if it has a position.
+    pub fn from_json_error(element: &[u8], err: &serde_json::Error) -> Option<Self> {
+        if err.line() == 0 {
+            return None;
+        }
+        if err.is_eof() {
+            return Some(Self::new(element, element.len()));
+        }
+        let line_start: usize = (element.split(|&c| c == b'\n'))
+            .take(err.line() - 1)
+            .map(|line| line.len() + 1)
+            .sum();
+        // the column is that of the byte at fault, counting from 1, except
+        // for control characters, which come right after it
+        let mut at = line_start + err.column().saturating_sub(1);
+        if element.get(at + 1).is_some_and(|&c| c < 0x20 && c != b'\n') {
+            at += 1;
+        }
+        Some(Self::new(element, at))
+    }
+}
+
+impl fmt::Display for Snippet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "  | {}", self.text)?;
+        write!(f, "  | {:1$}^", "", self.caret)?;
+        if let Some(hint) = self.hint {
+            write!(f, " {hint}")?;
+        }
+        Ok(())
+    }
+}
+
+fn is_continuation(c: u8) -> bool {
+    c & 0xC0 == 0x80
+}
+
+/// Append `bytes` to `text`, with control characters escaped so that each
+/// byte takes up a column or more and the caret lines up.
+fn push_escaped(text: &mut String, bytes: &[u8]) {
+    for c in String::from_utf8_lossy(bytes).chars() {
+        if c.is_control() {
+            text.extend(c.escape_debug());
+        } else {
+            text.push(c);
+        }
+    }
+}
+
+/// The likely cause of a failure at byte `at` of `element`, for the usual
+/// mistakes.
+fn hint(element: &[u8], at: usize) -> Option<&'static str> {
+    let before = (element[..at].iter().rev())
+        .find(|c| !c.is_ascii_whitespace())
+        .copied();
+    Some(match (before, element.get(at).copied()) {
+        (_, None) => "the element ends early: possible unclosed string, array or object",
+        (Some(b','), Some(b']' | b'}')) => "possible trailing comma",
+        (Some(b',' | b'['), Some(b',')) => "possible missing value between commas",
+        (_, Some(b'/')) => "possible comment",
+        (_, Some(b'\'')) => "possible single-quoted string",
+        (Some(b'{' | b','), Some(c)) if c.is_ascii_alphabetic() || c == b'_' || c == b'$' => {
+            "possible unquoted key"
+        }
+        (Some(b'"' | b'}' | b']' | b'0'..=b'9' | b'e' | b'l'), Some(b'"' | b'{' | b'[')) => {
+            "possible missing ','"
+        }
+        (_, Some(c)) if c < 0x20 => "possible raw control character in a string",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippet(element: &str) -> String {
+        let err = serde_json::from_str::<serde_json::Value>(element).unwrap_err();
+        Snippet::from_json_error(element.as_bytes(), &err)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn carets_and_hints() {
+        assert_eq!(
+            snippet("[1,2,]"),
+            "  | [1,2,]\n  |      ^ possible trailing comma"
+        );
+        assert_eq!(snippet("{a:1}"), "  | {a:1}\n  |  ^ possible unquoted key");
+        assert_eq!(
+            snippet("{\"a\":\n \"é\" \"b\"}"),
+            "  |  \"é\" \"b\"}\n  |      ^ possible missing ','"
+        );
+        assert_eq!(
+            snippet("[\"x"),
+            "  | [\"x\n  |    ^ the element ends early: possible unclosed string, array or object"
+        );
+        assert_eq!(
+            snippet("[\"a\tb\"]"),
+            "  | [\"a\\tb\"]\n  |    ^ possible raw control character in a string"
+        );
+        assert_eq!(snippet("[tru]"), "  | [tru]\n  |     ^");
+    }
+
+    #[test]
+    fn clipped() {
+        let element = format!("[{}, x, {}]", "1".repeat(100), "2".repeat(100));
+        let snippet = Snippet::new(element.as_bytes(), 103);
+        assert_eq!(snippet.caret, 43);
+        assert!(snippet.text.starts_with("...") && snippet.text.ends_with("..."));
+        assert_eq!(snippet.text.len(), 3 + 2 * WIDTH + 3);
+    }
+}
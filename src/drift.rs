@@ -0,0 +1,204 @@
+use crate::spill::OVERHEAD;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// The JSON types, as bits of a set.
+pub(crate) const TYPES: [&str; 6] = ["null", "boolean", "number", "string", "array", "object"];
+
+pub(crate) fn type_bit(value: &Value) -> u8 {
+    1 << match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
+    }
+}
+
+pub(crate) fn type_names(types: u8) -> String {
+    let names: Vec<_> = (TYPES.iter().enumerate())
+        .filter(|(i, _)| types & (1 << i) != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    names.join("|")
+}
+
+/// The types other than null, unless there are none: a field that is null
+/// in some records of a file and not in those of another hasn't changed.
+fn non_null(types: u8) -> u8 {
+    match types & !1 {
+        0 => types,
+        other => other,
+    }
+}
+
+/// The schema inferred from the records of a file: the types found at each
+/// dotted path into the objects.
+#[derive(Debug, Clone, Default)]
+struct Schema {
+    name: String,
+    records: u64,
+    fields: BTreeMap<String, u8>,
+    /// whether some fields were left out, past the budget of the tables
+    incomplete: bool,
+}
+
+impl Schema {
+    /// Add the fields of `value` under `prefix`, counting the new ones
+    /// against the bytes left in `room`.
+    fn add(&mut self, prefix: &mut String, value: &Value, room: &mut usize) {
+        let Value::Object(fields) = value else {
+            return;
+        };
+        for (key, value) in fields {
+            let len = prefix.len();
+            if len > 0 {
+                prefix.push('.');
+            }
+            prefix.push_str(key);
+            match self.fields.get_mut(prefix.as_str()) {
+                Some(types) => *types |= type_bit(value),
+                None if *room >= prefix.len() + OVERHEAD => {
+                    *room -= prefix.len() + OVERHEAD;
+                    self.fields.insert(prefix.clone(), type_bit(value));
+                }
+                None => self.incomplete = true,
+            }
+            self.add(prefix, value, room);
+            prefix.truncate(len);
+        }
+    }
+}
+
+/// Compares the schemas of the records of several files, to report the
+/// fields that appear, disappear or change type from one file to the next,
+/// e.g. when an export changes format.
+#[derive(Debug, Clone)]
+pub struct Drift {
+    schemas: Vec<Schema>,
+    /// bytes left for the fields of all the files
+    room: usize,
+}
+
+impl Default for Drift {
+    fn default() -> Self {
+        Self {
+            schemas: vec![],
+            room: usize::MAX,
+        }
+    }
+}
+
+impl Drift {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep about `max_bytes` of fields at most, for all the files: those
+    /// past them are left out of the comparison, and the report says so.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.room = max_bytes;
+        self
+    }
+
+    /// Start the records of file `name`.
+    pub fn start(&mut self, name: &str) {
+        self.schemas.push(Schema {
+            name: name.to_string(),
+            ..Schema::default()
+        });
+    }
+
+    /// Add a record of the current file. Fields are the members of objects,
+    /// nested ones included, but not those of the objects in arrays.
+    pub fn add(&mut self, record: &Value) {
+        if self.schemas.is_empty() {
+            self.start("");
+        }
+        let schema = self.schemas.last_mut().unwrap();
+        schema.records += 1;
+        schema.add(&mut String::new(), record, &mut self.room);
+    }
+
+    /// Write the changes between each file and the previous one with
+    /// records, returning the number of files that differ from the one
+    /// before them.
+    pub fn write_report(&self, mut w: impl Write) -> io::Result<usize> {
+        let schemas: Vec<_> = self.schemas.iter().filter(|s| s.records > 0).collect();
+        let mut changed = 0;
+        for pair in schemas.windows(2) {
+            let (before, after) = (pair[0], pair[1]);
+            let mut changes = vec![];
+            for (path, &types) in &after.fields {
+                match before.fields.get(path) {
+                    None => changes.push(format!("  + {path}: {}", type_names(types))),
+                    Some(&old) if non_null(old) != non_null(types) => changes.push(format!(
+                        "  ~ {path}: {} -> {}",
+                        type_names(old),
+                        type_names(types)
+                    )),
+                    Some(_) => (),
+                }
+            }
+            for (path, &types) in &before.fields {
+                if !after.fields.contains_key(path) {
+                    changes.push(format!("  - {path}: {}", type_names(types)));
+                }
+            }
+            if changes.is_empty() {
+                continue;
+            }
+            changed += 1;
+            writeln!(w, "{} -> {}:", before.name, after.name)?;
+            for change in changes {
+                writeln!(w, "{change}")?;
+            }
+        }
+        if changed == 0 {
+            writeln!(w, "no schema drift across {} files", schemas.len())?;
+        }
+        for schema in schemas.iter().filter(|s| s.incomplete) {
+            writeln!(
+                w,
+                "some fields of {} were not compared, past the memory limit",
+                schema.name
+            )?;
+        }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn report(mut drift: Drift) -> String {
+        drift.start("a.json");
+        drift.add(&json!({"x": 1, "y": "s"}));
+        drift.start("b.json");
+        drift.add(&json!({"x": "t", "z": null}));
+        let mut report = vec![];
+        assert_eq!(drift.write_report(&mut report).unwrap(), 1);
+        String::from_utf8(report).unwrap()
+    }
+
+    #[test]
+    fn changes() {
+        assert_eq!(
+            report(Drift::new()),
+            "a.json -> b.json:\n  ~ x: number -> string\n  + z: null\n  - y: string\n"
+        );
+    }
+
+    #[test]
+    fn max_bytes() {
+        assert_eq!(
+            report(Drift::new().max_bytes(3 * (1 + OVERHEAD))),
+            "a.json -> b.json:\n  ~ x: number -> string\n  - y: string\n\
+             some fields of b.json were not compared, past the memory limit\n"
+        );
+    }
+}
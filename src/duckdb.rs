@@ -0,0 +1,288 @@
+use crate::sink::Sink;
+use ::duckdb::types::Value as DbValue;
+use ::duckdb::{Connection, appender_params_from_iter};
+use anyhow::{Context, bail};
+use serde_json::{Map, Value};
+
+/// Records the columns of a new table are inferred from.
+const SAMPLE: usize = 1024;
+
+/// What to do with the table of a [`DuckdbSink`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableMode {
+    /// create it, failing if it exists
+    Create,
+    /// create it, replacing the existing one
+    Replace,
+    /// add to the existing one, by column name
+    Append,
+}
+
+/// Settings of a [`DuckdbSink`].
+#[derive(Debug, Clone)]
+pub struct DuckdbConfig {
+    /// the database file
+    pub path: String,
+    pub table: String,
+    pub mode: TableMode,
+    /// Rows per appender flush
+    pub batch: usize,
+}
+
+/// Loads the records, which must be objects, as rows of a table of a DuckDB
+/// database through its appender, in a single transaction: the table only
+/// changes if all the records make it.
+///
+/// A new table has a column for each field of the first records, in the order
+/// they first appear: BOOLEAN, BIGINT or DOUBLE for the fields that only hold
+/// those (or null), and VARCHAR otherwise, arrays and objects being written
+/// as their JSON text.
+pub struct DuckdbSink {
+    conn: Connection,
+    config: DuckdbConfig,
+    /// those of the table, once it is created or found
+    columns: Option<Vec<String>>,
+    pending: Vec<Map<String, Value>>,
+}
+
+/// Quote `name` as an SQL identifier.
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// The type of a column holding `values`.
+fn column_type<'a>(values: impl Iterator<Item = &'a Value>) -> &'static str {
+    let (mut bools, mut ints, mut numbers, mut others) = (false, false, false, false);
+    for value in values {
+        match value {
+            Value::Null => (),
+            Value::Bool(_) => bools = true,
+            Value::Number(n) if n.as_i64().is_some() => ints = true,
+            Value::Number(_) => numbers = true,
+            _ => others = true,
+        }
+    }
+    match (bools, ints, numbers, others) {
+        (true, false, false, false) => "BOOLEAN",
+        (false, true, false, false) => "BIGINT",
+        (false, _, true, false) => "DOUBLE",
+        _ => "VARCHAR",
+    }
+}
+
+fn db_value(value: Option<Value>) -> DbValue {
+    match value {
+        None | Some(Value::Null) => DbValue::Null,
+        Some(Value::Bool(b)) => DbValue::Boolean(b),
+        Some(Value::Number(n)) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => DbValue::BigInt(i),
+            (None, Some(f)) => DbValue::Double(f),
+            (None, None) => DbValue::Text(n.to_string()),
+        },
+        Some(Value::String(s)) => DbValue::Text(s),
+        Some(other) => DbValue::Text(other.to_string()),
+    }
+}
+
+impl DuckdbSink {
+    pub fn open(config: DuckdbConfig) -> anyhow::Result<Self> {
+        let conn = Connection::open(&config.path)
+            .with_context(|| format!("opening the database {}", config.path))?;
+        conn.execute_batch("BEGIN TRANSACTION")?;
+        let mut sink = Self {
+            conn,
+            config,
+            columns: None,
+            pending: vec![],
+        };
+        if sink.config.mode == TableMode::Append {
+            let columns = sink.table_columns()?;
+            if columns.is_empty() {
+                bail!("there is no table {} to append to", sink.config.table);
+            }
+            sink.columns = Some(columns);
+        }
+        Ok(sink)
+    }
+
+    fn table_columns(&self) -> anyhow::Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT column_name FROM information_schema.columns \
+             WHERE table_name = ? ORDER BY ordinal_position",
+        )?;
+        let rows = stmt.query_map([&self.config.table], |row| row.get(0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Create the table, with columns for the pending records.
+    fn create_table(&mut self) -> anyhow::Result<()> {
+        let mut columns: Vec<String> = vec![];
+        for record in &self.pending {
+            for key in record.keys() {
+                if !columns.contains(key) {
+                    columns.push(key.clone());
+                }
+            }
+        }
+        if columns.is_empty() {
+            bail!(
+                "the records have no fields to make the columns of {} from",
+                self.config.table
+            );
+        }
+        let defs: Vec<_> = (columns.iter())
+            .map(|c| {
+                let values = self.pending.iter().filter_map(|r| r.get(c));
+                format!("{} {}", quote(c), column_type(values))
+            })
+            .collect();
+        let create = match self.config.mode {
+            TableMode::Replace => "CREATE OR REPLACE TABLE",
+            _ => "CREATE TABLE",
+        };
+        let table = quote(&self.config.table);
+        (self.conn)
+            .execute_batch(&format!("{create} {table} ({})", defs.join(", ")))
+            .with_context(|| format!("creating the table {}", self.config.table))?;
+        self.columns = Some(columns);
+        Ok(())
+    }
+
+    /// Append the pending records, creating the table first if there is
+    /// none yet.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if self.columns.is_none() {
+            if self.pending.is_empty() {
+                return Ok(());
+            }
+            self.create_table()?;
+        }
+        let columns = self.columns.as_ref().unwrap();
+        let mut appender = self.conn.appender(&self.config.table)?;
+        for mut record in self.pending.drain(..) {
+            if let Some(key) = record.keys().find(|k| !columns.contains(k)) {
+                bail!("field {key:?} is not a column of {}", self.config.table);
+            }
+            let row = columns.iter().map(|c| db_value(record.remove(c)));
+            appender.append_row(appender_params_from_iter(row))?;
+        }
+        appender.flush()?;
+        Ok(())
+    }
+}
+
+impl Sink for DuckdbSink {
+    fn push(&mut self, record: &[u8]) -> anyhow::Result<()> {
+        let Ok(record) = serde_json::from_slice(record) else {
+            bail!("--to duckdb loads objects into the columns of a table, got another value");
+        };
+        self.pending.push(record);
+        let batch = match self.columns {
+            None => SAMPLE,
+            Some(_) => self.config.batch,
+        };
+        if self.pending.len() >= batch {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.flush()?;
+        self.conn.execute_batch("COMMIT")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sink(path: &str, mode: TableMode) -> anyhow::Result<DuckdbSink> {
+        DuckdbSink::open(DuckdbConfig {
+            path: path.to_string(),
+            table: "records".to_string(),
+            mode,
+            batch: 2,
+        })
+    }
+
+    fn load(sink: &mut DuckdbSink, records: &[&str]) -> anyhow::Result<()> {
+        for record in records {
+            sink.push(record.as_bytes())?;
+        }
+        sink.finish()
+    }
+
+    #[test]
+    fn columns() {
+        let mut sink = sink(":memory:", TableMode::Create).unwrap();
+        let records = [
+            r#"{"id":1,"ok":true,"x":1}"#,
+            r#"{"id":2,"x":2.5,"tags":["a"],"name":"b"}"#,
+            r#"{"ok":null,"id":3}"#,
+        ];
+        load(&mut sink, &records).unwrap();
+        let columns = sink.table_columns().unwrap();
+        assert_eq!(columns, ["id", "ok", "x", "tags", "name"]);
+        let types: Vec<String> = (sink.conn)
+            .prepare("SELECT data_type FROM information_schema.columns ORDER BY ordinal_position")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(types, ["BIGINT", "BOOLEAN", "DOUBLE", "VARCHAR", "VARCHAR"]);
+        type Row = (i64, Option<bool>, Option<f64>, Option<String>);
+        let rows: Vec<Row> = (sink.conn)
+            .prepare("SELECT id, ok, x, tags FROM records ORDER BY id")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let tags = Some(r#"["a"]"#.to_string());
+        assert_eq!(
+            rows,
+            [
+                (1, Some(true), Some(1.0), None),
+                (2, None, Some(2.5), tags),
+                (3, None, None, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn modes() {
+        let dir = std::env::temp_dir().join(format!("json2jsonl-duckdb-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("db.duckdb");
+        let path = path.to_str().unwrap();
+        let e = sink(path, TableMode::Append).err().unwrap();
+        assert_eq!(e.to_string(), "there is no table records to append to");
+        // nothing is kept of a failed load
+        let mut failed = sink(path, TableMode::Create).unwrap();
+        assert!(load(&mut failed, &[r#"{"a":1}"#, "[2]"]).is_err());
+        drop(failed);
+        load(&mut sink(path, TableMode::Create).unwrap(), &[r#"{"a":1}"#]).unwrap();
+        let mut appended = sink(path, TableMode::Append).unwrap();
+        load(&mut appended, &[r#"{"a":2}"#, "{}"]).unwrap();
+        let count: i64 = (appended.conn)
+            .query_row("SELECT count(*) FROM records", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 3);
+        drop(appended);
+        let e = load(&mut sink(path, TableMode::Append).unwrap(), &[r#"{"b":1}"#]).unwrap_err();
+        assert_eq!(e.to_string(), "field \"b\" is not a column of records");
+        let e = load(&mut sink(path, TableMode::Create).unwrap(), &[r#"{"a":1}"#]).unwrap_err();
+        assert_eq!(e.to_string(), "creating the table records");
+        load(
+            &mut sink(path, TableMode::Replace).unwrap(),
+            &[r#"{"c":1}"#],
+        )
+        .unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
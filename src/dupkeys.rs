@@ -0,0 +1,213 @@
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde_json::{Map, Number, Value};
+use std::fmt;
+
+/// What to do when an object has the same key several times.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, clap::ValueEnum)]
+pub enum DuplicateKeys {
+    /// Reject the record
+    Error,
+    /// Keep the first value
+    First,
+    /// Keep the last value
+    Last,
+    /// Keep the last value, and report the key
+    Warn,
+}
+
+/// The key serde_json uses to pass arbitrary precision numbers as a map.
+const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+/// `-0` as a number of its own. Numbers keep their text with
+/// arbitrary_precision, but serde_json parses this one into 0, and only has
+/// a hidden constructor for the others.
+fn negative_zero() -> Number {
+    Number::from_string_unchecked("-0".to_string())
+}
+
+/// Parse `element` like `serde_json::from_slice`, but keeping a `-0` as it
+/// is rather than turning it into 0.
+pub fn from_slice(element: &[u8]) -> serde_json::Result<Value> {
+    if memchr::memmem::find(element, b"-0").is_none() {
+        return serde_json::from_slice(element);
+    }
+    // the same as serde_json's for duplicates: the last value, in the place of the first
+    let mut dups = vec![];
+    let mut deser = serde_json::Deserializer::from_slice(element);
+    let value = Seed::new(DuplicateKeys::Last, &mut dups)
+        .negative_zero(true)
+        .deserialize(&mut deser)?;
+    deser.end()?;
+    Ok(value)
+}
+
+/// Deserializes a [`Value`], applying a [`DuplicateKeys`] policy and
+/// collecting the duplicated keys in `dups`.
+pub struct Seed<'a> {
+    policy: DuplicateKeys,
+    dups: &'a mut Vec<String>,
+    negative_zero: bool,
+}
+
+impl<'a> Seed<'a> {
+    pub fn new(policy: DuplicateKeys, dups: &'a mut Vec<String>) -> Self {
+        Self {
+            policy,
+            dups,
+            negative_zero: false,
+        }
+    }
+
+    /// Read the signed 0 integer as `-0`, for serde_json's deserializer,
+    /// which only passes `-0` on as one (the other integers it reads as
+    /// signed are negative).
+    pub fn negative_zero(mut self, yes: bool) -> Self {
+        self.negative_zero = yes;
+        self
+    }
+
+    fn reborrow(&mut self) -> Seed<'_> {
+        Seed {
+            policy: self.policy,
+            dups: self.dups,
+            negative_zero: self.negative_zero,
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for Seed<'_> {
+    type Value = Value;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, d: D) -> Result<Value, D::Error> {
+        d.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Seed<'_> {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any valid JSON value")
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<Value, E> {
+        Ok(Value::Bool(b))
+    }
+
+    fn visit_i64<E>(self, n: i64) -> Result<Value, E> {
+        match n {
+            0 if self.negative_zero => Ok(Value::Number(negative_zero())),
+            n => Ok(n.into()),
+        }
+    }
+
+    fn visit_u64<E>(self, n: u64) -> Result<Value, E> {
+        Ok(n.into())
+    }
+
+    fn visit_i128<E: de::Error>(self, n: i128) -> Result<Value, E> {
+        n.to_string().parse().map(Value::Number).map_err(E::custom)
+    }
+
+    fn visit_u128<E: de::Error>(self, n: u128) -> Result<Value, E> {
+        n.to_string().parse().map(Value::Number).map_err(E::custom)
+    }
+
+    fn visit_f64<E>(self, f: f64) -> Result<Value, E> {
+        Ok(Number::from_f64(f).map_or(Value::Null, Value::Number))
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Value, E> {
+        Ok(Value::String(s.to_string()))
+    }
+
+    fn visit_string<E>(self, s: String) -> Result<Value, E> {
+        Ok(Value::String(s))
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = vec![];
+        while let Some(x) = seq.next_element_seed(self.reborrow())? {
+            items.push(x);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<Value, A::Error> {
+        let mut obj = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if key == NUMBER_TOKEN && obj.is_empty() {
+                let n: String = map.next_value()?;
+                return n.parse().map(Value::Number).map_err(de::Error::custom);
+            }
+            let value = map.next_value_seed(self.reborrow())?;
+            if !obj.contains_key(&key) {
+                obj.insert(key, value);
+                continue;
+            }
+            match self.policy {
+                DuplicateKeys::Error => {
+                    return Err(de::Error::custom(format!("duplicate key {key:?}")));
+                }
+                DuplicateKeys::First => (),
+                DuplicateKeys::Last | DuplicateKeys::Warn => {
+                    obj.insert(key.clone(), value);
+                }
+            }
+            self.dups.push(key);
+        }
+        Ok(Value::Object(obj))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_zero() {
+        let text = r#"{"a":-0,"b":[0,-0,-1,-0.0,-0e1],"c":"-0"}"#;
+        assert_eq!(from_slice(text.as_bytes()).unwrap().to_string(), text);
+        let mut dups = vec![];
+        let mut deser = serde_json::Deserializer::from_str(text);
+        let seed = Seed::new(DuplicateKeys::Error, &mut dups).negative_zero(true);
+        assert_eq!(seed.deserialize(&mut deser).unwrap().to_string(), text);
+    }
+
+    #[test]
+    fn duplicates() {
+        let text = r#"{"a":1,"b":2,"a":3}"#;
+        for (policy, expected) in [
+            (DuplicateKeys::First, r#"{"a":1,"b":2}"#),
+            (DuplicateKeys::Last, r#"{"a":3,"b":2}"#),
+        ] {
+            let mut dups = vec![];
+            let mut deser = serde_json::Deserializer::from_str(text);
+            let value = Seed::new(policy, &mut dups)
+                .deserialize(&mut deser)
+                .unwrap();
+            assert_eq!(value.to_string(), expected);
+            assert_eq!(dups, ["a"]);
+        }
+        let mut dups = vec![];
+        let mut deser = serde_json::Deserializer::from_str(text);
+        assert!(
+            Seed::new(DuplicateKeys::Error, &mut dups)
+                .deserialize(&mut deser)
+                .is_err()
+        );
+        // same as serde_json's
+        assert_eq!(
+            from_slice(br#"{"a":-0,"b":2,"a":3}"#).unwrap().to_string(),
+            r#"{"a":3,"b":2}"#
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::io::{self, ErrorKind, Read};
+
+/// Character encoding of the input.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, clap::ValueEnum)]
+pub enum Encoding {
+    /// Detect from the byte order mark, or from the zero bytes around the first character
+    Auto,
+    Utf8,
+    Utf16le,
+    Utf16be,
+    Utf32le,
+    Utf32be,
+}
+
+impl Encoding {
+    /// Guess the encoding from the first bytes of the input. JSON text starts
+    /// with an ASCII character, so the zero bytes give it away even without
+    /// a byte order mark.
+    fn detect(buf: &[u8]) -> Encoding {
+        match buf {
+            [0xFF, 0xFE, 0, 0, ..] | [_, 0, 0, 0, ..] => Encoding::Utf32le,
+            [0, 0, 0xFE, 0xFF, ..] | [0, 0, 0, _, ..] => Encoding::Utf32be,
+            [0xFF, 0xFE, ..] | [_, 0, ..] => Encoding::Utf16le,
+            [0xFE, 0xFF, ..] | [0, _, ..] => Encoding::Utf16be,
+            _ => Encoding::Utf8,
+        }
+    }
+
+    /// The encoding of an input starting with `buf`: this one, or the one
+    /// detected from `buf` for [`Encoding::Auto`].
+    pub fn resolve(self, buf: &[u8]) -> Encoding {
+        match self {
+            Encoding::Auto => Encoding::detect(buf),
+            e => e,
+        }
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// Transcodes UTF-16 or UTF-32 input to UTF-8; UTF-8 input is passed through.
+///
+/// A byte order mark is transcoded like any other character, and left for
+/// the caller to skip.
+pub struct Decoder<R> {
+    rd: R,
+    encoding: Encoding,
+    /// input bytes not decoded yet
+    raw: Vec<u8>,
+    /// offset in the input of `raw[0]`
+    offset: u64,
+    out: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(rd: R, encoding: Encoding) -> Self {
+        Self {
+            rd,
+            encoding,
+            raw: vec![],
+            offset: 0,
+            out: vec![],
+            pos: 0,
+        }
+    }
+
+    /// The encoding of the input, known once something has been read.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Read more input into `raw`. Returns `false` at the end of input.
+    fn fill_raw(&mut self) -> io::Result<bool> {
+        let mut chunk = [0u8; 16 * 1024];
+        let n = self.rd.read(&mut chunk)?;
+        self.raw.extend_from_slice(&chunk[..n]);
+        Ok(n > 0)
+    }
+
+    /// Decode the complete characters of `raw` into `out`.
+    fn decode(&mut self, eof: bool) -> io::Result<()> {
+        let mut buf = [0u8; 4];
+        let mut used = 0;
+        match self.encoding {
+            Encoding::Utf16le | Encoding::Utf16be => {
+                let be = self.encoding == Encoding::Utf16be;
+                let mut units: Vec<u16> = self
+                    .raw
+                    .chunks_exact(2)
+                    .map(|b| {
+                        let b = [b[0], b[1]];
+                        if be {
+                            u16::from_be_bytes(b)
+                        } else {
+                            u16::from_le_bytes(b)
+                        }
+                    })
+                    .collect();
+                // the other half of the pair is in the next chunk
+                if !eof && units.last().is_some_and(|u| (0xD800..0xDC00).contains(u)) {
+                    units.pop();
+                }
+                for c in char::decode_utf16(units) {
+                    let c = c.map_err(|e| {
+                        invalid(format!(
+                            "unpaired surrogate {:#06x} at offset {}",
+                            e.unpaired_surrogate(),
+                            self.offset + used as u64
+                        ))
+                    })?;
+                    self.out
+                        .extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                    used += 2 * c.len_utf16();
+                }
+            }
+            Encoding::Utf32le | Encoding::Utf32be => {
+                let be = self.encoding == Encoding::Utf32be;
+                for b in self.raw.chunks_exact(4) {
+                    let b = [b[0], b[1], b[2], b[3]];
+                    let u = if be {
+                        u32::from_be_bytes(b)
+                    } else {
+                        u32::from_le_bytes(b)
+                    };
+                    let c = char::from_u32(u).ok_or_else(|| {
+                        invalid(format!(
+                            "invalid code point {u:#x} at offset {}",
+                            self.offset + used as u64
+                        ))
+                    })?;
+                    self.out
+                        .extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                    used += 4;
+                }
+            }
+            Encoding::Auto | Encoding::Utf8 => unreachable!(),
+        }
+        if eof && used < self.raw.len() {
+            return Err(invalid(format!(
+                "truncated character at offset {}",
+                self.offset + used as u64
+            )));
+        }
+        self.raw.drain(..used);
+        self.offset += used as u64;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.encoding == Encoding::Auto {
+            while self.raw.len() < 4 && self.fill_raw()? {}
+            self.encoding = Encoding::detect(&self.raw);
+        }
+        if self.encoding == Encoding::Utf8 {
+            if self.raw.is_empty() {
+                return self.rd.read(buf);
+            }
+            let n = buf.len().min(self.raw.len());
+            buf[..n].copy_from_slice(&self.raw[..n]);
+            self.raw.drain(..n);
+            return Ok(n);
+        }
+        while self.pos == self.out.len() {
+            self.out.clear();
+            self.pos = 0;
+            let eof = !self.fill_raw()?;
+            self.decode(eof)?;
+            if eof {
+                break;
+            }
+        }
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,100 @@
+use age::stream::StreamWriter;
+use age::{Encryptor, Recipient};
+use std::io::{self, Write};
+
+/// Parse an age recipient: an `age1...` X25519 key, or an SSH public key.
+pub fn recipient(s: &str) -> Result<Box<dyn Recipient + Send>, String> {
+    if s.starts_with("ssh-") {
+        match s.parse::<age::ssh::Recipient>() {
+            Ok(r) => Ok(Box::new(r)),
+            Err(e) => Err(format!("invalid SSH public key {s:?}: {e:?}")),
+        }
+    } else {
+        match s.parse::<age::x25519::Recipient>() {
+            Ok(r) => Ok(Box::new(r)),
+            Err(e) => Err(format!(
+                "expected an age1... or ssh- public key, got {s:?} ({e})"
+            )),
+        }
+    }
+}
+
+/// Encrypts what is written to it to age recipients, for
+/// --encrypt-recipient, in the format of the `age` command.
+pub struct Encrypted<W: Write> {
+    /// taken by `finish`
+    stream: Option<StreamWriter<W>>,
+}
+
+impl<W: Write> Encrypted<W> {
+    /// Encrypt to `out`, for each of `recipients` to decrypt.
+    pub fn new(recipients: &[String], out: W) -> anyhow::Result<Self> {
+        let recipients = (recipients.iter())
+            .map(|s| recipient(s).map_err(anyhow::Error::msg))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let encryptor =
+            Encryptor::with_recipients(recipients.iter().map(|r| &**r as &dyn Recipient))?;
+        Ok(Self {
+            stream: Some(encryptor.wrap_output(out)?),
+        })
+    }
+
+    /// Write the last chunk, without which the output doesn't decrypt, and
+    /// flush it.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(stream) = self.stream.take() {
+            stream.finish()?.flush()?;
+        }
+        Ok(())
+    }
+
+    fn stream(&mut self) -> io::Result<&mut StreamWriter<W>> {
+        (self.stream.as_mut()).ok_or_else(|| io::Error::other("the encrypted output is finished"))
+    }
+}
+
+impl<W: Write> Write for Encrypted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream()?.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use age::x25519::Identity;
+    use std::io::Read;
+
+    #[test]
+    fn decrypts() {
+        let identity = Identity::generate();
+        let recipient = identity.to_public().to_string();
+        let mut out = vec![];
+        let mut encrypted = Encrypted::new(&[recipient], &mut out).unwrap();
+        encrypted.write_all(b"{\"a\":1}\n").unwrap();
+        encrypted.write_all(b"{\"b\":2}\n").unwrap();
+        encrypted.finish().unwrap();
+        assert!(encrypted.write_all(b"x").is_err());
+        drop(encrypted);
+        let decryptor = age::Decryptor::new(&out[..]).unwrap();
+        let mut rd = decryptor
+            .decrypt(std::iter::once(&identity as &dyn age::Identity))
+            .unwrap();
+        let mut records = String::new();
+        rd.read_to_string(&mut records).unwrap();
+        assert_eq!(records, "{\"a\":1}\n{\"b\":2}\n");
+    }
+
+    #[test]
+    fn recipients() {
+        let error = |s| recipient(s).err().unwrap();
+        assert!(error("age1nope").starts_with("expected an age1... or ssh-"));
+        assert!(error("ssh-ed25519 AAAA").starts_with("invalid SSH public key"));
+        let key = Identity::generate().to_public().to_string();
+        assert!(recipient(&key).is_ok());
+    }
+}
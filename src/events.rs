@@ -0,0 +1,70 @@
+use serde_json::Value;
+
+/// Append the events of `jq --stream` for `value`, element number `index`
+/// of the input array, to `out`, each followed by a newline: `[path, leaf]`
+/// for each scalar (or empty array or object), and `[path]` with the path of
+/// the last member when an array or object ends.
+///
+/// The array of the input gets its own closing event, [`end_event`], once
+/// all of its elements are written.
+pub fn write_events(value: &Value, index: u64, out: &mut Vec<u8>) -> serde_json::Result<()> {
+    walk(value, &mut vec![Value::from(index)], out)
+}
+
+/// The event closing the input array after its last element, number `index`.
+pub fn end_event(index: u64) -> Vec<u8> {
+    format!("[[{index}]]").into_bytes()
+}
+
+fn walk(value: &Value, path: &mut Vec<Value>, out: &mut Vec<u8>) -> serde_json::Result<()> {
+    let last = match value {
+        Value::Array(items) if !items.is_empty() => {
+            for (i, item) in items.iter().enumerate() {
+                path.push(i.into());
+                walk(item, path, out)?;
+                path.pop();
+            }
+            Value::from(items.len() - 1)
+        }
+        Value::Object(entries) if !entries.is_empty() => {
+            for (key, entry) in entries {
+                path.push(key.as_str().into());
+                walk(entry, path, out)?;
+                path.pop();
+            }
+            Value::from(entries.keys().next_back().unwrap().as_str())
+        }
+        leaf => {
+            serde_json::to_writer(&mut *out, &(&path, leaf))?;
+            out.push(b'\n');
+            return Ok(());
+        }
+    };
+    path.push(last);
+    serde_json::to_writer(&mut *out, &(&path,))?;
+    out.push(b'\n');
+    path.pop();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn like_jq_stream() {
+        // `echo '[{"a":1,"b":[2,{}]},[],"x"]' | jq -c --stream`
+        let mut out = vec![];
+        let elements = [json!({"a": 1, "b": [2, {}]}), json!([]), json!("x")];
+        for (i, element) in elements.iter().enumerate() {
+            write_events(element, i as u64, &mut out).unwrap();
+        }
+        out.extend(end_event(2));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[[0,\"a\"],1]\n[[0,\"b\",0],2]\n[[0,\"b\",1],{}]\n[[0,\"b\",1]]\n[[0,\"b\"]]\n\
+             [[1],[]]\n[[2],\"x\"]\n[[2]]"
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use crate::datetime::DateTime;
+use serde_json::{Number, Value};
+
+/// Parse a numeric wrapper's payload, leaving it as a string if it's not
+/// a valid JSON number (e.g. `"NaN"` or `"Infinity"`).
+fn to_number(s: &str) -> Value {
+    match s.parse::<Number>() {
+        Ok(n) => Value::Number(n),
+        Err(_) => Value::String(s.to_string()),
+    }
+}
+
+/// Rewrite MongoDB Extended JSON wrappers such as `{"$oid": "..."}`,
+/// `{"$date": ...}` or `{"$numberLong": "..."}` into plain JSON scalars.
+/// Dates are always strings: those given as milliseconds since the epoch
+/// become RFC 3339 timestamps in UTC, like the ISO ones of relaxed mode.
+pub fn relax(v: &mut Value) {
+    match v {
+        Value::Array(items) => items.iter_mut().for_each(relax),
+        Value::Object(obj) => {
+            obj.values_mut().for_each(relax);
+            if obj.len() != 1 {
+                return;
+            }
+            let (key, inner) = obj.iter_mut().next().unwrap();
+            let relaxed = match (key.as_str(), inner) {
+                ("$oid" | "$date", inner @ Value::String(_)) => inner.take(),
+                // a number of ms, from `{"$numberLong": ...}` relaxed already
+                ("$date", Value::Number(n)) => {
+                    match n.as_i64().and_then(DateTime::from_unix_millis) {
+                        Some(t) => Value::String(t.to_rfc3339()),
+                        None => return,
+                    }
+                }
+                (
+                    "$numberLong" | "$numberInt" | "$numberDouble" | "$numberDecimal",
+                    Value::String(s),
+                ) => to_number(s),
+                _ => return,
+            };
+            *v = relaxed;
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relaxed(text: &str) -> String {
+        let mut v: Value = serde_json::from_str(text).unwrap();
+        relax(&mut v);
+        v.to_string()
+    }
+
+    #[test]
+    fn wrappers() {
+        assert_eq!(
+            relaxed(
+                r#"{"_id": {"$oid": "5f1d"}, "n": {"$numberLong": "9007199254740993"},
+                "d": {"$numberDecimal": "0.10000000000000000001"}, "f": {"$numberDouble": "NaN"},
+                "l": [{"$numberInt": "7"}]}"#
+            ),
+            r#"{"_id":"5f1d","n":9007199254740993,"d":0.10000000000000000001,"f":"NaN","l":[7]}"#
+        );
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(
+            relaxed(r#"[{"$date": "2020-01-02T03:04:05Z"}, {"$date": 1577934245000}]"#),
+            r#"["2020-01-02T03:04:05Z","2020-01-02T03:04:05Z"]"#
+        );
+        assert_eq!(
+            relaxed(r#"{"$date": {"$numberLong": "-1"}}"#),
+            r#""1969-12-31T23:59:59.999Z""#
+        );
+    }
+
+    #[test]
+    fn other_objects() {
+        let text = r#"{"$oid":"a","b":1}"#;
+        assert_eq!(relaxed(text), text);
+        assert_eq!(relaxed(r#"{"$date":true}"#), r#"{"$date":true}"#);
+    }
+}
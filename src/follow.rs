@@ -0,0 +1,46 @@
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Seek};
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait before checking the file for new data again.
+const POLL: Duration = Duration::from_millis(250);
+
+/// Reads a file that is still being written, like `tail -f`: at the end of
+/// the file, waits for the writer to append more instead of returning EOF.
+///
+/// The reader never ends by itself, it is up to the caller to stop reading
+/// (e.g. at the closing `]` of the array).
+pub struct Follow {
+    file: File,
+    pos: u64,
+}
+
+impl Follow {
+    /// Follow `file` from its current position.
+    pub fn new(mut file: File) -> io::Result<Self> {
+        let pos = file.stream_position()?;
+        Ok(Self { file, pos })
+    }
+}
+
+impl Read for Follow {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.file.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                self.pos += n as u64;
+                return Ok(n);
+            }
+            if self.file.metadata()?.len() < self.pos {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("file truncated while following it at offset {}", self.pos),
+                ));
+            }
+            thread::sleep(POLL);
+            // reading past the end may have left the cursor there on some platforms
+            self.file.seek(io::SeekFrom::Start(self.pos))?;
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::io::{self, BufRead, ErrorKind, Write};
+
+/// How the length of each record is encoded in length-prefixed output.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, clap::ValueEnum)]
+pub enum LengthPrefix {
+    /// 4 bytes, big-endian
+    U32,
+    /// unsigned LEB128, as in protobuf
+    Varint,
+}
+
+/// How records are delimited in the output.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Framing {
+    /// Each record is followed by the given terminator (a newline for JSONL)
+    Terminated(Vec<u8>),
+    /// RFC 7464: each record is preceded by RS (0x1E) and followed by a newline
+    JsonSeq,
+    /// Each record is preceded by its length in bytes
+    LengthPrefixed(LengthPrefix),
+}
+
+impl Framing {
+    /// Write a single, already serialized, `record`.
+    pub fn write_record<W: Write + ?Sized>(&self, w: &mut W, record: &[u8]) -> io::Result<()> {
+        match self {
+            Framing::Terminated(terminator) => {
+                w.write_all(record)?;
+                w.write_all(terminator)
+            }
+            Framing::JsonSeq => {
+                w.write_all(b"\x1e")?;
+                w.write_all(record)?;
+                w.write_all(b"\n")
+            }
+            Framing::LengthPrefixed(prefix) => {
+                write_length(w, *prefix, record.len())?;
+                w.write_all(record)
+            }
+        }
+    }
+
+    /// The number of bytes written before a record of `len` bytes.
+    pub fn prefix_len(&self, len: usize) -> usize {
+        match self {
+            Framing::Terminated(_) => 0,
+            Framing::JsonSeq => 1,
+            Framing::LengthPrefixed(LengthPrefix::U32) => 4,
+            Framing::LengthPrefixed(LengthPrefix::Varint) => (len.max(1).ilog2() / 7 + 1) as usize,
+        }
+    }
+
+    /// What is written after each record.
+    pub fn suffix(&self) -> &[u8] {
+        match self {
+            Framing::Terminated(terminator) => terminator,
+            Framing::JsonSeq => b"\n",
+            Framing::LengthPrefixed(_) => b"",
+        }
+    }
+
+    /// Write what comes before a record written in pieces, whose length isn't
+    /// known upfront. Fails for length-prefixed framing.
+    pub fn write_start<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        match self {
+            Framing::Terminated(_) => Ok(()),
+            Framing::JsonSeq => w.write_all(b"\x1e"),
+            Framing::LengthPrefixed(_) => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "length-prefixed records must be written whole",
+            )),
+        }
+    }
+
+    /// Write what comes after a record written in pieces.
+    pub fn write_end<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        match self {
+            Framing::Terminated(terminator) => w.write_all(terminator),
+            Framing::JsonSeq => w.write_all(b"\n"),
+            Framing::LengthPrefixed(_) => Ok(()),
+        }
+    }
+}
+
+/// Parse a record separator given on the command line, which may use the
+/// escapes `\n`, `\r`, `\t`, `\0`, `\\` and `\xHH`, and can't be empty.
+pub fn unescape(s: &str) -> Result<Vec<u8>, String> {
+    if s.is_empty() {
+        return Err("the record separator can't be empty".to_string());
+    }
+    let mut out = vec![];
+    let mut bytes = s.bytes();
+    while let Some(c) = bytes.next() {
+        if c != b'\\' {
+            out.push(c);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'n') => out.push(b'\n'),
+            Some(b'r') => out.push(b'\r'),
+            Some(b't') => out.push(b'\t'),
+            Some(b'0') => out.push(0),
+            Some(b'\\') => out.push(b'\\'),
+            Some(b'x') => {
+                let hex = [bytes.next(), bytes.next()];
+                let byte = match hex {
+                    [Some(a), Some(b)] => std::str::from_utf8(&[a, b])
+                        .ok()
+                        .and_then(|h| u8::from_str_radix(h, 16).ok()),
+                    _ => None,
+                };
+                out.push(byte.ok_or_else(|| format!("invalid \\x escape in {s:?}"))?);
+            }
+            Some(c) => return Err(format!("unknown escape '\\{}' in {s:?}", c as char)),
+            None => return Err(format!("trailing '\\' in {s:?}")),
+        }
+    }
+    Ok(out)
+}
+
+fn write_length<W: Write + ?Sized>(w: &mut W, prefix: LengthPrefix, len: usize) -> io::Result<()> {
+    match prefix {
+        LengthPrefix::U32 => {
+            let len = u32::try_from(len)
+                .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "record too large"))?;
+            w.write_all(&len.to_be_bytes())
+        }
+        LengthPrefix::Varint => {
+            let mut len = len as u64;
+            let mut buf = [0u8; 10];
+            let mut i = 0;
+            loop {
+                let byte = (len & 0x7F) as u8;
+                len >>= 7;
+                if len == 0 {
+                    buf[i] = byte;
+                    break;
+                }
+                buf[i] = byte | 0x80;
+                i += 1;
+            }
+            w.write_all(&buf[..=i])
+        }
+    }
+}
+
+/// Reads back records written with [`Framing::LengthPrefixed`].
+pub struct LengthPrefixedReader<R> {
+    rd: R,
+    prefix: LengthPrefix,
+}
+
+impl<R: BufRead> LengthPrefixedReader<R> {
+    pub fn new(rd: R, prefix: LengthPrefix) -> Self {
+        Self { rd, prefix }
+    }
+
+    /// Read the length of the next record, or `None` on a clean end of input.
+    fn read_length(&mut self) -> io::Result<Option<usize>> {
+        if self.rd.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        match self.prefix {
+            LengthPrefix::U32 => {
+                let mut buf = [0u8; 4];
+                self.rd.read_exact(&mut buf)?;
+                Ok(Some(u32::from_be_bytes(buf) as usize))
+            }
+            LengthPrefix::Varint => {
+                let mut len: u64 = 0;
+                for shift in (0..64).step_by(7) {
+                    let mut byte = [0u8];
+                    self.rd.read_exact(&mut byte)?;
+                    len |= ((byte[0] & 0x7F) as u64) << shift;
+                    if byte[0] & 0x80 == 0 {
+                        return Ok(Some(len as usize));
+                    }
+                }
+                Err(io::Error::new(ErrorKind::InvalidData, "varint too long"))
+            }
+        }
+    }
+
+    /// Read the next record into `buf` (replacing its content).
+    /// Returns `false` at the end of input.
+    pub fn read_record(&mut self, buf: &mut Vec<u8>) -> io::Result<bool> {
+        let Some(len) = self.read_length()? else {
+            return Ok(false);
+        };
+        buf.clear();
+        buf.resize(len, 0);
+        self.rd.read_exact(buf)?;
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Iterator for LengthPrefixedReader<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = vec![];
+        match self.read_record(&mut buf) {
+            Ok(true) => Some(Ok(buf)),
+            Ok(false) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separators() {
+        assert_eq!(unescape("\\r\\n").unwrap(), b"\r\n");
+        assert_eq!(unescape("a\\t\\0\\\\\\x1e").unwrap(), b"a\t\0\\\x1e");
+        assert_eq!(unescape("\\x1E").unwrap(), b"\x1e");
+        assert_eq!(unescape("é").unwrap(), "é".as_bytes());
+    }
+
+    #[test]
+    fn invalid_separators() {
+        for s in ["", "\\", "\\q", "\\x1", "\\xzz"] {
+            assert!(unescape(s).is_err(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn records() {
+        let mut out = vec![];
+        Framing::Terminated(b"\r\n".to_vec())
+            .write_record(&mut out, b"1")
+            .unwrap();
+        Framing::JsonSeq.write_record(&mut out, b"2").unwrap();
+        let u32 = Framing::LengthPrefixed(LengthPrefix::U32);
+        u32.write_record(&mut out, b"[3]").unwrap();
+        assert_eq!(out, b"1\r\n\x1e2\n\0\0\0\x03[3]");
+    }
+
+    #[test]
+    fn length_prefixes() {
+        let framing = Framing::LengthPrefixed(LengthPrefix::Varint);
+        let records: Vec<Vec<u8>> = [0, 1, 127, 128, 300, 20000]
+            .into_iter()
+            .map(|len| vec![b'1'; len])
+            .collect();
+        let mut out = vec![];
+        for record in &records {
+            framing.write_record(&mut out, record).unwrap();
+        }
+        let expected: usize = records
+            .iter()
+            .map(|r| framing.prefix_len(r.len()) + r.len())
+            .sum();
+        assert_eq!(out.len(), expected);
+        let read: Vec<_> = LengthPrefixedReader::new(&out[..], LengthPrefix::Varint)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(read, records);
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::{Context, bail};
+use std::io::{self, Write};
+use std::process::Command;
+
+/// Size of the chunks of a resumable upload; must be a multiple of 256 KiB.
+const CHUNK: usize = 8 * 1024 * 1024;
+
+/// Is the argument a `gs://bucket/object` URI?
+pub fn is_uri(s: &str) -> bool {
+    s.starts_with("gs://")
+}
+
+/// The API endpoint; `STORAGE_EMULATOR_HOST` points to an emulator instead,
+/// as with Google's client libraries.
+fn endpoint() -> String {
+    match std::env::var("STORAGE_EMULATOR_HOST") {
+        Ok(host) if host.contains("://") => host.trim_end_matches('/').to_string(),
+        Ok(host) if !host.is_empty() => format!("http://{host}"),
+        _ => "https://storage.googleapis.com".to_string(),
+    }
+}
+
+fn parse(uri: &str) -> anyhow::Result<(&str, &str)> {
+    uri.strip_prefix("gs://")
+        .and_then(|s| s.split_once('/'))
+        .filter(|(bucket, object)| !bucket.is_empty() && !object.is_empty())
+        .with_context(|| format!("expected gs://bucket/object, got {uri:?}"))
+}
+
+/// Percent-encode an object name for use as a single path segment or query value.
+fn encode(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+/// An OAuth access token, from `GOOGLE_OAUTH_ACCESS_TOKEN` or else from
+/// `gcloud auth print-access-token`. Without either, requests are anonymous,
+/// which works for public objects.
+fn access_token() -> Option<String> {
+    if let Ok(token) = std::env::var("GOOGLE_OAUTH_ACCESS_TOKEN")
+        && !token.is_empty()
+    {
+        return Some(token);
+    }
+    let out = Command::new("gcloud")
+        .args(["auth", "print-access-token"])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())?;
+    let token = String::from_utf8(out.stdout).ok()?.trim().to_string();
+    (!token.is_empty()).then_some(token)
+}
+
+fn auth_headers() -> Vec<(String, String)> {
+    access_token()
+        .map(|token| ("Authorization".to_string(), format!("Bearer {token}")))
+        .into_iter()
+        .collect()
+}
+
+/// The media download URL of `gs://bucket/object`, with the headers to send.
+pub fn download(uri: &str) -> anyhow::Result<(String, Vec<(String, String)>)> {
+    let (bucket, object) = parse(uri)?;
+    let url = format!(
+        "{}/storage/v1/b/{bucket}/o/{}?alt=media",
+        endpoint(),
+        encode(object)
+    );
+    Ok((url, auth_headers()))
+}
+
+/// Writes an object with a resumable upload, sent in chunks as data comes.
+///
+/// Nothing is visible in the bucket until [`Upload::finish`] succeeds.
+pub struct Upload {
+    agent: ureq::Agent,
+    session: String,
+    headers: Vec<(String, String)>,
+    buf: Vec<u8>,
+    /// number of bytes persisted by the server so far
+    offset: u64,
+}
+
+impl Upload {
+    pub fn create(uri: &str) -> anyhow::Result<Self> {
+        let (bucket, object) = parse(uri)?;
+        // 308 is how the server acknowledges a chunk, not a redirect
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .max_redirects(0)
+            .build()
+            .into();
+        let headers = auth_headers();
+        let url = format!(
+            "{}/upload/storage/v1/b/{bucket}/o?uploadType=resumable&name={}",
+            endpoint(),
+            encode(object)
+        );
+        let req = headers
+            .iter()
+            .fold(agent.post(&url), |req, (k, v)| req.header(k, v));
+        let resp = req
+            .header("X-Upload-Content-Type", "application/octet-stream")
+            .send_empty()?;
+        let session = resp
+            .headers()
+            .get("location")
+            .and_then(|v| v.to_str().ok())
+            .context("no upload session in the server's response")?
+            .to_string();
+        Ok(Self {
+            agent,
+            session,
+            headers,
+            buf: Vec::with_capacity(CHUNK),
+            offset: 0,
+        })
+    }
+
+    /// Send the whole chunks of `buf`, or all of it (completing the upload)
+    /// if `last`.
+    fn send(&mut self, last: bool) -> anyhow::Result<()> {
+        let len = if last {
+            self.buf.len()
+        } else {
+            self.buf.len() / CHUNK * CHUNK
+        };
+        let (start, end) = (self.offset, self.offset + len as u64);
+        let range = match (last, len) {
+            (true, 0) => format!("bytes */{end}"),
+            (true, _) => format!("bytes {start}-{}/{end}", end - 1),
+            (false, 0) => return Ok(()),
+            (false, _) => format!("bytes {start}-{}/*", end - 1),
+        };
+        let req = self
+            .headers
+            .iter()
+            .fold(self.agent.put(&self.session), |req, (k, v)| {
+                req.header(k, v)
+            });
+        let resp = req.header("Content-Range", &range).send(&self.buf[..len])?;
+        let persisted = match resp.status().as_u16() {
+            200 | 201 if last => end,
+            308 if !last => resp
+                .headers()
+                .get("range")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|r| r.rsplit_once('-'))
+                .and_then(|(_, n)| n.parse::<u64>().ok())
+                .map_or(0, |n| n + 1),
+            status => bail!("unexpected status {status} for upload chunk {range}"),
+        };
+        // the server may keep only part of the chunk, the rest is sent again
+        self.buf.drain(..(persisted - start) as usize);
+        self.offset = persisted;
+        Ok(())
+    }
+
+    /// Upload the remaining data and complete the object.
+    pub fn finish(&mut self) -> anyhow::Result<()> {
+        self.send(true)
+    }
+}
+
+impl Write for Upload {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= CHUNK {
+            self.send(false).map_err(io::Error::other)?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
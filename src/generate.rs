@@ -0,0 +1,81 @@
+use std::io::{self, Write};
+
+const WORDS: [&str; 8] = [
+    "alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel",
+];
+
+/// Write a JSON array of `n` made-up records to `w`, as input for benchmarks.
+///
+/// The records are objects mixing the usual types, with a nested object and
+/// array, strings with escapes, and fields that vary in length from one
+/// record to the next. The output is the same for the same `n`.
+pub fn write_records<W: Write>(w: &mut W, n: u64) -> io::Result<()> {
+    let mut state = 0x2545_F491_4F6C_DD1D_u64;
+    let mut next = || {
+        // xorshift64
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    w.write_all(b"[\n")?;
+    for id in 0..n {
+        let r = next();
+        let word = |i: u64| WORDS[((r >> (3 * i)) % WORDS.len() as u64) as usize];
+        let tags = (r % 4) as usize;
+        if id > 0 {
+            w.write_all(b",\n")?;
+        }
+        write!(
+            w,
+            r#"  {{"id": {id}, "name": "{} {}", "score": {}.{:02}, "active": {}, "count": {}, "#,
+            word(1),
+            word(2),
+            r % 1000,
+            (r >> 10) % 100,
+            r & 1 == 0,
+            (r >> 20) as i32,
+        )?;
+        write!(w, r#""tags": ["#)?;
+        for i in 0..tags {
+            if i > 0 {
+                w.write_all(b", ")?;
+            }
+            write!(w, r#""{}""#, word(3 + i as u64))?;
+        }
+        write!(
+            w,
+            r#"], "address": {{"city": "{}", "zip": "{:05}", "geo": [{}.5, -{}.25]}}, "#,
+            word(7),
+            (r >> 30) % 100_000,
+            (r >> 8) % 90,
+            (r >> 16) % 180,
+        )?;
+        write!(
+            w,
+            r#""note": "said \"{}\" at\t{}é", "parent": null}}"#,
+            word(8),
+            word(9),
+        )?;
+    }
+    w.write_all(b"\n]\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn records() {
+        let mut out = vec![];
+        write_records(&mut out, 50).unwrap();
+        let records: Vec<Value> = serde_json::from_slice(&out).unwrap();
+        assert_eq!(records.len(), 50);
+        assert_eq!(records[7]["id"], 7);
+        assert!(records.iter().all(Value::is_object));
+        let mut again = vec![];
+        write_records(&mut again, 50).unwrap();
+        assert!(again == out);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::scan::malformed;
+use anyhow::Context;
+use serde::Deserialize;
+use serde::de::IgnoredAny;
+use serde_json::{Map, Value};
+use std::io::{self, BufRead};
+
+/// Peek at the next non-whitespace byte, without consuming it.
+fn peek_non_ws<R: BufRead>(rd: &mut R) -> io::Result<Option<u8>> {
+    loop {
+        let buf = rd.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(None);
+        }
+        match buf.iter().position(|c| !c.is_ascii_whitespace()) {
+            Some(i) => {
+                let c = buf[i];
+                rd.consume(i);
+                return Ok(Some(c));
+            }
+            None => {
+                let n = buf.len();
+                rd.consume(n)
+            }
+        }
+    }
+}
+
+fn expect<R: BufRead>(rd: &mut R, expected: u8) -> anyhow::Result<()> {
+    match peek_non_ws(rd)? {
+        Some(c) if c == expected => {
+            rd.consume(1);
+            Ok(())
+        }
+        Some(c) => malformed!(
+            "malformed geojson: expected '{}', got '{}'",
+            expected as char,
+            c as char
+        ),
+        None => malformed!("malformed geojson: unexpected end of input"),
+    }
+}
+
+/// Skip over a single member value.
+///
+/// Numbers are skipped by hand: serde_json needs to peek one byte past
+/// their end, and that byte would be lost when the deserializer is dropped.
+fn skip_value<R: BufRead>(rd: &mut R) -> anyhow::Result<()> {
+    match peek_non_ws(rd)? {
+        Some(c) if c == b'-' || c.is_ascii_digit() => loop {
+            let buf = rd.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(());
+            }
+            let n = buf
+                .iter()
+                .position(|&c| !matches!(c, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E'))
+                .unwrap_or(buf.len());
+            let done = n < buf.len();
+            rd.consume(n);
+            if done {
+                return Ok(());
+            }
+        },
+        _ => {
+            let mut deser = serde_json::Deserializer::from_reader(&mut *rd);
+            IgnoredAny::deserialize(&mut deser)?;
+            Ok(())
+        }
+    }
+}
+
+/// Advance `rd` up to the opening `[` of the `features` array of a
+/// FeatureCollection, leaving the `[` itself to be consumed by the caller.
+/// Returns whether there is a FeatureCollection around the array, to be
+/// read with [`finish_collection`] after it.
+///
+/// A bare array (of features) is accepted as is.
+pub fn seek_features<R: BufRead>(rd: &mut R) -> anyhow::Result<bool> {
+    if peek_non_ws(rd)? == Some(b'[') {
+        return Ok(false);
+    }
+    expect(rd, b'{')?;
+    loop {
+        let key = {
+            let mut deser = serde_json::Deserializer::from_reader(&mut *rd);
+            String::deserialize(&mut deser).context("reading geojson member name")?
+        };
+        expect(rd, b':')?;
+        match key.as_str() {
+            "features" => {
+                if peek_non_ws(rd)? != Some(b'[') {
+                    malformed!("malformed geojson: `features` is not an array");
+                }
+                return Ok(true);
+            }
+            "type" => {
+                let ty = {
+                    let mut deser = serde_json::Deserializer::from_reader(&mut *rd);
+                    String::deserialize(&mut deser).context("reading geojson type")?
+                };
+                if ty != "FeatureCollection" {
+                    malformed!("expected a geojson FeatureCollection, got a {ty}");
+                }
+            }
+            _ => skip_value(rd)?,
+        }
+        match peek_non_ws(rd)? {
+            Some(b',') => rd.consume(1),
+            Some(b'}') | None => malformed!("geojson FeatureCollection has no `features` member"),
+            Some(c) => malformed!("malformed geojson: unexpected '{}'", c as char),
+        }
+    }
+}
+
+/// Read the rest of a FeatureCollection, after the closing `]` of its
+/// `features` array: the members that follow and the closing `}`. Returns
+/// whether anything but whitespace comes after that, leaving it unread.
+pub fn finish_collection<R: BufRead>(rd: &mut R) -> anyhow::Result<bool> {
+    loop {
+        match peek_non_ws(rd)? {
+            Some(b',') => rd.consume(1),
+            Some(b'}') => {
+                rd.consume(1);
+                return Ok(peek_non_ws(rd)?.is_some());
+            }
+            Some(c) => malformed!("malformed geojson: unexpected '{}'", c as char),
+            None => malformed!("malformed geojson: unexpected end of input"),
+        }
+        let mut deser = serde_json::Deserializer::from_reader(&mut *rd);
+        String::deserialize(&mut deser).context("reading geojson member name")?;
+        expect(rd, b':')?;
+        skip_value(rd)?;
+    }
+}
+
+/// Move the members of a feature's `properties` to the top level,
+/// keeping its `id` and `geometry`.
+pub fn lift_properties(feature: &mut Value) {
+    let Value::Object(obj) = feature else {
+        return;
+    };
+    let mut lifted = match obj.remove("properties") {
+        Some(Value::Object(props)) => props,
+        _ => Map::new(),
+    };
+    for key in ["id", "geometry"] {
+        if let Some(v) = obj.remove(key) {
+            lifted.insert(key.to_string(), v);
+        }
+    }
+    *obj = lifted;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// What [`seek_features`] returns for `input`, and what it leaves.
+    fn seek(input: &str) -> anyhow::Result<(bool, String)> {
+        let mut rd = input.as_bytes();
+        let collection = seek_features(&mut rd)?;
+        Ok((collection, String::from_utf8(rd.to_vec()).unwrap()))
+    }
+
+    #[test]
+    fn feature_collections() {
+        let input = r#"{"type": "FeatureCollection", "bbox": [0, {"a": "]"}], "features": [1]}"#;
+        assert_eq!(seek(input).unwrap(), (true, "[1]}".to_string()));
+        assert_eq!(seek(" [1]").unwrap(), (false, "[1]".to_string()));
+    }
+
+    #[test]
+    fn not_feature_collections() {
+        for input in [
+            r#"{"type": "Feature", "features": []}"#,
+            r#"{"features": {}}"#,
+            r#"{"type": "FeatureCollection"}"#,
+            "1",
+        ] {
+            assert!(seek(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn collection_ends() {
+        let end = |input: &str| finish_collection(&mut input.as_bytes());
+        assert!(!end(r#", "crs": {"type": "name"}, "n": [1] } "#).unwrap());
+        assert!(!end("}").unwrap());
+        assert!(end("} {}").unwrap());
+        assert!(end(", \"n\": ").is_err());
+        assert!(end("").is_err());
+    }
+
+    #[test]
+    fn lifted_properties() {
+        let mut feature = json!({"type": "Feature", "id": 1, "geometry": null,
+            "properties": {"name": "a", "id": "p"}});
+        lift_properties(&mut feature);
+        assert_eq!(
+            feature.to_string(),
+            r#"{"name":"a","id":1,"geometry":null}"#
+        );
+    }
+}
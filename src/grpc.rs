@@ -0,0 +1,266 @@
+use anyhow::Context;
+use std::io::{self, BufWriter, Read, Write};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{Request, Response, Status, Streaming};
+use tracing::warn;
+
+mod proto {
+    tonic::include_proto!("json2jsonl");
+}
+
+use proto::converter_server::{Converter, ConverterServer};
+use proto::{Chunk, Output};
+
+/// Messages buffered each way of a call.
+const CHANNEL: usize = 16;
+
+/// Reads the chunks of a call as they come, from a blocking thread.
+struct ChunkReader {
+    chunks: mpsc::Receiver<Result<Vec<u8>, Status>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.chunks.blocking_recv() {
+                Some(Ok(chunk)) => (self.chunk, self.pos) = (chunk, 0),
+                Some(Err(status)) => return Err(io::Error::other(status)),
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..][..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Sends what is written as the output messages of a call.
+struct OutputWriter(mpsc::Sender<Result<Output, Status>>);
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let data = buf.to_vec();
+        (self.0.blocking_send(Ok(Output { data })))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the call was cancelled"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Service<F, C> {
+    handle: Arc<F>,
+    client_error: Arc<C>,
+}
+
+#[tonic::async_trait]
+impl<F, C> Converter for Service<F, C>
+where
+    F: Fn(&mut dyn Read, &mut BufWriter<Box<dyn Write + Send>>) -> anyhow::Result<()>
+        + Send
+        + Sync
+        + 'static,
+    C: Fn(&anyhow::Error) -> bool + Send + Sync + 'static,
+{
+    type ConvertStream = ReceiverStream<Result<Output, Status>>;
+
+    async fn convert(
+        &self,
+        request: Request<Streaming<Chunk>>,
+    ) -> Result<Response<Self::ConvertStream>, Status> {
+        let mut stream = request.into_inner();
+        let (chunks_tx, chunks) = mpsc::channel(CHANNEL);
+        let (out, out_rx) = mpsc::channel(CHANNEL);
+        tokio::spawn(async move {
+            loop {
+                let chunk = match stream.message().await {
+                    Ok(Some(chunk)) => Ok(chunk.data),
+                    Ok(None) => break,
+                    Err(status) => Err(status),
+                };
+                if chunks_tx.send(chunk).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let (handle, client_error) = (self.handle.clone(), self.client_error.clone());
+        tokio::task::spawn_blocking(move || {
+            let mut rd = ChunkReader {
+                chunks,
+                chunk: vec![],
+                pos: 0,
+            };
+            let mut wr =
+                BufWriter::new(Box::new(OutputWriter(out.clone())) as Box<dyn Write + Send>);
+            let res = handle(&mut rd, &mut wr);
+            // the records before an error go out before it
+            let res = res.and(wr.flush().map_err(anyhow::Error::from));
+            if let Err(e) = res {
+                warn!("call failed: {e:#}");
+                let status = match client_error(&e) {
+                    true => Status::invalid_argument(format!("{e:#}")),
+                    false => Status::internal(format!("{e:#}")),
+                };
+                let _ = out.blocking_send(Err(status));
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(out_rx)))
+    }
+}
+
+/// Serve conversions over gRPC on `addr` (`unix:PATH`, or `[tcp:]HOST:PORT`)
+/// forever, like [`crate::server::serve_http`]: each Convert call of the
+/// json2jsonl.Converter service of proto/json2jsonl.proto streams in the
+/// input for `handle` to read, from a thread of its own, and streams back
+/// what it writes. An error ends the call with INVALID_ARGUMENT if
+/// `client_error` says it's the input's fault, and INTERNAL otherwise.
+pub fn serve<F, C>(addr: &str, handle: F, client_error: C) -> anyhow::Result<()>
+where
+    F: Fn(&mut dyn Read, &mut BufWriter<Box<dyn Write + Send>>) -> anyhow::Result<()>
+        + Send
+        + Sync
+        + 'static,
+    C: Fn(&anyhow::Error) -> bool + Send + Sync + 'static,
+{
+    let service = ConverterServer::new(Service {
+        handle: Arc::new(handle),
+        client_error: Arc::new(client_error),
+    });
+    let router = tonic::transport::Server::builder().add_service(service);
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        if let Some(path) = addr.strip_prefix("unix:") {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::FileTypeExt;
+                use tokio_stream::wrappers::UnixListenerStream;
+                // a socket left over by a previous run would make bind fail
+                if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                    std::fs::remove_file(path)?;
+                }
+                let listener = (tokio::net::UnixListener::bind(path))
+                    .with_context(|| format!("listening on {path}"))?;
+                router
+                    .serve_with_incoming(UnixListenerStream::new(listener))
+                    .await?;
+                return Ok(());
+            }
+            #[cfg(not(unix))]
+            anyhow::bail!("unix sockets are not supported on this platform ({path})");
+        }
+        let addr = addr.strip_prefix("tcp:").unwrap_or(addr);
+        let listener = (tokio::net::TcpListener::bind(addr).await)
+            .with_context(|| format!("listening on {addr}"))?;
+        router
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::converter_client::ConverterClient;
+    use std::net::TcpListener;
+    use std::time::Duration;
+    use tonic::transport::Endpoint;
+
+    /// The output of a Convert call sending `chunks`, and the status it
+    /// ended with, if not OK.
+    async fn call(addr: &str, chunks: &[&str]) -> (String, Option<Status>) {
+        let endpoint = Endpoint::from_shared(format!("http://{addr}")).unwrap();
+        let mut client = loop {
+            // until the server is up
+            match endpoint.connect().await {
+                Ok(channel) => break ConverterClient::new(channel),
+                Err(_) => std::thread::sleep(Duration::from_millis(20)),
+            }
+        };
+        let chunks: Vec<_> = (chunks.iter())
+            .map(|c| Chunk {
+                data: c.as_bytes().to_vec(),
+            })
+            .collect();
+        let mut stream = match client.convert(tokio_stream::iter(chunks)).await {
+            Ok(response) => response.into_inner(),
+            Err(status) => return (String::new(), Some(status)),
+        };
+        let mut output = vec![];
+        loop {
+            match stream.message().await {
+                Ok(Some(out)) => output.extend(out.data),
+                Ok(None) => return (String::from_utf8(output).unwrap(), None),
+                Err(status) => return (String::from_utf8(output).unwrap(), Some(status)),
+            }
+        }
+    }
+
+    #[test]
+    fn calls() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let addr = addr.to_string();
+        let server = addr.clone();
+        std::thread::spawn(move || {
+            serve(
+                &server,
+                |rd: &mut dyn Read, wr: &mut BufWriter<Box<dyn Write + Send>>| {
+                    let mut input = String::new();
+                    rd.read_to_string(&mut input)?;
+                    wr.write_all(input.to_uppercase().as_bytes())?;
+                    match input.split_once('!') {
+                        Some((_, "internal")) => anyhow::bail!("something broke"),
+                        Some(_) => anyhow::bail!("malformed input"),
+                        None => Ok(()),
+                    }
+                },
+                |e| e.to_string().starts_with("malformed"),
+            )
+            .unwrap()
+        });
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let (output, status) = call(&addr, &["[1,", "", "\"a\"]"]).await;
+            assert_eq!(output, "[1,\"A\"]");
+            assert!(status.is_none(), "{status:?}");
+            let (output, status) = call(&addr, &["[x", "!"]).await;
+            // the output before the error comes first
+            assert_eq!(output, "[X!");
+            let status = status.unwrap();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+            assert_eq!(status.message(), "malformed input");
+            let (_, status) = call(&addr, &["!internal"]).await;
+            assert_eq!(status.unwrap().code(), tonic::Code::Internal);
+        });
+    }
+
+    #[test]
+    fn chunks() {
+        let (tx, chunks) = mpsc::channel(CHANNEL);
+        for chunk in [&b"[1,"[..], b"", b"2", b"]"] {
+            tx.try_send(Ok(chunk.to_vec())).unwrap();
+        }
+        tx.try_send(Err(Status::cancelled("gone"))).unwrap();
+        drop(tx);
+        let mut rd = ChunkReader {
+            chunks,
+            chunk: vec![],
+            pos: 0,
+        };
+        let mut input = vec![];
+        let e = rd.read_to_end(&mut input).unwrap_err();
+        assert_eq!(input, b"[1,2]");
+        assert!(e.to_string().contains("gone"), "{e}");
+        assert_eq!(rd.read(&mut [0; 4]).unwrap(), 0);
+    }
+}
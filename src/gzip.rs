@@ -0,0 +1,39 @@
+use flate2::bufread::MultiGzDecoder;
+use std::io::{self, BufRead, BufReader, Read};
+
+/// The first bytes of a gzip member.
+const MAGIC: &[u8] = b"\x1F\x8B";
+
+/// Decompresses gzip input, detected from its magic bytes, and passes any
+/// other input through.
+///
+/// Concatenated gzip members, as written by `cat a.gz b.gz` or by block
+/// gzip producers, are decompressed one after the other as a single stream.
+pub enum MaybeGzip<R> {
+    Plain(BufReader<R>),
+    Gzip(MultiGzDecoder<BufReader<R>>),
+}
+
+impl<R: Read> MaybeGzip<R> {
+    pub fn new(rd: R) -> io::Result<Self> {
+        let mut rd = BufReader::new(rd);
+        Ok(if rd.fill_buf()?.starts_with(MAGIC) {
+            MaybeGzip::Gzip(MultiGzDecoder::new(rd))
+        } else {
+            MaybeGzip::Plain(rd)
+        })
+    }
+
+    pub fn is_gzip(&self) -> bool {
+        matches!(self, MaybeGzip::Gzip(_))
+    }
+}
+
+impl<R: Read> Read for MaybeGzip<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            MaybeGzip::Plain(rd) => rd.read(buf),
+            MaybeGzip::Gzip(rd) => rd.read(buf),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use crate::retry::Retry;
+use std::io::{self, Read};
+use std::thread;
+use tracing::warn;
+use ureq::BodyReader;
+
+/// Is the input argument a URL rather than a path?
+pub fn is_url(s: &str) -> bool {
+    s.starts_with("http://") || s.starts_with("https://")
+}
+
+fn get(
+    url: &str,
+    headers: &[(String, String)],
+) -> ureq::RequestBuilder<ureq::typestate::WithoutBody> {
+    headers
+        .iter()
+        .fold(ureq::get(url), |req, (k, v)| req.header(k, v))
+}
+
+/// Is `err` worth retrying: a transport error, a 429 or a 5xx?
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    use ureq::Error;
+    matches!(
+        err.downcast_ref::<Error>(),
+        Some(
+            Error::Io(_)
+                | Error::Timeout(_)
+                | Error::HostNotFound
+                | Error::ConnectionFailed
+                | Error::BodyStalled
+        )
+    ) || matches!(err.downcast_ref(), Some(Error::StatusCode(code)) if *code == 429 || *code >= 500)
+}
+
+/// Streams the body of an HTTP(S) GET request.
+///
+/// Compressed bodies (`Content-Encoding`) are decompressed on the fly. If
+/// the server supports range requests, a dropped connection is resumed
+/// where it stopped.
+pub struct HttpReader {
+    url: String,
+    headers: Vec<(String, String)>,
+    body: BodyReader<'static>,
+    len: Option<u64>,
+    resumable: bool,
+    offset: u64,
+    retry: Retry,
+}
+
+impl HttpReader {
+    pub fn open(url: &str) -> anyhow::Result<Self> {
+        Self::open_with_headers(url, vec![])
+    }
+
+    /// Like [`HttpReader::open`], sending `headers` with every request.
+    pub fn open_with_headers(url: &str, headers: Vec<(String, String)>) -> anyhow::Result<Self> {
+        Self::open_with_retry(url, headers, Retry::default())
+    }
+
+    /// Like [`HttpReader::open_with_headers`], retrying the request and each
+    /// resumption of the body as `retry` says.
+    pub fn open_with_retry(
+        url: &str,
+        headers: Vec<(String, String)>,
+        retry: Retry,
+    ) -> anyhow::Result<Self> {
+        let resp = retry.run(&format!("requesting {url}"), is_transient, || {
+            Ok(get(url, &headers).call()?)
+        })?;
+        let header = |name| resp.headers().get(name).and_then(|v| v.to_str().ok());
+        // offsets in a decompressed body don't map to the bytes on the wire
+        let encoded = header("content-encoding").is_some_and(|e| e != "identity");
+        let resumable = !encoded && header("accept-ranges") == Some("bytes");
+        let len = if encoded {
+            None
+        } else {
+            resp.body().content_length()
+        };
+        Ok(Self {
+            url: url.to_string(),
+            headers,
+            body: resp.into_body().into_reader(),
+            len,
+            resumable,
+            offset: 0,
+            retry,
+        })
+    }
+
+    /// Size of the body, when known.
+    pub fn content_length(&self) -> Option<u64> {
+        self.len
+    }
+
+    fn resume(&mut self) -> anyhow::Result<()> {
+        let resp = get(&self.url, &self.headers)
+            .header("Range", format!("bytes={}-", self.offset))
+            .call()?;
+        if resp.status() != 206 {
+            anyhow::bail!(
+                "server answered {} to a range request for {}",
+                resp.status(),
+                self.url
+            );
+        }
+        self.body = resp.into_body().into_reader();
+        Ok(())
+    }
+
+    /// Resume the body after `err`, with a wait before each attempt.
+    fn reconnect(&mut self, err: io::Error) -> io::Result<()> {
+        let mut err = anyhow::Error::from(err);
+        for attempt in 0..self.retry.retries {
+            let delay = self.retry.backoff(attempt);
+            warn!(
+                attempt = attempt + 1,
+                "reading {} failed ({err:#}), resuming at byte {} in {delay:?}",
+                self.url,
+                self.offset
+            );
+            thread::sleep(delay);
+            match self.resume() {
+                Ok(()) => return Ok(()),
+                Err(e) => err = e,
+            }
+        }
+        Err(io::Error::other(format!(
+            "reading {} failed: {err:#}",
+            self.url
+        )))
+    }
+}
+
+impl Read for HttpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.body.read(buf) {
+                Ok(n) => {
+                    self.offset += n as u64;
+                    return Ok(n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) if self.resumable && self.retry.retries > 0 => self.reconnect(e)?,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
@@ -0,0 +1,378 @@
+use serde_json::{Number, Value};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Is the number written as an integer? Those may not fit in 64 bits.
+fn is_integer(s: &str) -> bool {
+    s.strip_prefix('-')
+        .unwrap_or(s)
+        .bytes()
+        .all(|c| c.is_ascii_digit())
+}
+
+/// Write `v` as an Ion text value.
+///
+/// JSON is almost a subset of Ion text, the only difference being that
+/// non-integer numbers are read back as decimals, so they get an exponent
+/// unless `decimals` is set. Ion integers have no `-0`, the decimals do.
+pub fn write_text<W: Write>(w: &mut W, v: &Value, decimals: bool) -> io::Result<()> {
+    match v {
+        Value::Number(n) => {
+            let s = n.to_string();
+            if decimals && s == "-0" {
+                w.write_all(b"-0d0")
+            } else if decimals || is_integer(&s) || s.contains(['e', 'E']) {
+                w.write_all(s.as_bytes())
+            } else {
+                write!(w, "{s}e0")
+            }
+        }
+        Value::Array(items) => {
+            w.write_all(b"[")?;
+            for (i, x) in items.iter().enumerate() {
+                if i > 0 {
+                    w.write_all(b",")?;
+                }
+                write_text(w, x, decimals)?;
+            }
+            w.write_all(b"]")
+        }
+        Value::Object(obj) => {
+            w.write_all(b"{")?;
+            for (i, (k, x)) in obj.iter().enumerate() {
+                if i > 0 {
+                    w.write_all(b",")?;
+                }
+                serde_json::to_writer(&mut *w, k)?;
+                w.write_all(b":")?;
+                write_text(w, x, decimals)?;
+            }
+            w.write_all(b"}")
+        }
+        _ => Ok(serde_json::to_writer(w, v)?),
+    }
+}
+
+/// Ion binary version marker.
+const BVM: [u8; 4] = [0xE0, 0x01, 0x00, 0xEA];
+
+// system symbol IDs
+const SID_ION_SYMBOL_TABLE: usize = 3;
+const SID_IMPORTS: usize = 6;
+const SID_SYMBOLS: usize = 7;
+const FIRST_LOCAL_SID: usize = 10;
+
+// type codes
+const T_BOOL: u8 = 0x1;
+const T_POS_INT: u8 = 0x2;
+const T_NEG_INT: u8 = 0x3;
+const T_FLOAT: u8 = 0x4;
+const T_DECIMAL: u8 = 0x5;
+const T_SYMBOL: u8 = 0x7;
+const T_STRING: u8 = 0x8;
+const T_LIST: u8 = 0xB;
+const T_STRUCT: u8 = 0xD;
+const T_ANNOTATION: u8 = 0xE;
+
+fn push_var_uint(out: &mut Vec<u8>, mut n: usize) {
+    let mut groups = vec![(n & 0x7F) as u8 | 0x80];
+    n >>= 7;
+    while n > 0 {
+        groups.push((n & 0x7F) as u8);
+        n >>= 7;
+    }
+    out.extend(groups.iter().rev());
+}
+
+fn push_var_int(out: &mut Vec<u8>, n: i64) {
+    let mut mag = n.unsigned_abs();
+    let mut groups = vec![(mag & 0x7F) as u8 | 0x80];
+    mag >>= 7;
+    while mag > 0 {
+        groups.push((mag & 0x7F) as u8);
+        mag >>= 7;
+    }
+    // the first byte holds the sign
+    if groups.last().unwrap() & 0x40 != 0 {
+        groups.push(0);
+    }
+    if n < 0 {
+        *groups.last_mut().unwrap() |= 0x40;
+    }
+    out.extend(groups.iter().rev());
+}
+
+fn push_header(out: &mut Vec<u8>, ty: u8, len: usize) {
+    if len < 14 {
+        out.push((ty << 4) | len as u8);
+    } else {
+        out.push((ty << 4) | 14);
+        push_var_uint(out, len);
+    }
+}
+
+fn push_container(out: &mut Vec<u8>, ty: u8, content: &[u8]) {
+    push_header(out, ty, content.len());
+    out.extend_from_slice(content);
+}
+
+fn push_uint(out: &mut Vec<u8>, ty: u8, magnitude: u64) {
+    let bytes = magnitude.to_be_bytes();
+    let skip = (magnitude.leading_zeros() / 8) as usize;
+    push_container(out, ty, &bytes[skip..]);
+}
+
+/// Big-endian magnitude of a decimal integer of arbitrary size.
+fn decimal_magnitude(digits: &str) -> Vec<u8> {
+    let mut le: Vec<u8> = vec![];
+    for d in digits.bytes() {
+        let mut carry = (d - b'0') as u32;
+        for b in le.iter_mut() {
+            let v = *b as u32 * 10 + carry;
+            *b = v as u8;
+            carry = v >> 8;
+        }
+        if carry > 0 {
+            le.push(carry as u8);
+        }
+    }
+    le.reverse();
+    le
+}
+
+/// Encode a non-integer number as an exact Ion decimal.
+fn push_decimal(out: &mut Vec<u8>, s: &str) {
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let (mantissa, exp) = s.split_once(['e', 'E']).unwrap_or((s, "0"));
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let exp = exp.parse::<i64>().unwrap_or(0) - frac.len() as i64;
+
+    let mut coef = decimal_magnitude(&[int, frac].concat());
+    if coef.first().is_some_and(|b| b & 0x80 != 0) {
+        coef.insert(0, 0);
+    }
+    if neg {
+        match coef.first_mut() {
+            Some(b) => *b |= 0x80,
+            None => coef.push(0x80),
+        }
+    }
+    let mut content = vec![];
+    push_var_int(&mut content, exp);
+    content.extend_from_slice(&coef);
+    push_container(out, T_DECIMAL, &content);
+}
+
+fn push_number(out: &mut Vec<u8>, n: &Number, decimals: bool) {
+    let s = n.to_string();
+    if s == "-0" {
+        // there is no negative zero integer in Ion
+        match decimals {
+            true => push_decimal(out, &s),
+            false => push_uint(out, T_POS_INT, 0),
+        }
+    } else if let Some(u) = n.as_u64() {
+        push_uint(out, T_POS_INT, u)
+    } else if let Some(i) = n.as_i64() {
+        push_uint(out, T_NEG_INT, i.unsigned_abs())
+    } else if is_integer(&s) {
+        match s.strip_prefix('-') {
+            Some(digits) => push_container(out, T_NEG_INT, &decimal_magnitude(digits)),
+            None => push_container(out, T_POS_INT, &decimal_magnitude(&s)),
+        }
+    } else if decimals {
+        push_decimal(out, &s)
+    } else {
+        let f = s.parse::<f64>().unwrap_or(f64::NAN);
+        push_container(out, T_FLOAT, &f.to_be_bytes())
+    }
+}
+
+/// Streaming writer for the Ion binary encoding.
+///
+/// Field names are interned in the local symbol table, which is appended to
+/// whenever a record uses names that haven't been seen so far.
+#[derive(Default)]
+pub struct BinaryWriter {
+    decimals: bool,
+    symbols: HashMap<String, usize>,
+    started: bool,
+    new_symbols: Vec<String>,
+    buf: Vec<u8>,
+}
+
+impl BinaryWriter {
+    /// Encode non-integer numbers as decimals, which keep their exact digits,
+    /// rather than as floats.
+    pub fn decimals(mut self, yes: bool) -> Self {
+        self.decimals = yes;
+        self
+    }
+
+    fn intern(&mut self, s: &str) -> usize {
+        if let Some(&sid) = self.symbols.get(s) {
+            return sid;
+        }
+        let sid = FIRST_LOCAL_SID + self.symbols.len();
+        self.symbols.insert(s.to_string(), sid);
+        self.new_symbols.push(s.to_string());
+        sid
+    }
+
+    fn encode(&mut self, out: &mut Vec<u8>, v: &Value) {
+        match v {
+            Value::Null => out.push(0x0F),
+            Value::Bool(b) => out.push((T_BOOL << 4) | *b as u8),
+            Value::Number(n) => push_number(out, n, self.decimals),
+            Value::String(s) => push_container(out, T_STRING, s.as_bytes()),
+            Value::Array(items) => {
+                let mut content = vec![];
+                for x in items {
+                    self.encode(&mut content, x);
+                }
+                push_container(out, T_LIST, &content)
+            }
+            Value::Object(obj) => {
+                let mut content = vec![];
+                for (k, x) in obj {
+                    let sid = self.intern(k);
+                    push_var_uint(&mut content, sid);
+                    self.encode(&mut content, x);
+                }
+                push_container(out, T_STRUCT, &content)
+            }
+        }
+    }
+
+    /// The symbol table declaring `new_symbols`, appending to the current one if any.
+    fn push_symbol_table(&mut self, out: &mut Vec<u8>) {
+        let append = self.symbols.len() > self.new_symbols.len();
+        let mut symbols = vec![];
+        for s in self.new_symbols.drain(..) {
+            push_container(&mut symbols, T_STRING, s.as_bytes());
+        }
+        let mut content = vec![];
+        if append {
+            push_var_uint(&mut content, SID_IMPORTS);
+            push_uint(&mut content, T_SYMBOL, SID_ION_SYMBOL_TABLE as u64);
+        }
+        push_var_uint(&mut content, SID_SYMBOLS);
+        push_container(&mut content, T_LIST, &symbols);
+        let mut table = vec![];
+        push_container(&mut table, T_STRUCT, &content);
+
+        let mut annotated = vec![];
+        push_var_uint(&mut annotated, 1);
+        push_var_uint(&mut annotated, SID_ION_SYMBOL_TABLE);
+        annotated.extend_from_slice(&table);
+        push_container(out, T_ANNOTATION, &annotated);
+    }
+
+    /// Write `v` as one top-level Ion value.
+    pub fn write<W: Write>(&mut self, w: &mut W, v: &Value) -> io::Result<()> {
+        let mut value = std::mem::take(&mut self.buf);
+        value.clear();
+        self.encode(&mut value, v);
+
+        let mut prefix = vec![];
+        if !self.started {
+            prefix.extend_from_slice(&BVM);
+        }
+        if !self.new_symbols.is_empty() {
+            self.push_symbol_table(&mut prefix);
+        }
+        self.started = true;
+
+        w.write_all(&prefix)?;
+        w.write_all(&value)?;
+        self.buf = value;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(text: &str) -> Vec<Number> {
+        text.split(',').map(|n| n.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn text() {
+        let mut out = vec![];
+        let record = serde_json::json!({"a": [null, true, "s\n"], "b": {"c": 1.5}});
+        write_text(&mut out, &record, false).unwrap();
+        assert_eq!(out, br#"{"a":[null,true,"s\n"],"b":{"c":1.5e0}}"#);
+    }
+
+    #[test]
+    fn binary() {
+        let mut writer = BinaryWriter::default();
+        let mut out = vec![];
+        writer
+            .write(&mut out, &serde_json::json!({"a": true}))
+            .unwrap();
+        let first = out.len();
+        writer
+            .write(&mut out, &serde_json::json!({"a": null, "b": "x"}))
+            .unwrap();
+        let (first, second) = out.split_at(first);
+        // the version marker and a symbol table of "a", then {a: true}
+        assert_eq!(&first[..4], BVM);
+        assert_eq!(
+            first[4..],
+            [
+                0xE7, 0x81, 0x83, 0xD4, 0x87, 0xB2, 0x81, b'a', 0xD2, 0x8A, 0x11
+            ]
+        );
+        // a table appending "b" to it, then {a: null, b: "x"}
+        assert_eq!(
+            second,
+            [
+                0xEA, 0x81, 0x83, 0xD7, 0x86, 0x71, 0x03, 0x87, 0xB2, 0x81, b'b', 0xD5, 0x8A, 0x0F,
+                0x8B, 0x81, b'x'
+            ]
+        );
+    }
+
+    #[test]
+    fn text_numbers() {
+        let text = |v: &Value, decimals| {
+            let mut out = vec![];
+            write_text(&mut out, v, decimals).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let mut values: Vec<_> = numbers("1,-2,1.10,1e3,-0.0")
+            .into_iter()
+            .map(Value::Number)
+            .collect();
+        values.push(Value::Number(Number::from_string_unchecked("-0".into())));
+        let values = Value::Array(values);
+        assert_eq!(text(&values, false), "[1,-2,1.10e0,1e3,-0.0e0,-0]");
+        assert_eq!(text(&values, true), "[1,-2,1.10,1e3,-0.0,-0d0]");
+    }
+
+    #[test]
+    fn binary_numbers() {
+        let binary = |n: &Number, decimals| {
+            let mut out = vec![];
+            push_number(&mut out, n, decimals);
+            out
+        };
+        let ns = numbers("0,1,-1,1.10,-0.0");
+        assert_eq!(binary(&ns[0], false), [0x20]);
+        assert_eq!(binary(&ns[1], false), [0x21, 1]);
+        assert_eq!(binary(&ns[2], false), [0x31, 1]);
+        // 110 with an exponent of -2
+        assert_eq!(binary(&ns[3], true), [0x52, 0xc2, 0x6e]);
+        assert_eq!(binary(&ns[4], true), [0x52, 0xc1, 0x80]);
+        assert_eq!(binary(&ns[4], false)[0], 0x48);
+        let negative_zero = Number::from_string_unchecked("-0".into());
+        assert_eq!(binary(&negative_zero, true), [0x52, 0x80, 0x80]);
+        assert_eq!(binary(&negative_zero, false), [0x20]);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::retry::Retry;
+use crate::sink::Sink;
+use ::kafka::producer::{Producer, Record};
+use serde_json::value::RawValue;
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub use ::kafka::producer::RequiredAcks;
+
+/// Settings of a [`KafkaSink`].
+#[derive(Debug, Clone)]
+pub struct KafkaConfig {
+    pub brokers: Vec<String>,
+    pub topic: String,
+    /// Top-level field of the records holding the message key
+    pub key_field: Option<String>,
+    pub acks: RequiredAcks,
+    /// Messages per produce request
+    pub batch: usize,
+    /// Bytes per produce request: the batch is sent early when it gets that large
+    pub max_batch_bytes: usize,
+    /// Retries of a failed produce request; messages the brokers took before
+    /// the failure may then be sent twice
+    pub retry: Retry,
+}
+
+/// The key of a record: the value of `field` if the record is an object that
+/// has it, strings being unquoted.
+fn key(record: &[u8], field: &str) -> Option<Vec<u8>> {
+    let obj: HashMap<String, &RawValue> = serde_json::from_slice(record).ok()?;
+    let raw = obj.get(field)?;
+    match serde_json::from_str::<String>(raw.get()) {
+        Ok(s) => Some(s.into_bytes()),
+        Err(_) => Some(raw.get().as_bytes().to_vec()),
+    }
+}
+
+/// Publishes each record as a message of a Kafka topic.
+pub struct KafkaSink {
+    producer: Producer,
+    config: KafkaConfig,
+    /// (key, value) of the messages not sent yet
+    pending: Vec<(Vec<u8>, Vec<u8>)>,
+    pending_bytes: usize,
+}
+
+impl KafkaSink {
+    pub fn connect(config: KafkaConfig) -> anyhow::Result<Self> {
+        let producer = Producer::from_hosts(config.brokers.clone())
+            .with_ack_timeout(Duration::from_secs(30))
+            .with_required_acks(config.acks)
+            .create()?;
+        Ok(Self {
+            producer,
+            config,
+            pending: vec![],
+            pending_bytes: 0,
+        })
+    }
+
+    fn send(&mut self) -> anyhow::Result<()> {
+        let retry = self.config.retry;
+        let what = format!("producing to {}", self.config.topic);
+        retry.run(&what, |_| true, || self.send_once())?;
+        self.pending.clear();
+        self.pending_bytes = 0;
+        Ok(())
+    }
+
+    fn send_once(&mut self) -> anyhow::Result<()> {
+        let topic = self.config.topic.as_str();
+        let records: Vec<_> = self
+            .pending
+            .iter()
+            .map(|(k, v)| Record::from_key_value(topic, k.as_slice(), v.as_slice()))
+            .collect();
+        for confirm in self.producer.send_all(&records)? {
+            for p in confirm.partition_confirms {
+                if let Err(code) = p.offset {
+                    anyhow::bail!(
+                        "kafka rejected messages for {topic}/{}: {code:?}",
+                        p.partition
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Sink for KafkaSink {
+    fn push(&mut self, record: &[u8]) -> anyhow::Result<()> {
+        let key = match &self.config.key_field {
+            Some(field) => key(record, field).unwrap_or_default(),
+            None => vec![],
+        };
+        self.pending_bytes += key.len() + record.len();
+        self.pending.push((key, record.to_vec()));
+        if self.pending.len() >= self.config.batch
+            || self.pending_bytes >= self.config.max_batch_bytes
+        {
+            self.send()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        if !self.pending.is_empty() {
+            self.send()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        let record = br#"{"id":"a\"b","n":12,"o":{"x":[1]}}"#;
+        assert_eq!(key(record, "id").unwrap(), b"a\"b");
+        assert_eq!(key(record, "n").unwrap(), b"12");
+        assert_eq!(key(record, "o").unwrap(), br#"{"x":[1]}"#);
+        assert_eq!(key(record, "missing"), None);
+        assert_eq!(key(b"[1]", "id"), None);
+    }
+}
@@ -0,0 +1,162 @@
+use regex::Regex;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+/// A way of renaming keys, for --sanitize-keys.
+#[derive(Debug, Clone)]
+pub enum KeyRule {
+    /// Names that data warehouses take as they are: whitespace and
+    /// punctuation become `_`, other non-ASCII characters are dropped, and a
+    /// leading digit gets a `_` in front; a name left empty is `_`
+    Warehouse,
+    /// Replace the matches of the pattern, with `$1`-style references to its
+    /// groups in the replacement
+    Replace(Regex, String),
+}
+
+impl KeyRule {
+    /// Parse `warehouse` or `PATTERN=>REPLACEMENT`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        if s == "warehouse" {
+            return Ok(Self::Warehouse);
+        }
+        let Some((pattern, replacement)) = s.split_once("=>") else {
+            return Err(format!(
+                "expected 'warehouse' or PATTERN=>REPLACEMENT, got {s:?}"
+            ));
+        };
+        let pattern = Regex::new(pattern).map_err(|e| e.to_string())?;
+        Ok(Self::Replace(pattern, replacement.to_string()))
+    }
+
+    fn apply<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        match self {
+            Self::Warehouse => {
+                let ok = |c: char| c.is_ascii_alphanumeric() || c == '_';
+                if key.chars().all(ok) && key.starts_with(|c: char| !c.is_ascii_digit()) {
+                    return Cow::Borrowed(key);
+                }
+                let mut name = String::with_capacity(key.len() + 1);
+                if key.is_empty() || key.starts_with(|c: char| c.is_ascii_digit()) {
+                    name.push('_');
+                }
+                for c in key.chars() {
+                    if ok(c) {
+                        name.push(c);
+                    } else if c.is_whitespace() || c.is_ascii() {
+                        name.push('_');
+                    }
+                }
+                Cow::Owned(name)
+            }
+            Self::Replace(pattern, replacement) => pattern.replace_all(key, replacement.as_str()),
+        }
+    }
+}
+
+/// `key` renamed through `rules`, in order, or `_` if that leaves nothing
+/// of a key that wasn't empty.
+fn rename<'a>(key: &'a str, rules: &[KeyRule]) -> Cow<'a, str> {
+    let name = rules
+        .iter()
+        .fold(Cow::Borrowed(key), |key, rule| match key {
+            Cow::Borrowed(key) => rule.apply(key),
+            Cow::Owned(key) => Cow::Owned(rule.apply(&key).into_owned()),
+        });
+    match name {
+        name if name.is_empty() && !key.is_empty() => Cow::Borrowed("_"),
+        name => name,
+    }
+}
+
+/// Rename the keys of the objects in `v` (nested ones included) through
+/// `rules`, calling `renamed` with the old and new name of each key that
+/// changes. The keys that don't change keep their names, and a new name
+/// already taken in the object gets a `_2` suffix (or `_3`...).
+pub fn sanitize(v: &mut Value, rules: &[KeyRule], renamed: &mut impl FnMut(&str, &str)) {
+    match v {
+        Value::Array(items) => items.iter_mut().for_each(|v| sanitize(v, rules, renamed)),
+        Value::Object(obj) => {
+            obj.values_mut().for_each(|v| sanitize(v, rules, renamed));
+            let kept: HashSet<String> = (obj.keys())
+                .filter(|key| rename(key, rules) == key.as_str())
+                .cloned()
+                .collect();
+            if kept.len() == obj.len() {
+                return;
+            }
+            for (key, value) in std::mem::take(obj) {
+                if kept.contains(&key) {
+                    obj.insert(key, value);
+                    continue;
+                }
+                let base = rename(&key, rules);
+                let mut name = base.to_string();
+                let mut n = 2;
+                while kept.contains(&name) || obj.contains_key(&name) {
+                    name = format!("{base}_{n}");
+                    n += 1;
+                }
+                renamed(&key, &name);
+                obj.insert(name, value);
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// The JSON text of `v` with its keys renamed through `rules`, and the
+    /// renames.
+    fn sanitized(mut v: Value, rules: &[&str]) -> (String, Vec<(String, String)>) {
+        let rules: Vec<_> = rules.iter().map(|s| KeyRule::parse(s).unwrap()).collect();
+        let mut renames = vec![];
+        sanitize(&mut v, &rules, &mut |from, to| {
+            renames.push((from.to_string(), to.to_string()))
+        });
+        (v.to_string(), renames)
+    }
+
+    #[test]
+    fn warehouse_names() {
+        let v = json!({"ok_1": 1, "a b": 2, "1st": 3, "x-y": {"c.d": 4}, "né": [{"f(x)": 5}]});
+        assert_eq!(
+            sanitized(v, &["warehouse"]).0,
+            r#"{"ok_1":1,"a_b":2,"_1st":3,"x_y":{"c_d":4},"n":[{"f_x_":5}]}"#
+        );
+        // nothing left of the name
+        assert_eq!(sanitized(json!({"é": 1}), &["warehouse"]).0, r#"{"_":1}"#);
+        assert_eq!(sanitized(json!({"": 1}), &["warehouse"]).0, r#"{"_":1}"#);
+    }
+
+    #[test]
+    fn renames_that_collide() {
+        // the keys that don't change keep their names
+        let (text, renames) = sanitized(json!({"a 1": 1, "a_1": 2}), &["warehouse"]);
+        assert_eq!(text, r#"{"a_1_2":1,"a_1":2}"#);
+        assert_eq!(renames, [("a 1".to_string(), "a_1_2".to_string())]);
+        let (text, _) = sanitized(json!({"a-b": 1, "a.b": 2, "a b": 3}), &["warehouse"]);
+        assert_eq!(text, r#"{"a_b":1,"a_b_2":2,"a_b_3":3}"#);
+    }
+
+    #[test]
+    fn replacements_in_order() {
+        let rules = ["^_+=>", "([a-z])([A-Z])=>${1}_$2", "warehouse"];
+        let (text, renames) = sanitized(json!({"__id": 1, "userName": 2, "x": 3}), &rules);
+        assert_eq!(text, r#"{"id":1,"user_Name":2,"x":3}"#);
+        assert_eq!(renames.len(), 2);
+        // a pattern that removes the whole key
+        assert_eq!(sanitized(json!({"abc": 1}), &[".=>"]).0, r#"{"_":1}"#);
+    }
+
+    #[test]
+    fn invalid_rules() {
+        assert!(KeyRule::parse("snake_case").is_err());
+        assert!(KeyRule::parse("(=>x").is_err());
+    }
+}
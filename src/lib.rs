@@ -0,0 +1,111 @@
+//! Convert JSON arrays to JSONL.
+
+#[cfg(feature = "amqp")]
+pub mod amqp;
+pub mod anonymize;
+pub mod archive;
+pub mod atomic;
+#[cfg(all(feature = "azure", not(target_arch = "wasm32")))]
+pub mod azure;
+pub mod bgzf;
+pub mod cache;
+#[cfg(all(feature = "json2jsonl-capi", unix))]
+pub mod capi;
+pub mod cast;
+pub mod checkpoint;
+pub mod compact;
+pub mod config;
+pub mod control;
+pub mod counts;
+pub mod csv;
+pub mod datetime;
+pub mod defaults;
+pub mod detect;
+pub mod diagnostic;
+pub mod drift;
+#[cfg(feature = "duckdb")]
+pub mod duckdb;
+pub mod dupkeys;
+pub mod encoding;
+#[cfg(feature = "age")]
+pub mod encrypt;
+pub mod events;
+pub mod extjson;
+pub mod follow;
+pub mod framing;
+#[cfg(all(feature = "gcs", not(target_arch = "wasm32")))]
+pub mod gcs;
+pub mod generate;
+pub mod geojson;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod gzip;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod http;
+pub mod ion;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod keys;
+pub mod lookup;
+pub mod manifest;
+pub mod merge;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod metrics;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod mmap;
+#[cfg(feature = "nats")]
+pub mod nats;
+pub mod nonfinite;
+pub mod numbers;
+pub mod pivot;
+#[cfg(feature = "plugin")]
+pub mod plugin;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod post;
+pub mod predicate;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod progress;
+pub mod push;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod rate;
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod retry;
+pub mod reverse;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod sample;
+pub mod scan;
+pub mod schema;
+#[cfg(feature = "script")]
+pub mod script;
+#[cfg(all(feature = "zstd", not(target_arch = "wasm32")))]
+pub mod seekable;
+pub mod server;
+pub mod shard;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod signals;
+#[cfg(feature = "simd")]
+pub mod simd;
+pub mod sink;
+pub mod spill;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sse;
+pub mod stats;
+pub mod strings;
+pub mod template;
+pub mod threaded;
+pub mod top;
+pub mod tz;
+pub mod unicode;
+pub mod unique;
+pub mod units;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub mod uring;
+pub mod utf8;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watch;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ws;
@@ -0,0 +1,173 @@
+use crate::{csv, template};
+use anyhow::{Context, bail};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Distinct unmapped values listed at most for each field in the report.
+const EXAMPLES: usize = 5;
+
+/// Replaces the values of a field of the records by looking them up in a
+/// table from a file, for --map-values.
+///
+/// A `.json` file is an object from the values to their replacements, which
+/// can be any JSON values. Otherwise the file is CSV, with a header line
+/// and the values and their replacements, as strings, in the first two
+/// columns. Strings are looked up by their text and other values by their
+/// JSON text (`42`, `true`); those not in the table are left as they are,
+/// and reported, and null is left alone.
+#[derive(Debug)]
+pub struct ValueMap {
+    field: String,
+    path: Vec<String>,
+    table: HashMap<String, Value>,
+    /// values not in the table, the first distinct ones, and whether there
+    /// were more
+    unmapped: Mutex<(u64, Vec<String>, bool)>,
+}
+
+impl ValueMap {
+    /// Map the values of `field`, a dotted path into the records like those
+    /// of a template, by the table at `path`.
+    pub fn load(field: &str, path: &Path) -> anyhow::Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let table = match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => parse_json(&text),
+            _ => parse_csv(&text),
+        }
+        .with_context(|| format!("parsing {}", path.display()))?;
+        Ok(Self {
+            field: field.to_string(),
+            path: field.split('.').map(String::from).collect(),
+            table,
+            unmapped: Mutex::default(),
+        })
+    }
+
+    /// Replace the value of the field in `record`, if it is in the table.
+    pub fn apply(&self, record: &mut Value) {
+        let Some(value) = template::lookup_mut(record, &self.path) else {
+            return;
+        };
+        let key = match &*value {
+            Value::Null => return,
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        match self.table.get(&key) {
+            Some(mapped) => *value = mapped.clone(),
+            None => {
+                let mut unmapped = self.unmapped.lock().unwrap();
+                unmapped.0 += 1;
+                if !unmapped.1.contains(&key) {
+                    match unmapped.1.len() < EXAMPLES {
+                        true => unmapped.1.push(key),
+                        false => unmapped.2 = true,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write the number of values of the field that weren't in the table,
+    /// with the first of them, if any.
+    pub fn write_report(&self, mut w: impl Write) -> io::Result<()> {
+        let (count, examples, more) = &*self.unmapped.lock().unwrap();
+        if *count > 0 {
+            let examples: Vec<_> = examples.iter().map(|s| format!("{s:?}")).collect();
+            writeln!(
+                w,
+                "{count} values of {} not in the map, left as they were: {}{}",
+                self.field,
+                examples.join(", "),
+                if *more { ", ..." } else { "" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_json(text: &str) -> anyhow::Result<HashMap<String, Value>> {
+    let Value::Object(table) = serde_json::from_str(text)? else {
+        bail!("expected an object from values to their replacements");
+    };
+    Ok(table.into_iter().collect())
+}
+
+fn parse_csv(text: &str) -> anyhow::Result<HashMap<String, Value>> {
+    let mut table = HashMap::new();
+    for (i, row) in csv::read(text)?.into_iter().enumerate().skip(1) {
+        let [key, value, ..] = &row[..] else {
+            bail!("expected a value and its replacement on row {}", i + 1);
+        };
+        table.insert(key.clone(), Value::String(value.clone()));
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn value_map(field: &str, table: HashMap<String, Value>) -> ValueMap {
+        ValueMap {
+            field: field.to_string(),
+            path: field.split('.').map(String::from).collect(),
+            table,
+            unmapped: Mutex::default(),
+        }
+    }
+
+    #[test]
+    fn json_tables() {
+        let table = parse_json(r#"{"FR": "France", "1": {"id": 1}, "true": null}"#).unwrap();
+        let map = value_map("c.code", table);
+        let mut records = [
+            json!({"c": {"code": "FR"}}),
+            json!({"c": {"code": 1}}),
+            json!({"c": {"code": true}}),
+            json!({"c": {"code": null}}),
+            json!({"c": {}}),
+        ];
+        records.iter_mut().for_each(|r| map.apply(r));
+        assert_eq!(
+            records.map(|r| r.to_string()),
+            [
+                r#"{"c":{"code":"France"}}"#,
+                r#"{"c":{"code":{"id":1}}}"#,
+                r#"{"c":{"code":null}}"#,
+                r#"{"c":{"code":null}}"#,
+                r#"{"c":{}}"#,
+            ]
+        );
+        assert!(parse_json("[]").is_err());
+    }
+
+    #[test]
+    fn csv_tables() {
+        let table = parse_csv("code,name,note\nFR,France,x\n\"D,E\",Germany\n").unwrap();
+        assert_eq!(table["FR"], "France");
+        assert_eq!(table["D,E"], "Germany");
+        assert!(parse_csv("code,name\nFR\n").is_err());
+    }
+
+    #[test]
+    fn unmapped_values() {
+        let map = value_map("c", parse_json(r#"{"a": 1}"#).unwrap());
+        for c in ["a", "b", "c", "b", "d", "e", "f", "g"] {
+            map.apply(&mut json!({ "c": c }));
+        }
+        let mut report = vec![];
+        map.write_report(&mut report).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "7 values of c not in the map, left as they were: \
+             \"b\", \"c\", \"d\", \"e\", \"f\", ...\n"
+        );
+    }
+}
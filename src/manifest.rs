@@ -0,0 +1,89 @@
+use crate::atomic::AtomicFile;
+use anyhow::Context;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// What the manifest records about an output file.
+#[derive(Clone, Default)]
+pub struct FileStats {
+    pub path: String,
+    /// Number of records written to the file
+    pub records: u64,
+    bytes: u64,
+    sha256: Sha256,
+}
+
+impl FileStats {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            ..Self::default()
+        }
+    }
+
+    /// Number of bytes written to the file
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Account for `data`, written to the file.
+    pub fn update(&mut self, data: &[u8]) {
+        self.bytes += data.len() as u64;
+        self.sha256.update(data);
+    }
+
+    fn sha256_hex(&self) -> String {
+        format!("{:x}", self.sha256.clone().finalize())
+    }
+}
+
+/// Write the manifest of the output `files` to `path`, as a JSON object
+/// with the path, number of records, size and SHA-256 of each, so that
+/// whoever reads them can check that they are complete.
+pub fn write(path: &Path, files: &[FileStats]) -> anyhow::Result<()> {
+    let files: Vec<_> = files
+        .iter()
+        .map(|f| {
+            json!({
+                "path": f.path,
+                "records": f.records,
+                "bytes": f.bytes,
+                "sha256": f.sha256_hex(),
+            })
+        })
+        .collect();
+    let write = || -> anyhow::Result<()> {
+        let tmp = {
+            let (tmp, mut file) = AtomicFile::create(path, path)?;
+            writeln!(file, "{:#}", json!({ "files": files }))?;
+            tmp
+        };
+        Ok(tmp.persist()?)
+    };
+    write().with_context(|| format!("writing manifest {}", path.display()))
+}
+
+/// Write the SHA-256 of the output `file` next to it, to `<path>.sha256`, in
+/// the format of `sha256sum` (with the file name, for `sha256sum -c` to check
+/// it from its directory), and return the path written.
+pub fn write_checksum(file: &FileStats) -> anyhow::Result<PathBuf> {
+    let path = Path::new(&file.path);
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".sha256");
+    let sidecar = PathBuf::from(sidecar);
+    let name = path
+        .file_name()
+        .map_or(file.path.as_str().into(), |n| n.to_string_lossy());
+    let write = || -> anyhow::Result<()> {
+        let tmp = {
+            let (tmp, mut out) = AtomicFile::create(&sidecar, &sidecar)?;
+            writeln!(out, "{}  {name}", file.sha256_hex())?;
+            tmp
+        };
+        Ok(tmp.persist()?)
+    };
+    write().with_context(|| format!("writing checksum {}", sidecar.display()))?;
+    Ok(sidecar)
+}
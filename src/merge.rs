@@ -0,0 +1,288 @@
+use crate::compact;
+use crate::scan::{self, Scanner};
+use crate::template;
+use anyhow::Context;
+use serde_json::Value;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::io::{BufRead, Write};
+
+/// The records of an input of [`merge`]: JSONL, or a JSON array.
+pub enum Records<R> {
+    Lines { rd: R, lineno: u64 },
+    Array(Scanner<R>),
+}
+
+impl<R: BufRead> Records<R> {
+    /// Read the records of `rd`, a JSON array if it starts with `[` and
+    /// JSONL otherwise.
+    pub fn new(mut rd: R) -> anyhow::Result<Self> {
+        scan::skip_bom(&mut rd)?;
+        let array = loop {
+            let buf = rd.fill_buf()?;
+            match buf.iter().position(|c| !c.is_ascii_whitespace()) {
+                Some(n) => {
+                    let array = buf[n] == b'[';
+                    rd.consume(n);
+                    break array;
+                }
+                None if buf.is_empty() => break false,
+                None => {
+                    let n = buf.len();
+                    rd.consume(n);
+                }
+            }
+        };
+        Ok(if array {
+            Records::Array(Scanner::new(rd))
+        } else {
+            Records::Lines { rd, lineno: 0 }
+        })
+    }
+
+    /// Read the next record into `record` (replacing its content), without
+    /// its insignificant whitespace. Returns `false` at the end of the
+    /// input.
+    pub fn next(&mut self, record: &mut Vec<u8>) -> anyhow::Result<bool> {
+        match self {
+            Records::Lines { rd, lineno } => {
+                let mut line = vec![];
+                loop {
+                    line.clear();
+                    if rd.read_until(b'\n', &mut line)? == 0 {
+                        return Ok(false);
+                    }
+                    *lineno += 1;
+                    let trimmed = line.trim_ascii();
+                    if trimmed.is_empty() {
+                        continue;
+                    }
+                    compact::validate(trimmed)
+                        .with_context(|| format!("invalid record on line {lineno}"))?;
+                    record.clear();
+                    compact::compact(trimmed, record);
+                    return Ok(true);
+                }
+            }
+            Records::Array(scanner) => scanner.read_record_into(record),
+        }
+    }
+}
+
+/// The value of the field records are merged by. Values of different types
+/// are ordered null (or missing) first, then booleans, numbers and strings;
+/// arrays and objects go by their JSON text, as strings.
+#[derive(Debug, Clone, PartialEq)]
+enum Key {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+impl Key {
+    fn of(record: &[u8], path: &[String]) -> anyhow::Result<Self> {
+        let value: Value = serde_json::from_slice(record)?;
+        Ok(match template::lookup(&value, path) {
+            None | Some(Value::Null) => Key::Null,
+            Some(Value::Bool(b)) => Key::Bool(*b),
+            Some(Value::Number(n)) => Key::Number(n.as_f64().unwrap_or(f64::NAN)),
+            Some(Value::String(s)) => Key::String(s.clone()),
+            Some(value) => Key::String(value.to_string()),
+        })
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Key::Null => 0,
+            Key::Bool(_) => 1,
+            Key::Number(_) => 2,
+            Key::String(_) => 3,
+        }
+    }
+}
+
+impl Eq for Key {}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Key::Bool(a), Key::Bool(b)) => a.cmp(b),
+            (Key::Number(a), Key::Number(b)) => a.total_cmp(b),
+            (Key::String(a), Key::String(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+/// The next record of an input, in the heap of [`merge`]: the smallest key
+/// comes first, and the first input among equal keys.
+#[derive(PartialEq, Eq)]
+struct Head {
+    key: Key,
+    descending: bool,
+    input: usize,
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let key = match self.descending {
+            false => self.key.cmp(&other.key),
+            true => other.key.cmp(&self.key),
+        };
+        key.then(self.input.cmp(&other.input))
+    }
+}
+
+/// Write the records of `inputs`, named for errors, to `w` as JSONL and
+/// return their number.
+///
+/// With `by`, a dotted path into the records like those of a template, the
+/// inputs must each be sorted by that field (in `descending` order or not),
+/// and their records are merged in that same order, holding just one
+/// record of each input in memory at a time. Records with equal values keep
+/// the order of their inputs. Without it, the inputs are concatenated.
+pub fn merge<R: BufRead>(
+    inputs: Vec<(String, Records<R>)>,
+    by: Option<&str>,
+    descending: bool,
+    mut w: impl Write,
+) -> anyhow::Result<u64> {
+    let mut count = 0;
+    let Some(by) = by else {
+        let mut record = vec![];
+        for (name, mut records) in inputs {
+            while records
+                .next(&mut record)
+                .with_context(|| format!("reading {name}"))?
+            {
+                w.write_all(&record)?;
+                w.write_all(b"\n")?;
+                count += 1;
+            }
+        }
+        w.flush()?;
+        return Ok(count);
+    };
+    let path: Vec<String> = by.split('.').map(String::from).collect();
+    let (names, mut inputs): (Vec<_>, Vec<_>) = inputs.into_iter().unzip();
+    // the next record of each input, and its number in the input
+    let mut next = vec![(vec![], 0u64); inputs.len()];
+    let mut heap = BinaryHeap::new();
+    let mut read = |input: usize, record: &mut (Vec<u8>, u64)| -> anyhow::Result<Option<Key>> {
+        let name = &names[input];
+        if !(inputs[input].next(&mut record.0)).with_context(|| format!("reading {name}"))? {
+            return Ok(None);
+        }
+        record.1 += 1;
+        let key = Key::of(&record.0, &path)
+            .with_context(|| format!("invalid record {} of {name}", record.1))?;
+        Ok(Some(key))
+    };
+    for (input, record) in next.iter_mut().enumerate() {
+        if let Some(key) = read(input, record)? {
+            heap.push(Reverse(Head {
+                key,
+                descending,
+                input,
+            }));
+        }
+    }
+    while let Some(Reverse(head)) = heap.pop() {
+        let input = head.input;
+        w.write_all(&next[input].0)?;
+        w.write_all(b"\n")?;
+        count += 1;
+        if let Some(key) = read(input, &mut next[input])? {
+            let following = Head {
+                key,
+                descending,
+                input,
+            };
+            if following < head {
+                anyhow::bail!(
+                    "{} is not sorted by {by}, at record {}",
+                    names[input],
+                    next[input].1
+                );
+            }
+            heap.push(Reverse(following));
+        }
+    }
+    w.flush()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merged(inputs: &[&str], by: Option<&str>, descending: bool) -> anyhow::Result<String> {
+        let inputs = (inputs.iter().enumerate())
+            .map(|(i, input)| Ok((format!("input {i}"), Records::new(input.as_bytes())?)))
+            .collect::<anyhow::Result<_>>()?;
+        let mut out = vec![];
+        merge(inputs, by, descending, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn sorted() {
+        let a = "{\"t\": 1, \"s\": \"a\"}\n\n{\"t\": 3, \"s\": \"a\"}\n";
+        let b = r#" [{"t": 2}, {"t": 3, "s": "b"}, {"t": 4}]"#;
+        assert_eq!(
+            merged(&[a, b], Some("t"), false).unwrap(),
+            "{\"t\":1,\"s\":\"a\"}\n{\"t\":2}\n{\"t\":3,\"s\":\"a\"}\n\
+             {\"t\":3,\"s\":\"b\"}\n{\"t\":4}\n"
+        );
+        assert_eq!(
+            merged(&[a, b], None, false).unwrap(),
+            "{\"t\":1,\"s\":\"a\"}\n{\"t\":3,\"s\":\"a\"}\n{\"t\":2}\n\
+             {\"t\":3,\"s\":\"b\"}\n{\"t\":4}\n"
+        );
+        // types in the order null, booleans, numbers, strings
+        let a = "{}\n{\"k\":false}\n{\"k\":\"1\"}\n";
+        let b = "{\"k\":null}\n{\"k\":true}\n{\"k\":-1.5}\n{\"k\":[1]}\n";
+        assert_eq!(
+            merged(&[a, b], Some("k"), false).unwrap(),
+            "{}\n{\"k\":null}\n{\"k\":false}\n{\"k\":true}\n{\"k\":-1.5}\n\
+             {\"k\":\"1\"}\n{\"k\":[1]}\n"
+        );
+        assert_eq!(
+            merged(
+                &["{\"a\":{\"b\":3}}\n{\"a\":{\"b\":1}}", "{\"a\":{\"b\":2}}"],
+                Some("a.b"),
+                true
+            )
+            .unwrap(),
+            "{\"a\":{\"b\":3}}\n{\"a\":{\"b\":2}}\n{\"a\":{\"b\":1}}\n"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error =
+            |inputs: &[&str]| format!("{:#}", merged(inputs, Some("t"), false).unwrap_err());
+        assert_eq!(
+            error(&["{\"t\":1}\n", "{\"t\":2}\n{\"t\":1}\n"]),
+            "input 1 is not sorted by t, at record 2"
+        );
+        assert!(
+            error(&["{\"t\":1}\n{\"t\":\n"])
+                .starts_with("reading input 0: invalid record on line 2")
+        );
+        assert!(error(&["[{\"t\":1}, 2"]).starts_with("reading input 0: "));
+    }
+}
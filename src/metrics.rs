@@ -0,0 +1,152 @@
+use crate::signals::STATUS;
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+
+/// Counters of a long-running conversion, on top of those of [`STATUS`],
+/// served by [`serve`].
+pub struct Metrics {
+    /// Inputs converted: connections, requests or watched files
+    pub conversions: AtomicU64,
+    /// Inputs whose conversion failed
+    pub errors: AtomicU64,
+    /// Invalid records skipped
+    pub skipped: AtomicU64,
+    /// Bytes of the records written, framing aside
+    pub output_bytes: AtomicU64,
+    /// Records pushed to each sink, and the time it took
+    sinks: Mutex<BTreeMap<&'static str, (u64, Duration)>>,
+}
+
+pub static METRICS: Metrics = Metrics {
+    conversions: AtomicU64::new(0),
+    errors: AtomicU64::new(0),
+    skipped: AtomicU64::new(0),
+    output_bytes: AtomicU64::new(0),
+    sinks: Mutex::new(BTreeMap::new()),
+};
+
+impl Metrics {
+    /// Count the conversion of an input, failed if `res` is an error.
+    pub fn conversion<T>(&self, res: anyhow::Result<T>) -> anyhow::Result<T> {
+        self.conversions.fetch_add(1, Ordering::Relaxed);
+        if res.is_err() {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        res
+    }
+
+    /// Count a push of a record to `sink` that took `elapsed`.
+    pub fn sink_push(&self, sink: &'static str, elapsed: Duration) {
+        let mut sinks = self.sinks.lock().unwrap();
+        let (n, total) = sinks.entry(sink).or_default();
+        *n += 1;
+        *total += elapsed;
+    }
+
+    /// The counters in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut s = String::new();
+        let counters = [
+            (
+                "records_total",
+                "Records written.",
+                STATUS.records.load(Ordering::Relaxed),
+            ),
+            (
+                "input_bytes_total",
+                "Bytes of input converted.",
+                STATUS.offset.load(Ordering::Relaxed),
+            ),
+            (
+                "output_bytes_total",
+                "Bytes of the records written, framing aside.",
+                self.output_bytes.load(Ordering::Relaxed),
+            ),
+            (
+                "skipped_records_total",
+                "Invalid records skipped.",
+                self.skipped.load(Ordering::Relaxed),
+            ),
+            (
+                "conversions_total",
+                "Inputs converted: connections, requests or watched files.",
+                self.conversions.load(Ordering::Relaxed),
+            ),
+            (
+                "errors_total",
+                "Inputs whose conversion failed.",
+                self.errors.load(Ordering::Relaxed),
+            ),
+        ];
+        for (name, help, value) in counters {
+            let _ = write!(
+                s,
+                "# HELP json2jsonl_{name} {help}\n# TYPE json2jsonl_{name} counter\njson2jsonl_{name} {value}\n"
+            );
+        }
+        let sinks = self.sinks.lock().unwrap();
+        if !sinks.is_empty() {
+            s.push_str(
+                "# HELP json2jsonl_sink_push_seconds Time taken pushing records to a sink.\n",
+            );
+            s.push_str("# TYPE json2jsonl_sink_push_seconds summary\n");
+        }
+        for (sink, (n, total)) in sinks.iter() {
+            let _ = write!(
+                s,
+                "json2jsonl_sink_push_seconds_sum{{sink=\"{sink}\"}} {}\njson2jsonl_sink_push_seconds_count{{sink=\"{sink}\"}} {n}\n",
+                total.as_secs_f64()
+            );
+        }
+        s
+    }
+}
+
+/// Serve [`METRICS`] on `[tcp:]HOST:PORT` from a background thread, at
+/// `GET /metrics`.
+pub fn serve(addr: &str) -> anyhow::Result<()> {
+    let addr = addr.strip_prefix("tcp:").unwrap_or(addr);
+    let listener = TcpListener::bind(addr).with_context(|| format!("listening on {addr}"))?;
+    thread::spawn(move || {
+        for conn in listener.incoming() {
+            let res = conn.and_then(|conn| {
+                let mut rd = BufReader::new(&conn);
+                let mut line = String::new();
+                rd.read_line(&mut line)?;
+                let target = line.split(' ').take(2).collect::<Vec<_>>();
+                // the rest of the head doesn't matter
+                loop {
+                    let mut header = String::new();
+                    if rd.read_line(&mut header)? == 0 || header.trim_end().is_empty() {
+                        break;
+                    }
+                }
+                let (status, content_type, body) = match target[..] {
+                    ["GET", "/metrics"] => (
+                        "200 OK",
+                        "text/plain; version=0.0.4",
+                        METRICS.render(),
+                    ),
+                    _ => ("404 Not Found", "text/plain", "see /metrics\n".to_string()),
+                };
+                write!(
+                    &conn,
+                    "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+            });
+            if let Err(e) = res {
+                warn!("serving metrics failed: {e}");
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{self, BufRead, Read};
+
+/// How much of the mapping [`Mapped::fill_buf`] hands out at a time, so that
+/// the scanner checks element sizes as often as with a buffered reader.
+const WINDOW: usize = 256 * 1024;
+
+/// Map `file` into memory for reading.
+///
+/// The file must not be truncated while mapped: the pages past the new end
+/// can't be read anymore and the process gets killed when touching them.
+pub fn map(file: &File) -> io::Result<Mmap> {
+    // SAFETY: the mapping is only ever read, and the caller is told above
+    // not to truncate the file while it is in use
+    let map = unsafe { Mmap::map(file)? };
+    #[cfg(unix)]
+    map.advise(memmap2::Advice::Sequential)?;
+    Ok(map)
+}
+
+/// Reads from a memory mapped file without copying it to a buffer first.
+pub struct Mapped<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Mapped<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// The bytes not consumed yet, all the way to the end of the mapping.
+    pub fn remaining(&self) -> &[u8] {
+        &self.data[self.pos..]
+    }
+}
+
+impl Read for Mapped<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.remaining().read(buf)?;
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl BufRead for Mapped<'_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let end = self.data.len().min(self.pos + WINDOW);
+        Ok(&self.data[self.pos..end])
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.pos += n;
+    }
+}
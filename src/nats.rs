@@ -0,0 +1,244 @@
+use crate::sink::Sink;
+use anyhow::{Context, bail};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// Settings of a [`NatsSink`].
+#[derive(Debug, Clone)]
+pub struct NatsConfig {
+    /// `nats://[user:pass@]host[:port]`, or with a token as `nats://token@host`
+    pub url: String,
+    pub subject: String,
+    /// Messages published but not acknowledged by JetStream yet, at most
+    pub max_pending: usize,
+}
+
+/// Publishes each record to a JetStream subject, over the plain text
+/// protocol.
+///
+/// Every message asks for an acknowledgement on an inbox of ours; at most
+/// `max_pending` may be outstanding, which keeps us at the pace of the
+/// stream.
+pub struct NatsSink {
+    rd: BufReader<TcpStream>,
+    wr: BufWriter<TcpStream>,
+    subject: String,
+    inbox: String,
+    next: u64,
+    pending: usize,
+    max_pending: usize,
+    line: String,
+}
+
+impl NatsSink {
+    pub fn connect(config: NatsConfig) -> anyhow::Result<Self> {
+        let rest = config.url.strip_prefix("nats://").unwrap_or(&config.url);
+        let (auth, addr) = match rest.rsplit_once('@') {
+            Some((auth, addr)) => (Some(auth), addr),
+            None => (None, rest),
+        };
+        let addr = addr.trim_end_matches('/');
+        let addr = if addr.contains(':') {
+            addr.to_string()
+        } else {
+            format!("{addr}:4222")
+        };
+        let stream =
+            TcpStream::connect(&addr).with_context(|| format!("connecting to NATS at {addr}"))?;
+        stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+        let mut sink = Self {
+            rd: BufReader::new(stream.try_clone()?),
+            wr: BufWriter::new(stream),
+            subject: config.subject,
+            inbox: format!("_INBOX.json2jsonl.{}", std::process::id()),
+            next: 0,
+            pending: 0,
+            max_pending: config.max_pending.max(1),
+            line: String::new(),
+        };
+
+        sink.read_line()?;
+        if !sink.line.starts_with("INFO ") {
+            bail!("not a NATS server at {addr}");
+        }
+        let mut connect = serde_json::json!({
+            "verbose": false,
+            "pedantic": false,
+            "headers": true,
+            "no_responders": true,
+            "name": "json2jsonl",
+            "lang": "rust",
+            "version": env!("CARGO_PKG_VERSION"),
+        });
+        match auth.map(|a| a.split_once(':')) {
+            Some(Some((user, pass))) => {
+                connect["user"] = user.into();
+                connect["pass"] = pass.into();
+            }
+            Some(None) => connect["auth_token"] = auth.into(),
+            None => (),
+        }
+        write!(
+            sink.wr,
+            "CONNECT {connect}\r\nSUB {}.* 1\r\nPING\r\n",
+            sink.inbox
+        )?;
+        sink.wr.flush()?;
+        // the PONG tells us CONNECT was accepted
+        loop {
+            sink.read_line()?;
+            match sink.line.as_str() {
+                "PONG" => break,
+                l if l.starts_with("-ERR") => bail!("NATS server refused connection: {l}"),
+                _ => (),
+            }
+        }
+        Ok(sink)
+    }
+
+    fn read_line(&mut self) -> anyhow::Result<()> {
+        self.line.clear();
+        if self.rd.read_line(&mut self.line)? == 0 {
+            bail!("NATS server closed the connection");
+        }
+        self.line.truncate(self.line.trim_end().len());
+        Ok(())
+    }
+
+    /// Wait for the next acknowledgement.
+    fn read_ack(&mut self) -> anyhow::Result<()> {
+        loop {
+            self.read_line()?;
+            let mut words = self.line.split(' ');
+            let (headers, total) = match words.next() {
+                Some("PING") => {
+                    self.wr.write_all(b"PONG\r\n")?;
+                    self.wr.flush()?;
+                    continue;
+                }
+                Some("-ERR") => bail!("NATS error: {}", self.line),
+                // MSG <subject> <sid> <len>
+                Some("MSG") => (0, words.nth(2)),
+                // HMSG <subject> <sid> <header len> <total len>
+                Some("HMSG") => (1, words.nth(3)),
+                _ => continue,
+            };
+            let total: usize = total
+                .and_then(|n| n.parse().ok())
+                .with_context(|| format!("malformed NATS message: {}", self.line))?;
+            let mut msg = vec![0; total + 2];
+            self.rd.read_exact(&mut msg)?;
+            msg.truncate(total);
+            if headers == 1 {
+                // only status messages come with headers here, such as
+                // 503 when no stream listens on the subject
+                let status = String::from_utf8_lossy(&msg);
+                let status = status.lines().next().unwrap_or_default();
+                bail!("no acknowledgement for {}: {status}", self.subject);
+            }
+            let ack: serde_json::Value = serde_json::from_slice(&msg)?;
+            if let Some(err) = ack.get("error") {
+                bail!("JetStream rejected a message on {}: {err}", self.subject);
+            }
+            self.pending -= 1;
+            return Ok(());
+        }
+    }
+}
+
+impl Sink for NatsSink {
+    fn push(&mut self, record: &[u8]) -> anyhow::Result<()> {
+        write!(
+            self.wr,
+            "PUB {} {}.{} {}\r\n",
+            self.subject,
+            self.inbox,
+            self.next,
+            record.len()
+        )?;
+        self.wr.write_all(record)?;
+        self.wr.write_all(b"\r\n")?;
+        self.next += 1;
+        self.pending += 1;
+        if self.pending >= self.max_pending {
+            self.wr.flush()?;
+            self.read_ack()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.wr.flush()?;
+        while self.pending > 0 {
+            self.read_ack()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// A JetStream server acknowledging each message published: the CONNECT
+    /// options it got, and (subject, payload) of the messages.
+    fn server(listener: TcpListener) -> (serde_json::Value, Vec<(String, String)>) {
+        let (stream, _) = listener.accept().unwrap();
+        let mut rd = BufReader::new(stream.try_clone().unwrap());
+        let mut wr = stream;
+        wr.write_all(b"INFO {\"server_id\":\"test\"}\r\n").unwrap();
+        let (mut connect, mut messages, mut seq) = (serde_json::Value::Null, vec![], 0);
+        let mut line = String::new();
+        while rd.read_line(&mut line).unwrap() > 0 {
+            let words: Vec<_> = line.split_whitespace().collect();
+            match words[..] {
+                ["PING"] => wr.write_all(b"PONG\r\n").unwrap(),
+                ["PUB", subject, reply, len] => {
+                    let mut payload = vec![0; len.parse::<usize>().unwrap() + 2];
+                    rd.read_exact(&mut payload).unwrap();
+                    payload.truncate(payload.len() - 2);
+                    messages.push((subject.to_string(), String::from_utf8(payload).unwrap()));
+                    seq += 1;
+                    let ack = format!("{{\"stream\":\"s\",\"seq\":{seq}}}");
+                    write!(wr, "MSG {reply} 1 {}\r\n{ack}\r\n", ack.len()).unwrap();
+                }
+                _ if line.starts_with("CONNECT ") => {
+                    connect = serde_json::from_str(&line[8..]).unwrap();
+                }
+                _ => (),
+            }
+            line.clear();
+        }
+        (connect, messages)
+    }
+
+    #[test]
+    fn publish() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || server(listener));
+        let mut sink = NatsSink::connect(NatsConfig {
+            url: format!("nats://secret@{addr}"),
+            subject: "events".to_string(),
+            max_pending: 2,
+        })
+        .unwrap();
+        for record in [&b"{\"a\":1}"[..], b"2", b"[3]"] {
+            sink.push(record).unwrap();
+        }
+        sink.finish().unwrap();
+        assert_eq!(sink.pending, 0);
+        drop(sink);
+        let (connect, messages) = server.join().unwrap();
+        assert_eq!(connect["auth_token"], "secret");
+        let messages: Vec<_> = (messages.iter())
+            .map(|(subject, payload)| (subject.as_str(), payload.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [("events", "{\"a\":1}"), ("events", "2"), ("events", "[3]")]
+        );
+    }
+}
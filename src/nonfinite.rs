@@ -0,0 +1,89 @@
+/// What to do with `NaN` and `Infinity` tokens, as emitted by Python's `json`
+/// module, which are not valid JSON.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, clap::ValueEnum)]
+pub enum NonFinite {
+    /// Reject the input
+    Error,
+    /// Replace them by `null`
+    Null,
+    /// Replace them by the strings "NaN", "Infinity" and "-Infinity"
+    String,
+}
+
+const TOKENS: [&[u8]; 2] = [b"NaN", b"Infinity"];
+
+/// Find the next non-finite token (with its sign, if any) outside of strings.
+fn find(buf: &[u8], mut i: usize) -> Option<(usize, usize)> {
+    let mut quote = None;
+    while i < buf.len() {
+        let c = buf[i];
+        match quote {
+            Some(_) if c == b'\\' => i += 1,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == b'"' || c == b'\'' => quote = Some(c),
+            None => {
+                if let Some(tok) = TOKENS.iter().find(|tok| buf[i..].starts_with(tok)) {
+                    let start = match i.checked_sub(1).map(|j| buf[j]) {
+                        Some(b'-' | b'+') => i - 1,
+                        _ => i,
+                    };
+                    return Some((start, i + tok.len()));
+                }
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Does the element contain non-finite numbers?
+pub fn contains(buf: &[u8]) -> bool {
+    find(buf, 0).is_some()
+}
+
+/// Rewrite non-finite numbers in the element according to `policy`.
+pub fn replace(buf: &mut Vec<u8>, policy: NonFinite) {
+    let mut i = 0;
+    while let Some((start, end)) = find(buf, i) {
+        let replacement = match policy {
+            NonFinite::Error => return,
+            NonFinite::Null => b"null".to_vec(),
+            NonFinite::String => {
+                let tok = buf[start..end]
+                    .strip_prefix(b"+")
+                    .unwrap_or(&buf[start..end]);
+                [b"\"", tok, b"\""].concat()
+            }
+        };
+        i = start + replacement.len();
+        buf.splice(start..end, replacement);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replaced(text: &str, policy: NonFinite) -> String {
+        let mut buf = text.as_bytes().to_vec();
+        replace(&mut buf, policy);
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn policies() {
+        let text = r#"[NaN,-Infinity,+Infinity,"NaN",'Infinity',1]"#;
+        assert!(contains(text.as_bytes()));
+        assert!(!contains(br#"["NaN","a\"Infinity"]"#));
+        assert_eq!(replaced(text, NonFinite::Error), text);
+        assert_eq!(
+            replaced(text, NonFinite::Null),
+            r#"[null,null,null,"NaN",'Infinity',1]"#
+        );
+        assert_eq!(
+            replaced(text, NonFinite::String),
+            r#"["NaN","-Infinity","Infinity","NaN",'Infinity',1]"#
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use crate::template;
+use serde_json::{Number, Value};
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+/// Values listed at most for each field in the report.
+const EXAMPLES: usize = 3;
+
+#[derive(Debug, Default)]
+struct Field {
+    name: String,
+    path: Vec<String>,
+    /// strings left as they were, and the first of them
+    failed: Mutex<(u64, Vec<String>)>,
+}
+
+/// Turns numbers written as strings for people, such as `"1,234.50"`,
+/// `"1.234,50"` or `"12 000"`, into JSON numbers, in some fields of the
+/// records.
+///
+/// Thousands separators (`,`, `.`, spaces and `'`) must separate groups of
+/// 3 digits, and the decimal separator is the last `.` or `,` when there
+/// are both. A single separator followed by 3 digits, as in `"1,234"`,
+/// could be either, and leading zeros may be those of an identifier: such
+/// strings are left as they are, and reported, like those that aren't
+/// numbers.
+#[derive(Debug)]
+pub struct NumberFields {
+    fields: Vec<Field>,
+}
+
+impl NumberFields {
+    /// Normalize `fields`, dotted paths into the records like those of a
+    /// template.
+    pub fn new(fields: &[String]) -> Self {
+        Self {
+            fields: (fields.iter())
+                .map(|name| Field {
+                    name: name.clone(),
+                    path: name.split('.').map(String::from).collect(),
+                    ..Field::default()
+                })
+                .collect(),
+        }
+    }
+
+    /// Turn the numeric strings of the fields of `record` into numbers.
+    /// Other values are left as they are, as are empty strings.
+    pub fn apply(&self, record: &mut Value) {
+        for field in &self.fields {
+            let Some(Value::String(s)) = template::lookup_mut(record, &field.path) else {
+                continue;
+            };
+            if s.trim().is_empty() {
+                continue;
+            }
+            match normalize(s) {
+                Some(n) => *template::lookup_mut(record, &field.path).unwrap() = Value::Number(n),
+                None => {
+                    let mut failed = field.failed.lock().unwrap();
+                    failed.0 += 1;
+                    if failed.1.len() < EXAMPLES {
+                        failed.1.push(s.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write the number of strings of each field left as they were, with
+    /// the first of them.
+    pub fn write_report(&self, mut w: impl Write) -> io::Result<()> {
+        for field in &self.fields {
+            let (count, examples) = &*field.failed.lock().unwrap();
+            if *count > 0 {
+                let examples: Vec<_> = examples.iter().map(|s| format!("{s:?}")).collect();
+                writeln!(
+                    w,
+                    "{count} values of {} not safely numbers, left as they were: {}{}",
+                    field.name,
+                    examples.join(", "),
+                    if *count as usize > examples.len() {
+                        ", ..."
+                    } else {
+                        ""
+                    }
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The number `s` is, with its separators, if it is clearly one.
+pub fn normalize(s: &str) -> Option<Number> {
+    let s = s.trim();
+    let (sign, digits) = match s.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", s.strip_prefix('+').unwrap_or(s)),
+    };
+    if digits.contains(['e', 'E']) {
+        return serde_json::from_str(digits)
+            .ok()
+            .filter(|_| !digits.starts_with('0') || digits.starts_with("0."))
+            .and_then(|n: Number| serde_json::from_str(&format!("{sign}{n}")).ok());
+    }
+    let decimal = match (digits.rfind('.'), digits.rfind(',')) {
+        (Some(dot), Some(comma)) => Some(dot.max(comma)),
+        (Some(i), None) | (None, Some(i)) if digits.matches(['.', ',']).count() == 1 => {
+            // "1,234" or "1.234" is a thousand or one and a bit, depending
+            // on the locale
+            if digits.len() - i == 4 && (1..=3).contains(&i) && !digits.starts_with('0') {
+                return None;
+            }
+            Some(i)
+        }
+        _ => None,
+    };
+    let (int, fraction) = match decimal {
+        Some(i) => (&digits[..i], Some(&digits[i + 1..])),
+        None => (digits, None),
+    };
+    // the thousands separator, if any, is the same throughout
+    let separators: Vec<char> = int.chars().filter(|c| !c.is_ascii_digit()).collect();
+    if let Some(&sep) = separators.first() {
+        if !matches!(sep, ',' | '.' | ' ' | '\'' | '\u{a0}' | '\u{202f}')
+            || separators.iter().any(|&c| c != sep)
+            || Some(sep) == decimal.map(|i| digits.as_bytes()[i] as char)
+        {
+            return None;
+        }
+        let mut groups = int.split(sep);
+        let first = groups.next()?;
+        if first.is_empty() || first.len() > 3 || groups.any(|g| g.len() != 3) {
+            return None;
+        }
+    }
+    let int: String = int.chars().filter(char::is_ascii_digit).collect();
+    let fraction = match fraction {
+        Some(f) if f.is_empty() || !f.bytes().all(|c| c.is_ascii_digit()) => return None,
+        fraction => fraction,
+    };
+    match int.as_str() {
+        "" if fraction.is_none() => return None,
+        // leading zeros are kept by identifiers and codes
+        int if int.len() > 1 && int.starts_with('0') => return None,
+        _ => (),
+    }
+    let int = if int.is_empty() { "0" } else { &int };
+    let text = match fraction {
+        Some(fraction) => format!("{sign}{int}.{fraction}"),
+        None => format!("{sign}{int}"),
+    };
+    serde_json::from_str(&text).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn normalized() {
+        for (s, n) in [
+            ("1,234.50", "1234.50"),
+            ("1.234,50", "1234.50"),
+            ("12 000", "12000"),
+            ("-1'234'567", "-1234567"),
+            ("+0,5", "0.5"),
+            (".5", "0.5"),
+            (" 12.5 ", "12.5"),
+            ("1,23", "1.23"),
+            ("1e3", "1e3"),
+            ("0", "0"),
+            ("1.234.567", "1234567"),
+        ] {
+            assert_eq!(normalize(s), Some(serde_json::from_str(n).unwrap()), "{s}");
+        }
+        for s in [
+            "1,234",
+            "1.234",
+            "007",
+            "00.5",
+            "1,2345,6",
+            "1,23,456",
+            "1.234 567",
+            "12a",
+            "1.",
+            "-",
+            "1_000",
+            "1,234,56.7.8",
+        ] {
+            assert_eq!(normalize(s), None, "{s}");
+        }
+        // a leading zero before the separator is not a thousand
+        assert_eq!(
+            normalize("0,234"),
+            Some(serde_json::from_str("0.234").unwrap())
+        );
+    }
+
+    #[test]
+    fn fields_and_report() {
+        let numbers = NumberFields::new(&["a".to_string(), "b.c".to_string()]);
+        let mut record = json!({"a": "1,234.5", "b": {"c": "12 000"}, "d": "5"});
+        numbers.apply(&mut record);
+        assert_eq!(
+            record.to_string(),
+            r#"{"a":1234.5,"b":{"c":12000},"d":"5"}"#
+        );
+        for a in [
+            json!("1,234"),
+            json!("x"),
+            json!(""),
+            json!(3),
+            json!("007"),
+            json!("n/a"),
+        ] {
+            numbers.apply(&mut json!({ "a": a }));
+        }
+        let mut report = vec![];
+        numbers.write_report(&mut report).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "4 values of a not safely numbers, left as they were: \"1,234\", \"x\", \"007\", ...\n"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use serde_json::{Map, Value};
+
+/// Turns each record into one record per field whose name matches a
+/// pattern, holding the other fields along with the name and value of that
+/// one, in the `key` and `value` fields:
+/// `{"id":1,"m_a":2,"m_b":3}` with `m_*` becomes `{"id":1,"key":"m_a","value":2}`
+/// and `{"id":1,"key":"m_b","value":3}`.
+#[derive(Debug, Clone)]
+pub struct Unpivot {
+    pattern: glob::Pattern,
+    key: String,
+    value: String,
+}
+
+impl Unpivot {
+    pub fn new(pattern: glob::Pattern, key: &str, value: &str) -> Self {
+        Self {
+            pattern,
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    /// The records that `record`, a JSON text, turns into. Records that
+    /// aren't objects, or have no field matching, stay as they are.
+    pub fn records(&self, record: &[u8]) -> serde_json::Result<Vec<Vec<u8>>> {
+        let Value::Object(fields) = serde_json::from_slice(record)? else {
+            return Ok(vec![record.to_vec()]);
+        };
+        let (wide, rest): (Map<_, _>, Map<_, _>) =
+            (fields.into_iter()).partition(|(name, _)| self.pattern.matches(name));
+        if wide.is_empty() {
+            return Ok(vec![record.to_vec()]);
+        }
+        let mut records = vec![];
+        for (name, value) in wide {
+            let mut long = rest.clone();
+            long.insert(self.key.clone(), Value::String(name));
+            long.insert(self.value.clone(), value);
+            records.push(serde_json::to_vec(&long)?);
+        }
+        Ok(records)
+    }
+}
+
+/// Merges runs of consecutive records that differ only in their `key` and
+/// `value` fields into one record, with a field named after each `key`
+/// holding its `value`: the reverse of [`Unpivot`].
+#[derive(Debug, Clone)]
+pub struct Pivot {
+    key: String,
+    value: String,
+    /// the fields shared by the records of the current run, and those
+    /// gathered from them
+    run: Option<(Map<String, Value>, Map<String, Value>)>,
+}
+
+impl Pivot {
+    pub fn new(key: &str, value: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            value: value.to_string(),
+            run: None,
+        }
+    }
+
+    /// Add `record`, a JSON text, returning the records complete as a
+    /// result. Records that aren't objects, or don't have the `key` field,
+    /// end the current run and stay as they are.
+    pub fn add(&mut self, record: &[u8]) -> serde_json::Result<Vec<Vec<u8>>> {
+        let mut records = vec![];
+        let mut fields = match serde_json::from_slice(record)? {
+            Value::Object(fields) if fields.contains_key(&self.key) => fields,
+            _ => {
+                records.extend(self.finish()?);
+                records.push(record.to_vec());
+                return Ok(records);
+            }
+        };
+        let name = match fields.remove(&self.key).unwrap() {
+            Value::String(name) => name,
+            name => name.to_string(),
+        };
+        let value = fields.remove(&self.value).unwrap_or(Value::Null);
+        match &mut self.run {
+            Some((rest, wide)) if *rest == fields => {
+                wide.insert(name, value);
+            }
+            _ => {
+                records.extend(self.finish()?);
+                self.run = Some((fields, [(name, value)].into_iter().collect()));
+            }
+        }
+        Ok(records)
+    }
+
+    /// The record of the current run, if any.
+    pub fn finish(&mut self) -> serde_json::Result<Option<Vec<u8>>> {
+        match self.run.take() {
+            Some((mut rest, wide)) => {
+                rest.extend(wide);
+                serde_json::to_vec(&rest).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(records: Vec<Vec<u8>>) -> Vec<String> {
+        (records.into_iter())
+            .map(|record| String::from_utf8(record).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn unpivot() {
+        let unpivot = Unpivot::new(glob::Pattern::new("m_*").unwrap(), "k", "v");
+        let records = |record: &str| texts(unpivot.records(record.as_bytes()).unwrap());
+        assert_eq!(
+            records(r#"{"id":1,"m_a":2,"m_b":[3]}"#),
+            [
+                r#"{"id":1,"k":"m_a","v":2}"#,
+                r#"{"id":1,"k":"m_b","v":[3]}"#
+            ]
+        );
+        assert_eq!(records(r#"{"id": 2}"#), [r#"{"id": 2}"#]);
+        assert_eq!(records("[1]"), ["[1]"]);
+        assert!(unpivot.records(b"{").is_err());
+    }
+
+    #[test]
+    fn pivot() {
+        let mut pivot = Pivot::new("key", "value");
+        let mut records = vec![];
+        for record in [
+            r#"{"id":1,"key":"a","value":2}"#,
+            r#"{"id":1,"key":"b","value":3}"#,
+            r#"{"id":1,"key":4}"#,
+            r#"{"id":2,"key":"a","value":4}"#,
+            "5",
+            r#"{"key":"a","value":1}"#,
+        ] {
+            records.extend(texts(pivot.add(record.as_bytes()).unwrap()));
+        }
+        records.extend(
+            pivot
+                .finish()
+                .unwrap()
+                .map(|record| String::from_utf8(record).unwrap()),
+        );
+        assert_eq!(
+            records,
+            [
+                r#"{"id":1,"a":2,"b":3,"4":null}"#,
+                r#"{"id":2,"a":4}"#,
+                "5",
+                r#"{"a":1}"#
+            ]
+        );
+        assert_eq!(pivot.finish().unwrap(), None);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::compact;
+use anyhow::{Context, bail};
+use std::path::Path;
+use wasmtime::{Engine, Linker, Memory, Module, Store, TypedFunc};
+use wasmtime_wasi::WasiCtxBuilder;
+use wasmtime_wasi::p1::{self, WasiP1Ctx};
+
+/// A WebAssembly module transforming each record, for --plugin.
+///
+/// The module (a WASI reactor, or making no WASI calls at all) exports its
+/// `memory` and two functions:
+///
+/// - `alloc(len: i32) -> i32`, the address of `len` bytes for the host to
+///   write a record into, which the module owns from then on;
+/// - `transform(ptr: i32, len: i32) -> i64`, given the record written there,
+///   returns the address of the transformed record in the upper 32 bits and
+///   its length in the lower ones, or -1 to drop the record. The bytes need
+///   only be valid until the next call.
+///
+/// Records are compact JSON, and so must be the ones returned. The module
+/// may write to stderr, and has no access to anything else.
+pub struct Plugin {
+    store: Store<WasiP1Ctx>,
+    memory: Memory,
+    alloc: TypedFunc<u32, u32>,
+    transform: TypedFunc<(u32, u32), i64>,
+}
+
+impl Plugin {
+    /// Compile and instantiate the module at `path` (binary or text format).
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let load = || -> anyhow::Result<Self> {
+            let engine = Engine::default();
+            let module = Module::from_file(&engine, path)?;
+            let mut linker = Linker::new(&engine);
+            p1::add_to_linker_sync(&mut linker, |cx| cx)?;
+            let wasi = WasiCtxBuilder::new().inherit_stderr().build_p1();
+            let mut store = Store::new(&engine, wasi);
+            let instance = linker.instantiate(&mut store, &module)?;
+            // reactors initialize themselves there
+            if let Some(init) = instance.get_func(&mut store, "_initialize") {
+                init.typed::<(), ()>(&store)?.call(&mut store, ())?;
+            }
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .context("the module doesn't export its `memory`")?;
+            let alloc = instance.get_typed_func(&mut store, "alloc")?;
+            let transform = instance.get_typed_func(&mut store, "transform")?;
+            Ok(Self {
+                store,
+                memory,
+                alloc,
+                transform,
+            })
+        };
+        load().with_context(|| format!("loading plugin {}", path.display()))
+    }
+
+    /// Transform `record` (replacing its content), or return `false` if the
+    /// plugin drops it.
+    pub fn transform(&mut self, record: &mut Vec<u8>) -> anyhow::Result<bool> {
+        let len = u32::try_from(record.len()).context("record too large for the plugin")?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory
+            .write(&mut self.store, ptr as usize, record)
+            .context("`alloc` returned an address out of bounds")?;
+        let res = self.transform.call(&mut self.store, (ptr, len))?;
+        if res < 0 {
+            return Ok(false);
+        }
+        let (ptr, len) = ((res >> 32) as usize, res as u32 as usize);
+        let Some(out) = self.memory.data(&self.store).get(ptr..ptr + len) else {
+            bail!("`transform` returned a record out of bounds");
+        };
+        compact::validate(out).context("`transform` returned invalid JSON")?;
+        record.clear();
+        compact::compact(out, record);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wraps records in an array, drops `{}`, and returns a record out of
+    /// bounds for `[]`.
+    const WRAP: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "alloc") (param i32) (result i32) i32.const 17)
+        (func (export "transform") (param $ptr i32) (param $len i32) (result i64)
+            (if (i32.eq (local.get $len) (i32.const 2))
+                (then
+                    (if (i32.eq (i32.load8_u (local.get $ptr)) (i32.const 123))
+                        (then (return (i64.const -1))))
+                    (return (i64.const 0x1_0000_0000_0002))))
+            (i32.store8 (i32.sub (local.get $ptr) (i32.const 1)) (i32.const 91))
+            (i32.store8 (i32.add (local.get $ptr) (local.get $len)) (i32.const 93))
+            (i64.or
+                (i64.shl (i64.extend_i32_u (i32.sub (local.get $ptr) (i32.const 1))) (i64.const 32))
+                (i64.extend_i32_u (i32.add (local.get $len) (i32.const 2))))))"#;
+
+    #[test]
+    fn transform() {
+        let path =
+            std::env::temp_dir().join(format!("json2jsonl-plugin-{}.wat", std::process::id()));
+        std::fs::write(&path, WRAP).unwrap();
+        let plugin = Plugin::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let mut plugin = plugin.unwrap();
+        let mut record = br#"{"a":1}"#.to_vec();
+        assert!(plugin.transform(&mut record).unwrap());
+        assert_eq!(record, br#"[{"a":1}]"#);
+        let mut record = b"{}".to_vec();
+        assert!(!plugin.transform(&mut record).unwrap());
+        let mut record = b"[]".to_vec();
+        let e = plugin.transform(&mut record).unwrap_err();
+        assert_eq!(e.to_string(), "`transform` returned a record out of bounds");
+    }
+
+    #[test]
+    fn load_errors() {
+        let path =
+            std::env::temp_dir().join(format!("json2jsonl-noplugin-{}.wat", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"(module (func (export "alloc") (param i32) (result i32) i32.const 0))"#,
+        )
+        .unwrap();
+        let e = Plugin::load(&path).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(
+            format!("{e:#}").contains("doesn't export its `memory`"),
+            "{e:#}"
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use crate::retry::Retry;
+use crate::sink::Sink;
+use anyhow::bail;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tracing::warn;
+
+/// How a batch of records is sent in a request body.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, clap::ValueEnum)]
+pub enum BatchBody {
+    /// One record per line (`application/x-ndjson`)
+    Ndjson,
+    /// A JSON array of the records (`application/json`)
+    Array,
+}
+
+/// Settings of a [`Poster`].
+#[derive(Debug, Clone)]
+pub struct PostConfig {
+    pub url: String,
+    pub body: BatchBody,
+    /// Records per request
+    pub batch: usize,
+    /// Bytes per request: the batch is sent early when it gets that large
+    pub max_batch_bytes: usize,
+    /// Retries of a request, for transport errors, 429 and 5xx; a
+    /// `Retry-After` header overrides the backoff
+    pub retry: Retry,
+    /// Requests in flight at once
+    pub concurrency: usize,
+}
+
+/// Sends records to an HTTP endpoint, in batches, from a pool of workers.
+pub struct Poster {
+    config: PostConfig,
+    body: Vec<u8>,
+    count: usize,
+    tx: Option<SyncSender<Vec<u8>>>,
+    workers: Vec<JoinHandle<()>>,
+    /// the first error of a worker
+    error: Arc<Mutex<Option<anyhow::Error>>>,
+}
+
+/// The URL inserting a batch of JSON records (one per line) into `table`,
+/// on the ClickHouse server at `url`, whose own query parameters (e.g.
+/// `user` and `password`) are kept.
+pub fn clickhouse_insert_url(url: &str, table: &str) -> String {
+    let query = format!("INSERT INTO {table} FORMAT JSONEachRow");
+    let mut encoded = String::new();
+    for b in query.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    let sep = if url.contains('?') { '&' } else { '?' };
+    format!("{url}{sep}query={encoded}")
+}
+
+fn post(agent: &ureq::Agent, config: &PostConfig, body: &[u8]) -> anyhow::Result<()> {
+    let content_type = match config.body {
+        BatchBody::Ndjson => "application/x-ndjson",
+        BatchBody::Array => "application/json",
+    };
+    let mut attempt = 0;
+    loop {
+        let res = agent
+            .post(&config.url)
+            .header("Content-Type", content_type)
+            .send(body);
+        let (retry_after, err) = match res {
+            Ok(resp) if resp.status().is_success() => return Ok(()),
+            Ok(resp) => {
+                let status = resp.status();
+                let err = anyhow::anyhow!("{} answered {status}", config.url);
+                if status != 429 && !status.is_server_error() {
+                    return Err(err);
+                }
+                let retry_after = resp
+                    .headers()
+                    .get("retry-after")
+                    .and_then(|v| v.to_str().ok()?.parse().ok())
+                    .map(Duration::from_secs);
+                (retry_after, err)
+            }
+            Err(e) => (None, e.into()),
+        };
+        if attempt >= config.retry.retries {
+            return Err(err.context(format!(
+                "posting to {} failed after {} attempts",
+                config.url,
+                attempt + 1
+            )));
+        }
+        let delay = retry_after.unwrap_or(config.retry.backoff(attempt));
+        warn!(
+            attempt = attempt + 1,
+            "posting to {} failed ({err:#}), retrying in {delay:?}", config.url
+        );
+        thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+fn worker(
+    config: PostConfig,
+    rx: Arc<Mutex<Receiver<Vec<u8>>>>,
+    error: Arc<Mutex<Option<anyhow::Error>>>,
+) {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .build()
+        .into();
+    loop {
+        let Ok(body) = rx.lock().unwrap().recv() else {
+            return;
+        };
+        if let Err(e) = post(&agent, &config, &body) {
+            error.lock().unwrap().get_or_insert(e);
+            return;
+        }
+    }
+}
+
+impl Poster {
+    pub fn new(config: PostConfig) -> Self {
+        let concurrency = config.concurrency.max(1);
+        // a batch waiting per worker, so that reading goes on during requests
+        let (tx, rx) = mpsc::sync_channel(concurrency);
+        let rx = Arc::new(Mutex::new(rx));
+        let error = Arc::new(Mutex::new(None));
+        let workers = (0..concurrency)
+            .map(|_| {
+                let (config, rx, error) = (config.clone(), rx.clone(), error.clone());
+                thread::spawn(move || worker(config, rx, error))
+            })
+            .collect();
+        Self {
+            config,
+            body: vec![],
+            count: 0,
+            tx: Some(tx),
+            workers,
+            error,
+        }
+    }
+
+    fn check(&self) -> anyhow::Result<()> {
+        match self.error.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn flush_batch(&mut self) -> anyhow::Result<()> {
+        if self.count == 0 {
+            return Ok(());
+        }
+        if self.config.body == BatchBody::Array {
+            self.body.push(b']');
+        }
+        let body = std::mem::take(&mut self.body);
+        self.count = 0;
+        let sent = self.tx.as_ref().is_some_and(|tx| tx.send(body).is_ok());
+        if !sent {
+            self.check()?;
+            bail!("all workers posting to {} have stopped", self.config.url);
+        }
+        Ok(())
+    }
+}
+
+impl Sink for Poster {
+    /// Add a serialized record to the current batch, sending it once full.
+    fn push(&mut self, record: &[u8]) -> anyhow::Result<()> {
+        self.check()?;
+        match (self.config.body, self.count) {
+            (BatchBody::Array, 0) => self.body.push(b'['),
+            (BatchBody::Array, _) => self.body.push(b','),
+            (BatchBody::Ndjson, _) => (),
+        }
+        self.body.extend_from_slice(record);
+        if self.config.body == BatchBody::Ndjson {
+            self.body.push(b'\n');
+        }
+        self.count += 1;
+        if self.count >= self.config.batch || self.body.len() >= self.config.max_batch_bytes {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    /// Send the last batch and wait for all requests to complete.
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.flush_batch()?;
+        self.tx = None;
+        for w in self.workers.drain(..) {
+            let _ = w.join();
+        }
+        self.check()
+    }
+}
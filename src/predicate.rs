@@ -0,0 +1,376 @@
+use crate::template;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Write};
+
+/// A comparison operator.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    /// a dotted path into the record, null if it isn't there
+    Path(Vec<String>),
+    /// the length of a string, array or object
+    Len(Box<Expr>),
+    /// whether a path is in the record (even if null)
+    Exists(Vec<String>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, Op, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(Value),
+    String(String),
+    Ident(String),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+/// A condition on the records, such as `amount >= 0 and len(items) > 0`.
+///
+/// Paths into the records are dotted, like those of a template, and compare
+/// to numbers, strings (in single or double quotes), `true`, `false` and
+/// `null` with `==`, `!=`, `<`, `<=`, `>` and `>=`; numbers and strings are
+/// ordered, but values of different types only ever differ. Conditions
+/// combine with `and`, `or` and `not` (or `&&`, `||` and `!`), and
+/// parentheses. `len(path)` is the length of a string, array or object, and
+/// `exists(path)` whether the record has the field at all.
+#[derive(Debug, Clone)]
+pub struct Predicate {
+    text: String,
+    expr: Expr,
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl std::str::FromStr for Predicate {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(format!("unexpected {token:?} in {text:?}"));
+        }
+        Ok(Self {
+            text: text.to_string(),
+            expr,
+        })
+    }
+}
+
+impl Predicate {
+    /// The condition that `field`, a dotted path, is there and not null:
+    /// `field != null`.
+    pub fn required(field: &str) -> Self {
+        Self {
+            text: format!("{field} != null"),
+            expr: Expr::Compare(
+                Box::new(Expr::Path(field.split('.').map(String::from).collect())),
+                Op::Ne,
+                Box::new(Expr::Literal(Value::Null)),
+            ),
+        }
+    }
+
+    /// Whether `record` meets the condition.
+    pub fn holds(&self, record: &Value) -> bool {
+        eval(&self.expr, record).as_ref() == &Value::Bool(true)
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let s = text.as_bytes();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < s.len() {
+        let c = s[i];
+        let start = i;
+        match c {
+            _ if c.is_ascii_whitespace() => i += 1,
+            b'(' => {
+                tokens.push(Token::Open);
+                i += 1;
+            }
+            b')' => {
+                tokens.push(Token::Close);
+                i += 1;
+            }
+            b'"' | b'\'' => {
+                i += 1;
+                let mut string = vec![];
+                while i < s.len() && s[i] != c {
+                    if s[i] == b'\\' && i + 1 < s.len() {
+                        i += 1;
+                    }
+                    string.push(s[i]);
+                    i += 1;
+                }
+                if i == s.len() {
+                    return Err(format!("unterminated string in {text:?}"));
+                }
+                i += 1;
+                tokens.push(Token::String(String::from_utf8(string).unwrap()));
+            }
+            b'0'..=b'9' | b'-' => {
+                i += 1;
+                while i < s.len() && (s[i].is_ascii_alphanumeric() || b".+-".contains(&s[i])) {
+                    i += 1;
+                }
+                let number = serde_json::from_str(&text[start..i])
+                    .ok()
+                    .filter(Value::is_number)
+                    .ok_or_else(|| format!("invalid number {:?} in {text:?}", &text[start..i]))?;
+                tokens.push(Token::Number(number));
+            }
+            _ if c.is_ascii_alphabetic() || c == b'_' => {
+                while i < s.len() && (s[i].is_ascii_alphanumeric() || b"_.".contains(&s[i])) {
+                    i += 1;
+                }
+                tokens.push(match &text[start..i] {
+                    "and" => Token::Op("&&"),
+                    "or" => Token::Op("||"),
+                    "not" => Token::Op("!"),
+                    ident => Token::Ident(ident.to_string()),
+                });
+            }
+            _ => {
+                let op = ["==", "!=", "<=", ">=", "&&", "||", "<", ">", "!"]
+                    .into_iter()
+                    .find(|op| s[i..].starts_with(op.as_bytes()))
+                    .ok_or_else(|| format!("unexpected {:?} in {text:?}", c as char))?;
+                tokens.push(Token::Op(op));
+                i += op.len();
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens, from the loosest operator to the
+/// tightest: `or`, `and`, `not`, comparisons.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.peek() == Some(token);
+        self.pos += found as usize;
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Op("||")) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.eat(&Token::Op("&&")) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.eat(&Token::Op("!")) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.compare()
+    }
+
+    fn compare(&mut self) -> Result<Expr, String> {
+        let left = self.operand()?;
+        let op = match self.peek() {
+            Some(Token::Op("==")) => Op::Eq,
+            Some(Token::Op("!=")) => Op::Ne,
+            Some(Token::Op("<")) => Op::Lt,
+            Some(Token::Op("<=")) => Op::Le,
+            Some(Token::Op(">")) => Op::Gt,
+            Some(Token::Op(">=")) => Op::Ge,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.operand()?;
+        Ok(Expr::Compare(Box::new(left), op, Box::new(right)))
+    }
+
+    fn operand(&mut self) -> Result<Expr, String> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        Ok(match token {
+            Some(Token::Number(n)) => Expr::Literal(n),
+            Some(Token::String(s)) => Expr::Literal(Value::String(s)),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                if !self.eat(&Token::Close) {
+                    return Err("missing )".to_string());
+                }
+                expr
+            }
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                "null" => Expr::Literal(Value::Null),
+                "len" | "exists" if self.eat(&Token::Open) => {
+                    let Some(Token::Ident(path)) = self.peek().cloned() else {
+                        return Err(format!("{ident}() takes a path"));
+                    };
+                    self.pos += 1;
+                    if !self.eat(&Token::Close) {
+                        return Err(format!("missing ) after {ident}({path}"));
+                    }
+                    let path = path.split('.').map(String::from).collect();
+                    match ident.as_str() {
+                        "len" => Expr::Len(Box::new(Expr::Path(path))),
+                        _ => Expr::Exists(path),
+                    }
+                }
+                path => Expr::Path(path.split('.').map(String::from).collect()),
+            },
+            Some(token) => return Err(format!("unexpected {token:?}")),
+            None => return Err("unexpected end of the condition".to_string()),
+        })
+    }
+}
+
+fn eval<'a>(expr: &'a Expr, record: &'a Value) -> Cow<'a, Value> {
+    let truth = |b: bool| Cow::Owned(Value::Bool(b));
+    let holds = |expr| eval(expr, record).as_ref() == &Value::Bool(true);
+    match expr {
+        Expr::Literal(value) => Cow::Borrowed(value),
+        Expr::Path(path) => Cow::Borrowed(template::lookup(record, path).unwrap_or(&Value::Null)),
+        Expr::Exists(path) => truth(template::lookup(record, path).is_some()),
+        Expr::Len(expr) => match eval(expr, record).as_ref() {
+            Value::String(s) => Cow::Owned(s.chars().count().into()),
+            Value::Array(values) => Cow::Owned(values.len().into()),
+            Value::Object(entries) => Cow::Owned(entries.len().into()),
+            _ => Cow::Owned(Value::Null),
+        },
+        Expr::Not(expr) => truth(!holds(expr)),
+        Expr::And(a, b) => truth(holds(a) && holds(b)),
+        Expr::Or(a, b) => truth(holds(a) || holds(b)),
+        Expr::Compare(a, op, b) => {
+            let (a, b) = (eval(a, record), eval(b, record));
+            let ordering = match (a.as_ref(), b.as_ref()) {
+                (Value::Number(a), Value::Number(b)) => a
+                    .as_f64()
+                    .zip(b.as_f64())
+                    .and_then(|(a, b)| a.partial_cmp(&b)),
+                (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+                (a, b) if a == b => Some(std::cmp::Ordering::Equal),
+                _ => None,
+            };
+            truth(match op {
+                Op::Eq => ordering.is_some_and(|o| o.is_eq()),
+                Op::Ne => !ordering.is_some_and(|o| o.is_eq()),
+                Op::Lt => ordering.is_some_and(|o| o.is_lt()),
+                Op::Le => ordering.is_some_and(|o| o.is_le()),
+                Op::Gt => ordering.is_some_and(|o| o.is_gt()),
+                Op::Ge => ordering.is_some_and(|o| o.is_ge()),
+            })
+        }
+    }
+}
+
+/// Checks the records against some predicates, counting those that fail
+/// each one.
+#[derive(Debug, Clone)]
+pub struct Assertions {
+    predicates: Vec<Predicate>,
+    /// the number of records failing each predicate, and the index of the
+    /// first of them
+    failed: Vec<(u64, u64)>,
+    /// records failing any of them
+    pub records: u64,
+    /// entries for the failing records not taken yet, if kept
+    failures: Option<Vec<Vec<u8>>>,
+}
+
+impl Assertions {
+    /// Check `predicates`, keeping an entry for each failing record if
+    /// `keep` is set.
+    pub fn new(predicates: &[Predicate], keep: bool) -> Self {
+        Self {
+            predicates: predicates.to_vec(),
+            failed: vec![(0, u64::MAX); predicates.len()],
+            records: 0,
+            failures: keep.then(Vec::new),
+        }
+    }
+
+    /// Check record number `index`, returning whether it meets all the
+    /// predicates.
+    pub fn check(&mut self, record: &Value, index: u64) -> bool {
+        let mut failed = vec![];
+        for (predicate, (count, first)) in self.predicates.iter().zip(&mut self.failed) {
+            if !predicate.holds(record) {
+                *count += 1;
+                // records may come out of order from several threads
+                *first = (*first).min(index);
+                failed.push(predicate.text.as_str());
+            }
+        }
+        if failed.is_empty() {
+            return true;
+        }
+        self.records += 1;
+        if let Some(failures) = &mut self.failures {
+            let entry = serde_json::json!({ "index": index, "failed": failed, "record": record });
+            failures.push(entry.to_string().into_bytes());
+        }
+        false
+    }
+
+    /// The entries for the records that failed since the last call, as JSON
+    /// texts with the `index` of the record, the predicates `failed` and
+    /// the `record`, if kept.
+    pub fn take_failures(&mut self) -> Vec<Vec<u8>> {
+        self.failures
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Write the number of records failing each predicate that some failed.
+    pub fn write_report(&self, mut w: impl Write) -> io::Result<()> {
+        for (predicate, &(count, first)) in self.predicates.iter().zip(&self.failed) {
+            if count > 0 {
+                writeln!(
+                    w,
+                    "{count} records failed {:?} (first: record {first})",
+                    predicate.text
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+use crate::signals::STATUS;
+use serde_json::json;
+use std::io::{self, Write};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Writes progress events as JSON lines at a regular interval, from the
+/// counts in [`STATUS`], for tools that display progress without a terminal.
+///
+/// Each event has the `bytes` of input and the `records` written so far,
+/// their rates over the last interval, the elapsed time and, when the size of
+/// the input is known, the ETA in seconds. The last one, written by
+/// [`JsonProgress::finish`], has `"event": "done"` rather than `"progress"`.
+pub struct JsonProgress {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl JsonProgress {
+    /// Start writing events to `w` every `interval`, for an input of
+    /// `total` bytes if known.
+    pub fn start(mut w: Box<dyn Write + Send>, total: Option<u64>, interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let start = Instant::now();
+            let mut last = (
+                start,
+                STATUS.offset.load(Ordering::Relaxed),
+                STATUS.records.load(Ordering::Relaxed),
+            );
+            loop {
+                let done = match stopped.recv_timeout(interval) {
+                    Ok(()) => true,
+                    Err(RecvTimeoutError::Timeout) => false,
+                    Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                };
+                let bytes = STATUS.offset.load(Ordering::Relaxed);
+                let records = STATUS.records.load(Ordering::Relaxed);
+                let secs = last.0.elapsed().as_secs_f64().max(1e-3);
+                let bytes_per_sec = bytes.saturating_sub(last.1) as f64 / secs;
+                let records_per_sec = records.saturating_sub(last.2) as f64 / secs;
+                let eta = match total {
+                    Some(_) if done => Some(0),
+                    Some(total) if bytes_per_sec > 0.0 => {
+                        Some((total.saturating_sub(bytes) as f64 / bytes_per_sec).ceil() as u64)
+                    }
+                    _ => None,
+                };
+                let event = json!({
+                    "event": if done { "done" } else { "progress" },
+                    "bytes": bytes,
+                    "total_bytes": total,
+                    "records": records,
+                    "bytes_per_sec": bytes_per_sec as u64,
+                    "records_per_sec": records_per_sec as u64,
+                    "elapsed_secs": start.elapsed().as_millis() as f64 / 1000.0,
+                    "eta_secs": eta,
+                });
+                writeln!(w, "{event}")?;
+                w.flush()?;
+                if done {
+                    return Ok(());
+                }
+                last = (Instant::now(), bytes, records);
+            }
+        });
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Write the final event, once the conversion is complete.
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(res)) => res,
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for JsonProgress {
+    fn drop(&mut self) {
+        // a disconnected channel stops the thread without a final event
+        self.stop = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(data)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn events() {
+        let out = Shared::default();
+        let progress =
+            JsonProgress::start(Box::new(out.clone()), Some(100), Duration::from_millis(20));
+        thread::sleep(Duration::from_millis(110));
+        progress.finish().unwrap();
+        let out = out.0.lock().unwrap();
+        let events: Vec<Value> = (out.split(|&b| b == b'\n'))
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert!(events.len() >= 3, "{events:?}");
+        let (done, progress) = events.split_last().unwrap();
+        assert!(progress.iter().all(|event| event["event"] == "progress"));
+        assert_eq!(done["event"], "done");
+        assert_eq!(done["total_bytes"], 100);
+        assert_eq!(done["eta_secs"], 0);
+        for key in ["bytes", "records", "bytes_per_sec", "records_per_sec"] {
+            assert!(done[key].is_u64(), "{key}");
+        }
+        assert!(done["elapsed_secs"].as_f64().unwrap() >= 0.1);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::scan::Scanner;
+
+/// Converts a JSON array to JSONL from chunks of it pushed as they come,
+/// rather than pulled from a reader, for the bindings to other languages.
+///
+/// Each chunk yields the records of the elements it completes: the bytes of
+/// an element that isn't complete yet are kept until the next chunk.
+#[derive(Default)]
+pub struct Converter {
+    /// input bytes after the last complete element
+    pending: Vec<u8>,
+    /// offset of `pending` in the input
+    offset: u64,
+    /// whether an element has been read, so that `pending` starts right
+    /// after one rather than at the beginning of the array
+    started: bool,
+    /// whether the closing `]` has been reached
+    done: bool,
+    json5: bool,
+    comments: bool,
+    trailing_commas: bool,
+    max_depth: Option<usize>,
+    record: Vec<u8>,
+}
+
+impl Converter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept JSON5 syntax: single-quoted strings, comments and a trailing comma.
+    pub fn json5(mut self, yes: bool) -> Self {
+        self.json5 = yes;
+        self
+    }
+
+    /// Strip `//` and `/* */` comments, both between and inside elements.
+    pub fn comments(mut self, yes: bool) -> Self {
+        self.comments = yes;
+        self
+    }
+
+    /// Accept (and drop) a comma before a closing `]` or `}`.
+    pub fn trailing_commas(mut self, yes: bool) -> Self {
+        self.trailing_commas = yes;
+        self
+    }
+
+    /// Reject elements with more than `n` levels of nested arrays and objects.
+    pub fn max_depth(mut self, n: usize) -> Self {
+        self.max_depth = Some(n);
+        self
+    }
+
+    /// Convert the next `chunk` of the input, appending the JSONL records of
+    /// the elements it completes (possibly none) to `out`.
+    pub fn push(&mut self, chunk: &[u8], out: &mut Vec<u8>) -> anyhow::Result<()> {
+        if self.done {
+            // anything after the array is ignored
+            return Ok(());
+        }
+        self.pending.extend_from_slice(chunk);
+        self.convert(false, out)
+    }
+
+    /// Convert the rest of the input, once all of it has been pushed. Fails
+    /// if the array isn't complete.
+    pub fn finish(&mut self, out: &mut Vec<u8>) -> anyhow::Result<()> {
+        if self.done {
+            return Ok(());
+        }
+        self.convert(true, out)
+    }
+
+    /// Read the elements complete in `pending`, and drop their bytes. Unless
+    /// this is the `end` of the input, running out of input only means that
+    /// the next element isn't complete yet.
+    fn convert(&mut self, end: bool, out: &mut Vec<u8>) -> anyhow::Result<()> {
+        let mut scanner = Scanner::new(&self.pending[..])
+            .json5(self.json5)
+            .comments(self.comments)
+            .trailing_commas(self.trailing_commas)
+            .max_depth(self.max_depth.unwrap_or(usize::MAX));
+        if self.started {
+            scanner = scanner.resume_at(self.offset);
+        }
+        // end of the last complete element
+        let mut consumed = self.offset;
+        loop {
+            match scanner.read_record_into(&mut self.record) {
+                Ok(true) => {
+                    out.extend_from_slice(&self.record);
+                    out.push(b'\n');
+                    self.started = true;
+                    consumed = scanner.offset();
+                }
+                Ok(false) => {
+                    self.done = true;
+                    break;
+                }
+                // the element goes on in the next chunk
+                Err(_) if !end && scanner.get_ref().is_empty() => break,
+                Err(e) => return Err(e),
+            }
+        }
+        if self.done {
+            self.pending = vec![];
+        } else {
+            self.pending.drain(..(consumed - self.offset) as usize);
+            self.offset = consumed;
+        }
+        Ok(())
+    }
+}
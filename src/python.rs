@@ -0,0 +1,176 @@
+use crate::atomic::AtomicFile;
+use crate::gzip::MaybeGzip;
+use crate::scan::{self, Scanner};
+use anyhow::Context;
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+type Input = Scanner<BufReader<MaybeGzip<File>>>;
+
+/// The scanner options, as keyword arguments.
+#[derive(Clone, Copy)]
+struct Options {
+    json5: bool,
+    comments: bool,
+    trailing_commas: bool,
+    max_depth: Option<usize>,
+}
+
+/// Open the array in `src`, gzipped or not, for scanning.
+fn open(src: &Path, opts: Options) -> anyhow::Result<Input> {
+    let open = || -> io::Result<_> {
+        let mut rd = BufReader::new(MaybeGzip::new(File::open(src)?)?);
+        scan::skip_bom(&mut rd)?;
+        Ok(rd)
+    };
+    let rd = open().with_context(|| format!("opening {}", src.display()))?;
+    Ok(Scanner::new(rd)
+        .json5(opts.json5)
+        .comments(opts.comments)
+        .trailing_commas(opts.trailing_commas)
+        .max_depth(opts.max_depth.unwrap_or(usize::MAX)))
+}
+
+/// `OSError` for failing to read or write, `ValueError` for a malformed input.
+fn to_py(e: anyhow::Error) -> PyErr {
+    if e.chain().any(|e| e.is::<io::Error>()) {
+        PyOSError::new_err(format!("{e:#}"))
+    } else {
+        PyValueError::new_err(format!("{e:#}"))
+    }
+}
+
+fn convert_file(src: &Path, dst: &Path, opts: Options) -> anyhow::Result<u64> {
+    let mut scanner = open(src, opts)?;
+    let (tmp, file) =
+        AtomicFile::create(dst, dst).with_context(|| format!("creating {}", dst.display()))?;
+    let mut w = BufWriter::new(file);
+    let mut record = vec![];
+    let mut records = 0;
+    while scanner.read_record_into(&mut record)? {
+        w.write_all(&record)?;
+        w.write_all(b"\n")?;
+        records += 1;
+    }
+    w.into_inner().map_err(io::IntoInnerError::into_error)?;
+    tmp.persist()?;
+    Ok(records)
+}
+
+/// Convert the JSON array in the file `src` to JSONL in the file `dst`,
+/// and return the number of records. `dst` only appears once complete.
+#[pyfunction]
+#[pyo3(signature = (src, dst, *, json5=false, comments=false, trailing_commas=false, max_depth=None))]
+fn convert(
+    py: Python<'_>,
+    src: PathBuf,
+    dst: PathBuf,
+    json5: bool,
+    comments: bool,
+    trailing_commas: bool,
+    max_depth: Option<usize>,
+) -> PyResult<u64> {
+    let opts = Options {
+        json5,
+        comments,
+        trailing_commas,
+        max_depth,
+    };
+    py.detach(|| convert_file(&src, &dst, opts)).map_err(to_py)
+}
+
+/// Iterates over the records of a JSON array, as compact JSON strings.
+#[pyclass]
+struct Records {
+    scanner: Input,
+    record: Vec<u8>,
+}
+
+#[pymethods]
+impl Records {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<String>> {
+        let Self { scanner, record } = self;
+        if !py
+            .detach(|| scanner.read_record_into(record))
+            .map_err(to_py)?
+        {
+            return Ok(None);
+        }
+        // the parser has checked that the record is valid JSON, hence UTF-8
+        Ok(Some(String::from_utf8_lossy(record).into_owned()))
+    }
+}
+
+/// Iterate over the records of the JSON array in the file `src`, each a
+/// string of compact JSON (for `json.loads`).
+#[pyfunction]
+#[pyo3(signature = (src, *, json5=false, comments=false, trailing_commas=false, max_depth=None))]
+fn records(
+    src: PathBuf,
+    json5: bool,
+    comments: bool,
+    trailing_commas: bool,
+    max_depth: Option<usize>,
+) -> PyResult<Records> {
+    let opts = Options {
+        json5,
+        comments,
+        trailing_commas,
+        max_depth,
+    };
+    let scanner = open(&src, opts).map_err(to_py)?;
+    Ok(Records {
+        scanner,
+        record: vec![],
+    })
+}
+
+/// The `json2jsonl` Python module.
+#[pymodule]
+fn json2jsonl(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(convert, m)?)?;
+    m.add_function(wrap_pyfunction!(records, m)?)?;
+    m.add_class::<Records>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files() {
+        let dir = std::env::temp_dir().join(format!("json2jsonl-python-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (src, dst) = (dir.join("in.json.gz"), dir.join("out.jsonl"));
+        let mut gz = flate2::write::GzEncoder::new(File::create(&src).unwrap(), Default::default());
+        gz.write_all(b"[{'a': 1}, /* c */ 2,]").unwrap();
+        gz.finish().unwrap();
+        let opts = Options {
+            json5: true,
+            comments: false,
+            trailing_commas: false,
+            max_depth: None,
+        };
+        assert_eq!(convert_file(&src, &dst, opts).unwrap(), 2);
+        assert_eq!(std::fs::read_to_string(&dst).unwrap(), "{\"a\":1}\n2\n");
+        // strict JSON by default, and no output then
+        let (src, dst) = (dir.join("in.json"), dir.join("failed.jsonl"));
+        std::fs::write(&src, "[1, 'x']").unwrap();
+        let strict = Options {
+            json5: false,
+            ..opts
+        };
+        let err = convert_file(&src, &dst, strict).unwrap_err();
+        assert!(format!("{err:#}").starts_with("invalid element"), "{err:#}");
+        assert!(!dst.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,46 @@
+use std::time::{Duration, Instant};
+
+/// Paces the output to a number of units (records, bytes) per second.
+///
+/// Unused capacity is kept for at most a second, so that the output can
+/// catch up on a slow input without flooding the downstream system.
+pub struct Limiter {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Limiter {
+    pub fn new(per_second: f64) -> Self {
+        Self {
+            rate: per_second,
+            tokens: per_second,
+            last: Instant::now(),
+        }
+    }
+
+    /// Account for `n` more units, returning how long to wait before
+    /// sending them, if the rate would be exceeded otherwise.
+    pub fn delay(&mut self, n: u64) -> Option<Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate) - n as f64;
+        (self.tokens < 0.).then(|| Duration::from_secs_f64(-self.tokens / self.rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay() {
+        let mut limiter = Limiter::new(100.);
+        // a second's worth goes right away
+        assert_eq!(limiter.delay(60), None);
+        assert_eq!(limiter.delay(40), None);
+        let delay = limiter.delay(50).unwrap();
+        assert!(delay > Duration::from_millis(450) && delay <= Duration::from_millis(500));
+    }
+}
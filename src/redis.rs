@@ -0,0 +1,285 @@
+use crate::sink::Sink;
+use anyhow::{Context, bail};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// How records are added to the key.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum RedisCommand {
+    /// As an entry of a stream, with the record in its `record` field
+    Xadd,
+    /// As an element at the tail of a list
+    Rpush,
+}
+
+/// Settings of a [`RedisSink`].
+#[derive(Debug, Clone)]
+pub struct RedisConfig {
+    /// `redis://[[user]:pass@]host[:port][/db]`
+    pub url: String,
+    pub key: String,
+    pub command: RedisCommand,
+    /// Commands sent before reading their replies
+    pub pipeline: usize,
+}
+
+/// Adds each record to a Redis stream or list, over RESP with pipelined
+/// commands.
+pub struct RedisSink {
+    rd: BufReader<TcpStream>,
+    wr: BufWriter<TcpStream>,
+    key: String,
+    add: RedisCommand,
+    pending: usize,
+    pipeline: usize,
+    line: String,
+}
+
+impl RedisSink {
+    pub fn connect(config: RedisConfig) -> anyhow::Result<Self> {
+        if config.url.starts_with("rediss://") {
+            bail!(
+                "TLS connections to Redis are not supported ({})",
+                config.url
+            );
+        }
+        let rest = config.url.strip_prefix("redis://").unwrap_or(&config.url);
+        let (auth, rest) = match rest.rsplit_once('@') {
+            Some((auth, rest)) => (Some(auth), rest),
+            None => (None, rest),
+        };
+        let (addr, db) = match rest.split_once('/') {
+            Some((addr, db)) => (addr, Some(db).filter(|db| !db.is_empty())),
+            None => (rest, None),
+        };
+        let addr = if addr.contains(':') {
+            addr.to_string()
+        } else {
+            format!("{addr}:6379")
+        };
+        let stream =
+            TcpStream::connect(&addr).with_context(|| format!("connecting to Redis at {addr}"))?;
+        stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+        let mut sink = Self {
+            rd: BufReader::new(stream.try_clone()?),
+            wr: BufWriter::new(stream),
+            key: config.key,
+            add: config.command,
+            pending: 0,
+            pipeline: config.pipeline.max(1),
+            line: String::new(),
+        };
+
+        match auth.map(|a| a.split_once(':')) {
+            Some(Some(("", pass))) => sink.command(&[b"AUTH", pass.as_bytes()])?,
+            Some(Some((user, pass))) => {
+                sink.command(&[b"AUTH", user.as_bytes(), pass.as_bytes()])?
+            }
+            Some(None) => sink.command(&[b"AUTH", auth.unwrap().as_bytes()])?,
+            None => (),
+        }
+        if let Some(db) = db {
+            sink.command(&[b"SELECT", db.as_bytes()])?;
+        }
+        sink.command(&[b"PING"])?;
+        sink.wr.flush()?;
+        while sink.pending > 0 {
+            sink.read_reply()
+                .with_context(|| format!("setting up the connection to Redis at {addr}"))?;
+        }
+        Ok(sink)
+    }
+
+    /// Queue a command, as an array of bulk strings.
+    fn command(&mut self, words: &[&[u8]]) -> anyhow::Result<()> {
+        write!(self.wr, "*{}\r\n", words.len())?;
+        for word in words {
+            write!(self.wr, "${}\r\n", word.len())?;
+            self.wr.write_all(word)?;
+            self.wr.write_all(b"\r\n")?;
+        }
+        self.pending += 1;
+        if self.pending >= self.pipeline {
+            self.wr.flush()?;
+            while self.pending > 0 {
+                self.read_reply()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> anyhow::Result<()> {
+        self.line.clear();
+        if self.rd.read_line(&mut self.line)? == 0 {
+            bail!("Redis server closed the connection");
+        }
+        self.line.truncate(self.line.trim_end().len());
+        Ok(())
+    }
+
+    /// Read the reply to the oldest pending command, failing on an error.
+    fn read_reply(&mut self) -> anyhow::Result<()> {
+        self.skip_value()?;
+        self.pending -= 1;
+        Ok(())
+    }
+
+    fn skip_value(&mut self) -> anyhow::Result<()> {
+        self.read_line()?;
+        let Some(kind) = self.line.chars().next() else {
+            bail!("empty reply from Redis");
+        };
+        // the length of a bulk string or array, -1 for a null one
+        let len = match kind {
+            '$' | '*' => (self.line[1..].parse::<i64>())
+                .with_context(|| format!("malformed reply from Redis: {}", self.line))?,
+            _ => 0,
+        };
+        match kind {
+            '+' | ':' => (),
+            '-' => bail!("Redis error: {}", &self.line[1..]),
+            '$' if len >= 0 => {
+                let mut data = vec![0; len as usize + 2];
+                self.rd.read_exact(&mut data)?;
+            }
+            '$' => (),
+            '*' => {
+                for _ in 0..len {
+                    self.skip_value()?;
+                }
+            }
+            _ => bail!("unexpected reply from Redis: {}", self.line),
+        }
+        Ok(())
+    }
+}
+
+impl Sink for RedisSink {
+    fn push(&mut self, record: &[u8]) -> anyhow::Result<()> {
+        let key = self.key.clone();
+        match self.add {
+            RedisCommand::Xadd => self.command(&[b"XADD", key.as_bytes(), b"*", b"record", record]),
+            RedisCommand::Rpush => self.command(&[b"RPUSH", key.as_bytes(), record]),
+        }
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.wr.flush()?;
+        while self.pending > 0 {
+            self.read_reply()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// A Redis server on a free port for one connection, replying to XADD
+    /// with an ID, to RPUSH with the length of the list and to the others
+    /// with OK, or with an error for a `bad` record: its address, and the
+    /// commands it got.
+    fn fake_server() -> (String, thread::JoinHandle<Vec<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut rd = BufReader::new(&stream);
+            let mut commands = vec![];
+            let mut line = String::new();
+            while rd.read_line(&mut line).unwrap() > 0 {
+                let n: usize = line.trim_end()[1..].parse().unwrap();
+                let mut words = vec![];
+                for _ in 0..n {
+                    line.clear();
+                    rd.read_line(&mut line).unwrap();
+                    let len: usize = line.trim_end()[1..].parse().unwrap();
+                    let mut word = vec![0; len + 2];
+                    rd.read_exact(&mut word).unwrap();
+                    words.push(String::from_utf8_lossy(&word[..len]).into_owned());
+                }
+                let reply = match words[0].as_str() {
+                    _ if words.last().is_some_and(|w| w == "bad") => "-ERR bad record\r\n".into(),
+                    "XADD" => "$3\r\n1-0\r\n".into(),
+                    "RPUSH" => format!(":{}\r\n", commands.len()),
+                    "PING" => "+PONG\r\n".into(),
+                    _ => "+OK\r\n".into(),
+                };
+                (&stream).write_all(reply.as_bytes()).unwrap();
+                commands.push(words);
+                line.clear();
+            }
+            commands
+        });
+        (addr, server)
+    }
+
+    fn sink(url: String, command: RedisCommand) -> RedisSink {
+        RedisSink::connect(RedisConfig {
+            url,
+            key: "imports".into(),
+            command,
+            pipeline: 2,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn commands() {
+        let (addr, server) = fake_server();
+        let mut redis = sink(format!("redis://:secret@{addr}/2"), RedisCommand::Xadd);
+        for record in [r#"{"a":1}"#, "2", "3"] {
+            redis.push(record.as_bytes()).unwrap();
+        }
+        redis.finish().unwrap();
+        drop(redis);
+        let commands = server.join().unwrap();
+        let commands: Vec<_> = commands.iter().map(|c| c.join(" ")).collect();
+        assert_eq!(
+            commands,
+            [
+                "AUTH secret",
+                "SELECT 2",
+                "PING",
+                r#"XADD imports * record {"a":1}"#,
+                "XADD imports * record 2",
+                "XADD imports * record 3",
+            ]
+        );
+        let (addr, server) = fake_server();
+        let mut redis = sink(format!("redis://user:pw@{addr}"), RedisCommand::Rpush);
+        redis.push(b"1").unwrap();
+        redis.finish().unwrap();
+        drop(redis);
+        let commands = server.join().unwrap();
+        assert_eq!(commands[0], ["AUTH", "user", "pw"]);
+        assert_eq!(commands[2], ["RPUSH", "imports", "1"]);
+    }
+
+    #[test]
+    fn errors() {
+        let (addr, server) = fake_server();
+        let mut redis = sink(addr, RedisCommand::Rpush);
+        redis.push(b"bad").unwrap();
+        let e = redis.finish().unwrap_err();
+        assert_eq!(e.to_string(), "Redis error: ERR bad record");
+        drop(redis);
+        server.join().unwrap();
+        let e = RedisSink::connect(RedisConfig {
+            url: "rediss://host".into(),
+            key: "k".into(),
+            command: RedisCommand::Xadd,
+            pipeline: 1,
+        });
+        assert!(
+            e.err()
+                .unwrap()
+                .to_string()
+                .starts_with("TLS connections to Redis")
+        );
+    }
+}
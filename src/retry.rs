@@ -0,0 +1,125 @@
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+
+/// Longest wait between two attempts.
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// How a failed network operation is retried: up to `retries` times,
+/// waiting `delay` before the first retry and twice as long before each
+/// next one, up to a minute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    pub retries: u32,
+    pub delay: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            retries: 5,
+            delay: Duration::from_millis(100),
+        }
+    }
+}
+
+impl Retry {
+    /// The wait before retry number `attempt`, counting from 0.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        (self.delay.saturating_mul(1 << attempt.min(16))).min(MAX_DELAY)
+    }
+
+    /// Call `f` until it succeeds, fails with an error that `transient`
+    /// rejects, or runs out of retries, warning that `what` failed before
+    /// each retry.
+    pub fn run<T>(
+        &self,
+        what: &str,
+        transient: impl Fn(&anyhow::Error) -> bool,
+        mut f: impl FnMut() -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Ok(v) => return Ok(v),
+                Err(e) if attempt < self.retries && transient(&e) => {
+                    let delay = self.backoff(attempt);
+                    warn!(
+                        attempt = attempt + 1,
+                        "{what} failed ({e:#}), retrying in {delay:?}"
+                    );
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+                Err(e) if attempt > 0 => {
+                    return Err(e.context(format!("{what} failed after {} attempts", attempt + 1)));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        let retry = Retry {
+            retries: 3,
+            delay: Duration::from_secs(1),
+        };
+        let delays: Vec<_> = [0, 1, 5, 6, 40].map(|n| retry.backoff(n).as_secs()).into();
+        assert_eq!(delays, [1, 2, 32, 60, 60]);
+    }
+
+    #[test]
+    fn attempts() {
+        let retry = Retry {
+            retries: 2,
+            delay: Duration::ZERO,
+        };
+        let mut calls = 0;
+        let res = retry.run(
+            "reading",
+            |_| true,
+            || {
+                calls += 1;
+                if calls < 3 {
+                    anyhow::bail!("blip")
+                } else {
+                    Ok(calls)
+                }
+            },
+        );
+        assert_eq!(res.unwrap(), 3);
+        let mut calls = 0;
+        let res = retry.run(
+            "reading",
+            |_| true,
+            || -> anyhow::Result<()> {
+                calls += 1;
+                anyhow::bail!("down")
+            },
+        );
+        assert_eq!(
+            format!("{:#}", res.unwrap_err()),
+            "reading failed after 3 attempts: down"
+        );
+        // not retried
+        let mut calls = 0;
+        let res = retry.run(
+            "reading",
+            |e| e.to_string() != "denied",
+            || -> anyhow::Result<()> {
+                calls += 1;
+                anyhow::bail!("denied")
+            },
+        );
+        assert_eq!(
+            (res.unwrap_err().to_string(), calls),
+            ("denied".to_string(), 1)
+        );
+    }
+}
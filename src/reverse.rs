@@ -0,0 +1,330 @@
+use crate::compact;
+use anyhow::Context;
+use serde::ser::{Error as _, SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer as _};
+use serde_json::ser::{Formatter, PrettyFormatter};
+use serde_json::value::RawValue;
+use serde_json::{Map, Serializer, Value};
+use std::cell::{Cell, RefCell};
+use std::io::{self, BufRead, Write};
+
+/// How [`reverse`] lays out the array.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Layout {
+    /// Each record on its own line, between the brackets
+    Lines,
+    /// No whitespace at all
+    Compact,
+    /// The records pretty-printed, with this many spaces per level of nesting
+    Indent(usize),
+}
+
+/// An object to nest the array in, as `{"key":[...]}`, for APIs that want
+/// it that way.
+#[derive(Debug, Clone, Default)]
+pub struct Wrapper {
+    pub key: String,
+    /// Other members of the object, after the array
+    pub extra: Map<String, Value>,
+}
+
+/// A part of the input for [`reverse`], for arrays of a bounded size.
+#[derive(Debug, Clone, Copy)]
+pub struct Chunk {
+    /// The most records to write
+    pub records: u64,
+    /// Lines of the input read so far, for the line numbers of the errors
+    pub lines: u64,
+}
+
+impl Chunk {
+    /// Skip the blank lines at the start of `rd`, and return whether there
+    /// is a record after them for another chunk.
+    pub fn more(&mut self, rd: &mut impl BufRead) -> io::Result<bool> {
+        loop {
+            let buf = rd.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(false);
+            }
+            let blank = buf.iter().take_while(|c| c.is_ascii_whitespace()).count();
+            self.lines += buf[..blank].iter().filter(|&&c| c == b'\n').count() as u64;
+            let rest = blank < buf.len();
+            rd.consume(blank);
+            if rest {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+/// Write the JSONL records read from `rd` to `w` as a JSON array (followed
+/// by a newline), possibly nested in `wrapper`, and return the number of
+/// records. Blank lines are skipped. With a `chunk`, the array ends after
+/// its number of records, leaving the others in `rd`.
+pub fn reverse(
+    rd: impl BufRead,
+    w: impl Write,
+    layout: Layout,
+    wrapper: Option<&Wrapper>,
+    chunk: Option<&mut Chunk>,
+) -> anyhow::Result<u64> {
+    if let Some(wrapper) = wrapper
+        && wrapper.extra.contains_key(&wrapper.key)
+    {
+        anyhow::bail!("the extra members already have a {:?} key", wrapper.key);
+    }
+    let (limit, lines) = chunk
+        .as_ref()
+        .map_or((None, 0), |c| (Some(c.records), c.lines));
+    let (records, lines) = match layout {
+        Layout::Lines => {
+            let ser = Serializer::with_formatter(w, Lines::default());
+            write_array(ser, Records::new(rd, raw, limit, lines), wrapper)?
+        }
+        Layout::Compact => write_array(
+            Serializer::new(w),
+            Records::new(rd, raw, limit, lines),
+            wrapper,
+        )?,
+        Layout::Indent(n) => {
+            let indent = vec![b' '; n];
+            let ser = Serializer::with_formatter(w, PrettyFormatter::with_indent(&indent));
+            write_array(ser, Records::new(rd, value, limit, lines), wrapper)?
+        }
+    };
+    if let Some(chunk) = chunk {
+        chunk.lines = lines;
+    }
+    Ok(records)
+}
+
+/// The record `line` as it is, without its insignificant whitespace.
+fn raw(line: &[u8]) -> serde_json::Result<Box<RawValue>> {
+    let mut record = Vec::with_capacity(line.len());
+    compact::compact(line, &mut record);
+    serde_json::from_slice(&record)
+}
+
+fn value(line: &[u8]) -> serde_json::Result<Value> {
+    serde_json::from_slice(line)
+}
+
+fn write_array<W: Write, F: Formatter, R: BufRead, T: Serialize>(
+    mut ser: Serializer<W, F>,
+    records: Records<R, impl Fn(&[u8]) -> serde_json::Result<T>>,
+    wrapper: Option<&Wrapper>,
+) -> anyhow::Result<(u64, u64)> {
+    let res = match wrapper {
+        None => records.serialize(&mut ser),
+        Some(wrapper) => (|| {
+            let mut map = ser.serialize_map(None)?;
+            map.serialize_entry(&wrapper.key, &records)?;
+            for (key, value) in &wrapper.extra {
+                map.serialize_entry(key, value)?;
+            }
+            SerializeMap::end(map)
+        })(),
+    };
+    if let Some(e) = records.err.take() {
+        return Err(e);
+    }
+    res?;
+    let mut w = ser.into_inner();
+    w.write_all(b"\n")?;
+    w.flush()?;
+    Ok((records.count.get(), records.lineno.get()))
+}
+
+/// The records of the input, serialized as an array as they are read.
+struct Records<R, P> {
+    rd: RefCell<R>,
+    parse: P,
+    count: Cell<u64>,
+    limit: Option<u64>,
+    /// lines read so far
+    lineno: Cell<u64>,
+    /// why reading stopped, which the serializer can't carry
+    err: RefCell<Option<anyhow::Error>>,
+}
+
+impl<R, P> Records<R, P> {
+    fn new(rd: R, parse: P, limit: Option<u64>, lineno: u64) -> Self {
+        Self {
+            rd: RefCell::new(rd),
+            parse,
+            count: Cell::new(0),
+            limit,
+            lineno: Cell::new(lineno),
+            err: RefCell::new(None),
+        }
+    }
+}
+
+impl<R: BufRead, T: Serialize, P: Fn(&[u8]) -> serde_json::Result<T>> Serialize for Records<R, P> {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let fail = |e: anyhow::Error| {
+            *self.err.borrow_mut() = Some(e);
+            S::Error::custom("reading the records failed")
+        };
+        let mut rd = self.rd.borrow_mut();
+        let mut seq = ser.serialize_seq(None)?;
+        let mut line = vec![];
+        let mut lineno = self.lineno.get();
+        while Some(self.count.get()) != self.limit {
+            line.clear();
+            match rd.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(_) => lineno += 1,
+                Err(e) => return Err(fail(e.into())),
+            }
+            let record = line.trim_ascii();
+            if record.is_empty() {
+                continue;
+            }
+            let value = (self.parse)(record)
+                .with_context(|| format!("invalid record on line {lineno}"))
+                .map_err(fail)?;
+            seq.serialize_element(&value)?;
+            self.count.set(self.count.get() + 1);
+        }
+        self.lineno.set(lineno);
+        seq.end()
+    }
+}
+
+/// Puts the records on their own lines, and writes anything else compactly.
+/// The records are raw values, so the first array it sees is theirs.
+#[derive(Default)]
+struct Lines {
+    /// in the array of the records, or past it
+    inside: bool,
+    past: bool,
+    any: bool,
+}
+
+impl Formatter for Lines {
+    fn begin_array<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        self.inside = !self.past;
+        w.write_all(b"[")
+    }
+
+    fn begin_array_value<W: ?Sized + Write>(&mut self, w: &mut W, first: bool) -> io::Result<()> {
+        if self.inside {
+            self.any = true;
+            w.write_all(if first { b"\n" } else { b",\n" })
+        } else if first {
+            Ok(())
+        } else {
+            w.write_all(b",")
+        }
+    }
+
+    fn end_array<W: ?Sized + Write>(&mut self, w: &mut W) -> io::Result<()> {
+        if std::mem::take(&mut self.inside) {
+            self.past = true;
+            w.write_all(if self.any { b"\n]" } else { b"]" })
+        } else {
+            w.write_all(b"]")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "{\"a\": [1, 2]}\n\n  \"x\"\n";
+
+    fn array(input: &str, layout: Layout, wrapper: Option<&Wrapper>) -> String {
+        let mut out = vec![];
+        let records = reverse(input.as_bytes(), &mut out, layout, wrapper, None).unwrap();
+        assert_eq!(
+            records,
+            input.lines().filter(|l| !l.trim().is_empty()).count() as u64
+        );
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn layouts() {
+        assert_eq!(
+            array(INPUT, Layout::Lines, None),
+            "[\n{\"a\":[1,2]},\n\"x\"\n]\n"
+        );
+        assert_eq!(
+            array(INPUT, Layout::Compact, None),
+            "[{\"a\":[1,2]},\"x\"]\n"
+        );
+        assert_eq!(
+            array(INPUT, Layout::Indent(1), None),
+            "[\n {\n  \"a\": [\n   1,\n   2\n  ]\n },\n \"x\"\n]\n"
+        );
+        assert_eq!(array("", Layout::Lines, None), "[]\n");
+        assert_eq!(array("\n", Layout::Indent(2), None), "[]\n");
+    }
+
+    #[test]
+    fn invalid_records() {
+        for layout in [Layout::Lines, Layout::Indent(2)] {
+            let e = reverse(&b"1\n\n{\"a\":}\n"[..], io::sink(), layout, None, None).unwrap_err();
+            assert_eq!(e.to_string(), "invalid record on line 3");
+        }
+    }
+
+    #[test]
+    fn wrapped() {
+        let extra = serde_json::json!({"meta": {"v": 1}, "n": 2});
+        let wrapper = Wrapper {
+            key: "items".to_string(),
+            extra: extra.as_object().unwrap().clone(),
+        };
+        assert_eq!(
+            array(INPUT, Layout::Lines, Some(&wrapper)),
+            "{\"items\":[\n{\"a\":[1,2]},\n\"x\"\n],\"meta\":{\"v\":1},\"n\":2}\n"
+        );
+        assert_eq!(
+            array(INPUT, Layout::Indent(1), Some(&wrapper))
+                .lines()
+                .next(),
+            Some("{")
+        );
+        let wrapper = Wrapper {
+            key: "n".to_string(),
+            ..wrapper
+        };
+        let e = reverse(
+            &b"1\n"[..],
+            io::sink(),
+            Layout::Compact,
+            Some(&wrapper),
+            None,
+        );
+        assert_eq!(
+            e.unwrap_err().to_string(),
+            "the extra members already have a \"n\" key"
+        );
+    }
+
+    #[test]
+    fn chunks() {
+        let mut rd = &b"1\n\n2\n3\n\n\n4\n\n{\"a\":}\n"[..];
+        let mut chunk = Chunk {
+            records: 2,
+            lines: 0,
+        };
+        let mut arrays = vec![];
+        for _ in 0..2 {
+            assert!(chunk.more(&mut rd).unwrap());
+            let mut out = vec![];
+            let records = reverse(&mut rd, &mut out, Layout::Compact, None, Some(&mut chunk));
+            assert_eq!(records.unwrap(), 2);
+            arrays.push(String::from_utf8(out).unwrap());
+        }
+        assert_eq!(arrays, ["[1,2]\n", "[3,4]\n"]);
+        // the line numbers go on from those of the chunks before
+        assert!(chunk.more(&mut rd).unwrap());
+        let e = reverse(&mut rd, io::sink(), Layout::Compact, None, Some(&mut chunk));
+        assert_eq!(e.unwrap_err().to_string(), "invalid record on line 9");
+        assert!(!chunk.more(&mut &b"\n \n"[..]).unwrap());
+    }
+}
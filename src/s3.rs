@@ -0,0 +1,293 @@
+use crate::http;
+use crate::retry::Retry;
+use anyhow::{Context, bail};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// How long presigned URLs stay valid, in seconds. This bounds how late a
+/// dropped connection can be resumed.
+const EXPIRES: u64 = 24 * 3600;
+
+/// Size of the parts of a multipart upload, held in memory one at a time. An
+/// upload has at most 10,000 parts, which makes for 80 GB.
+const PART: usize = 8 * 1024 * 1024;
+
+/// Is the input argument an `s3://bucket/key` URI?
+pub fn is_uri(s: &str) -> bool {
+    s.starts_with("s3://")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hmac(key: &[u8], msg: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("any key size works");
+    mac.update(msg.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encode everything but unreserved characters (and `/` if
+/// `keep_slash`), as SigV4 wants it.
+fn uri_encode(s: &str, keep_slash: bool) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            b'/' if keep_slash => out.push('/'),
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+/// The canonical query string of SigV4: sorted and encoded.
+fn query_string(query: &mut [(&str, String)]) -> String {
+    query.sort();
+    query
+        .iter()
+        .map(|(k, v)| format!("{k}={}", uri_encode(v, false)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// The current UTC time as `YYYYMMDDTHHMMSSZ`.
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+/// Turn `s3://bucket/key` into an HTTPS URL that can be fetched with a
+/// plain GET.
+///
+/// Credentials come from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and
+/// `AWS_SESSION_TOKEN`; without them the URL is unsigned, which works for
+/// public buckets. `AWS_REGION` (or `AWS_DEFAULT_REGION`) selects the region,
+/// and `AWS_ENDPOINT_URL` an S3-compatible service, addressed path-style.
+pub fn presign(uri: &str) -> anyhow::Result<String> {
+    presign_request("GET", uri, &[])
+}
+
+/// Like [`presign`], for a `method` request with the query parameters
+/// `params`.
+fn presign_request(method: &str, uri: &str, params: &[(&str, &str)]) -> anyhow::Result<String> {
+    let env = |name| std::env::var(name).ok().filter(|v| !v.is_empty());
+    let (bucket, key) = uri
+        .strip_prefix("s3://")
+        .and_then(|s| s.split_once('/'))
+        .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+        .with_context(|| format!("expected s3://bucket/key, got {uri:?}"))?;
+    let region = env("AWS_REGION")
+        .or_else(|| env("AWS_DEFAULT_REGION"))
+        .unwrap_or_else(|| "us-east-1".to_string());
+
+    let (base, host, path) = match env("AWS_ENDPOINT_URL") {
+        Some(endpoint) => {
+            let endpoint = endpoint.trim_end_matches('/').to_string();
+            let host = endpoint
+                .split_once("://")
+                .map_or(endpoint.as_str(), |(_, rest)| rest)
+                .to_string();
+            let path = format!("/{}/{}", uri_encode(bucket, false), uri_encode(key, true));
+            (endpoint, host, path)
+        }
+        None => {
+            let host = format!("{bucket}.s3.{region}.amazonaws.com");
+            (
+                format!("https://{host}"),
+                host,
+                format!("/{}", uri_encode(key, true)),
+            )
+        }
+    };
+    let mut query: Vec<_> = (params.iter()).map(|&(k, v)| (k, v.to_string())).collect();
+    let (Some(key_id), Some(secret)) = (env("AWS_ACCESS_KEY_ID"), env("AWS_SECRET_ACCESS_KEY"))
+    else {
+        return Ok(match query_string(&mut query) {
+            query if query.is_empty() => format!("{base}{path}"),
+            query => format!("{base}{path}?{query}"),
+        });
+    };
+
+    let datetime = timestamp();
+    let date = &datetime[..8];
+    let scope = format!("{date}/{region}/s3/aws4_request");
+    query.extend([
+        ("X-Amz-Algorithm", "AWS4-HMAC-SHA256".to_string()),
+        ("X-Amz-Credential", format!("{key_id}/{scope}")),
+        ("X-Amz-Date", datetime.clone()),
+        ("X-Amz-Expires", EXPIRES.to_string()),
+        ("X-Amz-SignedHeaders", "host".to_string()),
+    ]);
+    if let Some(token) = env("AWS_SESSION_TOKEN") {
+        query.push(("X-Amz-Security-Token", token));
+    }
+    let query = query_string(&mut query);
+
+    let request = format!("{method}\n{path}\n{query}\nhost:{host}\n\nhost\nUNSIGNED-PAYLOAD");
+    let to_sign = format!(
+        "AWS4-HMAC-SHA256\n{datetime}\n{scope}\n{}",
+        hex(&Sha256::digest(request.as_bytes()))
+    );
+    let mut signing_key = hmac(format!("AWS4{secret}").as_bytes(), date);
+    for part in [region.as_str(), "s3", "aws4_request"] {
+        signing_key = hmac(&signing_key, part);
+    }
+    let signature = hex(&hmac(&signing_key, &to_sign));
+    Ok(format!("{base}{path}?{query}&X-Amz-Signature={signature}"))
+}
+
+/// The text of the first `<tag>` element of an XML response.
+fn xml_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{tag}>"))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{tag}>"))?;
+    Some(&xml[start..end])
+}
+
+/// Writes an object with a multipart upload, sent part by part as data
+/// comes, with one part in memory at a time.
+///
+/// Nothing is visible in the bucket until [`Upload::finish`] completes the
+/// upload; dropping an unfinished one aborts it, so that its parts aren't
+/// kept (and billed) by S3.
+pub struct Upload {
+    uri: String,
+    agent: ureq::Agent,
+    upload_id: String,
+    buf: Vec<u8>,
+    /// ETags of the parts uploaded so far
+    etags: Vec<String>,
+    retry: Retry,
+    done: bool,
+}
+
+impl Upload {
+    /// Start a multipart upload to `s3://bucket/key`, with each request
+    /// retried as `retry` says.
+    pub fn create(uri: &str, retry: Retry) -> anyhow::Result<Self> {
+        let agent = ureq::Agent::new_with_defaults();
+        let what = format!("starting the upload of {uri}");
+        let xml = retry.run(&what, http::is_transient, || {
+            let url = presign_request("POST", uri, &[("uploads", "")])?;
+            Ok(agent.post(&url).send_empty()?.body_mut().read_to_string()?)
+        })?;
+        let upload_id = xml_text(&xml, "UploadId")
+            .context("no upload ID in the response to CreateMultipartUpload")?
+            .to_string();
+        Ok(Self {
+            uri: uri.to_string(),
+            agent,
+            upload_id,
+            buf: Vec::with_capacity(PART),
+            etags: vec![],
+            retry,
+            done: false,
+        })
+    }
+
+    fn put_part(&mut self) -> anyhow::Result<()> {
+        let number = (self.etags.len() + 1).to_string();
+        let what = format!("uploading part {number} of {}", self.uri);
+        let etag = self.retry.run(&what, http::is_transient, || {
+            let params = [
+                ("partNumber", number.as_str()),
+                ("uploadId", &self.upload_id),
+            ];
+            let url = presign_request("PUT", &self.uri, &params)?;
+            let resp = self.agent.put(&url).send(&self.buf[..])?;
+            let etag = resp.headers().get("etag").and_then(|v| v.to_str().ok());
+            Ok(etag
+                .context("no ETag in the response to UploadPart")?
+                .to_string())
+        })?;
+        self.etags.push(etag);
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Upload the remaining data and complete the object.
+    pub fn finish(&mut self) -> anyhow::Result<()> {
+        // an empty object still takes a part
+        if !self.buf.is_empty() || self.etags.is_empty() {
+            self.put_part()?;
+        }
+        let mut xml = String::from("<CompleteMultipartUpload>");
+        for (i, etag) in self.etags.iter().enumerate() {
+            xml.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{etag}</ETag></Part>",
+                i + 1
+            ));
+        }
+        xml.push_str("</CompleteMultipartUpload>");
+        let what = format!("completing the upload of {}", self.uri);
+        let resp = self.retry.run(&what, http::is_transient, || {
+            let url = presign_request("POST", &self.uri, &[("uploadId", &self.upload_id)])?;
+            Ok(self
+                .agent
+                .post(&url)
+                .send(&xml)?
+                .body_mut()
+                .read_to_string()?)
+        })?;
+        // a failure can come after a 200, in the body
+        if let Some(code) = xml_text(&resp, "Code") {
+            bail!("completing the upload of {} failed: {code}", self.uri);
+        }
+        self.done = true;
+        Ok(())
+    }
+
+    fn abort(&self) -> anyhow::Result<()> {
+        let url = presign_request("DELETE", &self.uri, &[("uploadId", &self.upload_id)])?;
+        self.agent.delete(&url).call()?;
+        Ok(())
+    }
+}
+
+impl Write for Upload {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = data.len().min(PART - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        if self.buf.len() == PART {
+            self.put_part().map_err(io::Error::other)?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Upload {
+    fn drop(&mut self) {
+        if !self.done
+            && let Err(e) = self.abort()
+        {
+            warn!("aborting the upload of {} failed: {e:#}", self.uri);
+        }
+    }
+}
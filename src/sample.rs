@@ -0,0 +1,354 @@
+use crate::spill::{self, OVERHEAD, SortedRuns};
+use crate::template;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io;
+
+/// The state of the random numbers of the group of records with `key`, so
+/// that its sample doesn't depend on the records of the other groups.
+fn seed(seed: u64, key: Option<&str>) -> u64 {
+    let mut state = seed ^ 0x2545_F491_4F6C_DD1D;
+    if let Some(key) = key {
+        // FNV-1a
+        state ^= 0xCBF2_9CE4_8422_2325;
+        for &b in key.as_bytes() {
+            state ^= u64::from(b);
+            state = state.wrapping_mul(0x100_0000_01B3);
+        }
+    }
+    // xorshift64 gets stuck at 0
+    state.max(1)
+}
+
+/// The records kept for a value of the field.
+#[derive(Debug, Clone, Default)]
+struct Group {
+    /// records of the group seen so far
+    seen: u64,
+    state: u64,
+    /// the number of the first record of the group among all the records
+    first: u64,
+    /// (number among all the records, record)
+    kept: Vec<(u64, Vec<u8>)>,
+}
+
+impl Group {
+    fn new(state: u64, first: u64) -> Self {
+        Self {
+            state,
+            first,
+            ..Self::default()
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        // xorshift64
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Consider record number `seq` for the sample of `n`, returning how
+    /// many bytes the records kept grow by.
+    fn add(&mut self, n: usize, seq: u64, record: &[u8]) -> isize {
+        let seen = self.seen;
+        self.seen += 1;
+        if (seen as usize) < n {
+            self.kept.push((seq, record.to_vec()));
+            return (record.len() + OVERHEAD) as isize;
+        }
+        // replace a kept record with probability n / (seen + 1)
+        let j = self.next() % (seen + 1);
+        match self.kept.get_mut(j as usize) {
+            Some(slot) => {
+                let old = std::mem::replace(slot, (seq, record.to_vec()));
+                record.len() as isize - old.1.len() as isize
+            }
+            None => 0,
+        }
+    }
+}
+
+/// The key that the entries of the group with `key` start with once
+/// spilled, to sort by group.
+fn group_key(key: Option<&str>) -> Vec<u8> {
+    let key = key.map_or(vec![0], |key| [&[1], key.as_bytes()].concat());
+    [&(key.len() as u32).to_be_bytes()[..], &key].concat()
+}
+
+/// The kinds of entries of a spilled group, sorting in this order.
+const HEADER: u8 = 0;
+const RECORD: u8 = 1;
+
+/// A random sample of up to `n` records for each value of a field, taken in
+/// a single pass with reservoir sampling, so that every record of a group
+/// has the same chance to be in it.
+///
+/// The sample is the same from one run to the next with the same seed. The
+/// groups are kept in memory up to a budget; past it, they and the records
+/// that follow are sorted by group on disk, and sampled at the end.
+#[derive(Debug)]
+pub struct GroupSample {
+    /// the field, or none to sample all the records together
+    path: Option<Vec<String>>,
+    n: usize,
+    seed: u64,
+    /// position of each group in `groups`, by value (`None` for records
+    /// without the field)
+    index: HashMap<Option<String>, usize>,
+    groups: Vec<Group>,
+    added: u64,
+    /// bytes of the groups, roughly
+    bytes: usize,
+    max_bytes: usize,
+    /// the groups, then the records added, once past `max_bytes`
+    spilled: Option<SortedRuns>,
+}
+
+impl GroupSample {
+    /// Sample `n` records by value of `field`, a dotted path into the
+    /// records like those of a template, or `n` records in all without.
+    pub fn new(field: Option<&str>, n: usize, seed: u64) -> Self {
+        Self {
+            path: field.map(|field| field.split('.').map(String::from).collect()),
+            n,
+            seed,
+            index: HashMap::new(),
+            groups: vec![],
+            added: 0,
+            bytes: 0,
+            max_bytes: usize::MAX,
+            spilled: None,
+        }
+    }
+
+    /// Keep about `max_bytes` of groups in memory, spilling to disk past
+    /// them.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Consider `record`, a JSON text. Strings are grouped by their value
+    /// and other values by their JSON text; records that aren't JSON or
+    /// don't have the field make a group of their own.
+    pub fn add(&mut self, record: &[u8]) -> io::Result<()> {
+        let key = self.path.as_ref().and_then(|path| {
+            let value = serde_json::from_slice::<Value>(record).ok()?;
+            match template::lookup(&value, path)? {
+                Value::String(s) => Some(s.clone()),
+                value => Some(value.to_string()),
+            }
+        });
+        let seq = self.added;
+        self.added += 1;
+        if let Some(spilled) = &mut self.spilled {
+            let mut entry = group_key(key.as_deref());
+            entry.push(RECORD);
+            entry.extend(seq.to_be_bytes());
+            return spilled.push(entry, record.to_vec());
+        }
+        let i = match self.index.get(&key) {
+            Some(&i) => i,
+            None => {
+                let state = seed(self.seed, key.as_deref());
+                self.groups.push(Group::new(state, seq));
+                self.bytes += key.as_ref().map_or(0, String::len) + OVERHEAD;
+                self.index.insert(key, self.groups.len() - 1);
+                self.groups.len() - 1
+            }
+        };
+        let grown = self.groups[i].add(self.n, seq, record);
+        self.bytes = self.bytes.saturating_add_signed(grown);
+        if self.bytes > self.max_bytes {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Move the groups to disk: a header with the state of each, followed
+    /// by its records kept so far and then by those added later.
+    fn spill(&mut self) -> io::Result<()> {
+        let mut spilled = SortedRuns::new(self.max_bytes);
+        let mut groups = std::mem::take(&mut self.groups);
+        for (key, i) in std::mem::take(&mut self.index) {
+            let group = std::mem::take(&mut groups[i]);
+            let key = group_key(key.as_deref());
+            let header = [
+                group.seen,
+                group.state,
+                group.first,
+                group.kept.len() as u64,
+            ];
+            spilled.push(
+                [&key[..], &[HEADER], &[0; 8]].concat(),
+                header.iter().flat_map(|n| n.to_be_bytes()).collect(),
+            )?;
+            for (seq, record) in group.kept {
+                spilled.push([&key[..], &[RECORD], &seq.to_be_bytes()].concat(), record)?;
+            }
+        }
+        self.bytes = 0;
+        self.spilled = Some(spilled);
+        Ok(())
+    }
+
+    /// The records sampled, group by group in the order they first appear,
+    /// each group in input order.
+    pub fn into_records(self) -> io::Result<Records> {
+        // by (first record of the group, record)
+        let mut sampled = SortedRuns::new(self.max_bytes);
+        let mut keep = |group: Group| -> io::Result<()> {
+            for (seq, record) in group.kept {
+                let key = [group.first.to_be_bytes(), seq.to_be_bytes()].concat();
+                sampled.push(key, record)?;
+            }
+            Ok(())
+        };
+        let Some(spilled) = self.spilled else {
+            let groups = self.groups.len();
+            for group in self.groups {
+                keep(group)?;
+            }
+            return Ok(Records {
+                groups,
+                merge: sampled.into_sorted()?,
+            });
+        };
+        let mut groups = 0;
+        // the group being sampled, with the number of its records still
+        // to read back into its sample as they were
+        let mut current: Option<(Vec<u8>, Group, u64)> = None;
+        for entry in spilled.into_sorted()? {
+            let (mut key, record) = entry?;
+            let seq = u64::from_be_bytes(key[key.len() - 8..].try_into().unwrap());
+            let kind = key[key.len() - 9];
+            key.truncate(key.len() - 9);
+            let (_, group, kept) = match &mut current {
+                Some(current) if current.0 == key => current,
+                _ => {
+                    groups += 1;
+                    let (group, kept) = if kind == HEADER {
+                        let n = |i: usize| u64::from_be_bytes(record[i..i + 8].try_into().unwrap());
+                        let group = Group {
+                            seen: n(0),
+                            state: n(8),
+                            first: n(16),
+                            kept: vec![],
+                        };
+                        (group, n(24))
+                    } else {
+                        let value = (key[4] == 1).then(|| String::from_utf8_lossy(&key[5..]));
+                        (Group::new(seed(self.seed, value.as_deref()), seq), 0)
+                    };
+                    if let Some((_, group, _)) = current.replace((key, group, kept)) {
+                        keep(group)?;
+                    }
+                    if kind == HEADER {
+                        continue;
+                    }
+                    current.as_mut().unwrap()
+                }
+            };
+            if *kept > 0 {
+                group.kept.push((seq, record));
+                *kept -= 1;
+            } else {
+                group.add(self.n, seq, &record);
+            }
+        }
+        if let Some((_, group, _)) = current {
+            keep(group)?;
+        }
+        Ok(Records {
+            groups,
+            merge: sampled.into_sorted()?,
+        })
+    }
+}
+
+/// The records of a [`GroupSample`], read back in order.
+pub struct Records {
+    /// the number of groups
+    pub groups: usize,
+    merge: spill::Merge,
+}
+
+impl Iterator for Records {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.merge.next()?.map(|(_, record)| record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(sample: GroupSample) -> Vec<String> {
+        let records = sample.into_records().unwrap();
+        records
+            .map(|record| String::from_utf8(record.unwrap()).unwrap())
+            .collect()
+    }
+
+    fn input() -> Vec<String> {
+        (0..3000)
+            .map(|i| format!(r#"{{"group":{},"i":{i}}}"#, (i * 7) % 130))
+            .chain(["not json".to_string()])
+            .collect()
+    }
+
+    #[test]
+    fn groups() {
+        let input = [
+            r#"{"group":"a"}"#,
+            "{}",
+            r#"{"group":"a","n":2}"#,
+            r#"{"group":1}"#,
+            r#"{"group":"a","n":3}"#,
+            r#"{"group":"1"}"#,
+        ];
+        let mut sample = GroupSample::new(Some("group"), 2, 0);
+        for record in input {
+            sample.add(record.as_bytes()).unwrap();
+        }
+        let records = sample.into_records().unwrap();
+        // strings are grouped by their value, so "1" and 1 are the same
+        assert_eq!(records.groups, 3);
+        let records: Vec<_> = records
+            .map(|record| String::from_utf8(record.unwrap()).unwrap())
+            .collect();
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[2..], ["{}", r#"{"group":1}"#, r#"{"group":"1"}"#]);
+    }
+
+    #[test]
+    fn seeds() {
+        let sample = |seed| {
+            let mut sample = GroupSample::new(None, 10, seed);
+            for record in input() {
+                sample.add(record.as_bytes()).unwrap();
+            }
+            records(sample)
+        };
+        assert_eq!(sample(1), sample(1));
+        assert_ne!(sample(1), sample(2));
+    }
+
+    #[test]
+    fn spilled() {
+        for (field, n) in [(None, 100), (Some("group"), 3)] {
+            let mut sample = GroupSample::new(field, n, 7);
+            let mut small = GroupSample::new(field, n, 7).max_bytes(4000);
+            for record in input() {
+                sample.add(record.as_bytes()).unwrap();
+                small.add(record.as_bytes()).unwrap();
+            }
+            assert!(small.spilled.as_ref().unwrap().spilled());
+            assert_eq!(records(small), records(sample));
+        }
+    }
+}
@@ -0,0 +1,1179 @@
+use crate::compact;
+use anyhow::Context;
+use serde::Deserialize;
+use serde::de::IgnoredAny;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+/// Lexical state inside an element, kept across buffer refills.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum Lex {
+    Normal,
+    Str(u8),
+    StrEscape(u8),
+    Slash,
+    LineComment,
+    BlockComment,
+    BlockCommentStar,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum State {
+    Start,
+    First,
+    Next,
+    Done,
+    /// at the value making up the input, with [`Scanner::single`]
+    Single,
+    /// past the end, nothing more to read
+    End,
+}
+
+/// The input is not a well-formed JSON array (or whatever was expected of it),
+/// and scanning can't go on.
+#[derive(Debug)]
+pub struct Malformed(pub String);
+
+impl fmt::Display for Malformed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Malformed {}
+
+/// Return a [`Malformed`] error with a message, like `bail!`.
+macro_rules! malformed {
+    ($($arg:tt)*) => {
+        return Err($crate::scan::Malformed(format!($($arg)*)).into())
+    };
+}
+pub(crate) use malformed;
+
+/// An element was larger than the maximum size given to [`Scanner::max_len`].
+///
+/// The element has been consumed, so scanning can go on with the next one.
+#[derive(Debug)]
+pub struct TooLarge {
+    /// Offset of the element in the input
+    pub offset: u64,
+    /// Size of the element in bytes
+    pub len: u64,
+}
+
+impl fmt::Display for TooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "element at offset {} is too large ({} bytes)",
+            self.offset, self.len
+        )
+    }
+}
+
+impl std::error::Error for TooLarge {}
+
+/// Something other than whitespace followed the closing `]` of the array,
+/// with [`Scanner::check_end`].
+#[derive(Debug)]
+pub struct TrailingData {
+    /// Offset of the first byte after the array
+    pub offset: u64,
+}
+
+impl fmt::Display for TrailingData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unexpected data after the end of the array at offset {}",
+            self.offset
+        )
+    }
+}
+
+impl std::error::Error for TrailingData {}
+
+/// The UTF-8 encoding of the byte order mark.
+pub const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Consume a UTF-8 byte order mark at the beginning of `rd`, if any.
+pub fn skip_bom<R: BufRead>(rd: &mut R) -> std::io::Result<()> {
+    if rd.fill_buf()?.starts_with(BOM) {
+        rd.consume(BOM.len());
+    }
+    Ok(())
+}
+
+/// What kind of JSON value starts with `c`, e.g. "an object", for messages.
+pub fn kind(c: u8) -> &'static str {
+    match c {
+        b'{' => "an object",
+        b'[' => "an array",
+        b'"' | b'\'' => "a string",
+        b't' | b'f' => "a boolean",
+        b'n' => "null",
+        b'-' | b'+' | b'.' | b'0'..=b'9' | b'I' | b'N' => "a number",
+        _ => "not a JSON value",
+    }
+}
+
+/// Whether `c` is whitespace, only that of RFC 8259 if `strict`.
+fn is_space(c: u8, strict: bool) -> bool {
+    if strict {
+        matches!(c, b' ' | b'\t' | b'\n' | b'\r')
+    } else {
+        c.is_ascii_whitespace()
+    }
+}
+
+/// Bytes that may end an element or change the lexical state outside of
+/// strings, quotes and comment starts included whether or not they apply.
+const STRUCTURAL: [bool; 256] = {
+    let mut table = [false; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = matches!(
+            i as u8,
+            b'"' | b'\'' | b'/' | b'[' | b']' | b'{' | b'}' | b','
+        );
+        i += 1;
+    }
+    table
+};
+
+/// The position of the `,` ending `buf`, if any and if it follows a value,
+/// ignoring whitespace: the commas of `[,]` or `[1,,]` are left for the
+/// parser to reject.
+fn trailing_comma(buf: &[u8]) -> Option<usize> {
+    let last = |buf: &[u8]| buf.iter().rposition(|c| !c.is_ascii_whitespace());
+    let i = last(buf).filter(|&i| buf[i] == b',')?;
+    last(&buf[..i])
+        .is_some_and(|j| !matches!(buf[j], b'[' | b'{' | b',' | b':'))
+        .then_some(i)
+}
+
+/// Remove the bytes of `buf` at `positions`, in increasing order.
+fn remove_positions(buf: &mut Vec<u8>, positions: &[usize]) {
+    let (mut i, mut positions) = (0, positions.iter().peekable());
+    buf.retain(|_| {
+        let keep = positions.next_if_eq(&&i).is_none();
+        i += 1;
+        keep
+    });
+}
+
+/// What a [`Scanner`] comes across in the input.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Event {
+    /// An opening `[`; there may be more than one array with
+    /// [`Scanner::concatenated`]
+    ArrayStart,
+    /// An element of the array
+    Element,
+    /// A closing `]`
+    ArrayEnd,
+}
+
+/// Receives the events of a [`Scanner`] from [`Scanner::drive`], for
+/// consumers of the elements (counting them, or loading them into a
+/// database, say) that have no use for records.
+pub trait Handler {
+    /// An array starts, at `offset` in the input.
+    fn on_array_start(&mut self, offset: u64) -> anyhow::Result<()> {
+        let _ = offset;
+        Ok(())
+    }
+
+    /// The bytes of an element at `offset`, as they are in the input: not
+    /// validated (see [`compact::validate`]), and only there for the call.
+    fn on_value(&mut self, raw: &[u8], offset: u64) -> anyhow::Result<()>;
+
+    /// An array ends, with the `]` at `offset`.
+    fn on_array_end(&mut self, offset: u64) -> anyhow::Result<()> {
+        let _ = offset;
+        Ok(())
+    }
+}
+
+/// Splits a top-level JSON array into the raw bytes of its elements.
+///
+/// The scanner only tracks strings and nesting, it leaves validating the
+/// elements themselves to the parser.
+pub struct Scanner<R> {
+    rd: R,
+    json5: bool,
+    comments: bool,
+    trailing_commas: bool,
+    trailing_separator: bool,
+    concatenated: bool,
+    check_end: bool,
+    allow_empty: bool,
+    single: bool,
+    strict: bool,
+    max_depth: usize,
+    max_len: usize,
+    state: State,
+    offset: u64,
+    /// offset of the last element read
+    element_offset: u64,
+    /// element bytes for [`Scanner::read_record_into`]
+    scratch: Vec<u8>,
+}
+
+impl<R: BufRead> Scanner<R> {
+    pub fn new(rd: R) -> Self {
+        Self {
+            rd,
+            json5: false,
+            comments: false,
+            trailing_commas: false,
+            trailing_separator: false,
+            concatenated: false,
+            check_end: false,
+            allow_empty: false,
+            single: false,
+            strict: false,
+            max_depth: usize::MAX,
+            max_len: usize::MAX,
+            state: State::Start,
+            offset: 0,
+            element_offset: 0,
+            scratch: vec![],
+        }
+    }
+
+    /// Accept JSON5 syntax: single-quoted strings, comments and a trailing comma.
+    pub fn json5(mut self, yes: bool) -> Self {
+        self.json5 = yes;
+        self
+    }
+
+    /// Strip `//` and `/* */` comments, both between and inside elements.
+    pub fn comments(mut self, yes: bool) -> Self {
+        self.comments = yes;
+        self
+    }
+
+    /// Accept (and drop) a comma before a closing `]` or `}`.
+    pub fn trailing_commas(mut self, yes: bool) -> Self {
+        self.trailing_commas = yes;
+        self
+    }
+
+    /// Accept a comma before the closing `]` of the array itself, as left by
+    /// writers that emit the separator along with each element.
+    pub fn trailing_separator(mut self, yes: bool) -> Self {
+        self.trailing_separator = yes;
+        self
+    }
+
+    /// Go on with the next array when another one follows the closing `]`,
+    /// as in concatenated files.
+    pub fn concatenated(mut self, yes: bool) -> Self {
+        self.concatenated = yes;
+        self
+    }
+
+    /// Read on after the closing `]`, up to the end of the input, and fail
+    /// with a [`TrailingData`] error if anything but whitespace (or another
+    /// array, with [`Scanner::concatenated`]) follows, instead of ignoring it.
+    /// Scanning ends either way.
+    pub fn check_end(mut self, yes: bool) -> Self {
+        self.check_end = yes;
+        self
+    }
+
+    /// Take an input that is empty, or only whitespace, for an empty array
+    /// instead of failing.
+    pub fn allow_empty(mut self, yes: bool) -> Self {
+        self.allow_empty = yes;
+        self
+    }
+
+    /// Take a top-level value that isn't an array for an array of that
+    /// single element, instead of failing.
+    pub fn single(mut self, yes: bool) -> Self {
+        self.single = yes;
+        self
+    }
+
+    /// Only take the whitespace of RFC 8259 (space, tab, line feed and
+    /// carriage return) around the elements, rejecting form feeds.
+    pub fn strict(mut self, yes: bool) -> Self {
+        self.strict = yes;
+        self
+    }
+
+    /// Reject elements with more than `n` levels of nested arrays and objects.
+    pub fn max_depth(mut self, n: usize) -> Self {
+        self.max_depth = n;
+        self
+    }
+
+    /// Skip elements larger than `n` bytes, returning a [`TooLarge`] error
+    /// with their first `n` bytes in the buffer. At most `n` bytes (plus a
+    /// buffer's worth) are kept in memory.
+    pub fn max_len(mut self, n: usize) -> Self {
+        self.max_len = n;
+        self
+    }
+
+    /// Start right after an element at `offset` in the array, as when
+    /// resuming from a checkpoint: the input begins with the `,` or `]`
+    /// that follows it.
+    pub fn resume_at(mut self, offset: u64) -> Self {
+        self.state = State::Next;
+        self.offset = offset;
+        self
+    }
+
+    fn allow_comments(&self) -> bool {
+        self.comments || self.json5
+    }
+
+    fn allow_trailing_commas(&self) -> bool {
+        self.trailing_commas || self.json5
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.rd
+    }
+
+    /// The input, e.g. to read what follows the array once scanning is done.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.rd
+    }
+
+    /// Number of bytes consumed so far.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Offset of the last element read by [`Scanner::next_element`], in the
+    /// same terms as [`Scanner::offset`].
+    pub fn element_offset(&self) -> u64 {
+        self.element_offset
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.rd.consume(n);
+        self.offset += n as u64;
+    }
+
+    /// Peek at the next significant byte, skipping whitespace (and comments).
+    fn peek(&mut self) -> anyhow::Result<Option<u8>> {
+        loop {
+            let buf = self.rd.fill_buf()?;
+            let Some(&c) = buf.first() else {
+                return Ok(None);
+            };
+            if c.is_ascii_whitespace() {
+                let strict = self.strict;
+                let n = buf.iter().take_while(|&&c| is_space(c, strict)).count();
+                if n == 0 {
+                    malformed!(
+                        "malformed json at offset {}: {:?} is not JSON whitespace",
+                        self.offset,
+                        c as char
+                    );
+                }
+                self.consume(n);
+            } else if c == b'/' && self.allow_comments() {
+                self.skip_comment()?;
+            } else {
+                return Ok(Some(c));
+            }
+        }
+    }
+
+    fn next_byte(&mut self) -> anyhow::Result<Option<u8>> {
+        let c = self.rd.fill_buf()?.first().copied();
+        if c.is_some() {
+            self.consume(1);
+        }
+        Ok(c)
+    }
+
+    fn skip_comment(&mut self) -> anyhow::Result<()> {
+        let offset = self.offset;
+        self.consume(1);
+        match self.next_byte()? {
+            Some(b'/') => while !matches!(self.next_byte()?, Some(b'\n') | None) {},
+            Some(b'*') => {
+                let mut star = false;
+                loop {
+                    match self.next_byte()? {
+                        Some(b'/') if star => break,
+                        Some(c) => star = c == b'*',
+                        None => malformed!("unterminated comment at offset {offset}"),
+                    }
+                }
+            }
+            _ => malformed!("malformed json at offset {offset}: unexpected '/'"),
+        }
+        Ok(())
+    }
+
+    /// Copy the bytes of the element starting here into `buf`, up to the
+    /// `,` or `]` that follows it. Comments are replaced by a single space.
+    fn read_element(&mut self, buf: &mut Vec<u8>) -> anyhow::Result<()> {
+        let start = self.offset;
+        self.element_offset = start;
+        let (comments, trailing_commas) = (self.allow_comments(), self.allow_trailing_commas());
+        let mut depth = 0usize;
+        let mut lex = Lex::Normal;
+        let mut too_large = false;
+        // the trailing commas in `buf`, removed once the element is read
+        let mut commas = vec![];
+        // the input ends with a single value
+        let single = self.state == State::Single;
+        if single {
+            self.state = State::Done;
+        }
+        buf.clear();
+        loop {
+            let chunk = self.rd.fill_buf()?;
+            if chunk.is_empty()
+                && single
+                && depth == 0
+                && matches!(lex, Lex::Normal | Lex::LineComment)
+            {
+                break;
+            }
+            if chunk.is_empty() {
+                malformed!("unexpected end of input in element starting at offset {start}");
+            }
+            let mut i = 0;
+            // start of the bytes of `chunk` that still need copying
+            let mut from = 0;
+            let mut done = false;
+            while i < chunk.len() {
+                // jump over the bytes that can't change the state
+                match lex {
+                    Lex::Normal => match chunk[i..].iter().position(|&c| STRUCTURAL[c as usize]) {
+                        Some(n) => i += n,
+                        None => {
+                            i = chunk.len();
+                            break;
+                        }
+                    },
+                    Lex::Str(q) => match memchr::memchr2(q, b'\\', &chunk[i..]) {
+                        Some(n) => i += n,
+                        None => {
+                            i = chunk.len();
+                            break;
+                        }
+                    },
+                    _ => (),
+                }
+                let c = chunk[i];
+                lex = match lex {
+                    Lex::Normal => match c {
+                        b'"' => Lex::Str(c),
+                        b'\'' if self.json5 => Lex::Str(c),
+                        b'/' if comments => {
+                            if !too_large {
+                                buf.extend_from_slice(&chunk[from..i]);
+                            }
+                            from = i + 1;
+                            Lex::Slash
+                        }
+                        b'[' | b'{' => {
+                            depth += 1;
+                            if depth > self.max_depth {
+                                malformed!(
+                                    "element starting at offset {start} is nested deeper than {}",
+                                    self.max_depth
+                                );
+                            }
+                            Lex::Normal
+                        }
+                        b',' | b']' if depth == 0 => {
+                            done = true;
+                            break;
+                        }
+                        b']' | b'}' if depth > 0 => {
+                            depth -= 1;
+                            if trailing_commas && !too_large {
+                                buf.extend_from_slice(&chunk[from..i]);
+                                from = i;
+                                commas.extend(trailing_comma(buf));
+                            }
+                            Lex::Normal
+                        }
+                        b'}' => malformed!(
+                            "malformed json at offset {}: unexpected '}}'",
+                            self.offset + i as u64
+                        ),
+                        _ => Lex::Normal,
+                    },
+                    Lex::Str(q) if c == b'\\' => Lex::StrEscape(q),
+                    Lex::Str(q) if c == q => Lex::Normal,
+                    Lex::Str(q) | Lex::StrEscape(q) => Lex::Str(q),
+                    Lex::Slash => {
+                        from = i + 1;
+                        match c {
+                            b'/' => Lex::LineComment,
+                            b'*' => Lex::BlockComment,
+                            // not a comment after all, process `c` normally
+                            _ => {
+                                if !too_large {
+                                    buf.push(b'/');
+                                }
+                                from = i;
+                                lex = Lex::Normal;
+                                continue;
+                            }
+                        }
+                    }
+                    Lex::LineComment if c == b'\n' => {
+                        from = i;
+                        Lex::Normal
+                    }
+                    Lex::BlockCommentStar if c == b'/' => {
+                        if !too_large {
+                            buf.push(b' ');
+                        }
+                        from = i + 1;
+                        Lex::Normal
+                    }
+                    Lex::LineComment | Lex::BlockComment | Lex::BlockCommentStar => {
+                        from = i + 1;
+                        match lex {
+                            Lex::LineComment => Lex::LineComment,
+                            _ if c == b'*' => Lex::BlockCommentStar,
+                            _ => Lex::BlockComment,
+                        }
+                    }
+                };
+                i += 1;
+            }
+            if !too_large {
+                buf.extend_from_slice(&chunk[from.min(i)..i]);
+            }
+            self.consume(i);
+            if !too_large && buf.len() > self.max_len {
+                // keep scanning, but stop accumulating past the start
+                too_large = true;
+                buf.truncate(self.max_len);
+            }
+            if done {
+                break;
+            }
+        }
+        if too_large {
+            let len = self.offset - start;
+            return Err(TooLarge { offset: start, len }.into());
+        }
+        remove_positions(buf, &commas);
+        while buf.last().is_some_and(|&c| is_space(c, self.strict)) {
+            buf.pop();
+        }
+        if buf.is_empty() {
+            malformed!("malformed json at offset {start}: empty element");
+        }
+        Ok(())
+    }
+
+    /// Read the next element of the array into `buf` (replacing its content).
+    /// Returns `false` once the closing `]` has been reached.
+    pub fn next_element(&mut self, buf: &mut Vec<u8>) -> anyhow::Result<bool> {
+        if !self.next_start()? {
+            return Ok(false);
+        }
+        self.read_element(buf)?;
+        Ok(true)
+    }
+
+    /// Move to the start of the next element of the array, to be read with
+    /// [`Scanner::copy_element`]. Returns `false` once the closing `]` has
+    /// been reached.
+    pub fn next_start(&mut self) -> anyhow::Result<bool> {
+        loop {
+            match self.step()? {
+                Some(Event::Element) => return Ok(true),
+                Some(_) => (),
+                None => return Ok(false),
+            }
+        }
+    }
+
+    /// Read on to the next event: with [`Event::Element`], the element is
+    /// read into `buf` (replacing its content). Returns `None` at the end.
+    pub fn next_event(&mut self, buf: &mut Vec<u8>) -> anyhow::Result<Option<Event>> {
+        let event = self.step()?;
+        if event == Some(Event::Element) {
+            self.read_element(buf)?;
+        }
+        Ok(event)
+    }
+
+    /// Scan the rest of the input, passing what comes by to `handler`, and
+    /// return the number of elements.
+    pub fn drive(&mut self, handler: &mut impl Handler) -> anyhow::Result<u64> {
+        let mut element = std::mem::take(&mut self.scratch);
+        let mut elements = 0;
+        let res = loop {
+            let res = match self.next_event(&mut element) {
+                Ok(Some(Event::ArrayStart)) => handler.on_array_start(self.offset - 1),
+                Ok(Some(Event::Element)) => {
+                    elements += 1;
+                    handler.on_value(&element, self.element_offset)
+                }
+                Ok(Some(Event::ArrayEnd)) => handler.on_array_end(self.offset - 1),
+                Ok(None) => break Ok(elements),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = res {
+                break Err(e);
+            }
+        };
+        self.scratch = element;
+        res
+    }
+
+    /// Move on to the next event, without reading the element if that is
+    /// one.
+    fn step(&mut self) -> anyhow::Result<Option<Event>> {
+        loop {
+            let c = match self.state {
+                State::End => return Ok(None),
+                State::Done if !self.concatenated && !self.check_end => return Ok(None),
+                State::Done => match self.peek()? {
+                    Some(b'[') if self.concatenated => {
+                        self.state = State::Start;
+                        continue;
+                    }
+                    Some(_) if self.check_end => {
+                        self.state = State::End;
+                        return Err(TrailingData {
+                            offset: self.offset,
+                        }
+                        .into());
+                    }
+                    _ => {
+                        self.state = State::End;
+                        return Ok(None);
+                    }
+                },
+                _ => self.peek()?,
+            };
+            let offset = self.offset;
+            match (self.state, c) {
+                (State::Start, None) if self.allow_empty => {
+                    self.state = State::End;
+                    return Ok(None);
+                }
+                (_, None) => malformed!("unexpected end of input at offset {offset}"),
+                (State::Start, Some(b'[')) => {
+                    self.consume(1);
+                    self.state = State::First;
+                    return Ok(Some(Event::ArrayStart));
+                }
+                (State::Start, Some(_)) if self.single => {
+                    self.state = State::Single;
+                    return Ok(Some(Event::Element));
+                }
+                (State::Start, Some(c)) => {
+                    malformed!(
+                        "malformed json at offset {offset}: expected an array, got {}",
+                        match kind(c) {
+                            "not a JSON value" => format!("'{}'", c as char),
+                            kind => kind.to_string(),
+                        }
+                    )
+                }
+                (State::First | State::Next, Some(b']')) => {
+                    self.consume(1);
+                    self.state = State::Done;
+                    return Ok(Some(Event::ArrayEnd));
+                }
+                (State::Next, Some(b',')) => {
+                    self.consume(1);
+                    if self.peek()? == Some(b']')
+                        && (self.allow_trailing_commas() || self.trailing_separator)
+                    {
+                        continue;
+                    }
+                    return Ok(Some(Event::Element));
+                }
+                (State::First, Some(_)) => {
+                    self.state = State::Next;
+                    return Ok(Some(Event::Element));
+                }
+                (State::Next, Some(c)) => {
+                    malformed!(
+                        "malformed json at offset {offset}: expected ',' or ']', got '{}'",
+                        c as char
+                    )
+                }
+                (State::Done | State::Single | State::End, _) => unreachable!(),
+            }
+        }
+    }
+
+    /// Copy the element starting here, after [`Scanner::next_start`], to
+    /// `out` as a JSONL record, without holding it in memory: the element is
+    /// validated as it goes through, so on error part of it may have been
+    /// written already. Only plain JSON is accepted, whatever the options.
+    pub fn copy_element<W: Write + ?Sized>(&mut self, out: &mut W) -> anyhow::Result<()> {
+        let start = self.offset;
+        let single = self.state == State::Single;
+        if single {
+            self.state = State::Done;
+        }
+        let mut element = ElementReader {
+            scanner: self,
+            out,
+            start,
+            single,
+            depth: 0,
+            lex: Lex::Normal,
+            partial: vec![],
+            done: false,
+            err: None,
+        };
+        // the parser reads a byte at a time
+        let mut de = serde_json::Deserializer::from_reader(BufReader::new(&mut element));
+        let res = IgnoredAny::deserialize(&mut de).and_then(|_| de.end());
+        drop(de);
+        if let Some(e) = element.err {
+            return Err(e);
+        }
+        res.with_context(|| format!("invalid element starting at offset {start}"))?;
+        if !element.partial.is_empty() {
+            anyhow::bail!("invalid UTF-8 at offset {}", self.offset);
+        }
+        Ok(())
+    }
+
+    /// Read the next element of the array into `record` (replacing its
+    /// content) as a JSONL record: checked to be valid JSON and without its
+    /// insignificant whitespace (converted to JSON with [`Scanner::json5`]).
+    /// Returns `false` once the closing `]` has been reached.
+    ///
+    /// The element goes through a buffer kept by the scanner, so once the
+    /// buffers have grown to the size of the largest element, reading a
+    /// record doesn't allocate.
+    pub fn read_record_into(&mut self, record: &mut Vec<u8>) -> anyhow::Result<bool> {
+        let mut element = std::mem::take(&mut self.scratch);
+        let res = self.next_element(&mut element);
+        let res = res.and_then(|more| {
+            record.clear();
+            let context = || format!("invalid element ending at offset {}", self.offset);
+            if more && self.json5 {
+                let value: serde_json::Value =
+                    json5::from_str(std::str::from_utf8(&element)?).with_context(context)?;
+                serde_json::to_writer(&mut *record, &value)?;
+            } else if more {
+                compact::validate(&element).with_context(context)?;
+                compact::compact(&element, record);
+            }
+            Ok(more)
+        });
+        self.scratch = element;
+        res
+    }
+}
+
+/// Reads the bytes of the element at the current position of a scanner, up
+/// to the `,` or `]` that follows it, writing them without their
+/// insignificant whitespace to `out` on the way.
+struct ElementReader<'a, R, W: ?Sized> {
+    scanner: &'a mut Scanner<R>,
+    out: &'a mut W,
+    /// offset of the element in the input
+    start: u64,
+    /// the element ends with the input
+    single: bool,
+    depth: usize,
+    lex: Lex,
+    /// the start of a UTF-8 sequence cut at the end of the last read
+    partial: Vec<u8>,
+    done: bool,
+    /// what went wrong beyond what the parser can tell
+    err: Option<anyhow::Error>,
+}
+
+impl<R: BufRead, W: Write + ?Sized> ElementReader<'_, R, W> {
+    /// Check that `s`, the next bytes of the element, are valid UTF-8
+    /// so far, keeping a sequence cut at the end for the next ones.
+    fn check_utf8(&mut self, mut s: &[u8]) -> anyhow::Result<()> {
+        let mut offset = self.scanner.offset;
+        while !self.partial.is_empty() && !s.is_empty() {
+            self.partial.push(s[0]);
+            (s, offset) = (&s[1..], offset + 1);
+            match std::str::from_utf8(&self.partial) {
+                Ok(_) => self.partial.clear(),
+                Err(e) if e.error_len().is_none() => (),
+                Err(_) => anyhow::bail!("invalid UTF-8 at offset {}", offset - 1),
+            }
+        }
+        if let Err(e) = std::str::from_utf8(s) {
+            if e.error_len().is_some() {
+                anyhow::bail!(
+                    "invalid UTF-8 at offset {}",
+                    offset + e.valid_up_to() as u64
+                );
+            }
+            self.partial = s[e.valid_up_to()..].to_vec();
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, e: anyhow::Error) -> io::Error {
+        self.err = Some(e);
+        io::Error::other("element copy failed")
+    }
+}
+
+impl<R: BufRead, W: Write + ?Sized> Read for ElementReader<'_, R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done {
+            return Ok(0);
+        }
+        let chunk = match self.scanner.rd.fill_buf() {
+            Ok([]) if self.single && self.depth == 0 && self.lex == Lex::Normal => {
+                self.done = true;
+                return Ok(0);
+            }
+            Ok([]) => {
+                let start = self.start;
+                return Err(self.fail(
+                    Malformed(format!(
+                        "unexpected end of input in element starting at offset {start}"
+                    ))
+                    .into(),
+                ));
+            }
+            Ok(chunk) => &chunk[..chunk.len().min(buf.len())],
+            Err(e) => return Err(self.fail(e.into())),
+        };
+        let mut end = chunk.len();
+        // start of the bytes of `chunk` not written yet
+        let mut from = 0;
+        let mut i = 0;
+        let mut res = Ok(());
+        while i < end {
+            if let Lex::Str(q) = self.lex {
+                // jump over the bytes that can't end the string
+                match memchr::memchr2(q, b'\\', &chunk[i..end]) {
+                    Some(n) => i += n,
+                    None => break,
+                }
+            }
+            let c = chunk[i];
+            self.lex = match self.lex {
+                Lex::Str(q) if c == b'\\' => Lex::StrEscape(q),
+                Lex::Str(q) if c == q => Lex::Normal,
+                Lex::Str(q) | Lex::StrEscape(q) => Lex::Str(q),
+                _ => match c {
+                    b'"' => Lex::Str(c),
+                    b'[' | b'{' => {
+                        self.depth += 1;
+                        if self.depth > self.scanner.max_depth {
+                            res = Err(Malformed(format!(
+                                "element starting at offset {} is nested deeper than {}",
+                                self.start, self.scanner.max_depth
+                            )));
+                            break;
+                        }
+                        Lex::Normal
+                    }
+                    b',' | b']' if self.depth == 0 => {
+                        end = i;
+                        self.done = true;
+                        break;
+                    }
+                    b']' | b'}' => {
+                        self.depth = self.depth.saturating_sub(1);
+                        Lex::Normal
+                    }
+                    b' ' | b'\t' | b'\n' | b'\r' => {
+                        if let Err(e) = self.out.write_all(&chunk[from..i]) {
+                            return Err(self.fail(e.into()));
+                        }
+                        from = i + 1;
+                        Lex::Normal
+                    }
+                    _ => Lex::Normal,
+                },
+            };
+            i += 1;
+        }
+        if let Err(e) = res {
+            return Err(self.fail(e.into()));
+        }
+        buf[..end].copy_from_slice(&chunk[..end]);
+        let res = self
+            .out
+            .write_all(&chunk[from.min(end)..end])
+            .map_err(anyhow::Error::from);
+        if let Err(e) = res.and_then(|()| self.check_utf8(&buf[..end])) {
+            return Err(self.fail(e));
+        }
+        self.scanner.consume(end);
+        Ok(end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The elements `scanner` reads, as strings.
+    fn scan<R: BufRead>(mut scanner: Scanner<R>) -> anyhow::Result<Vec<String>> {
+        let (mut buf, mut out) = (vec![], vec![]);
+        while scanner.next_element(&mut buf)? {
+            out.push(String::from_utf8(buf.clone()).unwrap());
+        }
+        Ok(out)
+    }
+
+    /// The elements of `input` as strings, scanned with trailing commas
+    /// accepted.
+    fn elements(input: &str) -> anyhow::Result<Vec<String>> {
+        scan(Scanner::new(input.as_bytes()).trailing_commas(true))
+    }
+
+    #[test]
+    fn elements_as_they_are() {
+        let input = " [ 1 , \"a]\\\",[\" ,{\"b\": [2, {}]},\n[] ,null]  ";
+        assert_eq!(
+            scan(Scanner::new(input.as_bytes())).unwrap(),
+            ["1", "\"a]\\\",[\"", "{\"b\": [2, {}]}", "[]", "null"]
+        );
+        assert!(scan(Scanner::new(&b"[]"[..])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn elements_across_buffers() {
+        let input = format!("[{}]", vec!["{\"a\":[1,2,\"x\"]}"; 100].join(","));
+        let scanner = Scanner::new(BufReader::with_capacity(7, input.as_bytes()));
+        assert_eq!(scan(scanner).unwrap(), vec!["{\"a\":[1,2,\"x\"]}"; 100]);
+    }
+
+    #[test]
+    fn records_into() {
+        let input = "[ {\"a\" : [1, 2]}, \"x y\", 3 , {\"b\":tru} ]";
+        let mut scanner = Scanner::new(input.as_bytes());
+        let mut record = Vec::with_capacity(64);
+        let buffer = record.as_ptr();
+        let mut records = vec![];
+        for _ in 0..3 {
+            assert!(scanner.read_record_into(&mut record).unwrap());
+            records.push(String::from_utf8(record.clone()).unwrap());
+        }
+        assert_eq!(records, ["{\"a\":[1,2]}", "\"x y\"", "3"]);
+        // the same buffer all along
+        assert_eq!(record.as_ptr(), buffer);
+        let err = scanner.read_record_into(&mut record).unwrap_err();
+        assert_eq!(err.to_string(), "invalid element ending at offset 39");
+        let mut scanner = Scanner::new(&b"[]"[..]);
+        assert!(!scanner.read_record_into(&mut record).unwrap());
+    }
+
+    #[test]
+    fn malformed_arrays() {
+        for input in ["", "{}", "[1,", "[[1]", "[1,,2]", "[1,]"] {
+            let res = scan(Scanner::new(input.as_bytes()));
+            assert!(res.unwrap_err().is::<Malformed>(), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn trailing_data() {
+        assert_eq!(scan(Scanner::new(&b"[1] x"[..])).unwrap(), ["1"]);
+        let err = scan(Scanner::new(&b"[1] x"[..]).check_end(true)).unwrap_err();
+        assert_eq!(err.downcast_ref::<TrailingData>().unwrap().offset, 4);
+        assert_eq!(
+            scan(Scanner::new(&b"[1] \n"[..]).check_end(true)).unwrap(),
+            ["1"]
+        );
+    }
+
+    #[test]
+    fn concatenated_arrays() {
+        let scanner = Scanner::new(&b"[1]\n[2,3]"[..]).concatenated(true);
+        assert_eq!(scan(scanner).unwrap(), ["1", "2", "3"]);
+    }
+
+    #[test]
+    fn empty_input() {
+        assert!(
+            scan(Scanner::new(&b" \n"[..]).allow_empty(true))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            scan(Scanner::new(&b" \n"[..]))
+                .unwrap_err()
+                .is::<Malformed>()
+        );
+    }
+
+    #[test]
+    fn single_values() {
+        assert_eq!(
+            scan(Scanner::new(&b" {\"a\":1} "[..]).single(true)).unwrap(),
+            ["{\"a\":1}"]
+        );
+    }
+
+    #[test]
+    fn comments_between_and_inside_elements() {
+        let input = "[ // first\n1, /* second */ {\"a\": /* x */ \"//\"}]";
+        assert_eq!(
+            scan(Scanner::new(input.as_bytes()).comments(true)).unwrap(),
+            ["1", "{\"a\":   \"//\"}"]
+        );
+    }
+
+    #[test]
+    fn elements_too_large() {
+        let mut scanner = Scanner::new(&b"[\"abcdefgh\", 1]"[..]).max_len(4);
+        let mut buf = vec![];
+        let err = scanner.next_element(&mut buf).unwrap_err();
+        let too_large = err.downcast_ref::<TooLarge>().unwrap();
+        assert_eq!((too_large.offset, too_large.len), (1, 10));
+        // the start of the element is kept, and scanning goes on
+        assert_eq!(buf, b"\"abc");
+        assert!(scanner.next_element(&mut buf).unwrap());
+        assert_eq!(buf, b"1");
+    }
+
+    #[test]
+    fn elements_too_deep() {
+        let scanner = Scanner::new(&b"[[[1]], [[[2]]]]"[..]).max_depth(2);
+        assert!(scan(scanner).unwrap_err().is::<Malformed>());
+    }
+
+    #[test]
+    fn trailing_commas_after_values() {
+        assert_eq!(
+            elements("[[1,],{\"a\":1 , }]").unwrap(),
+            ["[1]", "{\"a\":1  }"]
+        );
+        assert_eq!(elements("[[\"a,\", ]]").unwrap(), ["[\"a,\" ]"]);
+        assert_eq!(elements("[1,]").unwrap(), ["1"]);
+        assert_eq!(elements("[[[1,],[2 , ] , ],]").unwrap(), ["[[1],[2  ]  ]"]);
+    }
+
+    #[test]
+    fn elements_too_large_with_comments_and_trailing_commas() {
+        let element = format!("[{}]", "1, /* c */ [2,], ".repeat(1000));
+        let input = format!("[{element}, 3]");
+        let rd = BufReader::with_capacity(16, input.as_bytes());
+        let mut scanner = Scanner::new(rd)
+            .comments(true)
+            .trailing_commas(true)
+            .max_len(8);
+        let mut buf = vec![];
+        let err = scanner.next_element(&mut buf).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TooLarge>().unwrap().len,
+            element.len() as u64
+        );
+        assert_eq!(buf, b"[1,   [2");
+        assert!(scanner.next_element(&mut buf).unwrap());
+        assert_eq!(buf, b"3");
+    }
+
+    #[test]
+    fn trailing_commas_without_values() {
+        // left for the parser to reject
+        assert_eq!(elements("[[,]]").unwrap(), ["[,]"]);
+        assert_eq!(elements("[{ , }]").unwrap(), ["{ , }"]);
+        assert_eq!(elements("[[1,,]]").unwrap(), ["[1,,]"]);
+        assert!(elements("[,]").unwrap_err().is::<Malformed>());
+    }
+
+    #[test]
+    fn copy_elements() {
+        let input = "[ {\"a\" : [1, \"é x\"]},\n\"\\\"]\" , 3 ]";
+        let mut scanner = Scanner::new(BufReader::with_capacity(3, input.as_bytes()));
+        let mut records = vec![];
+        while scanner.next_start().unwrap() {
+            let mut out = vec![];
+            scanner.copy_element(&mut out).unwrap();
+            records.push(String::from_utf8(out).unwrap());
+        }
+        assert_eq!(records, ["{\"a\":[1,\"é x\"]}", "\"\\\"]\"", "3"]);
+        let errors = [
+            (
+                &b"[{\"a\":tru}]"[..],
+                "invalid element starting at offset 1",
+            ),
+            (b"[[1,]]", "invalid element starting at offset 1"),
+            (b"[\"\xff\"]", "invalid UTF-8 at offset 2"),
+        ];
+        for (input, msg) in errors {
+            let mut scanner = Scanner::new(input);
+            assert!(scanner.next_start().unwrap());
+            let e = scanner.copy_element(&mut vec![]).unwrap_err();
+            assert_eq!(e.to_string(), msg);
+        }
+    }
+
+    #[test]
+    fn events() {
+        let input = b"[1, {\"a\": [2]}]\n[ ]";
+        let mut scanner = Scanner::new(&input[..]).concatenated(true);
+        let mut buf = vec![];
+        let mut events = vec![];
+        while let Some(event) = scanner.next_event(&mut buf).unwrap() {
+            events.push((event, String::from_utf8(buf.clone()).unwrap()));
+            buf.clear();
+        }
+        let element = |s: &str| (Event::Element, s.to_string());
+        let (start, end) = ((Event::ArrayStart, "".into()), (Event::ArrayEnd, "".into()));
+        let expected = [
+            start.clone(),
+            element("1"),
+            element("{\"a\": [2]}"),
+            end.clone(),
+            start,
+            end,
+        ];
+        assert_eq!(events, expected);
+    }
+
+    /// The events it is given, the offsets included.
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl Handler for Recorder {
+        fn on_array_start(&mut self, offset: u64) -> anyhow::Result<()> {
+            self.0.push(format!("[ {offset}"));
+            Ok(())
+        }
+
+        fn on_value(&mut self, raw: &[u8], offset: u64) -> anyhow::Result<()> {
+            if raw == b"stop" {
+                anyhow::bail!("stopped at {offset}");
+            }
+            self.0
+                .push(format!("{} {offset}", String::from_utf8_lossy(raw)));
+            Ok(())
+        }
+
+        fn on_array_end(&mut self, offset: u64) -> anyhow::Result<()> {
+            self.0.push(format!("] {offset}"));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn handlers() {
+        let mut recorder = Recorder::default();
+        let mut scanner = Scanner::new(&b" [1, [2] ] [3]"[..]).concatenated(true);
+        assert_eq!(scanner.drive(&mut recorder).unwrap(), 3);
+        assert_eq!(
+            recorder.0,
+            ["[ 1", "1 2", "[2] 5", "] 9", "[ 11", "3 12", "] 13"]
+        );
+        let mut scanner = Scanner::new(&b"[1, stop, 2]"[..]);
+        let e = scanner.drive(&mut Recorder::default()).unwrap_err();
+        assert_eq!(e.to_string(), "stopped at 4");
+    }
+}
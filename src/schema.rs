@@ -0,0 +1,140 @@
+use crate::drift::{TYPES, type_bit};
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+
+/// What is known of the values seen at a place in the records.
+#[derive(Debug, Clone, Default)]
+struct Node {
+    types: u8,
+    /// whether some numbers have a fraction or an exponent
+    fractional: bool,
+    /// objects seen, and their members with the number of objects having each
+    objects: u64,
+    properties: BTreeMap<String, (u64, Node)>,
+    /// the values of all the arrays seen
+    items: Option<Box<Node>>,
+}
+
+impl Node {
+    fn add(&mut self, value: &Value) {
+        self.types |= type_bit(value);
+        match value {
+            Value::Number(n) => {
+                self.fractional |= n.to_string().contains(['.', 'e', 'E']);
+            }
+            Value::Object(fields) => {
+                self.objects += 1;
+                for (key, value) in fields {
+                    let (count, node) = self.properties.entry(key.clone()).or_default();
+                    *count += 1;
+                    node.add(value);
+                }
+            }
+            Value::Array(items) => {
+                let node = self.items.get_or_insert_default();
+                items.iter().for_each(|item| node.add(item));
+            }
+            _ => (),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let mut schema = Map::new();
+        let mut types: Vec<_> = (TYPES.iter().enumerate())
+            .filter(|(i, _)| self.types & (1 << i) != 0)
+            .map(|(_, &name)| match name {
+                "number" if !self.fractional => "integer",
+                name => name,
+            })
+            .collect();
+        match types.len() {
+            0 => (),
+            1 => _ = schema.insert("type".into(), types.remove(0).into()),
+            _ => _ = schema.insert("type".into(), types.into()),
+        }
+        if self.objects > 0 {
+            let properties: Map<_, _> = (self.properties.iter())
+                .map(|(key, (_, node))| (key.clone(), node.to_json()))
+                .collect();
+            let required: Vec<_> = (self.properties.iter())
+                .filter(|(_, (count, _))| *count == self.objects)
+                .map(|(key, _)| key.clone())
+                .collect();
+            schema.insert("properties".into(), properties.into());
+            if !required.is_empty() {
+                schema.insert("required".into(), required.into());
+            }
+        }
+        if let Some(items) = &self.items {
+            schema.insert("items".into(), items.to_json());
+        }
+        schema.into()
+    }
+}
+
+/// A JSON Schema inferred from the records, for the schema subcommand: the
+/// types found at each place, with the members of the objects, those that
+/// all of the objects there have being required.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    root: Node,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `record`, a JSON text.
+    pub fn add(&mut self, record: &[u8]) -> serde_json::Result<()> {
+        let value: Value = serde_json::from_slice(record)?;
+        self.root.add(&value);
+        Ok(())
+    }
+
+    /// The schema, in the JSON Schema 2020-12 dialect, that all the records
+    /// added so far match.
+    pub fn to_json(&self) -> Value {
+        let mut schema = json!({"$schema": "https://json-schema.org/draft/2020-12/schema"});
+        if let (Value::Object(schema), Value::Object(root)) = (&mut schema, self.root.to_json()) {
+            schema.extend(root);
+        }
+        schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inferred() {
+        let mut schema = Schema::new();
+        for record in [
+            r#"{"id":1,"tags":["a"],"user":{"name":"x","age":3}}"#,
+            r#"{"id":2,"tags":[],"user":{"name":"y"},"score":1.5}"#,
+            r#"{"id":3,"tags":[1],"user":null,"score":2}"#,
+        ] {
+            schema.add(record.as_bytes()).unwrap();
+        }
+        assert_eq!(
+            schema.to_json(),
+            json!({
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "type": "object",
+                "properties": {
+                    "id": {"type": "integer"},
+                    "score": {"type": "number"},
+                    "tags": {"type": "array", "items": {"type": ["integer", "string"]}},
+                    "user": {
+                        "type": ["null", "object"],
+                        "properties": {"age": {"type": "integer"}, "name": {"type": "string"}},
+                        "required": ["name"]
+                    }
+                },
+                "required": ["id", "tags", "user"]
+            })
+        );
+        assert!(schema.add(b"{").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{Context, bail};
+use rhai::{AST, Array, Dynamic, Engine, Map, Scope};
+use serde_json::{Number, Value};
+use std::path::Path;
+
+/// A Rhai script run on each record, for --script.
+///
+/// The script sees the parsed record as `record` and its index as `index`,
+/// and evaluates to the records to write in its place: a single value, an
+/// array of them to split the record, or `()` to drop it. For example
+/// `record.seen = true; record`.
+///
+/// Objects come out with their keys sorted, as Rhai keeps them.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+}
+
+impl Script {
+    /// Compile the script at `path`.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let mut engine = Engine::new();
+        // stdout is for the records
+        engine.on_print(|s| eprintln!("{s}"));
+        let ast = engine
+            .compile_file(path.into())
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .with_context(|| format!("loading script {}", path.display()))?;
+        Ok(Self { engine, ast })
+    }
+
+    /// Run the script on `record` (compact JSON), number `index` in the
+    /// input, and return the records it evaluates to.
+    pub fn run(&self, record: &[u8], index: u64) -> anyhow::Result<Vec<Vec<u8>>> {
+        let value: Value = serde_json::from_slice(record)?;
+        let mut scope = Scope::new();
+        scope.push("record", to_dynamic(value));
+        scope.push("index", index as rhai::INT);
+        let res: Dynamic = self
+            .engine
+            .eval_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+        let values = if res.is_unit() {
+            vec![]
+        } else if res.is_array() {
+            res.cast::<Array>()
+        } else {
+            vec![res]
+        };
+        values
+            .into_iter()
+            .map(|v| Ok(serde_json::to_vec(&from_dynamic(v)?)?))
+            .collect()
+    }
+}
+
+fn to_dynamic(value: Value) -> Dynamic {
+    match value {
+        Value::Null => Dynamic::UNIT,
+        Value::Bool(b) => b.into(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into(),
+            None => n.as_f64().unwrap_or(f64::NAN).into(),
+        },
+        Value::String(s) => s.into(),
+        Value::Array(values) => values.into_iter().map(to_dynamic).collect::<Array>().into(),
+        Value::Object(entries) => entries
+            .into_iter()
+            .map(|(k, v)| (k.into(), to_dynamic(v)))
+            .collect::<Map>()
+            .into(),
+    }
+}
+
+fn from_dynamic(value: Dynamic) -> anyhow::Result<Value> {
+    Ok(if value.is_unit() {
+        Value::Null
+    } else if let Ok(b) = value.as_bool() {
+        Value::Bool(b)
+    } else if let Ok(i) = value.as_int() {
+        Value::Number(i.into())
+    } else if let Ok(f) = value.as_float() {
+        match Number::from_f64(f) {
+            Some(n) => Value::Number(n),
+            None => bail!("the script returned {f}, which isn't valid JSON"),
+        }
+    } else if value.is_string() || value.is_char() {
+        Value::String(value.to_string())
+    } else if value.is_array() {
+        let values = value.cast::<Array>().into_iter().map(from_dynamic);
+        Value::Array(values.collect::<anyhow::Result<_>>()?)
+    } else if value.is_map() {
+        let entries = value.cast::<Map>().into_iter();
+        let entries = entries.map(|(k, v)| Ok((k.to_string(), from_dynamic(v)?)));
+        Value::Object(entries.collect::<anyhow::Result<_>>()?)
+    } else {
+        bail!(
+            "the script returned a {}, which isn't JSON",
+            value.type_name()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(source: &str) -> Script {
+        let path =
+            std::env::temp_dir().join(format!("json2jsonl-script-{}.rhai", std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        let script = Script::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        script.unwrap()
+    }
+
+    fn run(script: &Script, record: &str) -> Vec<String> {
+        let records = script.run(record.as_bytes(), 7).unwrap();
+        records
+            .into_iter()
+            .map(|r| String::from_utf8(r).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn records() {
+        let split = script(
+            "if record.drop == true { return (); }\n\
+             if type_of(record.items) == \"array\" { return record.items; }\n\
+             record.index = index; record",
+        );
+        assert_eq!(
+            run(&split, r#"{"b":null,"a":1.5}"#),
+            [r#"{"a":1.5,"b":null,"index":7}"#]
+        );
+        assert_eq!(
+            run(&split, r#"{"items":[1,"x",[true]]}"#),
+            ["1", r#""x""#, "[true]"]
+        );
+        assert!(run(&split, r#"{"drop":true}"#).is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let e = script("1.0 / 0.0").run(b"{}", 0).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "the script returned inf, which isn't valid JSON"
+        );
+        let e = script("|x| x").run(b"{}", 0).unwrap_err();
+        assert!(e.to_string().contains("which isn't JSON"), "{e}");
+        assert!(script("record.a").run(b"[1]", 0).is_err());
+    }
+}
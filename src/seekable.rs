@@ -0,0 +1,140 @@
+use std::io::{self, ErrorKind, Write};
+
+/// Magic number of the skippable frame holding the seek table.
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A5E;
+/// Magic number at the very end of a seekable stream.
+const SEEKABLE_MAGIC: u32 = 0x8F92_EAB1;
+
+/// Compresses to the zstd seekable format: independent zstd frames of
+/// about `frame_size` bytes of input each, followed by a seek table of
+/// their sizes, so that readers can decompress any part of the stream
+/// without going through what comes before it.
+///
+/// A frame only ends at a call to [`end_record`](Self::end_record), so that
+/// records are never split between two frames.
+pub struct SeekableWriter<W: Write> {
+    inner: W,
+    frame_size: usize,
+    level: i32,
+    /// input of the frame being built
+    buf: Vec<u8>,
+    /// (compressed, decompressed) sizes of the frames written
+    frames: Vec<(u32, u32)>,
+}
+
+impl<W: Write> SeekableWriter<W> {
+    pub fn new(inner: W, frame_size: usize, level: i32) -> Self {
+        Self {
+            inner,
+            frame_size: frame_size.max(1),
+            level,
+            buf: vec![],
+            frames: vec![],
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Mark the end of a record, ending the frame if it is large enough.
+    pub fn end_record(&mut self) -> io::Result<()> {
+        if self.buf.len() >= self.frame_size {
+            self.write_frame()?;
+        }
+        Ok(())
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        let decompressed = u32::try_from(self.buf.len())
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "zstd frame over 4GB"))?;
+        let frame = zstd::bulk::compress(&self.buf, self.level)?;
+        self.inner.write_all(&frame)?;
+        self.frames.push((frame.len() as u32, decompressed));
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Write the last frame and the seek table, and flush the output.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.write_frame()?;
+        }
+        let entries = self.frames.len() as u32;
+        let mut table = Vec::with_capacity(8 * self.frames.len() + 17);
+        table.extend(SKIPPABLE_MAGIC.to_le_bytes());
+        table.extend((8 * entries + 9).to_le_bytes());
+        for (compressed, decompressed) in self.frames.drain(..) {
+            table.extend(compressed.to_le_bytes());
+            table.extend(decompressed.to_le_bytes());
+        }
+        table.extend(entries.to_le_bytes());
+        // no checksums
+        table.push(0);
+        table.extend(SEEKABLE_MAGIC.to_le_bytes());
+        self.inner.write_all(&table)?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for SeekableWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    // frames only end with records, there is nothing to flush before then
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_end_at_records() {
+        let records: Vec<String> = (0..20).map(|i| format!("{{\"i\":{i}}}\n")).collect();
+        let mut w = SeekableWriter::new(vec![], 20, 3);
+        for record in &records {
+            w.write_all(record.as_bytes()).unwrap();
+            w.end_record().unwrap();
+        }
+        w.finish().unwrap();
+        let out = w.inner;
+        // a plain zstd decoder skips the seek table
+        assert_eq!(
+            zstd::decode_all(&out[..]).unwrap(),
+            records.concat().as_bytes()
+        );
+
+        let u32_at = |i: usize| u32::from_le_bytes(out[i..i + 4].try_into().unwrap());
+        assert_eq!(u32_at(out.len() - 4), SEEKABLE_MAGIC);
+        assert_eq!(out[out.len() - 5], 0);
+        let entries = u32_at(out.len() - 9) as usize;
+        let table = out.len() - 17 - 8 * entries;
+        assert_eq!(u32_at(table), SKIPPABLE_MAGIC);
+        assert_eq!(u32_at(table + 4) as usize, 8 * entries + 9);
+        // each frame decompresses on its own, to whole records
+        let (mut offset, mut frames) = (0, vec![]);
+        for entry in 0..entries {
+            let (compressed, decompressed) = (
+                u32_at(table + 8 + 8 * entry),
+                u32_at(table + 12 + 8 * entry),
+            );
+            let frame = zstd::decode_all(&out[offset..offset + compressed as usize]).unwrap();
+            assert_eq!(frame.len(), decompressed as usize);
+            frames.push(String::from_utf8(frame).unwrap());
+            offset += compressed as usize;
+        }
+        assert_eq!(offset, table);
+        assert_eq!(frames.len(), 7);
+        assert!(
+            frames
+                .iter()
+                .all(|frame| frame.ends_with('\n') && frame.len() >= 20 || frame == &frames[6])
+        );
+        assert_eq!(frames.concat(), records.concat());
+    }
+}
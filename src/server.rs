@@ -0,0 +1,307 @@
+use anyhow::Context;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use tracing::warn;
+
+/// A connection's halves, and how to close the writing one.
+enum Conn {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixStream),
+}
+
+impl Conn {
+    fn split(&self) -> io::Result<(Box<dyn Read + Send>, Box<dyn Write + Send>)> {
+        Ok(match self {
+            Conn::Tcp(s) => (Box::new(s.try_clone()?), Box::new(s.try_clone()?)),
+            #[cfg(unix)]
+            Conn::Unix(s) => (Box::new(s.try_clone()?), Box::new(s.try_clone()?)),
+        })
+    }
+
+    fn shutdown(&self) {
+        let _ = match self {
+            Conn::Tcp(s) => s.shutdown(Shutdown::Write),
+            #[cfg(unix)]
+            Conn::Unix(s) => s.shutdown(Shutdown::Write),
+        };
+    }
+}
+
+fn handle_conn<F>(conn: Conn, handle: &F)
+where
+    F: Fn(&mut dyn Read, &mut BufWriter<Box<dyn Write + Send>>) -> anyhow::Result<()>,
+{
+    let res = conn
+        .split()
+        .map_err(anyhow::Error::from)
+        .and_then(|(mut rd, wr)| {
+            let mut wr = BufWriter::new(wr);
+            handle(&mut rd, &mut wr)?;
+            Ok(wr.flush()?)
+        });
+    if let Err(e) = res {
+        warn!("connection failed: {e:#}");
+    }
+    conn.shutdown();
+}
+
+/// Accept connections on `addr` (`unix:PATH`, or `[tcp:]HOST:PORT`) forever,
+/// calling `handle` on each of them from its own thread with the reading
+/// and writing halves of the connection. The writing half is closed once
+/// `handle` returns.
+pub fn serve<F>(addr: &str, handle: F) -> anyhow::Result<()>
+where
+    F: Fn(&mut dyn Read, &mut BufWriter<Box<dyn Write + Send>>) -> anyhow::Result<()> + Sync,
+{
+    accept(addr, |conn| handle_conn(conn, &handle))
+}
+
+/// Serve conversions over HTTP on `addr`, like [`serve`]: `handle` reads the
+/// body of each `POST` request, and what it writes goes back as the body of
+/// the response, in chunks as it comes. An error before anything is written
+/// gets a response of its own, a 400 if `client_error` says it's the
+/// request's fault and a 500 otherwise; after that, the response is cut
+/// short. There is one request per connection.
+pub fn serve_http<F, C>(
+    addr: &str,
+    content_type: &str,
+    handle: F,
+    client_error: C,
+) -> anyhow::Result<()>
+where
+    F: Fn(&mut dyn Read, &mut BufWriter<Box<dyn Write + Send>>) -> anyhow::Result<()> + Sync,
+    C: Fn(&anyhow::Error) -> bool + Sync,
+{
+    accept(addr, |conn| {
+        if let Err(e) = handle_http(&conn, content_type, &handle, &client_error) {
+            warn!("request failed: {e:#}");
+        }
+        conn.shutdown();
+    })
+}
+
+fn accept<F: Fn(Conn) + Sync>(addr: &str, on_conn: F) -> anyhow::Result<()> {
+    if let Some(path) = addr.strip_prefix("unix:") {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            use std::os::unix::net::UnixListener;
+            // a socket left over by a previous run would make bind fail
+            if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                std::fs::remove_file(path)?;
+            }
+            let listener =
+                UnixListener::bind(path).with_context(|| format!("listening on {path}"))?;
+            return thread::scope(|s| {
+                for conn in listener.incoming() {
+                    match conn {
+                        Ok(conn) => {
+                            s.spawn(|| on_conn(Conn::Unix(conn)));
+                        }
+                        Err(e) => warn!("accepting a connection failed: {e}"),
+                    }
+                }
+                Ok(())
+            });
+        }
+        #[cfg(not(unix))]
+        anyhow::bail!("unix sockets are not supported on this platform ({path})");
+    }
+    let addr = addr.strip_prefix("tcp:").unwrap_or(addr);
+    let listener = TcpListener::bind(addr).with_context(|| format!("listening on {addr}"))?;
+    thread::scope(|s| {
+        for conn in listener.incoming() {
+            match conn {
+                Ok(conn) => {
+                    s.spawn(|| on_conn(Conn::Tcp(conn)));
+                }
+                Err(e) => warn!("accepting a connection failed: {e}"),
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Most bytes in the request line and headers.
+const MAX_HEAD: u64 = 64 * 1024;
+
+fn handle_http<F, C>(
+    conn: &Conn,
+    content_type: &str,
+    handle: &F,
+    client_error: &C,
+) -> anyhow::Result<()>
+where
+    F: Fn(&mut dyn Read, &mut BufWriter<Box<dyn Write + Send>>) -> anyhow::Result<()>,
+    C: Fn(&anyhow::Error) -> bool,
+{
+    let (rd, mut wr) = conn.split()?;
+    let mut rd = BufReader::new(rd);
+    let mut head = (&mut rd).take(MAX_HEAD);
+    let mut line = String::new();
+    head.read_line(&mut line)?;
+    let method = line.split(' ').next().unwrap_or_default().to_string();
+    let mut length = None;
+    let mut chunked = false;
+    let mut expect_continue = false;
+    loop {
+        line.clear();
+        if head.read_line(&mut line)? == 0 {
+            return respond(&mut wr, "400 Bad Request", "incomplete request head\n");
+        }
+        let Some((name, value)) = line.trim_end().split_once(':') else {
+            break;
+        };
+        let value = value.trim();
+        match name.to_ascii_lowercase().as_str() {
+            "content-length" => length = value.parse::<u64>().ok(),
+            "transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+            "expect" => expect_continue = value.eq_ignore_ascii_case("100-continue"),
+            _ => (),
+        }
+    }
+    if method != "POST" {
+        return respond(
+            &mut wr,
+            "405 Method Not Allowed\r\nAllow: POST",
+            "POST a JSON array to convert it\n",
+        );
+    }
+    let mut body: Box<dyn Read> = match length {
+        _ if chunked => Box::new(ChunkedBody::new(rd)),
+        Some(n) => Box::new(rd.take(n)),
+        None => return respond(&mut wr, "411 Length Required", "the request has no body\n"),
+    };
+    if expect_continue {
+        wr.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+    }
+
+    let started = Arc::new(AtomicBool::new(false));
+    let mut out = BufWriter::new(Box::new(Chunks {
+        w: conn.split()?.1,
+        content_type: content_type.to_string(),
+        started: started.clone(),
+    }) as Box<dyn Write + Send>);
+    match handle(&mut body, &mut out).and_then(|()| Ok(out.flush()?)) {
+        Ok(()) if started.load(Ordering::Relaxed) => Ok(wr.write_all(b"0\r\n\r\n")?),
+        Ok(()) => {
+            // nothing to chunk
+            let head = format!("200 OK\r\nContent-Type: {content_type}");
+            respond(&mut wr, &head, "")
+        }
+        Err(e) if started.load(Ordering::Relaxed) => Err(e.context("response cut short")),
+        Err(e) => {
+            let status = if client_error(&e) {
+                "400 Bad Request"
+            } else {
+                "500 Internal Server Error"
+            };
+            respond(&mut wr, status, &format!("{e:#}\n"))?;
+            Err(e)
+        }
+    }
+}
+
+/// Write a response with a complete `body`, after `status` (and any header
+/// lines it carries).
+fn respond(w: &mut dyn Write, status: &str, body: &str) -> anyhow::Result<()> {
+    write!(
+        w,
+        "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(w.flush()?)
+}
+
+/// Writes the body of a response in HTTP/1.1 chunks, after the head of a
+/// 200 response with the first one.
+struct Chunks {
+    w: Box<dyn Write + Send>,
+    content_type: String,
+    started: Arc<AtomicBool>,
+}
+
+impl Write for Chunks {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if !self.started.swap(true, Ordering::Relaxed) {
+            write!(
+                self.w,
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n",
+                self.content_type
+            )?;
+        }
+        write!(self.w, "{:x}\r\n", buf.len())?;
+        self.w.write_all(buf)?;
+        self.w.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+/// Reads the body of a request sent in chunks.
+struct ChunkedBody<R> {
+    rd: R,
+    /// bytes left in the current chunk
+    left: u64,
+    done: bool,
+}
+
+impl<R: BufRead> ChunkedBody<R> {
+    fn new(rd: R) -> Self {
+        Self {
+            rd,
+            left: 0,
+            done: false,
+        }
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        (&mut self.rd).take(MAX_HEAD).read_line(&mut line)?;
+        Ok(line)
+    }
+}
+
+impl<R: BufRead> Read for ChunkedBody<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.left == 0 {
+            if self.done {
+                return Ok(0);
+            }
+            let line = self.read_line()?;
+            let size = line.trim_end().split(';').next().unwrap_or_default();
+            self.left = u64::from_str_radix(size.trim(), 16).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("bad chunk size {size:?}"),
+                )
+            })?;
+            if self.left == 0 {
+                // the trailer, up to an empty line
+                while !self.read_line()?.trim_end().is_empty() {}
+                self.done = true;
+            }
+        }
+        let n = buf.len().min(self.left.try_into().unwrap_or(usize::MAX));
+        let n = self.rd.read(&mut buf[..n])?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.left -= n as u64;
+        if self.left == 0 {
+            // the CRLF after the data
+            self.read_line()?;
+        }
+        Ok(n)
+    }
+}
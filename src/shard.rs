@@ -0,0 +1,81 @@
+use crate::template;
+use serde_json::Value;
+
+/// Spreads records over a number of shards by the hash of a field, the
+/// records with the same value of the field always going to the same shard.
+#[derive(Debug, Clone)]
+pub struct Sharder {
+    path: Vec<String>,
+    shards: u32,
+}
+
+impl Sharder {
+    /// Shard by `field`, a dotted path into the records like those of a
+    /// template, over `shards` shards.
+    pub fn new(field: &str, shards: u32) -> Self {
+        Self {
+            path: field.split('.').map(String::from).collect(),
+            shards: shards.max(1),
+        }
+    }
+
+    /// The shard of `record`, from 0. Strings are hashed as they are and
+    /// other values as JSON; records that aren't JSON or don't have the
+    /// field all go to the first shard.
+    pub fn shard(&self, record: &[u8]) -> u32 {
+        let Ok(value) = serde_json::from_slice::<Value>(record) else {
+            return 0;
+        };
+        let json;
+        let key = match template::lookup(&value, &self.path) {
+            None => return 0,
+            Some(Value::String(s)) => s.as_str(),
+            Some(value) => {
+                json = value.to_string();
+                &json
+            }
+        };
+        (fnv1a(key.as_bytes()) % self.shards as u64) as u32
+    }
+}
+
+/// The name of shard `n`, from `pattern` with `{n}` where the number goes.
+pub fn path(pattern: &str, n: u32) -> String {
+    pattern.replace("{n}", &n.to_string())
+}
+
+/// 64-bit FNV-1a, which unlike the hasher of the standard library is the
+/// same from one run, version or platform to the next.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shards() {
+        let sharder = Sharder::new("user.id", 16);
+        let shard = |record: &str| sharder.shard(record.as_bytes());
+        // the same from one run or platform to the next
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(shard(r#"{"user":{"id":"a"}}"#), (fnv1a(b"a") % 16) as u32);
+        assert_eq!(shard(r#"{"user":{"id":7}}"#), (fnv1a(b"7") % 16) as u32);
+        assert_eq!(
+            shard(r#"{"user":{"id":[1, 2]}}"#),
+            (fnv1a(b"[1,2]") % 16) as u32
+        );
+        for record in [r#"{"user":{}}"#, "not json", "[1]"] {
+            assert_eq!(shard(record), 0);
+        }
+        let counts = (0..1600).fold([0; 16], |mut counts, i| {
+            counts[shard(&format!(r#"{{"user":{{"id":{i}}}}}"#)) as usize] += 1;
+            counts
+        });
+        assert!(counts.iter().all(|&n| n > 50), "{counts:?}");
+        assert_eq!(path("out-{n}.jsonl", 3), "out-3.jsonl");
+    }
+}
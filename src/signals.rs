@@ -0,0 +1,78 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+/// Exit code of a process killed by SIGINT, as reported by shells.
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+static INTERRUPTED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// Catch Ctrl-C (SIGINT), so that the conversion can stop after a complete
+/// record: the first one only sets the flag checked by [`interrupted`], a
+/// second one exits right away, for when the input is stuck.
+pub fn catch_interrupt() -> io::Result<()> {
+    let flag = INTERRUPTED.get_or_init(Default::default);
+    // registered first, so that it only fires once the flag is set
+    signal_hook::flag::register_conditional_shutdown(
+        signal_hook::consts::SIGINT,
+        EXIT_INTERRUPTED,
+        flag.clone(),
+    )?;
+    signal_hook::flag::register(signal_hook::consts::SIGINT, flag.clone())?;
+    Ok(())
+}
+
+/// Has Ctrl-C been pressed since [`catch_interrupt`]?
+pub fn interrupted() -> bool {
+    INTERRUPTED
+        .get()
+        .is_some_and(|flag| flag.load(Ordering::Relaxed))
+}
+
+/// How far the conversion got, as printed by [`report_status`].
+pub struct Status {
+    /// Offset reached in the input
+    pub offset: AtomicU64,
+    /// Number of records written
+    pub records: AtomicU64,
+}
+
+pub static STATUS: Status = Status {
+    offset: AtomicU64::new(0),
+    records: AtomicU64::new(0),
+};
+
+/// Print [`STATUS`] to stderr whenever the process gets SIGUSR1 (or SIGINFO,
+/// i.e. Ctrl-T, where it exists), from a background thread, so that it also
+/// works while waiting for input.
+#[cfg(unix)]
+pub fn report_status() -> io::Result<()> {
+    use signal_hook::consts::SIGUSR1;
+    #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+    let signals = [SIGUSR1, signal_hook::consts::SIGINFO];
+    #[cfg(not(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd")))]
+    let signals = [SIGUSR1];
+
+    let mut signals = signal_hook::iterator::Signals::new(signals)?;
+    let start = Instant::now();
+    let start_offset = STATUS.offset.load(Ordering::Relaxed);
+    std::thread::spawn(move || {
+        for _ in signals.forever() {
+            let offset = STATUS.offset.load(Ordering::Relaxed);
+            let records = STATUS.records.load(Ordering::Relaxed);
+            let secs = start.elapsed().as_secs_f64();
+            let rate = (offset - start_offset) as f64 / secs.max(1e-3) / 1e6;
+            eprintln!(
+                "status: input offset {offset}, {records} records written, {rate:.2} MB/s over {secs:.0}s"
+            );
+        }
+    });
+    Ok(())
+}
+
+/// Status reports are only available on Unix.
+#[cfg(not(unix))]
+pub fn report_status() -> io::Result<()> {
+    Ok(())
+}
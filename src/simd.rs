@@ -0,0 +1,46 @@
+use std::cell::RefCell;
+
+thread_local! {
+    /// The copy of the element simd-json parses in place, and its buffers.
+    static SCRATCH: RefCell<(Vec<u8>, Option<simd_json::Buffers>)> = const { RefCell::new((vec![], None)) };
+}
+
+/// Check that `element` is a valid JSON value, with simd-json.
+///
+/// Integers too large for 64 bits are accepted, as with serde_json, but
+/// not numbers out of the range of doubles.
+pub fn validate(element: &[u8]) -> Result<(), simd_json::Error> {
+    SCRATCH.with_borrow_mut(|(copy, buffers)| {
+        copy.clear();
+        copy.extend_from_slice(element);
+        let buffers = buffers.get_or_insert_with(|| simd_json::Buffers::new(element.len()));
+        simd_json::to_tape_with_buffers(copy, buffers).map(drop)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates() {
+        for valid in [
+            &b"{\"a\":[1,2.5,null]}"[..],
+            b"\"x\"",
+            b"123456789012345678901234567890",
+        ] {
+            assert!(
+                validate(valid).is_ok(),
+                "{}",
+                String::from_utf8_lossy(valid)
+            );
+        }
+        for invalid in [&b"{\"a\":}"[..], b"[1,]", b"1e400", b"\"\\x\""] {
+            assert!(
+                validate(invalid).is_err(),
+                "{}",
+                String::from_utf8_lossy(invalid)
+            );
+        }
+    }
+}
@@ -0,0 +1,9 @@
+/// A destination that takes whole records rather than a byte stream, such
+/// as an HTTP endpoint or a message broker.
+pub trait Sink {
+    /// Send (or queue) one serialized record.
+    fn push(&mut self, record: &[u8]) -> anyhow::Result<()>;
+
+    /// Send whatever is queued, and wait until it is delivered.
+    fn finish(&mut self) -> anyhow::Result<()>;
+}
@@ -0,0 +1,247 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::debug;
+
+/// Bytes counted for each entry of a table besides its contents, for the
+/// allocations and the table itself.
+pub const OVERHEAD: usize = 64;
+
+/// Runs merged at once: past this many, they are merged into one first, so
+/// that few files are open and each has a buffer of its own.
+const FAN_IN: usize = 16;
+
+/// Temporary files made so far, for their names.
+static FILES: AtomicU64 = AtomicU64::new(0);
+
+/// A temporary file, removed when dropped.
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl TempFile {
+    /// Make a new empty file in the directory of temporary files, e.g.
+    /// $TMPDIR.
+    pub fn new() -> io::Result<Self> {
+        let n = FILES.fetch_add(1, Ordering::Relaxed);
+        let name = format!("json2jsonl-spill-{}-{n}", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let file = (File::options().read(true).write(true))
+            .create_new(true)
+            .open(&path)?;
+        Ok(Self { path, file })
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+type Entry = (Vec<u8>, Vec<u8>);
+
+fn write_entry(mut w: impl Write, (key, value): &Entry) -> io::Result<()> {
+    w.write_all(&(key.len() as u32).to_le_bytes())?;
+    w.write_all(&(value.len() as u32).to_le_bytes())?;
+    w.write_all(key)?;
+    w.write_all(value)
+}
+
+fn read_entry(mut r: impl Read) -> io::Result<Option<Entry>> {
+    let mut lens = [0; 8];
+    match r.read_exact(&mut lens) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        res => res?,
+    }
+    let (key, value) = lens.split_at(4);
+    let mut key = vec![0; u32::from_le_bytes(key.try_into().unwrap()) as usize];
+    let mut value = vec![0; u32::from_le_bytes(value.try_into().unwrap()) as usize];
+    r.read_exact(&mut key)?;
+    r.read_exact(&mut value)?;
+    Ok(Some((key, value)))
+}
+
+/// Entries of a key and a value sorted by an external merge sort, by key
+/// and then by value: they are kept in memory up to a budget, written to
+/// temporary files in sorted runs past it, and merged back in order at the
+/// end, so that tables larger than memory can be sorted.
+#[derive(Debug)]
+pub struct SortedRuns {
+    budget: usize,
+    pending: Vec<Entry>,
+    /// bytes of the pending entries, roughly
+    bytes: usize,
+    runs: Vec<TempFile>,
+}
+
+impl SortedRuns {
+    /// Sort entries keeping about `budget` bytes of them in memory.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            pending: vec![],
+            bytes: 0,
+            runs: vec![],
+        }
+    }
+
+    pub fn push(&mut self, key: Vec<u8>, value: Vec<u8>) -> io::Result<()> {
+        self.bytes += key.len() + value.len() + OVERHEAD;
+        self.pending.push((key, value));
+        if self.bytes > self.budget {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Whether some of the entries were written to disk.
+    pub fn spilled(&self) -> bool {
+        !self.runs.is_empty()
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        self.pending.sort_unstable();
+        debug!(entries = self.pending.len(), "spilled a sorted run to disk");
+        let run = TempFile::new()?;
+        let mut w = BufWriter::new(run.file());
+        for entry in std::mem::take(&mut self.pending) {
+            write_entry(&mut w, &entry)?;
+        }
+        w.flush()?;
+        drop(w);
+        self.bytes = 0;
+        self.runs.push(run);
+        if self.runs.len() == FAN_IN {
+            let run = TempFile::new()?;
+            let mut w = BufWriter::new(run.file());
+            for entry in Merge::new(std::mem::take(&mut self.runs), vec![])? {
+                write_entry(&mut w, &entry?)?;
+            }
+            w.flush()?;
+            drop(w);
+            self.runs.push(run);
+        }
+        Ok(())
+    }
+
+    /// All the entries, in order.
+    pub fn into_sorted(mut self) -> io::Result<Merge> {
+        self.pending.sort_unstable();
+        Merge::new(
+            std::mem::take(&mut self.runs),
+            std::mem::take(&mut self.pending),
+        )
+    }
+}
+
+/// The entries of [`SortedRuns`] in order, read from the runs on disk and
+/// the entries left in memory.
+pub struct Merge {
+    readers: Vec<BufReader<File>>,
+    memory: std::vec::IntoIter<Entry>,
+    /// the next entry from each source, numbered like the readers and then
+    /// the memory, the smallest on top
+    heap: BinaryHeap<Reverse<(Entry, usize)>>,
+    /// removed once merged
+    _runs: Vec<TempFile>,
+}
+
+impl Merge {
+    fn new(runs: Vec<TempFile>, memory: Vec<Entry>) -> io::Result<Self> {
+        let readers = (runs.iter())
+            .map(|run| {
+                let mut file = run.file().try_clone()?;
+                file.seek(SeekFrom::Start(0))?;
+                Ok(BufReader::new(file))
+            })
+            .collect::<io::Result<_>>()?;
+        let mut merge = Self {
+            readers,
+            memory: memory.into_iter(),
+            heap: BinaryHeap::new(),
+            _runs: runs,
+        };
+        for i in 0..=merge.readers.len() {
+            merge.refill(i)?;
+        }
+        Ok(merge)
+    }
+
+    fn refill(&mut self, i: usize) -> io::Result<()> {
+        let entry = match self.readers.get_mut(i) {
+            Some(reader) => read_entry(reader)?,
+            None => self.memory.next(),
+        };
+        if let Some(entry) = entry {
+            self.heap.push(Reverse((entry, i)));
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Merge {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((entry, i)) = self.heap.pop()?;
+        Some(self.refill(i).map(|()| entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(runs: SortedRuns) -> Vec<Entry> {
+        runs.into_sorted().unwrap().map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn in_memory() {
+        let mut runs = SortedRuns::new(usize::MAX);
+        for key in ["b", "c", "a", "b"] {
+            runs.push(key.into(), vec![]).unwrap();
+        }
+        assert!(!runs.spilled());
+        let keys: Vec<_> = sorted(runs).into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, [b"a", b"b", b"b", b"c"]);
+    }
+
+    #[test]
+    fn spilled() {
+        // more runs than merged at once
+        let mut runs = SortedRuns::new(10 * OVERHEAD);
+        let mut expected = vec![];
+        for i in 0..1000u32 {
+            let key = (i.wrapping_mul(7919) % 1000).to_be_bytes().to_vec();
+            let value = i.to_string().into_bytes();
+            expected.push((key.clone(), value.clone()));
+            runs.push(key, value).unwrap();
+        }
+        assert!(runs.spilled());
+        expected.sort();
+        assert_eq!(sorted(runs), expected);
+    }
+
+    #[test]
+    fn files_removed() {
+        let mut runs = SortedRuns::new(0);
+        runs.push(b"key".to_vec(), b"value".to_vec()).unwrap();
+        let path = runs.runs[0].path.clone();
+        assert!(path.exists());
+        let mut merge = runs.into_sorted().unwrap();
+        assert_eq!(merge.next().unwrap().unwrap().0, b"key");
+        drop(merge);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,130 @@
+use std::io::{self, BufRead, BufReader, Read};
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+use ureq::BodyReader;
+
+/// Subscribes to a Server-Sent Events endpoint, presenting the data of
+/// each event, a JSON value, as an element of a single JSON array.
+///
+/// When the connection drops, it is reopened after the delay requested by
+/// the server (3s by default) with the `Last-Event-ID` of the last event
+/// seen. The array ends when the server answers 204 No Content. As with
+/// [`WsReader`](crate::ws::WsReader), each element is followed by its `,`.
+pub struct SseReader {
+    url: String,
+    body: Option<BufReader<BodyReader<'static>>>,
+    last_id: Option<String>,
+    retry: Duration,
+    /// data lines of the event being read
+    data: Vec<u8>,
+    line: String,
+    buf: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl SseReader {
+    pub fn connect(url: &str) -> anyhow::Result<Self> {
+        let mut rd = Self {
+            url: url.to_string(),
+            body: None,
+            last_id: None,
+            retry: Duration::from_secs(3),
+            data: vec![],
+            line: String::new(),
+            buf: b"[".to_vec(),
+            pos: 0,
+            done: false,
+        };
+        // errors on the first connection are not worth retrying
+        rd.open()?;
+        Ok(rd)
+    }
+
+    fn open(&mut self) -> anyhow::Result<()> {
+        let mut req = ureq::get(&self.url)
+            .header("Accept", "text/event-stream")
+            .header("Cache-Control", "no-cache");
+        if let Some(id) = &self.last_id {
+            req = req.header("Last-Event-ID", id);
+        }
+        let resp = req.call()?;
+        if resp.status() == 204 {
+            self.done = true;
+            self.buf.push(b']');
+            return Ok(());
+        }
+        self.body = Some(BufReader::new(resp.into_body().into_reader()));
+        Ok(())
+    }
+
+    fn reconnect(&mut self, why: &dyn std::fmt::Display) -> io::Result<()> {
+        self.body = None;
+        self.data.clear();
+        warn!(
+            "event stream interrupted ({why}), reconnecting in {:?}",
+            self.retry
+        );
+        thread::sleep(self.retry);
+        self.open().map_err(io::Error::other)
+    }
+
+    /// Process one line of the stream, per the event stream format.
+    fn process_line(&mut self) {
+        let line = self.line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            // end of an event
+            if !self.data.is_empty() {
+                self.data.pop(); // the separator after the last data line
+                self.buf.append(&mut self.data);
+                self.buf.push(b',');
+            }
+            return;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => {
+                self.data.extend_from_slice(value.as_bytes());
+                // lines are joined by newlines, which in JSON text are only
+                // ever whitespace; a space keeps records on one line
+                self.data.push(b' ');
+            }
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(ms) = value.parse() {
+                    self.retry = Duration::from_millis(ms);
+                }
+            }
+            // comments (empty field name), event types, unknown fields
+            _ => (),
+        }
+    }
+}
+
+impl Read for SseReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() && !self.done {
+            self.buf.clear();
+            self.pos = 0;
+            let Some(body) = &mut self.body else {
+                self.reconnect(&"not connected")?;
+                continue;
+            };
+            self.line.clear();
+            match body.read_line(&mut self.line) {
+                Ok(0) => self.reconnect(&"end of stream")?,
+                Ok(_) => self.process_line(),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => self.reconnect(&e)?,
+            }
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
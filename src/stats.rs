@@ -0,0 +1,182 @@
+use crate::drift::{type_bit, type_names};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// What is known of the values found at a dotted path.
+#[derive(Debug, Clone, Default)]
+struct Field {
+    /// records with the field, null or not
+    present: u64,
+    nulls: u64,
+    types: u8,
+    /// smallest and largest number
+    range: Option<(f64, f64)>,
+    /// shortest and longest string, in characters
+    lengths: Option<(usize, usize)>,
+}
+
+impl Field {
+    fn add(&mut self, value: &Value) {
+        self.present += 1;
+        self.types |= type_bit(value);
+        match value {
+            Value::Null => self.nulls += 1,
+            Value::Number(n) => {
+                // as a double, which is close enough for a range
+                let n = n.as_f64().unwrap_or(f64::NAN);
+                self.range = Some(match self.range {
+                    Some((min, max)) => (min.min(n), max.max(n)),
+                    None => (n, n),
+                });
+            }
+            Value::String(s) => {
+                let len = s.chars().count();
+                self.lengths = Some(match self.lengths {
+                    Some((min, max)) => (min.min(len), max.max(len)),
+                    None => (len, len),
+                });
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Statistics on the fields of the records, for the stats subcommand: how
+/// many records have each field, with which types, and the range of its
+/// numbers and of the lengths of its strings.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    records: u64,
+    /// total and largest size of the records, as JSON text
+    bytes: u64,
+    largest: u64,
+    fields: BTreeMap<String, Field>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `record`, a JSON text. Fields are the members of objects, nested
+    /// ones included, but not those of the objects in arrays.
+    pub fn add(&mut self, record: &[u8]) -> serde_json::Result<()> {
+        let value: Value = serde_json::from_slice(record)?;
+        self.records += 1;
+        self.bytes += record.len() as u64;
+        self.largest = self.largest.max(record.len() as u64);
+        self.add_fields(&mut String::new(), &value);
+        Ok(())
+    }
+
+    fn add_fields(&mut self, prefix: &mut String, value: &Value) {
+        let Value::Object(fields) = value else {
+            return;
+        };
+        for (key, value) in fields {
+            let len = prefix.len();
+            if len > 0 {
+                prefix.push('.');
+            }
+            prefix.push_str(key);
+            self.fields.entry(prefix.clone()).or_default().add(value);
+            self.add_fields(prefix, value);
+            prefix.truncate(len);
+        }
+    }
+
+    /// Write the number and size of the records, and a table of their
+    /// fields in the order of their paths.
+    pub fn write_report(&self, mut w: impl Write) -> io::Result<()> {
+        let average = self.bytes.checked_div(self.records).unwrap_or(0);
+        writeln!(
+            w,
+            "{} records, {} bytes of JSON ({average} on average, {} at most)",
+            self.records, self.bytes, self.largest
+        )?;
+        if self.fields.is_empty() {
+            return Ok(());
+        }
+        let mut rows =
+            vec![["field", "present", "null", "types", "min", "max", "length"].map(String::from)];
+        for (path, field) in &self.fields {
+            let (min, max) = match field.range {
+                Some((min, max)) => (min.to_string(), max.to_string()),
+                None => Default::default(),
+            };
+            rows.push([
+                path.clone(),
+                format!(
+                    "{} ({:.0}%)",
+                    field.present,
+                    100.0 * field.present as f64 / self.records as f64
+                ),
+                field.nulls.to_string(),
+                type_names(field.types),
+                min,
+                max,
+                match field.lengths {
+                    Some((min, max)) if min == max => min.to_string(),
+                    Some((min, max)) => format!("{min}..{max}"),
+                    None => String::new(),
+                },
+            ]);
+        }
+        let mut widths = [0; 7];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        writeln!(w)?;
+        for row in &rows {
+            let mut line = String::new();
+            for (i, (cell, width)) in row.iter().zip(widths).enumerate() {
+                if i > 0 {
+                    line.push_str("  ");
+                }
+                line.push_str(&format!("{cell:width$}"));
+            }
+            writeln!(w, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(records: &[&str]) -> String {
+        let mut stats = Stats::new();
+        for record in records {
+            stats.add(record.as_bytes()).unwrap();
+        }
+        let mut report = vec![];
+        stats.write_report(&mut report).unwrap();
+        String::from_utf8(report).unwrap()
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(
+            report(&[
+                r#"{"a":1,"b":{"c":"héé"}}"#,
+                r#"{"a":-2.5,"b":{"c":"x"},"d":[{"e":1}]}"#,
+                r#"{"a":null,"b":null}"#,
+                "[1]",
+            ]),
+            "4 records, 85 bytes of JSON (21 on average, 38 at most)\n\n\
+             field  present  null  types        min   max  length\n\
+             a      3 (75%)  1     null|number  -2.5  1\n\
+             b      3 (75%)  1     null|object\n\
+             b.c    2 (50%)  0     string                  1..3\n\
+             d      1 (25%)  0     array\n"
+        );
+        assert_eq!(
+            report(&[]),
+            "0 records, 0 bytes of JSON (0 on average, 0 at most)\n"
+        );
+    }
+}
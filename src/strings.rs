@@ -0,0 +1,132 @@
+use crate::template;
+use serde_json::Value;
+
+/// The strings a cleanup applies to.
+#[derive(Debug)]
+enum Scope {
+    /// all those of the records
+    All,
+    /// those in the values of these dotted paths
+    Fields(Vec<Vec<String>>),
+}
+
+impl Scope {
+    fn new(fields: &[String]) -> Self {
+        match fields {
+            [] => Scope::All,
+            fields => Scope::Fields(
+                (fields.iter())
+                    .map(|field| field.split('.').map(String::from).collect())
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Call `f` on each string value in scope of `record`.
+    fn for_each(&self, record: &mut Value, f: &impl Fn(&mut Value)) {
+        match self {
+            Scope::All => strings(record, f),
+            Scope::Fields(paths) => {
+                for path in paths {
+                    if let Some(value) = template::lookup_mut(record, path) {
+                        strings(value, f);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Call `f` on each string value in `v`, in nested arrays and objects too.
+fn strings(v: &mut Value, f: &impl Fn(&mut Value)) {
+    match v {
+        Value::String(_) => f(v),
+        Value::Array(items) => items.iter_mut().for_each(|v| strings(v, f)),
+        Value::Object(obj) => obj.values_mut().for_each(|v| strings(v, f)),
+        _ => (),
+    }
+}
+
+/// Cleans up the string values of the records, all of them or those of some
+/// fields: for --trim-strings and --empty-as-null.
+#[derive(Debug)]
+pub struct Cleanup {
+    trim: Option<Scope>,
+    empty_as_null: Option<Scope>,
+}
+
+impl Cleanup {
+    /// Strip whitespace around the strings of the `trim` fields, and then
+    /// turn the empty strings of the `empty_as_null` fields into null: dotted
+    /// paths into the records like those of a template, or all of them if
+    /// empty.
+    pub fn new(trim: Option<&[String]>, empty_as_null: Option<&[String]>) -> Self {
+        Self {
+            trim: trim.map(Scope::new),
+            empty_as_null: empty_as_null.map(Scope::new),
+        }
+    }
+
+    /// Clean up `record`, in place.
+    pub fn apply(&self, record: &mut Value) {
+        if let Some(scope) = &self.trim {
+            scope.for_each(record, &|v| {
+                if let Value::String(s) = v {
+                    let trimmed = s.trim();
+                    if trimmed.len() < s.len() {
+                        *s = trimmed.to_string();
+                    }
+                }
+            });
+        }
+        if let Some(scope) = &self.empty_as_null {
+            scope.for_each(record, &|v| {
+                if v.as_str() == Some("") {
+                    *v = Value::Null;
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fields(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|field| field.to_string()).collect()
+    }
+
+    #[test]
+    fn trim() {
+        let record = json!({"a": " x\t", "b": {"c": ["\n y ", 1, " "]}, "d": "z "});
+        let mut all = record.clone();
+        Cleanup::new(Some(&[]), None).apply(&mut all);
+        assert_eq!(all, json!({"a": "x", "b": {"c": ["y", 1, ""]}, "d": "z"}));
+        let mut some = record.clone();
+        Cleanup::new(Some(&fields(&["b", "e"])), None).apply(&mut some);
+        assert_eq!(
+            some,
+            json!({"a": " x\t", "b": {"c": ["y", 1, ""]}, "d": "z "})
+        );
+    }
+
+    #[test]
+    fn empty_as_null() {
+        let record = json!({"a": "", "b": {"c": ["", " ", 0]}, "d": "  "});
+        let mut all = record.clone();
+        Cleanup::new(None, Some(&[])).apply(&mut all);
+        assert_eq!(
+            all,
+            json!({"a": null, "b": {"c": [null, " ", 0]}, "d": "  "})
+        );
+        // after trimming, blank strings are empty too
+        let mut trimmed = record.clone();
+        Cleanup::new(Some(&[]), Some(&fields(&["b.c", "d"]))).apply(&mut trimmed);
+        assert_eq!(
+            trimmed,
+            json!({"a": "", "b": {"c": [null, null, 0]}, "d": null})
+        );
+    }
+}
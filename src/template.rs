@@ -0,0 +1,193 @@
+use crate::framing;
+use serde_json::Value;
+
+/// How a placeholder writes its value.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Modifier {
+    /// Strings as they are, anything else as JSON, like `jq -r`
+    Raw,
+    /// The value as JSON, strings quoted and escaped
+    Json,
+    /// Like `Json`, but without the quotes of strings, for a template that
+    /// quotes them itself
+    Escape,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Text(Vec<u8>),
+    Field(Vec<String>, Modifier),
+}
+
+/// A line format for --template, e.g. `{id}\t{user.name|json}`.
+///
+/// Placeholders are dotted paths into the record, array indices included
+/// (`{items.0.sku}`), with an optional modifier: `|json` for the value as
+/// JSON, or `|escape` for the same without the quotes of a string. Without
+/// one, strings are written as they are and other values as JSON; a missing
+/// field writes nothing. `{{` and `}}` are literal braces, and the text
+/// between placeholders may use the escapes of --record-sep.
+#[derive(Debug, Clone)]
+pub struct Template(Vec<Part>);
+
+impl Template {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut rest = s;
+        while let Some(i) = rest.find(['{', '}']) {
+            text.push_str(&rest[..i]);
+            let (brace, after) = (&rest[i..i + 1], &rest[i + 1..]);
+            if after.starts_with(brace) {
+                text.push_str(brace);
+                rest = &after[1..];
+                continue;
+            }
+            if brace == "}" {
+                return Err(format!("unmatched '}}' in {s:?} (use '}}}}' for a brace)"));
+            }
+            let Some(end) = after.find('}') else {
+                return Err(format!("unclosed '{{' in {s:?}"));
+            };
+            let (path, modifier) = match after[..end].split_once('|') {
+                None => (&after[..end], Modifier::Raw),
+                Some((path, "json")) => (path, Modifier::Json),
+                Some((path, "escape")) => (path, Modifier::Escape),
+                Some((_, m)) => return Err(format!("unknown modifier '{m}' in {s:?}")),
+            };
+            let path = path.trim();
+            if path.is_empty() {
+                return Err(format!("empty placeholder in {s:?}"));
+            }
+            if !text.is_empty() {
+                parts.push(Part::Text(framing::unescape(&std::mem::take(&mut text))?));
+            }
+            parts.push(Part::Field(
+                path.split('.').map(String::from).collect(),
+                modifier,
+            ));
+            rest = &after[end + 1..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(framing::unescape(&text)?));
+        }
+        Ok(Self(parts))
+    }
+
+    /// Append `record` rendered through the template to `out`.
+    pub fn render(&self, record: &Value, out: &mut Vec<u8>) -> serde_json::Result<()> {
+        for part in &self.0 {
+            match part {
+                Part::Text(text) => out.extend_from_slice(text),
+                Part::Field(path, modifier) => {
+                    let Some(value) = lookup(record, path) else {
+                        continue;
+                    };
+                    match (value, modifier) {
+                        (Value::String(s), Modifier::Raw) => out.extend_from_slice(s.as_bytes()),
+                        (Value::String(_), Modifier::Escape) => {
+                            let start = out.len();
+                            serde_json::to_writer(&mut *out, value)?;
+                            // drop the quotes
+                            out.pop();
+                            out.remove(start);
+                        }
+                        _ => serde_json::to_writer(&mut *out, value)?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The value at `path` in `value`, if any.
+pub(crate) fn lookup<'a>(value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, key| match value {
+        Value::Object(entries) => entries.get(key),
+        Value::Array(values) => values.get(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Like [`lookup`], for changing the value.
+pub(crate) fn lookup_mut<'a>(value: &'a mut Value, path: &[String]) -> Option<&'a mut Value> {
+    path.iter().try_fold(value, |value, key| match value {
+        Value::Object(entries) => entries.get_mut(key),
+        Value::Array(values) => values.get_mut(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Remove the value at `path` in `value`, if any. An element of an array
+/// becomes null instead, as it can't go without moving the others.
+pub(crate) fn remove(value: &mut Value, path: &[String]) {
+    let Some((last, parent)) = path.split_last() else {
+        return;
+    };
+    match lookup_mut(value, parent) {
+        Some(Value::Object(entries)) => {
+            entries.shift_remove(last);
+        }
+        Some(Value::Array(values)) => {
+            if let Ok(i) = last.parse::<usize>()
+                && let Some(value) = values.get_mut(i)
+            {
+                *value = Value::Null;
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, record: &Value) -> String {
+        let mut out = vec![];
+        Template::parse(template)
+            .unwrap()
+            .render(record, &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn placeholders() {
+        let record = json!({"id": 1, "user": {"name": "a\"b"}, "items": [{"sku": "x"}]});
+        assert_eq!(
+            render(
+                r"{id}\t{user.name}|{user.name|json}|{user.name|escape}",
+                &record
+            ),
+            "1\ta\"b|\"a\\\"b\"|a\\\"b"
+        );
+        assert_eq!(
+            render("{{{items.0.sku}}} {items.1.sku}{missing}.", &record),
+            "{x} ."
+        );
+        assert_eq!(render("{ user }", &record), r#"{"name":"a\"b"}"#);
+    }
+
+    #[test]
+    fn errors() {
+        for template in ["a}", "{a", "{}", "{a|yaml}"] {
+            assert!(Template::parse(template).is_err(), "{template}");
+        }
+    }
+
+    #[test]
+    fn paths() {
+        let mut record = json!({"a": {"b": 1}, "c": [1, 2]});
+        let path = |s: &str| s.split('.').map(String::from).collect::<Vec<_>>();
+        assert_eq!(lookup(&record, &path("a.b")), Some(&json!(1)));
+        assert_eq!(lookup(&record, &path("c.x")), None);
+        *lookup_mut(&mut record, &path("c.1")).unwrap() = json!(3);
+        remove(&mut record, &path("c.0"));
+        remove(&mut record, &path("a.b"));
+        assert_eq!(record, json!({"a": {}, "c": [null, 3]}));
+    }
+}
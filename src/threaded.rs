@@ -0,0 +1,183 @@
+use std::io::{self, Write};
+use std::mem;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+enum Request {
+    Write(Vec<u8>),
+    Flush,
+}
+
+enum Reply {
+    /// a buffer that has been written out, to fill again
+    Written(Vec<u8>),
+    Flushed,
+}
+
+/// Writes to another writer from a thread of its own: the caller fills a
+/// buffer while the previous one is being written, and only waits when it
+/// gets a buffer ahead of the thread, or flushes.
+///
+/// An error of the thread is returned by the next write or flush.
+pub struct ThreadedWriter {
+    buf: Vec<u8>,
+    capacity: usize,
+    spare: Vec<Vec<u8>>,
+    tx: Option<SyncSender<Request>>,
+    rx: Receiver<Reply>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl ThreadedWriter {
+    /// Write to `w` in chunks of about `capacity` bytes.
+    pub fn new<W: Write + Send + 'static>(mut w: W, capacity: usize) -> Self {
+        let (tx, requests) = mpsc::sync_channel(1);
+        let (replies, rx) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            for req in requests {
+                match req {
+                    Request::Write(mut buf) => {
+                        w.write_all(&buf)?;
+                        buf.clear();
+                        let _ = replies.send(Reply::Written(buf));
+                    }
+                    Request::Flush => {
+                        w.flush()?;
+                        let _ = replies.send(Reply::Flushed);
+                    }
+                }
+            }
+            w.flush()
+        });
+        Self {
+            buf: Vec::with_capacity(capacity),
+            capacity,
+            spare: vec![],
+            tx: Some(tx),
+            rx,
+            thread: Some(thread),
+        }
+    }
+
+    /// Why the thread isn't there to take requests anymore.
+    fn stopped(&mut self) -> io::Error {
+        self.tx = None;
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(Err(e))) => e,
+            _ => io::Error::other("the output thread stopped"),
+        }
+    }
+
+    fn send(&mut self, req: Request) -> io::Result<()> {
+        match &self.tx {
+            Some(tx) if tx.send(req).is_ok() => Ok(()),
+            _ => Err(self.stopped()),
+        }
+    }
+
+    /// Hand the buffer over to the thread, going on with a spare one.
+    fn send_buf(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        while let Ok(Reply::Written(buf)) = self.rx.try_recv() {
+            self.spare.push(buf);
+        }
+        let next = self
+            .spare
+            .pop()
+            .unwrap_or_else(|| Vec::with_capacity(self.capacity));
+        let buf = mem::replace(&mut self.buf, next);
+        self.send(Request::Write(buf))
+    }
+}
+
+impl Write for ThreadedWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.buf.len() + data.len() > self.capacity {
+            self.send_buf()?;
+        }
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buf()?;
+        self.send(Request::Flush)?;
+        loop {
+            match self.rx.recv() {
+                Ok(Reply::Written(buf)) => self.spare.push(buf),
+                Ok(Reply::Flushed) => return Ok(()),
+                Err(_) => return Err(self.stopped()),
+            }
+        }
+    }
+}
+
+impl Drop for ThreadedWriter {
+    fn drop(&mut self) {
+        let _ = self.send_buf();
+        self.tx = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Records the writes it gets, failing past `fail_after` bytes.
+    #[derive(Clone)]
+    struct Recorder {
+        writes: Arc<Mutex<Vec<Vec<u8>>>>,
+        fail_after: usize,
+    }
+
+    impl Write for Recorder {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            let mut writes = self.writes.lock().unwrap();
+            if writes.iter().map(Vec::len).sum::<usize>() + data.len() > self.fail_after {
+                return Err(io::Error::other("disk full"));
+            }
+            writes.push(data.to_vec());
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn chunks() {
+        let rec = Recorder {
+            writes: Arc::default(),
+            fail_after: usize::MAX,
+        };
+        let mut w = ThreadedWriter::new(rec.clone(), 10);
+        for i in 0..20 {
+            write!(w, "{i},").unwrap();
+        }
+        w.flush().unwrap();
+        let writes = rec.writes.lock().unwrap().clone();
+        assert!(writes.iter().all(|write| write.len() <= 10));
+        let all: String = writes.iter().map(|w| String::from_utf8_lossy(w)).collect();
+        assert_eq!(all, (0..20).map(|i| format!("{i},")).collect::<String>());
+    }
+
+    #[test]
+    fn errors() {
+        let rec = Recorder {
+            writes: Arc::default(),
+            fail_after: 25,
+        };
+        let mut w = ThreadedWriter::new(rec, 10);
+        let res = (0..100)
+            .try_for_each(|_| w.write_all(b"abcd"))
+            .and_then(|()| w.flush());
+        assert_eq!(res.unwrap_err().to_string(), "disk full");
+    }
+}
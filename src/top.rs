@@ -0,0 +1,249 @@
+use crate::spill::TempFile;
+use crate::template;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use tracing::debug;
+
+/// Where a kept record is.
+enum Stored {
+    Memory(Vec<u8>),
+    /// its offset and length in the spill file
+    Spilled(u64, usize),
+}
+
+/// The file that [`TopK`] moves its records to past its budget, appending
+/// those kept later to it.
+struct Spill {
+    file: TempFile,
+    writer: BufWriter<File>,
+    len: u64,
+}
+
+impl Spill {
+    fn new() -> io::Result<Self> {
+        let file = TempFile::new()?;
+        let writer = BufWriter::new(file.file().try_clone()?);
+        Ok(Self {
+            file,
+            writer,
+            len: 0,
+        })
+    }
+
+    fn append(&mut self, record: &[u8]) -> io::Result<Stored> {
+        self.writer.write_all(record)?;
+        let stored = Stored::Spilled(self.len, record.len());
+        self.len += record.len() as u64;
+        Ok(stored)
+    }
+
+    fn read(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut file = self.file.file();
+        file.seek(SeekFrom::Start(offset))?;
+        let mut record = vec![0; len];
+        file.read_exact(&mut record)?;
+        Ok(record)
+    }
+}
+
+/// A record kept by [`TopK`], ordered worst first, the later of two with
+/// the same value being the worse.
+struct Entry {
+    value: f64,
+    smallest: bool,
+    /// the number of the record among those added
+    seq: u64,
+    record: Stored,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let value = match self.smallest {
+            false => other.value.total_cmp(&self.value),
+            true => self.value.total_cmp(&other.value),
+        };
+        value.then(self.seq.cmp(&other.seq))
+    }
+}
+
+/// The `k` records with the largest (or smallest) values of a numeric
+/// field, out of those added, holding no more than them in memory: past a
+/// budget, the records themselves are moved to a temporary file.
+pub struct TopK {
+    path: Vec<String>,
+    k: usize,
+    smallest: bool,
+    /// worst of the kept records on top
+    heap: BinaryHeap<Entry>,
+    added: u64,
+    /// bytes of the records kept in memory
+    bytes: usize,
+    max_bytes: usize,
+    spill: Option<Spill>,
+    /// records without a number in the field
+    pub missing: u64,
+}
+
+impl TopK {
+    /// Keep the top `k` records by `field`, a dotted path into the records
+    /// like those of a template.
+    pub fn new(field: &str, k: usize, smallest: bool) -> Self {
+        Self {
+            path: field.split('.').map(String::from).collect(),
+            k,
+            smallest,
+            heap: BinaryHeap::with_capacity(k + 1),
+            added: 0,
+            bytes: 0,
+            max_bytes: usize::MAX,
+            spill: None,
+            missing: 0,
+        }
+    }
+
+    /// Keep about `max_bytes` of records in memory, spilling to disk past
+    /// them.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Consider `record`, a JSON text, keeping it if its value is among the
+    /// top ones so far. Records without a number in the field are left out.
+    pub fn add(&mut self, record: &[u8]) -> io::Result<()> {
+        let value = serde_json::from_slice::<Value>(record)
+            .ok()
+            .and_then(|value| template::lookup(&value, &self.path)?.as_f64());
+        let Some(value) = value else {
+            self.missing += 1;
+            return Ok(());
+        };
+        let entry = Entry {
+            value,
+            smallest: self.smallest,
+            seq: self.added,
+            record: Stored::Memory(vec![]),
+        };
+        self.added += 1;
+        if self.heap.len() == self.k {
+            match self.heap.peek() {
+                Some(worst) if entry < *worst => {
+                    if let Some(Entry {
+                        record: Stored::Memory(worst),
+                        ..
+                    }) = self.heap.pop()
+                    {
+                        self.bytes -= worst.len();
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+        let record = match &mut self.spill {
+            Some(spill) => spill.append(record)?,
+            None => {
+                self.bytes += record.len();
+                Stored::Memory(record.to_vec())
+            }
+        };
+        self.heap.push(Entry { record, ..entry });
+        if self.bytes > self.max_bytes {
+            self.move_to_disk()?;
+        }
+        Ok(())
+    }
+
+    fn move_to_disk(&mut self) -> io::Result<()> {
+        debug!(
+            records = self.heap.len(),
+            "spilled the records of --top to disk"
+        );
+        let mut spill = Spill::new()?;
+        let mut entries = std::mem::take(&mut self.heap).into_vec();
+        for entry in &mut entries {
+            if let Stored::Memory(record) = &entry.record {
+                entry.record = spill.append(record)?;
+            }
+        }
+        self.heap = entries.into();
+        self.bytes = 0;
+        self.spill = Some(spill);
+        Ok(())
+    }
+
+    /// The records kept, best first, ties in the order they were added.
+    pub fn into_records(mut self) -> io::Result<impl Iterator<Item = io::Result<Vec<u8>>>> {
+        if let Some(spill) = &mut self.spill {
+            spill.writer.flush()?;
+        }
+        let spill = self.spill;
+        Ok(
+            (self.heap.into_sorted_vec().into_iter()).map(move |entry| match entry.record {
+                Stored::Memory(record) => Ok(record),
+                Stored::Spilled(offset, len) => spill.as_ref().unwrap().read(offset, len),
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn top(k: usize, smallest: bool, max_bytes: usize) -> Vec<String> {
+        let mut top = TopK::new("n", k, smallest).max_bytes(max_bytes);
+        for i in 0..200 {
+            let record = format!(r#"{{"n":{},"i":{i}}}"#, (i * 37) % 50);
+            top.add(record.as_bytes()).unwrap();
+        }
+        top.add(br#"{"i":"no n"}"#).unwrap();
+        assert_eq!(top.missing, 1);
+        assert_eq!(top.spill.is_some(), max_bytes < usize::MAX);
+        (top.into_records().unwrap())
+            .map(|record| String::from_utf8(record.unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn largest() {
+        assert_eq!(
+            top(3, false, usize::MAX),
+            [
+                r#"{"n":49,"i":27}"#,
+                r#"{"n":49,"i":77}"#,
+                r#"{"n":49,"i":127}"#
+            ]
+        );
+    }
+
+    #[test]
+    fn smallest() {
+        assert_eq!(
+            top(2, true, usize::MAX),
+            [r#"{"n":0,"i":0}"#, r#"{"n":0,"i":50}"#]
+        );
+    }
+
+    #[test]
+    fn spilled() {
+        assert_eq!(top(20, false, 100), top(20, false, usize::MAX));
+        assert_eq!(top(20, true, 100), top(20, true, usize::MAX));
+    }
+}
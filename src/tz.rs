@@ -0,0 +1,381 @@
+use crate::datetime::{DateTime, civil_from_days, days_from_civil, days_in_month};
+use anyhow::{Context, bail};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// When daylight saving time starts or ends: on `day` (0 for Sunday) of the
+/// `week`th week of `month`, the 5th being the last, `time` seconds after
+/// midnight, local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Change {
+    month: u32,
+    week: u32,
+    day: u32,
+    time: i64,
+}
+
+impl Change {
+    /// The local time of the change in `year`, in seconds since the epoch.
+    fn local(&self, year: i64) -> i64 {
+        let first = days_from_civil(year, self.month, 1);
+        let weekday = (first + 4).rem_euclid(7) as u32;
+        let mut day = 1 + (self.day + 7 - weekday) % 7 + (self.week - 1) * 7;
+        while day > days_in_month(year, self.month) {
+            day -= 7;
+        }
+        (first + day as i64 - 1) * 86400 + self.time
+    }
+}
+
+/// The rule of a POSIX `TZ` string, such as `CET-1CEST,M3.5.0,M10.5.0/3`,
+/// for the times past the transitions of a zone file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rule {
+    std: i32,
+    /// the offset in daylight saving time, and when it starts and ends
+    dst: Option<(i32, Change, Change)>,
+}
+
+impl Rule {
+    fn parse(s: &str) -> Option<Self> {
+        let mut s = s.as_bytes();
+        name(&mut s)?;
+        // POSIX offsets are west of UTC
+        let std = -hms(&mut s)? as i32;
+        if s.is_empty() {
+            return Some(Self { std, dst: None });
+        }
+        name(&mut s)?;
+        let dst = match s.first() {
+            Some(b',') => std + 3600,
+            _ => -hms(&mut s)? as i32,
+        };
+        let mut change = || {
+            s = s.strip_prefix(b",")?.strip_prefix(b"M")?;
+            let mut fields = [0; 3];
+            for (i, field) in fields.iter_mut().enumerate() {
+                if i > 0 {
+                    s = s.strip_prefix(b".")?;
+                }
+                *field = digits(&mut s)?;
+            }
+            let time = match s.strip_prefix(b"/") {
+                Some(rest) => {
+                    s = rest;
+                    hms(&mut s)?
+                }
+                None => 7200,
+            };
+            let [month, week, day] = fields;
+            let valid = (1..=12).contains(&month) && (1..=5).contains(&week) && day < 7;
+            valid.then_some(Change {
+                month,
+                week,
+                day,
+                time,
+            })
+        };
+        let (start, end) = (change()?, change()?);
+        s.is_empty().then_some(Self {
+            std,
+            dst: Some((dst, start, end)),
+        })
+    }
+
+    fn offset_at(&self, unix: i64) -> i32 {
+        let Some((dst, start, end)) = self.dst else {
+            return self.std;
+        };
+        let (year, _, _) = civil_from_days((unix + self.std as i64).div_euclid(86400));
+        let start = start.local(year) - self.std as i64;
+        let end = end.local(year) - dst as i64;
+        let in_dst = if start < end {
+            (start..end).contains(&unix)
+        } else {
+            // in the southern hemisphere, summer spans the new year
+            !(end..start).contains(&unix)
+        };
+        if in_dst { dst } else { self.std }
+    }
+}
+
+/// The name of a zone in a `TZ` string, `CET` or `<+03>`.
+fn name(s: &mut &[u8]) -> Option<()> {
+    let len = match s.first()? {
+        b'<' => s.iter().position(|&c| c == b'>')? + 1,
+        _ => s.iter().take_while(|c| c.is_ascii_alphabetic()).count(),
+    };
+    (len >= 3).then(|| *s = &s[len..])
+}
+
+fn digits(s: &mut &[u8]) -> Option<u32> {
+    let len = s.iter().take_while(|c| c.is_ascii_digit()).count();
+    let n = std::str::from_utf8(&s[..len]).ok()?.parse().ok()?;
+    *s = &s[len..];
+    Some(n)
+}
+
+/// `[+-]hh[:mm[:ss]]` in seconds.
+fn hms(s: &mut &[u8]) -> Option<i64> {
+    let sign = match s.first() {
+        Some(b'-') => -1,
+        _ => 1,
+    };
+    if matches!(s.first(), Some(b'+' | b'-')) {
+        *s = &s[1..];
+    }
+    let mut secs = digits(s)? as i64 * 3600;
+    for unit in [60, 1] {
+        match s.strip_prefix(b":") {
+            Some(rest) => {
+                *s = rest;
+                secs += digits(s)? as i64 * unit;
+            }
+            None => break,
+        }
+    }
+    Some(sign * secs)
+}
+
+/// A time zone: its offsets from UTC over time, from the tz database.
+#[derive(Debug, Clone)]
+pub struct Zone {
+    /// times at which the offset changes, with the new offset
+    transitions: Vec<(i64, i32)>,
+    /// the offset before the first transition
+    initial: i32,
+    /// for the times after the last transition
+    rule: Option<Rule>,
+}
+
+impl Zone {
+    /// The zone named `name`: `UTC`, a fixed offset such as `+05:30`, or a
+    /// zone of the tz database such as `Europe/Paris`, read from `$TZDIR`
+    /// or else /usr/share/zoneinfo.
+    pub fn load(name: &str) -> anyhow::Result<Self> {
+        let fixed = |offset| Self {
+            transitions: vec![],
+            initial: offset,
+            rule: None,
+        };
+        if name.eq_ignore_ascii_case("utc") || name == "Z" {
+            return Ok(fixed(0));
+        }
+        if let Some(time) = DateTime::parse_rfc3339(&format!("1970-01-01T00:00:00{name}"))
+            && let Some(offset) = time.offset
+        {
+            return Ok(fixed(offset));
+        }
+        if name.starts_with('/') || name.split('/').any(|part| part == "..") {
+            bail!("invalid time zone {name}");
+        }
+        let dir = env::var_os("TZDIR").map_or_else(|| "/usr/share/zoneinfo".into(), PathBuf::from);
+        let path = dir.join(name);
+        let data = fs::read(&path)
+            .with_context(|| format!("unknown time zone {name} ({})", path.display()))?;
+        Self::parse(&data).with_context(|| format!("reading {}", path.display()))
+    }
+
+    /// Parse a TZif file, as in RFC 8536.
+    fn parse(data: &[u8]) -> anyhow::Result<Self> {
+        let invalid = || anyhow::anyhow!("not a TZif file");
+        let u32_at = |i: usize| -> anyhow::Result<usize> {
+            let bytes = data.get(i..i + 4).ok_or_else(invalid)?;
+            Ok(u32::from_be_bytes(bytes.try_into().unwrap()) as usize)
+        };
+        if !data.starts_with(b"TZif") {
+            return Err(invalid());
+        }
+        // the counts of a header: isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt
+        let counts = |start: usize| -> anyhow::Result<[usize; 6]> {
+            let mut counts = [0; 6];
+            for (i, count) in counts.iter_mut().enumerate() {
+                *count = u32_at(start + 20 + 4 * i)?;
+            }
+            Ok(counts)
+        };
+        let [isut, isstd, leap, time, types, chars] = counts(0)?;
+        let v1_len = 44 + time * 5 + types * 6 + chars + leap * 8 + isstd + isut;
+        let (start, time_size) = match data.get(4) {
+            // version 2 and later repeat the data with 64-bit times
+            Some(b'2'..) => (v1_len, 8),
+            _ => (0, 4),
+        };
+        let [isut, isstd, leap, time, types, chars] = counts(start)?;
+        let mut pos = start + 44;
+        let mut take = |len: usize| -> anyhow::Result<&[u8]> {
+            let bytes = data.get(pos..pos + len).ok_or_else(invalid)?;
+            pos += len;
+            Ok(bytes)
+        };
+        let times: Vec<i64> = (take(time * time_size)?.chunks(time_size))
+            .map(|b| match time_size {
+                8 => i64::from_be_bytes(b.try_into().unwrap()),
+                _ => i32::from_be_bytes(b.try_into().unwrap()) as i64,
+            })
+            .collect();
+        let indexes = take(time)?.to_vec();
+        let offsets: Vec<i32> = (take(types * 6)?.chunks(6))
+            .map(|b| i32::from_be_bytes(b[..4].try_into().unwrap()))
+            .collect();
+        if offsets.is_empty() || indexes.iter().any(|&i| i as usize >= offsets.len()) {
+            return Err(invalid());
+        }
+        take(chars + leap * (time_size + 4) + isstd + isut)?;
+        let rule = match time_size {
+            8 => {
+                let footer = &data[pos..];
+                let footer = footer.strip_prefix(b"\n").unwrap_or(footer);
+                let end = footer
+                    .iter()
+                    .position(|&c| c == b'\n')
+                    .unwrap_or(footer.len());
+                std::str::from_utf8(&footer[..end])
+                    .ok()
+                    .and_then(Rule::parse)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            transitions: (times.into_iter())
+                .zip(indexes.iter().map(|&i| offsets[i as usize]))
+                .collect(),
+            initial: offsets[0],
+            rule,
+        })
+    }
+
+    /// The offset from UTC at `unix` seconds since the epoch.
+    pub fn offset_at(&self, unix: i64) -> i32 {
+        match self.transitions.partition_point(|&(t, _)| t <= unix) {
+            0 => self.initial,
+            i if i == self.transitions.len() && self.rule.is_some() => {
+                self.rule.unwrap().offset_at(unix)
+            }
+            i => self.transitions[i - 1].1,
+        }
+    }
+
+    /// Seconds since the epoch of the wall-clock time `local`, in this zone.
+    /// A time that occurs twice, as the clocks go back, is the first of the
+    /// two, and one that doesn't occur, as they go forward, is read with the
+    /// offset from before.
+    pub fn local_to_unix(&self, local: i64) -> i64 {
+        let before = self.offset_at(local - 86400) as i64;
+        let after = self.offset_at(local + 86400) as i64;
+        [before, after]
+            .into_iter()
+            .find(|&offset| self.offset_at(local - offset) as i64 == offset)
+            .map_or(local - before, |offset| local - offset)
+    }
+
+    /// `time` in this zone, taking a naive time to be in `from`.
+    pub fn convert(&self, time: DateTime, from: &Zone) -> DateTime {
+        let unix = match time.offset {
+            Some(offset) => time.local - offset as i64,
+            None => from.local_to_unix(time.local),
+        };
+        let offset = self.offset_at(unix);
+        DateTime {
+            local: unix + offset as i64,
+            nanos: time.nanos,
+            offset: Some(offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A TZif file of version 2 for Central European Time, with one
+    /// transition and the rule of the zone past it.
+    fn cet() -> Vec<u8> {
+        let header = |time: u32, types: u32, chars: u32| {
+            let mut header = b"TZif2".to_vec();
+            header.extend([0; 15]);
+            for count in [0, 0, 0, time, types, chars] {
+                header.extend(count.to_be_bytes());
+            }
+            header
+        };
+        let mut data = header(0, 1, 4);
+        data.extend(b"\0\0\x0E\x10\0\0CET\0");
+        data.extend(header(1, 2, 9));
+        data.extend(0i64.to_be_bytes());
+        data.push(0);
+        data.extend(b"\0\0\x0E\x10\0\0\0\0\x1C\x20\x01\x04CET\0CEST\0");
+        data.extend(b"\nCET-1CEST,M3.5.0,M10.5.0/3\n");
+        data
+    }
+
+    fn local(s: &str) -> i64 {
+        DateTime::parse_rfc3339(s).unwrap().local
+    }
+
+    #[test]
+    fn rules() {
+        let rule = Rule::parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        let dst = Change {
+            month: 3,
+            week: 5,
+            day: 0,
+            time: 7200,
+        };
+        assert_eq!(rule.std, 3600);
+        assert_eq!(rule.dst.unwrap().0, 7200);
+        assert_eq!(rule.dst.unwrap().1, dst);
+        assert_eq!(dst.local(2024), local("2024-03-31T02:00:00"));
+        // the southern hemisphere, summer over the new year
+        let rule = Rule::parse("<+13>-13<+14>,M9.5.0,M4.1.0/4").unwrap();
+        assert_eq!(rule.offset_at(local("2024-01-01T00:00:00")), 14 * 3600);
+        assert_eq!(rule.offset_at(local("2024-06-01T00:00:00")), 13 * 3600);
+        assert_eq!(Rule::parse("EST5").unwrap().std, -5 * 3600);
+        for s in [
+            "E5",
+            "CET-1CEST,M3.5.0",
+            "CET-1CEST,M13.5.0,M10.5.0",
+            "CET-1CEST,J60,J300",
+        ] {
+            assert_eq!(Rule::parse(s), None, "{s}");
+        }
+    }
+
+    #[test]
+    fn zones() {
+        let paris = Zone::parse(&cet()).unwrap();
+        let utc = Zone::load("UTC").unwrap();
+        let convert = |s: &str, to: &Zone, from: &Zone| {
+            to.convert(DateTime::parse_rfc3339(s).unwrap(), from)
+                .to_rfc3339()
+        };
+        for (s, converted) in [
+            ("2024-01-15T12:00:00Z", "2024-01-15T13:00:00+01:00"),
+            ("2024-07-15T12:00:00Z", "2024-07-15T14:00:00+02:00"),
+            ("2024-03-31T00:59:59Z", "2024-03-31T01:59:59+01:00"),
+            ("2024-03-31T01:00:00Z", "2024-03-31T03:00:00+02:00"),
+        ] {
+            assert_eq!(convert(s, &paris, &utc), converted, "{s}");
+        }
+        // naive times in Paris, the first of those that repeat as the
+        // clocks go back, and those skipped as they go forward read with
+        // the offset from before
+        for (s, converted) in [
+            ("2024-07-15T14:00:00", "2024-07-15T12:00:00Z"),
+            ("2024-10-27T02:30:00", "2024-10-27T00:30:00Z"),
+            ("2024-03-31T02:30:00", "2024-03-31T01:30:00Z"),
+        ] {
+            assert_eq!(convert(s, &utc, &paris), converted, "{s}");
+        }
+        let india = Zone::load("+05:30").unwrap();
+        assert_eq!(
+            convert("2024-01-01T00:00:00", &utc, &india),
+            "2023-12-31T18:30:00Z"
+        );
+        assert!(Zone::parse(b"TZif2").is_err());
+        assert!(Zone::parse(&cet()[..60]).is_err());
+        assert!(Zone::load("../etc/passwd").is_err());
+        assert!(Zone::load("Nowhere/Nothing").is_err());
+    }
+}
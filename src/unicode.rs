@@ -0,0 +1,122 @@
+use crate::template;
+use serde_json::Value;
+use unicode_normalization::UnicodeNormalization;
+
+/// A Unicode normalization form, for --unicode-normalize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Form {
+    /// Canonical composition: "é" written as `e` and a combining accent becomes a single `é`
+    Nfc,
+    /// Compatibility composition: also folds variants such as "ﬁ" into "fi" and full-width
+    /// "Ａ" into "A"
+    Nfkc,
+}
+
+impl Form {
+    /// `s` in this form, if it isn't already.
+    fn normalize(self, s: &str) -> Option<String> {
+        let normalized = match self {
+            Form::Nfc if unicode_normalization::is_nfc(s) => return None,
+            Form::Nfkc if unicode_normalization::is_nfkc(s) => return None,
+            Form::Nfc => s.nfc().collect(),
+            Form::Nfkc => s.nfkc().collect(),
+        };
+        Some(normalized)
+    }
+}
+
+/// Normalizes the strings of the records to a Unicode normalization form, so
+/// that text that looks the same compares equal.
+#[derive(Debug)]
+pub struct Normalizer {
+    form: Form,
+    /// dotted paths of the fields to normalize, all of the record if empty
+    paths: Vec<Vec<String>>,
+}
+
+impl Normalizer {
+    /// Normalize the values of `fields`, dotted paths into the records like
+    /// those of a template, or the whole records (keys included) if there are
+    /// none.
+    pub fn new(form: Form, fields: &[String]) -> Self {
+        Self {
+            form,
+            paths: (fields.iter())
+                .map(|field| field.split('.').map(String::from).collect())
+                .collect(),
+        }
+    }
+
+    /// Normalize the strings of `record`, in place.
+    pub fn apply(&self, record: &mut Value) {
+        if self.paths.is_empty() {
+            return normalize(record, self.form);
+        }
+        for path in &self.paths {
+            if let Some(value) = template::lookup_mut(record, path) {
+                normalize(value, self.form);
+            }
+        }
+    }
+}
+
+/// Normalize the strings in `v`, and the keys of its objects (nested ones
+/// included). Of keys that become the same, the last one is kept, as for
+/// duplicate keys in the input.
+pub fn normalize(v: &mut Value, form: Form) {
+    match v {
+        Value::String(s) => {
+            if let Some(normalized) = form.normalize(s) {
+                *s = normalized;
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| normalize(v, form)),
+        Value::Object(obj) => {
+            obj.values_mut().for_each(|v| normalize(v, form));
+            if obj.keys().all(|key| form.normalize(key).is_none()) {
+                return;
+            }
+            for (key, value) in std::mem::take(obj) {
+                obj.insert(form.normalize(&key).unwrap_or(key), value);
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn forms() {
+        let mut record = json!({"e\u{301}": ["cafe\u{301}", "\u{fb01}ve", 1], "\u{ff21}": "x"});
+        normalize(&mut record, Form::Nfc);
+        assert_eq!(
+            record,
+            json!({"\u{e9}": ["caf\u{e9}", "\u{fb01}ve", 1], "\u{ff21}": "x"})
+        );
+        normalize(&mut record, Form::Nfkc);
+        assert_eq!(
+            record,
+            json!({"\u{e9}": ["caf\u{e9}", "five", 1], "A": "x"})
+        );
+        // keys that become the same keep the last value
+        let mut record = json!({"\u{e9}": 1, "e\u{301}": 2});
+        normalize(&mut record, Form::Nfc);
+        assert_eq!(record, json!({"\u{e9}": 2}));
+    }
+
+    #[test]
+    fn fields() {
+        let normalizer = Normalizer::new(Form::Nfc, &["a.b".to_string(), "c".to_string()]);
+        let mut record =
+            json!({"a": {"b": "e\u{301}"}, "c": {"e\u{301}": "e\u{301}"}, "d": "e\u{301}"});
+        normalizer.apply(&mut record);
+        assert_eq!(
+            record,
+            json!({"a": {"b": "\u{e9}"}, "c": {"\u{e9}": "\u{e9}"}, "d": "e\u{301}"})
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use crate::spill::{OVERHEAD, SortedRuns};
+use crate::template;
+use serde_json::Value;
+use std::collections::{BinaryHeap, HashMap};
+use std::io::{self, Write};
+
+/// Indexes of records listed at most for each value found more than once.
+const INDEXES: usize = 10;
+
+/// The entry of record `index` with value `key` once spilled, sorting by
+/// value and then by index.
+fn entry(key: &str, index: u64) -> Vec<u8> {
+    let mut entry = Vec::with_capacity(4 + key.len() + 8);
+    entry.extend((key.len() as u32).to_be_bytes());
+    entry.extend(key.as_bytes());
+    entry.extend(index.to_be_bytes());
+    entry
+}
+
+/// Checks that no two records have the same value of a field, as a primary
+/// key, remembering the values seen so far.
+///
+/// The values are kept in memory up to a budget, and past it sorted on disk
+/// with the indexes of their records, to find those that repeat at the end.
+#[derive(Debug)]
+pub struct UniqueKeys {
+    field: String,
+    path: Vec<String>,
+    /// the index of the first record with each value, as JSON
+    first: HashMap<String, u64>,
+    /// the indexes of the other records with the values seen more than once
+    duplicates: HashMap<String, Vec<u64>>,
+    /// bytes of the tables above, roughly
+    bytes: usize,
+    max_bytes: usize,
+    /// the values and indexes of all the records, once past `max_bytes`
+    spilled: Option<SortedRuns>,
+    /// records without the field
+    pub missing: u64,
+}
+
+impl UniqueKeys {
+    /// Check the values of `field`, a dotted path into the records like
+    /// those of a template.
+    pub fn new(field: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            path: field.split('.').map(String::from).collect(),
+            first: HashMap::new(),
+            duplicates: HashMap::new(),
+            bytes: 0,
+            max_bytes: usize::MAX,
+            spilled: None,
+            missing: 0,
+        }
+    }
+
+    /// Keep about `max_bytes` of values in memory, spilling to disk past
+    /// them.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Note the value of record number `index`. Values are compared as
+    /// JSON, so the string "1" and the number 1 are different keys.
+    pub fn add(&mut self, record: &Value, index: u64) -> io::Result<()> {
+        let Some(value) = template::lookup(record, &self.path) else {
+            self.missing += 1;
+            return Ok(());
+        };
+        let key = value.to_string();
+        if let Some(spilled) = &mut self.spilled {
+            return spilled.push(entry(&key, index), vec![]);
+        }
+        match self.first.get_mut(&key) {
+            // records may come out of order from several threads
+            Some(first) if index < *first => {
+                let later = std::mem::replace(first, index);
+                self.repeat(key, later);
+            }
+            Some(_) => self.repeat(key, index),
+            None => {
+                self.bytes += key.len() + OVERHEAD;
+                self.first.insert(key, index);
+            }
+        }
+        if self.bytes > self.max_bytes {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn repeat(&mut self, key: String, index: u64) {
+        match self.duplicates.get_mut(&key) {
+            Some(others) => others.push(index),
+            None => {
+                self.bytes += key.len() + OVERHEAD;
+                self.duplicates.insert(key, vec![index]);
+            }
+        }
+        self.bytes += 8;
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        let mut spilled = SortedRuns::new(self.max_bytes);
+        for (key, index) in std::mem::take(&mut self.first) {
+            spilled.push(entry(&key, index), vec![])?;
+        }
+        for (key, others) in std::mem::take(&mut self.duplicates) {
+            for index in others {
+                spilled.push(entry(&key, index), vec![])?;
+            }
+        }
+        self.bytes = 0;
+        self.spilled = Some(spilled);
+        Ok(())
+    }
+
+    /// The values found in more than one record so far, listing up to
+    /// `limit` of them. Once spilled, the values are read back from disk,
+    /// and can't be added to any more.
+    pub fn duplicates(&mut self, limit: usize) -> io::Result<Duplicates> {
+        let mut duplicates = Duplicates {
+            field: self.field.clone(),
+            count: 0,
+            listed: vec![],
+        };
+        let Some(spilled) = self.spilled.take() else {
+            duplicates.count = self.duplicates.len();
+            duplicates.listed = (self.duplicates.iter())
+                .map(|(key, others)| {
+                    let mut indexes = others.clone();
+                    indexes.push(self.first[key]);
+                    indexes.sort_unstable();
+                    let n = indexes.len();
+                    indexes.truncate(INDEXES);
+                    (indexes[1], key.clone(), indexes, n)
+                })
+                .collect();
+            duplicates.listed.sort_unstable();
+            duplicates.listed.truncate(limit);
+            return Ok(duplicates);
+        };
+        // the first `limit` by the index of their second record, the last
+        // of them on top
+        let mut listed = BinaryHeap::new();
+        let mut list = |key: &[u8], indexes: Vec<u64>, n: usize| {
+            if n < 2 {
+                return;
+            }
+            duplicates.count += 1;
+            let key = String::from_utf8_lossy(&key[4..]).into_owned();
+            listed.push((indexes[1], key, indexes, n));
+            if listed.len() > limit {
+                listed.pop();
+            }
+        };
+        let mut current: Option<(Vec<u8>, Vec<u64>, usize)> = None;
+        for entry in spilled.into_sorted()? {
+            let (mut key, _) = entry?;
+            let index = u64::from_be_bytes(key[key.len() - 8..].try_into().unwrap());
+            key.truncate(key.len() - 8);
+            match &mut current {
+                Some((value, indexes, n)) if *value == key => {
+                    if indexes.len() < INDEXES {
+                        indexes.push(index);
+                    }
+                    *n += 1;
+                }
+                _ => {
+                    if let Some((value, indexes, n)) = current.replace((key, vec![index], 1)) {
+                        list(&value, indexes, n);
+                    }
+                }
+            }
+        }
+        if let Some((value, indexes, n)) = current {
+            list(&value, indexes, n);
+        }
+        duplicates.listed = listed.into_sorted_vec();
+        Ok(duplicates)
+    }
+}
+
+/// The values that [`UniqueKeys`] found more than once.
+#[derive(Debug, Clone)]
+pub struct Duplicates {
+    field: String,
+    /// the number of values found in more than one record
+    pub count: usize,
+    /// (index of the second record, value, indexes of the first records,
+    /// number of records) of those listed, those that repeat first in the
+    /// input first
+    listed: Vec<(u64, String, Vec<u64>, usize)>,
+}
+
+impl Duplicates {
+    /// Write the values listed, with the indexes of their records.
+    pub fn write_report(&self, mut w: impl Write) -> io::Result<()> {
+        writeln!(
+            w,
+            "{} values of {} found in more than one record:",
+            self.count, self.field
+        )?;
+        for (_, key, indexes, n) in &self.listed {
+            let mut indexes: Vec<_> = indexes.iter().map(|i| i.to_string()).collect();
+            if *n > INDEXES {
+                indexes.push(format!("... ({n} in all)"));
+            }
+            writeln!(w, "  {key}: records {}", indexes.join(", "))?;
+        }
+        if self.count > self.listed.len() {
+            writeln!(w, "  ... and {} more", self.count - self.listed.len())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn report(mut keys: UniqueKeys, limit: usize) -> String {
+        let mut report = vec![];
+        let duplicates = keys.duplicates(limit).unwrap();
+        duplicates.write_report(&mut report).unwrap();
+        String::from_utf8(report).unwrap()
+    }
+
+    fn add(keys: &mut UniqueKeys, ids: impl IntoIterator<Item = u64>) {
+        for (index, id) in ids.into_iter().enumerate() {
+            keys.add(&json!({"id": id}), index as u64).unwrap();
+        }
+    }
+
+    #[test]
+    fn duplicates() {
+        let mut keys = UniqueKeys::new("id");
+        add(&mut keys, [1, 2, 1, 3, 2, 1]);
+        keys.add(&json!({"name": "x"}), 6).unwrap();
+        // out of order
+        keys.add(&json!({"id": 4}), 8).unwrap();
+        keys.add(&json!({"id": 4}), 7).unwrap();
+        assert_eq!(keys.missing, 1);
+        assert_eq!(
+            report(keys, 20),
+            "3 values of id found in more than one record:\n  \
+             1: records 0, 2, 5\n  2: records 1, 4\n  4: records 7, 8\n"
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let mut keys = UniqueKeys::new("id");
+        add(&mut keys, (0..30).map(|i| i % 2).chain([5, 5]));
+        assert_eq!(
+            report(keys, 1),
+            "3 values of id found in more than one record:\n  0: records 0, 2, 4, 6, 8, \
+             10, 12, 14, 16, 18, ... (15 in all)\n  ... and 2 more\n"
+        );
+    }
+
+    #[test]
+    fn spilled() {
+        let ids: Vec<u64> = (0..2000).map(|i| i % 1500 + (i % 7) * 10_000).collect();
+        let mut keys = UniqueKeys::new("id");
+        add(&mut keys, ids.iter().copied());
+        let mut small = UniqueKeys::new("id").max_bytes(2000);
+        add(&mut small, ids.iter().copied());
+        assert!(small.spilled.as_ref().unwrap().spilled());
+        assert_eq!(report(small, 5), report(keys, 5));
+    }
+}
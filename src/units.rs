@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+/// Parse a size in bytes, such as `512MB` or `4KiB`. Multiples are decimal
+/// (`kB`, `MB`, `GB`, `TB`) or binary (`KiB`, `MiB`, `GiB`, `TiB`).
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let (n, unit) = split_number(s);
+    let mult: u64 = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "m" | "mb" => 1_000_000,
+        "g" | "gb" => 1_000_000_000,
+        "t" | "tb" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => return Err(format!("unknown size unit {unit:?} in {s:?}")),
+    };
+    let n: f64 = n.parse().map_err(|_| format!("invalid size {s:?}"))?;
+    Ok((n * mult as f64) as u64)
+}
+
+/// Parse an amount per unit of time, such as `10000/s` or `50MB/s`, into an
+/// amount per second, `amount` parsing the part before the `/`. The time
+/// unit is `s`, `m` or `h`, and defaults to seconds.
+pub fn parse_per_second(
+    s: &str,
+    amount: impl Fn(&str) -> Result<u64, String>,
+) -> Result<f64, String> {
+    let (n, per) = s.split_once('/').unwrap_or((s, "s"));
+    let secs = match per {
+        "s" | "sec" => 1.,
+        "m" | "min" => 60.,
+        "h" => 3600.,
+        _ => return Err(format!("unknown time unit {per:?} in {s:?}")),
+    };
+    let n = amount(n)?;
+    if n == 0 {
+        return Err(format!("rate {s:?} must be positive"));
+    }
+    Ok(n as f64 / secs)
+}
+
+/// Split `s` into its leading number and the unit after it.
+fn split_number(s: &str) -> (&str, &str) {
+    let i = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    (&s[..i], s[i..].trim_start())
+}
+
+/// Parse a duration such as `30m`, `1h30m` or `90s`, in days (`d`), hours
+/// (`h`), minutes (`m`), seconds (`s`) or milliseconds (`ms`). A bare number
+/// is in seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err("empty duration".to_string());
+    }
+    let mut total = Duration::ZERO;
+    while !rest.is_empty() {
+        let (n, after) = split_number(rest);
+        let i = after
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(after.len());
+        let (unit, next) = after.split_at(i);
+        let secs = match unit.trim() {
+            "ms" => 0.001,
+            "" | "s" => 1.,
+            "m" | "min" => 60.,
+            "h" => 3600.,
+            "d" => 86400.,
+            unit => return Err(format!("unknown time unit {unit:?} in {s:?}")),
+        };
+        let n: f64 = n.parse().map_err(|_| format!("invalid duration {s:?}"))?;
+        total += Duration::from_secs_f64(n * secs);
+        rest = next;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_second() {
+        let count = |s: &str| s.parse::<u64>().map_err(|e| e.to_string());
+        assert_eq!(parse_per_second("10000/s", count), Ok(10000.));
+        assert_eq!(parse_per_second("600/m", count), Ok(10.));
+        assert_eq!(parse_per_second("50", count), Ok(50.));
+        assert_eq!(parse_per_second("50MB/s", parse_size), Ok(50e6));
+        assert_eq!(parse_per_second("1KiB/h", parse_size), Ok(1024. / 3600.));
+        assert!(parse_per_second("0/s", count).is_err());
+        assert!(parse_per_second("1/week", count).is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(1800)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("3w").is_err());
+    }
+}
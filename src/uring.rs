@@ -0,0 +1,322 @@
+use io_uring::{IoUring, opcode, types};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Seek, Write};
+use std::os::fd::AsRawFd;
+
+/// Number of buffers, and so of reads or writes in flight at once.
+const DEPTH: usize = 4;
+/// Size of each buffer.
+const BUF_LEN: usize = 1 << 20;
+
+/// A transfer between `buf[pos..end]` and the file at `offset`.
+#[derive(Debug, Copy, Clone)]
+struct Op {
+    offset: u64,
+    pos: usize,
+    end: usize,
+}
+
+/// An io_uring instance with [`DEPTH`] registered buffers.
+struct Ring {
+    // dropped before the buffers it points to
+    ring: IoUring,
+    file: File,
+    bufs: Vec<Box<[u8]>>,
+    /// the operation in flight on each buffer
+    ops: Vec<Option<Op>>,
+    in_flight: usize,
+}
+
+impl Ring {
+    fn new(file: File) -> io::Result<Self> {
+        let ring = IoUring::new(DEPTH as u32)?;
+        let mut bufs: Vec<Box<[u8]>> = (0..DEPTH)
+            .map(|_| vec![0; BUF_LEN].into_boxed_slice())
+            .collect();
+        let iovecs: Vec<libc::iovec> = bufs
+            .iter_mut()
+            .map(|buf| libc::iovec {
+                iov_base: buf.as_mut_ptr().cast(),
+                iov_len: buf.len(),
+            })
+            .collect();
+        // SAFETY: the buffers are never moved or freed while the ring exists
+        unsafe { ring.submitter().register_buffers(&iovecs)? };
+        Ok(Self {
+            ring,
+            file,
+            bufs,
+            ops: vec![None; DEPTH],
+            in_flight: 0,
+        })
+    }
+
+    /// Start reading (or writing) `op` with buffer `i`.
+    fn start(&mut self, i: usize, op: Op, write: bool) -> io::Result<()> {
+        let fd = types::Fd(self.file.as_raw_fd());
+        let ptr = self.bufs[i][op.pos..].as_mut_ptr();
+        let len = (op.end - op.pos) as u32;
+        let entry = if write {
+            opcode::WriteFixed::new(fd, ptr, len, i as u16)
+                .offset(op.offset)
+                .build()
+        } else {
+            opcode::ReadFixed::new(fd, ptr, len, i as u16)
+                .offset(op.offset)
+                .build()
+        };
+        // SAFETY: buffer `i` is left alone until the operation completes,
+        // and there is room for it since there is one entry per buffer
+        unsafe {
+            self.ring
+                .submission()
+                .push(&entry.user_data(i as u64))
+                .expect("submission queue is full");
+        }
+        self.ring.submit()?;
+        self.ops[i] = Some(op);
+        self.in_flight += 1;
+        Ok(())
+    }
+
+    /// Wait for an operation to complete, returning its buffer, the
+    /// operation and the number of bytes transferred.
+    fn complete(&mut self) -> io::Result<(usize, Op, usize)> {
+        loop {
+            if let Some(c) = self.ring.completion().next() {
+                self.in_flight -= 1;
+                let i = c.user_data() as usize;
+                let op = self.ops[i].take().unwrap();
+                return match c.result() {
+                    n if n < 0 => Err(io::Error::from_raw_os_error(-n)),
+                    n => Ok((i, op, n as usize)),
+                };
+            }
+            self.ring.submit_and_wait(1)?;
+        }
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        // the kernel may still be using the buffers
+        while self.in_flight > 0 {
+            match self.ring.submit_and_wait(self.in_flight) {
+                Ok(_) => self.in_flight -= self.ring.completion().count(),
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// Reads a file through io_uring, keeping [`DEPTH`] reads of the next parts
+/// of the file in flight while the previous ones are being consumed.
+pub struct UringReader {
+    ring: Ring,
+    /// offset of the next read to start
+    next: u64,
+    /// buffers with a read started, in file order
+    queue: VecDeque<usize>,
+    /// bytes read into each buffer, once its read is complete
+    ready: Vec<Option<usize>>,
+    /// buffer being consumed, position and length
+    current: Option<(usize, usize, usize)>,
+    eof: bool,
+}
+
+impl UringReader {
+    /// Read `file` from its current position.
+    pub fn new(mut file: File) -> io::Result<Self> {
+        let next = file.stream_position()?;
+        let mut rd = Self {
+            ring: Ring::new(file)?,
+            next,
+            queue: VecDeque::new(),
+            ready: vec![None; DEPTH],
+            current: None,
+            eof: false,
+        };
+        for i in 0..DEPTH {
+            rd.read_ahead(i)?;
+        }
+        Ok(rd)
+    }
+
+    fn read_ahead(&mut self, i: usize) -> io::Result<()> {
+        let op = Op {
+            offset: self.next,
+            pos: 0,
+            end: BUF_LEN,
+        };
+        self.ring.start(i, op, false)?;
+        self.next += BUF_LEN as u64;
+        self.queue.push_back(i);
+        Ok(())
+    }
+}
+
+impl Read for UringReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some((i, pos, len)) = &mut self.current {
+                if *pos < *len {
+                    let n = buf.len().min(*len - *pos);
+                    buf[..n].copy_from_slice(&self.ring.bufs[*i][*pos..*pos + n]);
+                    *pos += n;
+                    return Ok(n);
+                }
+                let i = *i;
+                self.current = None;
+                if !self.eof {
+                    self.read_ahead(i)?;
+                }
+                continue;
+            }
+            let Some(&i) = self.queue.front() else {
+                return Ok(0);
+            };
+            while self.ready[i].is_none() {
+                let (j, op, n) = self.ring.complete()?;
+                if n == 0 || op.pos + n == op.end {
+                    self.ready[j] = Some(op.pos + n);
+                } else {
+                    // short read, get the rest of the buffer
+                    let op = Op {
+                        offset: op.offset + n as u64,
+                        pos: op.pos + n,
+                        end: op.end,
+                    };
+                    self.ring.start(j, op, false)?;
+                }
+            }
+            self.queue.pop_front();
+            let len = self.ready[i].take().unwrap();
+            self.eof |= len < BUF_LEN;
+            self.current = Some((i, 0, len));
+        }
+    }
+}
+
+/// Writes a file through io_uring, filling a buffer while the previous ones
+/// are being written.
+pub struct UringWriter {
+    ring: Ring,
+    /// offset of the next write to start
+    offset: u64,
+    free: Vec<usize>,
+    /// buffer being filled, and its length
+    current: Option<(usize, usize)>,
+}
+
+impl UringWriter {
+    /// Write `file` from its current position.
+    pub fn new(mut file: File) -> io::Result<Self> {
+        let offset = file.stream_position()?;
+        Ok(Self {
+            ring: Ring::new(file)?,
+            offset,
+            free: (0..DEPTH).rev().collect(),
+            current: None,
+        })
+    }
+
+    /// Wait for a write to complete, starting it again if it was short.
+    fn complete(&mut self) -> io::Result<()> {
+        let (i, op, n) = self.ring.complete()?;
+        if n == 0 {
+            return Err(ErrorKind::WriteZero.into());
+        }
+        if op.pos + n < op.end {
+            let op = Op {
+                offset: op.offset + n as u64,
+                pos: op.pos + n,
+                end: op.end,
+            };
+            return self.ring.start(i, op, true);
+        }
+        self.free.push(i);
+        Ok(())
+    }
+
+    /// Start writing the buffer being filled.
+    fn write_current(&mut self) -> io::Result<()> {
+        if let Some((i, len)) = self.current.take() {
+            let op = Op {
+                offset: self.offset,
+                pos: 0,
+                end: len,
+            };
+            self.ring.start(i, op, true)?;
+            self.offset += len as u64;
+        }
+        Ok(())
+    }
+}
+
+impl Write for UringWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let (i, len) = match self.current {
+            Some(current) => current,
+            None => {
+                while self.free.is_empty() {
+                    self.complete()?;
+                }
+                (self.free.pop().unwrap(), 0)
+            }
+        };
+        let n = buf.len().min(BUF_LEN - len);
+        self.ring.bufs[i][len..len + n].copy_from_slice(&buf[..n]);
+        self.current = Some((i, len + n));
+        if len + n == BUF_LEN {
+            self.write_current()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_current()?;
+        while self.ring.in_flight > 0 {
+            self.complete()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for UringWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_read() {
+        let path = std::env::temp_dir().join(format!("json2jsonl-uring-{}", std::process::id()));
+        // more than all the buffers, in writes of odd sizes
+        let data: Vec<u8> = (0..DEPTH * BUF_LEN * 2 + 12345)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let mut file = File::create(&path).unwrap();
+        file.write_all(b"head").unwrap();
+        let mut w = UringWriter::new(file).unwrap();
+        for chunk in data.chunks(100_003) {
+            w.write_all(chunk).unwrap();
+        }
+        w.flush().unwrap();
+        drop(w);
+        let mut file = File::open(&path).unwrap();
+        file.read_exact(&mut [0; 4]).unwrap();
+        let mut read = vec![];
+        UringReader::new(file)
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(read == data);
+    }
+}
@@ -0,0 +1,37 @@
+/// What to do with byte sequences that are not valid UTF-8.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, clap::ValueEnum)]
+pub enum InvalidUtf8 {
+    /// Reject the record
+    Strict,
+    /// Replace them by U+FFFD
+    Lossy,
+    /// Replace each byte by the text `\xHH`
+    Escape,
+}
+
+/// Rewrite the invalid sequences of `buf` according to `policy`.
+/// Returns the offset of the first one, if any.
+pub fn repair(buf: &mut Vec<u8>, policy: InvalidUtf8) -> Option<usize> {
+    let first = std::str::from_utf8(buf).err()?.valid_up_to();
+    if policy == InvalidUtf8::Strict {
+        return Some(first);
+    }
+    let mut out = Vec::with_capacity(buf.len() + 16);
+    for chunk in buf.utf8_chunks() {
+        out.extend_from_slice(chunk.valid().as_bytes());
+        if chunk.invalid().is_empty() {
+            continue;
+        }
+        match policy {
+            InvalidUtf8::Lossy => out.extend_from_slice("\u{FFFD}".as_bytes()),
+            // the backslash is escaped, as this ends up in a JSON string
+            _ => {
+                for b in chunk.invalid() {
+                    out.extend_from_slice(format!("\\\\x{b:02X}").as_bytes());
+                }
+            }
+        }
+    }
+    *buf = out;
+    Some(first)
+}
@@ -0,0 +1,85 @@
+use crate::push;
+use wasm_bindgen::prelude::*;
+
+/// Converts a JSON array to JSONL from chunks of it, for JavaScript: the
+/// chunks can be fed as they are read (from a `File` stream, say), and each
+/// call returns the records of the elements completed so far.
+///
+/// ```js
+/// const converter = new Converter().json5(true);
+/// for await (const chunk of file.stream()) {
+///     upload(converter.push(chunk));
+/// }
+/// upload(converter.finish());
+/// ```
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Converter(push::Converter);
+
+#[wasm_bindgen]
+impl Converter {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept JSON5 syntax: single-quoted strings, comments and a trailing comma.
+    pub fn json5(self, yes: bool) -> Self {
+        Self(self.0.json5(yes))
+    }
+
+    /// Strip `//` and `/* */` comments, both between and inside elements.
+    pub fn comments(self, yes: bool) -> Self {
+        Self(self.0.comments(yes))
+    }
+
+    /// Accept (and drop) a comma before a closing `]` or `}`.
+    #[wasm_bindgen(js_name = trailingCommas)]
+    pub fn trailing_commas(self, yes: bool) -> Self {
+        Self(self.0.trailing_commas(yes))
+    }
+
+    /// Reject elements with more than `n` levels of nested arrays and objects.
+    #[wasm_bindgen(js_name = maxDepth)]
+    pub fn max_depth(self, n: usize) -> Self {
+        Self(self.0.max_depth(n))
+    }
+
+    /// Convert the next `chunk` of the input, returning the JSONL records
+    /// of the elements it completes (possibly none).
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<u8>, JsError> {
+        let mut out = vec![];
+        self.0.push(chunk, &mut out).map_err(to_js)?;
+        Ok(out)
+    }
+
+    /// Convert the rest of the input, once all of it has been pushed. Fails
+    /// if the array isn't complete.
+    pub fn finish(&mut self) -> Result<Vec<u8>, JsError> {
+        let mut out = vec![];
+        self.0.finish(&mut out).map_err(to_js)?;
+        Ok(out)
+    }
+}
+
+fn to_js(e: anyhow::Error) -> JsError {
+    JsError::new(&format!("{e:#}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks() {
+        // the JsError of a failure can only be made in a wasm runtime
+        let mut converter = Converter::new().json5(true);
+        let input = b"[{a: 1}, 'x', [2,],]";
+        let mut out = vec![];
+        for chunk in input.chunks(3) {
+            out.extend(converter.push(chunk).ok().unwrap());
+        }
+        out.extend(converter.finish().ok().unwrap());
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"a\":1}\n\"x\"\n[2]\n");
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::Context;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+/// How long to wait between two scans of the directory.
+const POLL: Duration = Duration::from_secs(1);
+
+/// Scan `dir` for `*.json` files forever, calling `handle` on each new one.
+///
+/// A file is only handed over once its size and modification time are the
+/// same in two scans in a row, so that files still being written are left
+/// alone; hidden files, as used by many writers for the temporary copy, are
+/// ignored. Failures are reported and the file is not tried again. With
+/// `move_done`, converted files are moved to `dir/done/`.
+pub fn watch(
+    dir: &Path,
+    move_done: bool,
+    mut handle: impl FnMut(&Path) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let done = dir.join("done");
+    if move_done {
+        fs::create_dir_all(&done).with_context(|| format!("creating {}", done.display()))?;
+    }
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    let mut stamps: HashMap<PathBuf, (u64, SystemTime)> = HashMap::new();
+    loop {
+        let mut new_stamps = HashMap::new();
+        for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+            let entry = entry?;
+            let path = entry.path();
+            let name = entry.file_name();
+            if name.to_string_lossy().starts_with('.')
+                || path.extension().is_none_or(|ext| ext != "json")
+                || seen.contains(&path)
+            {
+                continue;
+            }
+            let meta = entry.metadata()?;
+            if !meta.is_file() {
+                continue;
+            }
+            let stamp = (meta.len(), meta.modified()?);
+            if stamps.get(&path) != Some(&stamp) {
+                new_stamps.insert(path, stamp);
+                continue;
+            }
+            match handle(&path) {
+                Ok(()) if move_done => {
+                    let to = done.join(&name);
+                    fs::rename(&path, &to).with_context(|| {
+                        format!("moving {} to {}", path.display(), to.display())
+                    })?;
+                    debug!("moved {} to {}", path.display(), to.display());
+                }
+                Ok(()) => {
+                    seen.insert(path);
+                }
+                Err(e) => {
+                    warn!("converting {} failed: {e:#}", path.display());
+                    seen.insert(path);
+                }
+            }
+        }
+        stamps = new_stamps;
+        thread::sleep(POLL);
+    }
+}
@@ -0,0 +1,81 @@
+use std::io::{self, Read};
+use std::net::TcpStream;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Error, Message, WebSocket};
+
+/// Is the input argument a WebSocket URL?
+pub fn is_url(s: &str) -> bool {
+    s.starts_with("ws://") || s.starts_with("wss://")
+}
+
+/// Presents the messages received on a WebSocket, each a JSON value or an
+/// array of values, as a single JSON array.
+///
+/// Every message is followed by a `,` as soon as it arrives, so that its
+/// last element can be converted without waiting for the next message; the
+/// array is closed when the connection is, which leaves a trailing `,` (see
+/// [`Scanner::trailing_separator`](crate::scan::Scanner::trailing_separator)).
+pub struct WsReader {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    buf: Vec<u8>,
+    pos: usize,
+    started: bool,
+    done: bool,
+}
+
+impl WsReader {
+    pub fn connect(url: &str) -> anyhow::Result<Self> {
+        let (socket, _) = tungstenite::connect(url)?;
+        Ok(Self {
+            socket,
+            buf: vec![],
+            pos: 0,
+            started: false,
+            done: false,
+        })
+    }
+
+    fn push_message(&mut self, msg: &[u8]) {
+        let msg = msg.trim_ascii();
+        let items = match msg {
+            [b'[', inner @ .., b']'] => inner,
+            _ => msg,
+        };
+        if !items.trim_ascii().is_empty() {
+            self.buf.extend_from_slice(items);
+            self.buf.push(b',');
+        }
+    }
+}
+
+impl Read for WsReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.buf.clear();
+            self.pos = 0;
+            if !self.started {
+                self.started = true;
+                self.buf.push(b'[');
+                continue;
+            }
+            match self.socket.read() {
+                Ok(Message::Text(text)) => self.push_message(text.as_bytes()),
+                Ok(Message::Binary(data)) => self.push_message(&data),
+                Ok(Message::Close(_)) | Err(Error::ConnectionClosed | Error::AlreadyClosed) => {
+                    self.buf.push(b']');
+                    self.done = true;
+                }
+                Ok(_) => (),
+                Err(Error::Io(e)) => return Err(e),
+                Err(e) => return Err(io::Error::other(e)),
+            }
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,97 @@
+//! The checks on the records: assertions, required fields and unique keys,
+//! and their exit codes.
+
+mod common;
+
+use common::{run, stderr, temp_dir};
+
+#[test]
+fn assertions_with_skipped_records() {
+    let dir = temp_dir("assertions_with_skipped_records");
+    let failures = dir.join("failures.jsonl");
+    let output = run(
+        &[
+            "--skip-invalid",
+            "--assert",
+            "a >= 0",
+            "--assert-failures",
+            failures.to_str().unwrap(),
+        ],
+        r#"[{"a":1},{"a":-1},x,{"a":-2}]"#,
+    );
+    // the failed assertions win over the skipped record
+    assert_eq!(output.status.code(), Some(7));
+    assert_eq!(output.stdout, b"{\"a\":1}\n{\"a\":-1}\n{\"a\":-2}\n");
+    let stderr = stderr(&output);
+    assert!(stderr.contains("1 invalid records skipped"), "{stderr}");
+    assert!(
+        stderr.contains("2 records failed \"a >= 0\" (first: record 1)"),
+        "{stderr}"
+    );
+    assert_eq!(
+        std::fs::read_to_string(failures).unwrap(),
+        "{\"index\":1,\"failed\":[\"a >= 0\"],\"record\":{\"a\":-1}}\n\
+         {\"index\":3,\"failed\":[\"a >= 0\"],\"record\":{\"a\":-2}}\n"
+    );
+
+    let output = run(&["--skip-invalid", "--assert", "a >= 0"], r#"[{"a":1},x]"#);
+    assert_eq!(output.status.code(), Some(5));
+}
+
+#[test]
+fn exit_codes() {
+    let missing = temp_dir("exit_codes").join("missing.json");
+    let cases: [(&[&str], &str, i32); 7] = [
+        (&[], "[1]", 0),
+        (&["--no-such-flag"], "[1]", 2),
+        (&[], "[1,{]", 3),
+        (&[missing.to_str().unwrap()], "", 4),
+        (&["--skip-invalid"], "[1,{\"a\":}]", 5),
+        (&["--require-objects"], "[{},1]", 6),
+        (&["--assert", "a > 1"], "[{\"a\":1}]", 7),
+    ];
+    for (args, input, code) in cases {
+        let output = run(args, input);
+        assert_eq!(
+            output.status.code(),
+            Some(code),
+            "{args:?}: {}",
+            stderr(&output)
+        );
+    }
+}
+
+#[test]
+fn required() {
+    let dir = temp_dir("required");
+    let failures = dir.join("failures.jsonl");
+    let output = run(
+        &[
+            "--required",
+            "a,b.c",
+            "--assert-failures",
+            failures.to_str().unwrap(),
+        ],
+        r#"[{"a":1,"b":{"c":false}},{"a":null,"b":{"c":0}},{"b":{}}]"#,
+    );
+    assert_eq!(output.status.code(), Some(7));
+    let stderr = stderr(&output);
+    assert!(
+        stderr.contains("2 records failed \"a != null\" (first: record 1)"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("1 records failed \"b.c != null\" (first: record 2)"),
+        "{stderr}"
+    );
+    assert_eq!(
+        std::fs::read_to_string(failures).unwrap(),
+        "{\"index\":1,\"failed\":[\"a != null\"],\"record\":{\"a\":null,\"b\":{\"c\":0}}}\n\
+         {\"index\":2,\"failed\":[\"a != null\",\"b.c != null\"],\"record\":{\"b\":{}}}\n"
+    );
+    assert_eq!(
+        run(&["--required", "a"], r#"[{"a":""}]"#).status.code(),
+        Some(0)
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
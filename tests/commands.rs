@@ -0,0 +1,210 @@
+//! The subcommands, besides the conversion itself.
+
+mod common;
+
+use common::{convert, run, stderr, temp_dir};
+use std::fs;
+
+#[test]
+fn bench() {
+    let output = run(
+        &["bench", "--runs", "1", "--records", "500", "--", "-j", "1"],
+        "",
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report = String::from_utf8(output.stdout).unwrap();
+    let mut lines = report
+        .lines()
+        .skip_while(|line| line.starts_with("generating"));
+    assert_eq!(
+        lines.next().unwrap().split_whitespace().collect::<Vec<_>>(),
+        ["flags", "MB/s", "records/s"]
+    );
+    let runs: Vec<_> = lines
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .collect();
+    assert_eq!(runs[0][0], "(defaults)");
+    assert_eq!(runs[1][0], "--mmap");
+    for run in &runs {
+        assert!(run[run.len() - 2].parse::<f64>().unwrap() > 0.);
+        assert!(run[run.len() - 1].parse::<u64>().unwrap() > 0);
+    }
+}
+
+#[test]
+fn completions() {
+    for (shell, start) in [
+        ("bash", "_json2jsonl() {"),
+        ("zsh", "#compdef json2jsonl"),
+        ("fish", "# Print an optspec"),
+        (
+            "powershell",
+            "\nusing namespace System.Management.Automation",
+        ),
+    ] {
+        let output = run(&["completions", shell], "");
+        assert!(output.status.success(), "{shell}: {}", stderr(&output));
+        let script = String::from_utf8(output.stdout).unwrap();
+        assert!(script.starts_with(start), "{shell}: {script:.100}");
+        // flags of the conversion and subcommands
+        assert!(script.contains("skip-invalid"), "{shell}");
+        assert!(script.contains("completions"), "{shell}");
+    }
+    assert_eq!(run(&["completions", "tcsh"], "").status.code(), Some(2));
+}
+
+#[test]
+fn man() {
+    let output = run(&["man"], "");
+    assert!(output.status.success(), "{}", stderr(&output));
+    let page = String::from_utf8(output.stdout).unwrap();
+    assert!(page.contains(".TH json2jsonl 1 "));
+    for section in ["NAME", "SYNOPSIS", "DESCRIPTION", "OPTIONS", "SUBCOMMANDS"] {
+        assert!(page.contains(&format!("\n.SH {section}\n")), "{section}");
+    }
+    // from the definitions of the flags
+    assert!(page.contains("\\fB\\-\\-skip\\-invalid\\fR\n"));
+    assert!(page.contains("\njson2jsonl\\-reverse(1)\n"));
+    // hidden from the help
+    let help = String::from_utf8(run(&["--help"], "").stdout).unwrap();
+    assert!(
+        !help
+            .lines()
+            .any(|line| line.trim_start().starts_with("man "))
+    );
+}
+
+#[test]
+fn reverse_wrapped() {
+    let dir = temp_dir("reverse-wrapped");
+    let (meta, list) = (dir.join("meta.json"), dir.join("list.json"));
+    fs::write(&meta, r#"{"total": 2}"#).unwrap();
+    fs::write(&list, "[1]").unwrap();
+    let args = [
+        "reverse",
+        "--compact",
+        "--wrap-key",
+        "items",
+        "--merge-extra",
+    ];
+    assert_eq!(
+        convert(&[&args[..], &[meta.to_str().unwrap()]].concat(), "1\n2\n"),
+        "{\"items\":[1,2],\"total\":2}\n"
+    );
+    let output = run(&[&args[..], &[list.to_str().unwrap()]].concat(), "1\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("list.json is not a JSON object"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn merge_sorted() {
+    let dir = temp_dir("merge-sorted");
+    let (a, b) = (dir.join("a.jsonl"), dir.join("b.json"));
+    fs::write(&a, "{\"t\":1}\n{\"t\":3}\n").unwrap();
+    fs::write(&b, r#"[{"t":2},{"t":4}]"#).unwrap();
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+    assert_eq!(
+        convert(&["merge", "--sorted", "--by", "t", a, b], ""),
+        "{\"t\":1}\n{\"t\":2}\n{\"t\":3}\n{\"t\":4}\n"
+    );
+    let output = run(
+        &["merge", "--sorted", "--by", "t", "--descending", a, b],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("b.json is not sorted by t, at record 2"));
+    assert_eq!(run(&["merge", "--sorted", a], "").status.code(), Some(2));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reverse_chunks() {
+    let dir = temp_dir("reverse-chunks");
+    let pattern = dir.join("part-{n}.json");
+    let pattern = pattern.to_str().unwrap();
+    let args = [
+        "reverse",
+        "--compact",
+        "--chunk-records",
+        "2",
+        "-o",
+        pattern,
+    ];
+    assert_eq!(convert(&args, "1\n2\n\n3\n4\n5\n"), "");
+    let chunks: Vec<_> = (0..3)
+        .map(|n| fs::read_to_string(dir.join(format!("part-{n}.json"))).unwrap())
+        .collect();
+    assert_eq!(chunks, ["[1,2]\n", "[3,4]\n", "[5]\n"]);
+    assert!(!dir.join("part-3.json").exists());
+    // even without records, there is an array
+    let empty = dir.join("empty-{n}.json");
+    convert(
+        &[
+            "reverse",
+            "--chunk-records",
+            "2",
+            "-o",
+            empty.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("empty-0.json")).unwrap(),
+        "[]\n"
+    );
+    let output = run(
+        &["reverse", "--chunk-records", "2", "-o", "out.json"],
+        "1\n",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("--chunk-records needs an -o with {n}"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn subcommands() {
+    let input = r#"[{"a":1,"b":"xy"},{"a":2.5},{"a":null,"b":"z"}]"#;
+    // the bare invocation is short for convert
+    assert_eq!(convert(&["convert"], input), convert(&[], input));
+
+    let output = run(&["validate"], input);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"");
+    assert!(stderr(&output).contains("validated 3 records"));
+    assert_eq!(
+        run(&["validate", "--required", "b"], input).status.code(),
+        Some(7)
+    );
+    assert_eq!(run(&["validate"], "[1,{]").status.code(), Some(3));
+
+    assert!(
+        convert(&["stats"], input).contains("\na      3 (100%)  1     null|number  1    2.5\n")
+    );
+    let schema: serde_json::Value = serde_json::from_str(&convert(&["schema"], input)).unwrap();
+    assert_eq!(schema["required"], serde_json::json!(["a"]));
+
+    let dir = temp_dir("subcommands");
+    let pattern = dir.join("part-{n}.jsonl");
+    convert(
+        &["split", "--records", "2", "-o", pattern.to_str().unwrap()],
+        "[1,2,3,4,5]",
+    );
+    let parts: Vec<_> = (0..3)
+        .map(|n| fs::read_to_string(dir.join(format!("part-{n}.jsonl"))).unwrap())
+        .collect();
+    assert_eq!(parts, ["1\n2\n", "3\n4\n", "5\n"]);
+    assert_eq!(
+        run(&["split", "--records", "2"], "[1]").status.code(),
+        Some(2)
+    );
+    fs::remove_dir_all(dir).unwrap();
+
+    let ten = "[1,2,3,4,5,6,7,8,9,10]";
+    let sample = convert(&["sample", "-n", "3"], ten);
+    let numbers: Vec<u32> = sample.lines().map(|line| line.parse().unwrap()).collect();
+    assert_eq!(numbers.len(), 3);
+    assert!(numbers.is_sorted(), "in input order: {numbers:?}");
+    // the same sample from one run to the next
+    assert_eq!(convert(&["sample", "-n", "3"], ten), sample);
+}
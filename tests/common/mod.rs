@@ -0,0 +1,203 @@
+//! Running the json2jsonl binary from the tests.
+
+#![allow(dead_code)]
+
+use std::ffi::OsStr;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Output, Stdio};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// The output of json2jsonl run with `args` on `input`, successful or not.
+pub fn run(args: &[&str], input: &str) -> Output {
+    run_bytes(args, input.as_bytes())
+}
+
+pub fn run_bytes(args: &[&str], input: &[u8]) -> Output {
+    run_env(args, input, &[])
+}
+
+/// Like [`run_bytes`], with `vars` set in the environment.
+pub fn run_env(args: &[&str], input: &[u8], vars: &[(&str, &OsStr)]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_json2jsonl"))
+        .args(args)
+        .envs(vars.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // written while the output is read, for outputs larger than a pipe
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_vec();
+    let writer = std::thread::spawn(move || {
+        // the input may be left unread, if the arguments are rejected
+        let _ = stdin.write_all(&input);
+    });
+    let output = child.wait_with_output().unwrap();
+    writer.join().unwrap();
+    output
+}
+
+/// The standard output of json2jsonl run with `args` on `input`, which must
+/// succeed.
+pub fn convert(args: &[&str], input: &str) -> String {
+    let output = run(args, input);
+    assert!(
+        output.status.success(),
+        "json2jsonl {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// The standard error of `output`.
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// A new empty directory for the files of test `name`.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("json2jsonl-test-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A request received by [`http_server`].
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// with the query string
+    pub path: String,
+    /// with lowercase names
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        (self.headers.iter())
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+fn read_request(r: &mut impl BufRead) -> Option<Request> {
+    let mut line = String::new();
+    r.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = (parts.next()?.to_string(), parts.next()?.to_string());
+    let mut headers = vec![];
+    loop {
+        line.clear();
+        r.read_line(&mut line).ok()?;
+        match line.trim_end().split_once(':') {
+            Some((name, value)) => {
+                headers.push((name.to_lowercase(), value.trim().to_string()));
+            }
+            None => break,
+        }
+    }
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: vec![],
+    };
+    if request.header("transfer-encoding") == Some("chunked") {
+        loop {
+            line.clear();
+            r.read_line(&mut line).ok()?;
+            let len = usize::from_str_radix(line.trim(), 16).ok()?;
+            let mut chunk = vec![0; len + 2];
+            r.read_exact(&mut chunk).ok()?;
+            if len == 0 {
+                break;
+            }
+            request.body.extend(&chunk[..len]);
+        }
+    } else if let Some(len) = request.header("content-length") {
+        request.body = vec![0; len.parse().ok()?];
+        r.read_exact(&mut request.body).ok()?;
+    }
+    Some(request)
+}
+
+/// An HTTP server on a free local port, answering the requests in turn with
+/// `responses` of a status and a body, then stopping: its address, e.g.
+/// 127.0.0.1:1234, and the requests it got.
+pub fn http_server(responses: Vec<(u16, Vec<u8>)>) -> (String, JoinHandle<Vec<Request>>) {
+    let responses = (responses.into_iter())
+        .map(|(status, body)| (status, "", body))
+        .collect();
+    http_server_with_headers(responses)
+}
+
+/// Like [`http_server`], with header lines (each ending with CRLF) to add
+/// to each response.
+pub fn http_server_with_headers(
+    responses: Vec<(u16, &'static str, Vec<u8>)>,
+) -> (String, JoinHandle<Vec<Request>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let server = std::thread::spawn(move || {
+        let mut requests = vec![];
+        for (status, headers, body) in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let Some(request) = read_request(&mut reader) else {
+                continue;
+            };
+            requests.push(request);
+            let head = format!(
+                "HTTP/1.1 {status} X\r\n{headers}content-length: {}\r\nconnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = (&stream).write_all(&[head.as_bytes(), &body].concat());
+        }
+        requests
+    });
+    (addr, server)
+}
+
+/// A local TCP address with a port free for a server, e.g. 127.0.0.1:1234.
+pub fn free_addr() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+/// json2jsonl running with `args`, killed when dropped, e.g. a server.
+pub struct Server(Child);
+
+impl Server {
+    pub fn start(args: &[&str]) -> Self {
+        let child = Command::new(env!("CARGO_BIN_EXE_json2jsonl"))
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        Self(child)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Call `connect` until the server it connects to is up.
+pub fn connect<T>(mut connect: impl FnMut() -> std::io::Result<T>) -> T {
+    for _ in 0..100 {
+        if let Ok(connection) = connect() {
+            return connection;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    connect().unwrap()
+}
@@ -0,0 +1,56 @@
+//! Flags set from a config file or the environment.
+
+mod common;
+
+use common::{run_env, stderr, temp_dir};
+use std::ffi::OsStr;
+use std::fs;
+
+#[test]
+fn config_file() {
+    let dir = temp_dir("config_file");
+    let config = dir.join("j2j.toml");
+    fs::write(&config, "skip_invalid = true\nformat = \"json-seq\"\n").unwrap();
+    let input = b"[{\"a\":1},{\"b\":}]";
+    let config_arg = format!("--config={}", config.display());
+    // the command line wins over the file
+    let output = run_env(&[&config_arg, "--format", "jsonl"], input, &[]);
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    assert_eq!(output.stdout, b"{\"a\":1}\n");
+    // found in the config directory
+    let home = dir.join("config");
+    fs::create_dir_all(home.join("json2jsonl")).unwrap();
+    fs::copy(&config, home.join("json2jsonl/config.toml")).unwrap();
+    let xdg: [(&str, &OsStr); 1] = [("XDG_CONFIG_HOME", home.as_os_str())];
+    let output = run_env(&[], input, &xdg);
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    assert_eq!(output.stdout, b"\x1e{\"a\":1}\n");
+    let output = run_env(&["--no-config"], input, &xdg);
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    fs::write(&config, "nope = 1").unwrap();
+    let output = run_env(&[&config_arg], input, &[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("unknown flag `nope`"));
+}
+
+#[test]
+fn environment() {
+    let input = b"[{\"a\":1},{\"b\":}]";
+    let vars: [(&str, &OsStr); 2] = [
+        ("J2J_SKIP_INVALID", OsStr::new("true")),
+        ("J2J_FORMAT", OsStr::new("json-seq")),
+    ];
+    let output = run_env(&["--no-config"], input, &vars);
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    assert_eq!(output.stdout, b"\x1e{\"a\":1}\n");
+    // over the config file, under the command line
+    let config = temp_dir("environment").join("j2j.toml");
+    fs::write(&config, "format = \"jsonl\"\nskip_invalid = false\n").unwrap();
+    let config = format!("--config={}", config.display());
+    let output = run_env(&[&config], input, &vars);
+    assert_eq!(output.stdout, b"\x1e{\"a\":1}\n");
+    let output = run_env(&[&config, "--format", "jsonl"], input, &vars);
+    assert_eq!(output.stdout, b"{\"a\":1}\n");
+    let output = run_env(&["--no-config"], input, &[("J2J_THREADS", OsStr::new("x"))]);
+    assert_eq!(output.status.code(), Some(2));
+}
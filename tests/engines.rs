@@ -0,0 +1,125 @@
+//! The ways of reading, converting and writing the records, which must all
+//! give the same output.
+
+mod common;
+
+use common::{convert, temp_dir};
+use std::fs;
+
+/// An array of `n` elements of various kinds and sizes, with whitespace,
+/// and the records they are converted to.
+fn input(n: usize) -> (String, String) {
+    let mut input = String::from("[\n");
+    let mut records = String::new();
+    for i in 0..n {
+        let record = match i % 4 {
+            0 => format!("{{\"i\":{i},\"s\":\"{}\"}}", "x".repeat(i % 300)),
+            1 => format!("[{i},\"a\\\"b\",null]"),
+            2 => format!("\"{}\"", "é".repeat(i % 50)),
+            _ => format!("{{\"nested\":{{\"i\":{i},\"t\":true}}}}"),
+        };
+        input += &record.replace(',', " ,\n  ").replace(':', ": ");
+        input += if i + 1 < n { ",\n" } else { "\n]\n" };
+        records += &record;
+        records.push('\n');
+    }
+    (input, records)
+}
+
+#[test]
+fn threads() {
+    // several batches of elements
+    let (input, records) = input(5000);
+    for threads in ["1", "4", "0"] {
+        assert_eq!(convert(&["-j", threads], &input), records, "-j {threads}");
+    }
+}
+
+#[test]
+fn unordered() {
+    let (input, records) = input(5000);
+    let output = convert(&["-j", "4", "--unordered"], &input);
+    // the same records, in any order
+    let mut lines: Vec<_> = output.lines().collect();
+    let mut expected: Vec<_> = records.lines().collect();
+    lines.sort_unstable();
+    expected.sort_unstable();
+    assert_eq!(lines, expected);
+}
+
+#[cfg(feature = "simd")]
+#[test]
+fn simd() {
+    let (input, records) = input(3000);
+    assert_eq!(convert(&["--engine", "simd"], &input), records);
+    let output = common::run(&["--engine", "simd"], "[1,{\"a\":tru}]");
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn raw() {
+    // copied as they were in the input, but for the whitespace
+    assert_eq!(
+        convert(
+            &[],
+            "[ {\"b\" : 1.50, \"a\":1e5 ,\"c\":\"\\u00e9\\/ \" , \"b\":[ 1 , -0.0 ]} ]"
+        ),
+        "{\"b\":1.50,\"a\":1e5,\"c\":\"\\u00e9\\/ \",\"b\":[1,-0.0]}\n"
+    );
+}
+
+#[test]
+fn mmap() {
+    // larger than the window of the mapping handed out at a time
+    let (input, records) = input(20_000);
+    let path = temp_dir("mmap").join("in.json");
+    fs::write(&path, &input).unwrap();
+    let path = path.to_str().unwrap();
+    assert_eq!(convert(&["--mmap", path], ""), records);
+    assert_eq!(convert(&["--mmap", "-j", "4", path], ""), records);
+}
+
+#[test]
+fn output_buffer() {
+    let (input, records) = input(5000);
+    let out = temp_dir("output-buffer").join("out.jsonl");
+    for size in ["1", "4KiB", "4MB"] {
+        assert_eq!(
+            convert(&["--output-buffer", size], &input),
+            records,
+            "{size}"
+        );
+        convert(
+            &[
+                "--output-buffer",
+                size,
+                "--force",
+                "-o",
+                out.to_str().unwrap(),
+            ],
+            &input,
+        );
+        assert_eq!(fs::read_to_string(&out).unwrap(), records, "{size}");
+    }
+    let output = common::run(&["--output-buffer", "4XB"], "[]");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn output_thread() {
+    let (input, records) = input(5000);
+    assert_eq!(convert(&["--output-thread"], &input), records);
+    let out = temp_dir("output-thread").join("out.jsonl");
+    convert(&["--output-thread", "-o", out.to_str().unwrap()], &input);
+    assert_eq!(fs::read_to_string(&out).unwrap(), records);
+}
+
+#[test]
+fn read_buffer() {
+    // elements larger than the buffer, and split across it
+    let (input, records) = input(2000);
+    for size in ["16", "1000", "8MB"] {
+        assert_eq!(convert(&["--read-buffer", size], &input), records, "{size}");
+    }
+    assert_eq!(convert(&["--write-buffer", "100"], &input), records);
+}
@@ -0,0 +1,421 @@
+//! Input files read as they are written, and where the output goes.
+
+mod common;
+
+use common::{convert, run, stderr, temp_dir};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn follow() {
+    let dir = temp_dir("follow");
+    let path = dir.join("in.json");
+    fs::write(&path, "[1,\n{\"a\":").unwrap();
+    let child = Command::new(env!("CARGO_BIN_EXE_json2jsonl"))
+        .arg("--follow")
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // appended once converting, the record split in between
+    thread::sleep(Duration::from_millis(500));
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(b"2},\n").unwrap();
+    thread::sleep(Duration::from_millis(300));
+    file.write_all(b"3]\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "1\n{\"a\":2}\n3\n"
+    );
+}
+
+#[test]
+fn watch() {
+    let dir = temp_dir("watch");
+    let (input, out) = (dir.join("in"), dir.join("out"));
+    fs::create_dir(&input).unwrap();
+    fs::write(input.join(".partial.json"), "[").unwrap();
+    fs::write(input.join("notes.txt"), "[1]").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_json2jsonl"))
+        .arg("--watch")
+        .arg(&input)
+        .arg("-o")
+        .arg(&out)
+        .arg("--move-done")
+        .spawn()
+        .unwrap();
+    fs::write(input.join("a.json"), "[1,{\"b\":2}]").unwrap();
+    let converted = out.join("a.jsonl");
+    for _ in 0..100 {
+        if converted.exists() && input.join("done/a.json").exists() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert_eq!(fs::read_to_string(&converted).unwrap(), "1\n{\"b\":2}\n");
+    assert!(!input.join("a.json").exists());
+    // hidden files and those of other extensions are left alone
+    let mut outputs: Vec<_> = fs::read_dir(&out)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    outputs.sort();
+    assert_eq!(outputs, ["a.jsonl"]);
+    assert!(input.join(".partial.json").exists());
+}
+
+#[test]
+fn resume() {
+    let dir = temp_dir("resume");
+    let (input, out, checkpoint) = (
+        dir.join("in.json"),
+        dir.join("out.jsonl"),
+        dir.join("cp.json"),
+    );
+    fs::write(&input, r#"[{"a":1}, {"a":2},{"a":3}]"#).unwrap();
+    // killed after the first record, in the middle of the second
+    fs::write(&out, "{\"a\":1}\n{\"a\":").unwrap();
+    let cp = r#"{"input_offset":8,"elements":1,"records":1,"output_len":8}"#;
+    fs::write(&checkpoint, cp).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_json2jsonl"))
+        .arg("--checkpoint")
+        .arg(&checkpoint)
+        .arg("--resume")
+        .arg("-o")
+        .arg(&out)
+        .arg(&input)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(
+        fs::read_to_string(&out).unwrap(),
+        "{\"a\":1}\n{\"a\":2}\n{\"a\":3}\n"
+    );
+    // removed once complete
+    assert!(!checkpoint.exists());
+}
+
+/// Pages of the file at `path` in the page cache.
+#[cfg(target_os = "linux")]
+fn cached_pages(path: &std::path::Path) -> usize {
+    let file = fs::File::open(path).unwrap();
+    // SAFETY: the file is not modified while mapped
+    let map = unsafe { memmap2::Mmap::map(&file).unwrap() };
+    let pages = map.len().div_ceil(4096);
+    let mut resident = vec![0u8; pages];
+    // SAFETY: the mapping is `map.len()` bytes long, and `resident` has a
+    // byte for each of its pages
+    let res = unsafe { libc::mincore(map.as_ptr() as *mut _, map.len(), resident.as_mut_ptr()) };
+    assert_eq!(res, 0);
+    resident.iter().filter(|&&b| b & 1 == 1).count()
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn drop_cache() {
+    // not in a tmpfs, whose pages can't be evicted
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("drop-cache.json");
+    let input = format!("[{}1]", "{\"a\":\"xxxxxxxxxxxxxxxx\"},".repeat(50_000));
+    fs::write(&path, &input).unwrap();
+    fs::File::open(&path).unwrap().sync_all().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_json2jsonl"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(cached_pages(&path) > 0);
+    let output = Command::new(env!("CARGO_BIN_EXE_json2jsonl"))
+        .arg("--drop-cache")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout.len(), 50_000 * 25 + 2);
+    assert_eq!(cached_pages(&path), 0);
+    fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn in_place() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("in_place");
+    let path = dir.join("data.json");
+    fs::write(&path, "[1,{\"a\":2}]").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+    convert(&["--in-place", path.to_str().unwrap()], "");
+    assert_eq!(fs::read_to_string(&path).unwrap(), "1\n{\"a\":2}\n");
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+    // nothing left behind
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    // kept, with the output next to it
+    fs::write(&path, "[3]").unwrap();
+    convert(
+        &["--in-place", path.to_str().unwrap(), "--suffix", ".jsonl"],
+        "",
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), "[3]");
+    assert_eq!(fs::read_to_string(dir.join("data.jsonl")).unwrap(), "3\n");
+    // left as it was on failure
+    let output = run(
+        &["--in-place", path.to_str().unwrap(), "--require-objects"],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(6));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "[3]");
+}
+
+#[test]
+fn atomic_output() {
+    let dir = temp_dir("atomic_output");
+    let out = dir.join("out.jsonl");
+    fs::write(&out, "old\n").unwrap();
+    let out = out.to_str().unwrap();
+    // a failed conversion leaves the previous file
+    let output = run(&["-o", out, "--force"], "[1,2,{]");
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(fs::read_to_string(out).unwrap(), "old\n");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    // and one written directly what it got to
+    let output = run(&["-o", out, "--force", "--no-atomic"], "[1,2,{]");
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(fs::read_to_string(out).unwrap(), "1\n2\n");
+}
+
+#[test]
+fn append() {
+    let out = temp_dir("append").join("out.jsonl");
+    let out = out.to_str().unwrap();
+    convert(&["--append", "-o", out], "[1]");
+    convert(&["--append", "-o", out], "[2,3]");
+    assert_eq!(fs::read_to_string(out).unwrap(), "1\n2\n3\n");
+    // a record cut short by a crash is terminated first
+    fs::write(out, "1\n{\"a\":").unwrap();
+    let output = run(&["--append", "-o", out], "[4]");
+    assert!(output.status.success());
+    assert!(stderr(&output).contains("does not end with a complete record"));
+    assert_eq!(fs::read_to_string(out).unwrap(), "1\n{\"a\":\n4\n");
+}
+
+#[test]
+fn force() {
+    let out = temp_dir("force").join("out.jsonl");
+    fs::write(&out, "old\n").unwrap();
+    let out = out.to_str().unwrap();
+    let output = run(&["-o", out], "[1]");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("already exists (use --force to overwrite it)"));
+    assert_eq!(fs::read_to_string(out).unwrap(), "old\n");
+    for force in ["-f", "--force"] {
+        convert(&[force, "-o", out], "[1]");
+        assert_eq!(fs::read_to_string(out).unwrap(), "1\n");
+    }
+}
+
+#[test]
+fn tee() {
+    let dir = temp_dir("tee");
+    let (out, copy) = (dir.join("out.jsonl"), dir.join("copy.jsonl"));
+    let [out, copy] = [&out, &copy].map(|path| path.to_str().unwrap());
+    let stdout = convert(&["-o", out, "--tee", copy, "--tee", "-"], "[1,{\"a\":2}]");
+    let records = "1\n{\"a\":2}\n";
+    assert_eq!(stdout, records);
+    assert_eq!(fs::read_to_string(out).unwrap(), records);
+    assert_eq!(fs::read_to_string(copy).unwrap(), records);
+}
+
+#[test]
+fn manifest() {
+    use sha2::{Digest, Sha256};
+
+    let dir = temp_dir("manifest");
+    let (out, copy, manifest) = (
+        dir.join("out.jsonl"),
+        dir.join("copy.jsonl"),
+        dir.join("m.json"),
+    );
+    let [out, copy, manifest] = [&out, &copy, &manifest].map(|path| path.to_str().unwrap());
+    convert(
+        &["-o", out, "--tee", copy, "--manifest", manifest],
+        "[1,{\"a\":2},3]",
+    );
+    let manifest: serde_json::Value = serde_json::from_slice(&fs::read(manifest).unwrap()).unwrap();
+    let files = manifest["files"].as_array().unwrap();
+    assert_eq!(files.len(), 2);
+    for (file, path) in files.iter().zip([out, copy]) {
+        let contents = fs::read(path).unwrap();
+        assert_eq!(file["path"], path);
+        assert_eq!(file["records"], 3);
+        assert_eq!(file["bytes"], contents.len());
+        assert_eq!(file["sha256"], format!("{:x}", Sha256::digest(&contents)));
+    }
+}
+
+#[test]
+fn shards() {
+    let dir = temp_dir("shards");
+    let pattern = dir.join("x-{n}.jsonl");
+    let pattern = pattern.to_str().unwrap();
+    let input = r#"[{"a":1},{"a":2},{"a":1},{"b":3}]"#;
+    let args = ["--shard-by", "a", "--shards", "2", "-o", pattern];
+    let output = run(&[&args[..], &["--dry-run"]].concat(), input);
+    assert_eq!(
+        stderr(&output),
+        format!("dry run, nothing written: 4 records, about 32 B to the 2 shards {pattern}\n")
+    );
+    assert!(fs::read_dir(&dir).unwrap().next().is_none());
+    convert(&args, input);
+    let shards = ["x-0.jsonl", "x-1.jsonl"].map(|name| fs::read_to_string(dir.join(name)).unwrap());
+    // the records without the field go to the first shard
+    let mut records: Vec<_> = shards.iter().flat_map(|shard| shard.lines()).collect();
+    records.sort_unstable();
+    assert_eq!(
+        records,
+        [r#"{"a":1}"#, r#"{"a":1}"#, r#"{"a":2}"#, r#"{"b":3}"#]
+    );
+    assert!(shards[0].contains("{\"b\":3}\n"));
+    assert!(
+        shards
+            .iter()
+            .any(|shard| shard.matches("{\"a\":1}\n").count() == 2)
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn checksum() {
+    use sha2::{Digest, Sha256};
+
+    let dir = temp_dir("checksum");
+    let pattern = dir.join("x-{n}.jsonl");
+    let input = "[{\"a\":1},{\"a\":2},{\"a\":3}]";
+    let args = [
+        "--shard-by",
+        "a",
+        "--shards",
+        "2",
+        "--checksum",
+        "sha256",
+        "-o",
+    ];
+    convert(&[&args[..], &[pattern.to_str().unwrap()]].concat(), input);
+    for name in ["x-0.jsonl", "x-1.jsonl"] {
+        let contents = fs::read(dir.join(name)).unwrap();
+        let sidecar = fs::read_to_string(dir.join(format!("{name}.sha256"))).unwrap();
+        assert_eq!(
+            sidecar,
+            format!("{:x}  {name}\n", Sha256::digest(&contents))
+        );
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn oversize() {
+    let dir = temp_dir("oversize");
+    let big = dir.join("big.jsonl");
+    let input = "[{\"a\":1},{\"a\":\"xxxxxxxxxxxxxxxx\"},2]";
+    let output = run(
+        &[
+            "--oversize-bytes",
+            "10",
+            "--oversize-out",
+            big.to_str().unwrap(),
+        ],
+        input,
+    );
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"{\"a\":1}\n2\n");
+    assert!(stderr(&output).contains("1 records over --oversize-bytes written to"));
+    assert_eq!(
+        fs::read_to_string(&big).unwrap(),
+        "{\"a\":\"xxxxxxxxxxxxxxxx\"}\n"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn dead_letter() {
+    let dir = temp_dir("dead_letter");
+    let path = dir.join("failed.jsonl");
+    let input = format!(
+        "[{{\"a\":1}}, {{\"b\":}} ,2, {{\"c\":\"{}\"}}]",
+        "x".repeat(40)
+    );
+    let args = [
+        "--require-objects",
+        "--max-record-bytes",
+        "20",
+        "--dead-letter",
+    ];
+    let output = run(&[&args[..], &[path.to_str().unwrap()]].concat(), &input);
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(output.stdout, b"{\"a\":1}\n");
+    assert!(stderr(&output).contains("3 records that failed written to"));
+    let failed: Vec<serde_json::Value> = (fs::read_to_string(&path).unwrap().lines())
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(failed[0]["index"], 1);
+    assert_eq!(failed[0]["text"], "{\"b\":}");
+    assert_eq!(
+        failed[1],
+        serde_json::json!({
+            "index": 2,
+            "error": "invalid record 2: expected an object, got a number",
+            "record": 2,
+        })
+    );
+    assert_eq!(failed[2]["text"], "{\"c\":\"xxxxxxxxxxxxxx");
+    assert_eq!(failed[2]["truncated"], true);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "script")]
+#[test]
+fn rejected_out() {
+    let dir = temp_dir("rejected_out");
+    let (script, rejected) = (dir.join("keep.rhai"), dir.join("rejected.jsonl"));
+    fs::write(&script, "if record.keep == true { record } else { () }").unwrap();
+    let [script, rejected] = [&script, &rejected].map(|path| path.to_str().unwrap());
+    let input = r#"[{"keep":true,"a":1},{"a":2},{"keep":false}]"#;
+    let output = run(&["--script", script, "--rejected-out", rejected], input);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(output.stdout, b"{\"a\":1,\"keep\":true}\n");
+    assert!(stderr(&output).contains("2 records dropped by the transform written to"));
+    assert_eq!(
+        fs::read_to_string(rejected).unwrap(),
+        "{\"a\":2}\n{\"keep\":false}\n"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn dry_run() {
+    let dir = temp_dir("dry_run");
+    let (out, copy) = (dir.join("out.jsonl"), dir.join("copy.jsonl"));
+    let [out, copy] = [&out, &copy].map(|path| path.to_str().unwrap());
+    let input = "[1,{\"a\":2},3]";
+    let output = run(&["--dry-run"], input);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        stderr(&output),
+        "dry run, nothing written: 3 records, about 12 B to stdout\n"
+    );
+    let output = run(&["--dry-run", "-o", out, "--tee", copy], input);
+    assert!(output.status.success());
+    assert_eq!(
+        stderr(&output),
+        format!("dry run, nothing written: 3 records, about 12 B to each of {out}, {copy}\n")
+    );
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+}
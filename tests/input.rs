@@ -0,0 +1,164 @@
+//! The formats and encodings of the input, and where it comes from.
+
+mod common;
+
+use common::{convert, run, run_bytes, stderr};
+
+#[test]
+fn json5() {
+    assert_eq!(
+        convert(
+            &["--from", "json5"],
+            "[{a: 1, 'b': 'x', c: [1, 2,], d: +1, e: .5, /* c */ f: 0x10,}, 'str',]"
+        ),
+        "{\"a\":1,\"b\":\"x\",\"c\":[1,2],\"d\":1,\"e\":0.5,\"f\":16}\n\"str\"\n"
+    );
+}
+
+#[test]
+fn nonfinite() {
+    let input = r#"[{"a":NaN,"b":-Infinity}]"#;
+    let output = run(&[], input);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(
+        stderr(&output).contains("invalid record 0: contains NaN or Infinity (see --nonfinite)")
+    );
+    assert_eq!(
+        convert(&["--nonfinite", "string"], input),
+        "{\"a\":\"NaN\",\"b\":\"-Infinity\"}\n"
+    );
+}
+
+#[test]
+fn duplicate_keys() {
+    let input = r#"[{"b":0},{"a":1,"a":2}]"#;
+    let output = run(&["--duplicate-keys", "warn"], input);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"{\"b\":0}\n{\"a\":2}\n");
+    assert!(stderr(&output).contains("duplicate key \"a\" record=1"));
+    assert_eq!(
+        convert(&["--duplicate-keys", "first"], input),
+        "{\"b\":0}\n{\"a\":1}\n"
+    );
+    let output = run(&["--duplicate-keys", "error"], input);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("invalid record 1: duplicate key \"a\""));
+}
+
+#[test]
+fn require_objects() {
+    let output = run(&["--require-objects"], r#"[{"a":1},2,{"b":1}]"#);
+    assert_eq!(output.status.code(), Some(6));
+    assert_eq!(output.stdout, b"{\"a\":1}\n");
+    assert!(stderr(&output).contains("invalid record 1: expected an object, got a number"));
+    let output = run(
+        &["--require-objects", "--skip-invalid"],
+        r#"[{"a":1},[2],{"b":1}]"#,
+    );
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(output.stdout, b"{\"a\":1}\n{\"b\":1}\n");
+}
+
+#[test]
+fn bom() {
+    let output = run_bytes(&[], b"\xef\xbb\xbf[{\"a\":1}]");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(output.stdout, b"{\"a\":1}\n");
+    assert_eq!(convert(&["--bom"], "[{\"a\":1}]"), "\u{feff}{\"a\":1}\n");
+}
+
+#[test]
+fn utf16() {
+    let text = "[{\"a\":\"é😀\"}]";
+    let le: Vec<u8> = (text.encode_utf16()).flat_map(u16::to_le_bytes).collect();
+    let be: Vec<u8> = (text.encode_utf16()).flat_map(u16::to_be_bytes).collect();
+    let expected = "{\"a\":\"é😀\"}\n".as_bytes();
+    // detected with the BOM
+    let output = run_bytes(&[], &[&[0xff, 0xfe], &le[..]].concat());
+    assert_eq!(output.stdout, expected, "{}", stderr(&output));
+    let output = run_bytes(&["--encoding", "utf16be"], &be);
+    assert_eq!(output.stdout, expected, "{}", stderr(&output));
+}
+
+#[test]
+fn invalid_utf8() {
+    let input = b"[{\"a\":1},{\"a\":\"x\xffy\"}]";
+    let output = run_bytes(&[], input);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("invalid record 1: invalid UTF-8 at byte 7"));
+    for (policy, expected) in [("lossy", "x\u{fffd}y"), ("escape", "x\\\\xFFy")] {
+        let output = run_bytes(&["--utf8", policy], input);
+        assert!(output.status.success(), "{}", stderr(&output));
+        let expected = format!("{{\"a\":1}}\n{{\"a\":\"{expected}\"}}\n");
+        assert!(stderr(&output).contains("invalid UTF-8 at byte 7 record=1"));
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    }
+}
+
+#[test]
+fn control_chars() {
+    let input = "[{\"a\":\"x\ty\u{1}\"}]";
+    let output = run(&[], input);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("possible raw control character in a string"));
+    assert_eq!(
+        convert(&["--control-chars", "escape"], input),
+        "{\"a\":\"x\\ty\\u0001\"}\n"
+    );
+    assert_eq!(
+        convert(&["--control-chars", "strip"], input),
+        "{\"a\":\"xy\"}\n"
+    );
+    let output = run(&["--control-chars", "error"], input);
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn strict() {
+    assert_eq!(convert(&["--strict"], "[1,\r\n\t2 ]"), "1\n2\n");
+    let errors = [
+        (
+            "[1,\x0c2]",
+            "malformed json at offset 3: '\\u{c}' is not JSON whitespace",
+        ),
+        ("[1,,2]", "malformed json at offset 3: empty element"),
+        (
+            "[1,\n {\"a\": tru}]",
+            "invalid record 1: expected ident at offset 14",
+        ),
+        (
+            "[1,\r\n +2]",
+            "invalid record 1: expected value at offset 6",
+        ),
+    ];
+    for (input, msg) in errors {
+        let output = run(&["--strict"], input);
+        assert_eq!(output.status.code(), Some(3), "{input:?}");
+        assert!(stderr(&output).contains(msg), "{}", stderr(&output));
+    }
+    // form feeds are whitespace otherwise
+    assert_eq!(convert(&[], "[1,\x0c2]"), "1\n2\n");
+    let output = run_bytes(&["--strict"], b"\xff\xfe[\x001\x00]\x00");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn detect() {
+    let report = convert(&["--detect"], "[{\"a\":1},]");
+    assert!(report.contains("shape: a JSON array\n"), "{report}");
+    assert!(
+        report.ends_with("flags: --allow-trailing-commas\n"),
+        "{report}"
+    );
+    let output = run_bytes(&["--detect"], b"\xff\xfe[\x00]\x00");
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        report.contains("encoding: utf16le, with a byte order mark\n"),
+        "{report}"
+    );
+    let report = convert(&["--detect"], "{\"a\":1}\n{\"a\":2}\n");
+    assert!(
+        report.contains("JSONL, one value per line: nothing to convert"),
+        "{report}"
+    );
+}
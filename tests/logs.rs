@@ -0,0 +1,64 @@
+//! What is logged to stderr, and how.
+
+mod common;
+
+use common::{run, stderr, temp_dir};
+
+/// The messages logged converting `input` with `args`, without their
+/// timestamps.
+fn logged(args: &[&str], input: &str) -> Vec<String> {
+    let output = run(args, input);
+    (stderr(&output).lines())
+        .map(|line| {
+            line.split_once(' ')
+                .map_or(line, |(_, rest)| rest)
+                .trim_start()
+        })
+        .map(String::from)
+        .collect()
+}
+
+#[test]
+fn verbosity() {
+    let input = "[1,{\"a\":}]";
+    assert_eq!(
+        logged(&["--skip-invalid"], input),
+        [
+            "WARN skipping invalid record 1: expected value at line 1 column 6",
+            "WARN 1 invalid records skipped",
+        ]
+    );
+    assert!(logged(&["--skip-invalid", "-q"], input).is_empty());
+    let verbose = logged(&["--skip-invalid", "-v"], input);
+    assert_eq!(verbose[0], "INFO opened input input=\"-\"");
+    assert!(
+        verbose
+            .iter()
+            .any(|line| line.starts_with("INFO converted records=1 "))
+    );
+    assert!(verbose.contains(&"INFO closed output".to_string()));
+    // debug messages only with -vv
+    let dead_letter = temp_dir("verbosity").join("dead.jsonl");
+    let dead_letter = ["--force", "--dead-letter", dead_letter.to_str().unwrap()];
+    let debug = |v| logged(&[&dead_letter[..], &[v]].concat(), input);
+    let dead_lettered =
+        "DEBUG dead-lettered invalid record 1: expected value at line 1 column 6 record=1";
+    assert!(!debug("-v").iter().any(|line| line.starts_with("DEBUG")));
+    assert!(debug("-vv").contains(&dead_lettered.to_string()));
+}
+
+#[test]
+fn json_logs() {
+    let output = run(&["--log-format", "json", "-v"], "[1,2]");
+    assert!(output.status.success());
+    let lines: Vec<serde_json::Value> = (stderr(&output).lines())
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let converted = lines
+        .iter()
+        .find(|line| line["fields"]["message"] == "converted")
+        .unwrap();
+    assert_eq!(converted["level"], "INFO");
+    assert_eq!(converted["fields"]["records"], 2);
+    assert!(converted["timestamp"].as_str().unwrap().ends_with('Z'));
+}
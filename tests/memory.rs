@@ -0,0 +1,76 @@
+//! Tables larger than --max-memory, spilled to disk.
+
+mod common;
+
+use common::{convert, run_env, stderr, temp_dir};
+
+/// An array of more records than the tables fit in the quarter of the
+/// smallest --max-memory, with long values of id.
+fn input(duplicates: &[u64]) -> String {
+    let ids = (0..60_000).chain(duplicates.iter().copied());
+    let records: Vec<_> = ids
+        .map(|i| format!("{{\"id\":\"{i:0>40}\",\"n\":{}}}", (i * 7919) % 1000))
+        .collect();
+    format!("[{}]", records.join(",\n"))
+}
+
+/// Run with a cap of 4MB, checking that the tables spilled and that the
+/// temporary files are gone.
+fn run_capped(name: &str, args: &[&str], input: &str) -> std::process::Output {
+    let dir = temp_dir(name);
+    let args = [args, &["--max-memory", "4MB", "-vv"]].concat();
+    let output = run_env(&args, input.as_bytes(), &[("TMPDIR", dir.as_os_str())]);
+    assert!(stderr(&output).contains("spilled"), "{}", stderr(&output));
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    output
+}
+
+#[test]
+fn assert_unique() {
+    let output = run_capped(
+        "assert-unique",
+        &["--assert-unique", "id"],
+        &input(&[7, 59_999, 7]),
+    );
+    assert!(!output.status.success());
+    let report = stderr(&output);
+    assert!(
+        report.contains(&format!(
+            "2 values of id found in more than one record:\n  \"{:0>40}\": records 7, 60000, 60002\n  \
+             \"{:0>40}\": records 59999, 60001\n",
+            7, 59_999
+        )),
+        "{report}"
+    );
+    let output = run_capped("assert-unique-ok", &["--assert-unique", "id"], &input(&[]));
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn top() {
+    let args = ["--top", "20000", "--by", "n"];
+    let input = input(&[]);
+    let output = run_capped("top", &args, &input);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        convert(&args, &input)
+    );
+}
+
+#[test]
+fn sample_per_group() {
+    let input = input(&[]);
+    // a group for each record, then 100 records for each of 1000 groups
+    for args in [
+        ["--sample-per-group", "1", "--group-by", "id"],
+        ["--sample-per-group", "100", "--group-by", "n"],
+    ] {
+        let output = run_capped(&format!("sample-{}", args[3]), &args, &input);
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            convert(&args, &input)
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! Inputs and outputs over the network, against local servers.
+
+mod common;
+
+use common::{convert, http_server, run, stderr};
+use tungstenite::Message;
+
+#[test]
+fn http_input() {
+    let (addr, server) = http_server(vec![(200, br#"[{"a":1},{"a":2}]"#.to_vec())]);
+    let url = format!("http://{addr}/data.json?v=1");
+    assert_eq!(convert(&[&url], ""), "{\"a\":1}\n{\"a\":2}\n");
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].method, "GET");
+    assert_eq!(requests[0].path, "/data.json?v=1");
+}
+
+#[test]
+fn retries() {
+    let (addr, server) = http_server(vec![(503, vec![]), (200, b"[1,2]".to_vec())]);
+    let url = format!("http://{addr}/data.json");
+    assert_eq!(
+        convert(&["--retries", "1", "--retry-delay", "10ms", &url], ""),
+        "1\n2\n"
+    );
+    assert_eq!(server.join().unwrap().len(), 2);
+    let (addr, server) = http_server(vec![(503, vec![]), (503, vec![])]);
+    let output = run(
+        &[
+            "--retries",
+            "1",
+            "--retry-delay",
+            "10ms",
+            &format!("http://{addr}/"),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(4));
+    assert!(
+        stderr(&output).contains("failed after 2 attempts"),
+        "{}",
+        stderr(&output)
+    );
+    server.join().unwrap();
+}
+
+#[cfg(feature = "s3")]
+#[test]
+fn s3_input() {
+    use common::{run_env, stderr};
+
+    let (addr, server) = http_server(vec![(200, br#"[{"a":1}]"#.to_vec())]);
+    let endpoint = format!("http://{addr}");
+    let vars = [
+        ("AWS_ENDPOINT_URL", endpoint.as_ref()),
+        ("AWS_ACCESS_KEY_ID", "AKID".as_ref()),
+        ("AWS_SECRET_ACCESS_KEY", "secret".as_ref()),
+        ("AWS_REGION", "eu-west-1".as_ref()),
+    ];
+    let output = run_env(&["s3://bucket/dir/data.json"], b"", &vars);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(output.stdout, b"{\"a\":1}\n");
+    let requests = server.join().unwrap();
+    let (path, query) = requests[0].path.split_once('?').unwrap();
+    assert_eq!(path, "/bucket/dir/data.json");
+    assert!(query.contains("X-Amz-Credential=AKID%2F"), "{query}");
+    assert!(
+        query.contains("%2Feu-west-1%2Fs3%2Faws4_request"),
+        "{query}"
+    );
+    assert!(query.contains("&X-Amz-Signature="), "{query}");
+}
+
+#[cfg(feature = "s3")]
+#[test]
+fn s3_output() {
+    use common::{http_server_with_headers, run_env, stderr};
+
+    let created =
+        b"<InitiateMultipartUploadResult><UploadId>u1</UploadId></InitiateMultipartUploadResult>";
+    let (addr, server) = http_server_with_headers(vec![
+        (200, "", created.to_vec()),
+        (200, "etag: \"e1\"\r\n", vec![]),
+        (200, "", b"<CompleteMultipartUploadResult/>".to_vec()),
+    ]);
+    let endpoint = format!("http://{addr}");
+    let vars = [
+        ("AWS_ENDPOINT_URL", endpoint.as_ref()),
+        ("AWS_REGION", "eu-west-1".as_ref()),
+    ];
+    let output = run_env(&["-o", "s3://bucket/out.jsonl"], b"[{\"a\":1},2]", &vars);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let requests = server.join().unwrap();
+    let [create, part, complete] = &requests[..] else {
+        panic!("{requests:?}");
+    };
+    assert_eq!(
+        (&*create.method, &*create.path),
+        ("POST", "/bucket/out.jsonl?uploads=")
+    );
+    assert_eq!(part.method, "PUT");
+    assert!(
+        part.path.contains("partNumber=1&uploadId=u1"),
+        "{}",
+        part.path
+    );
+    assert_eq!(part.body, b"{\"a\":1}\n2\n");
+    assert_eq!(complete.method, "POST");
+    assert_eq!(
+        String::from_utf8_lossy(&complete.body),
+        "<CompleteMultipartUpload><Part><PartNumber>1</PartNumber><ETag>\"e1\"</ETag></Part></CompleteMultipartUpload>"
+    );
+
+    // a failed part aborts the upload
+    let (addr, server) = http_server_with_headers(vec![
+        (200, "", created.to_vec()),
+        (403, "", vec![]),
+        (204, "", vec![]),
+    ]);
+    let endpoint = format!("http://{addr}");
+    let vars = [
+        ("AWS_ENDPOINT_URL", endpoint.as_ref()),
+        ("AWS_REGION", "eu-west-1".as_ref()),
+    ];
+    let output = run_env(&["-o", "s3://bucket/out.jsonl"], b"[1]", &vars);
+    assert!(!output.status.success());
+    let requests = server.join().unwrap();
+    assert_eq!(requests[2].method, "DELETE");
+    assert!(
+        requests[2].path.ends_with("?uploadId=u1"),
+        "{}",
+        requests[2].path
+    );
+}
+
+#[cfg(feature = "gcs")]
+#[test]
+fn gcs_input() {
+    use common::{run_env, stderr};
+
+    let (addr, server) = http_server(vec![(200, br#"[{"a":1}]"#.to_vec())]);
+    let vars = [
+        ("STORAGE_EMULATOR_HOST", addr.as_ref()),
+        ("GOOGLE_OAUTH_ACCESS_TOKEN", "token".as_ref()),
+    ];
+    let output = run_env(&["gs://bucket/dir/data.json"], b"", &vars);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(output.stdout, b"{\"a\":1}\n");
+    let requests = server.join().unwrap();
+    assert_eq!(
+        requests[0].path,
+        "/storage/v1/b/bucket/o/dir%2Fdata.json?alt=media"
+    );
+    assert_eq!(requests[0].header("authorization"), Some("Bearer token"));
+}
+
+#[cfg(feature = "azure")]
+#[test]
+fn azure() {
+    use common::{run_env, stderr};
+
+    let (addr, server) = http_server(vec![
+        (200, br#"[{"a":1}]"#.to_vec()),
+        (201, vec![]),
+        (201, vec![]),
+    ]);
+    let endpoint = format!("http://{addr}/account");
+    let vars = [
+        ("AZURE_STORAGE_BLOB_ENDPOINT", endpoint.as_ref()),
+        ("AZURE_STORAGE_SAS_TOKEN", "?sv=1&sig=x".as_ref()),
+    ];
+    let args = ["az://container/in.json", "-o", "az://container/out.jsonl"];
+    let output = run_env(&args, b"", &vars);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].path, "/account/container/in.json?sv=1&sig=x");
+    assert_eq!(requests[0].header("x-ms-version"), Some("2021-08-06"));
+    let (block, list) = (&requests[1], &requests[2]);
+    assert_eq!(block.method, "PUT");
+    assert_eq!(
+        block.path,
+        "/account/container/out.jsonl?sv=1&sig=x&comp=block&blockid=00000000"
+    );
+    assert_eq!(block.body, b"{\"a\":1}\n");
+    assert!(list.path.ends_with("&comp=blocklist"));
+    assert!(String::from_utf8_lossy(&list.body).contains("<Latest>00000000</Latest>"));
+}
+
+#[test]
+fn post() {
+    let input = r#"[{"a":1},{"a":2},{"a":3}]"#;
+    let (addr, server) = http_server(vec![(200, vec![]); 2]);
+    let url = format!("http://{addr}/ingest");
+    assert_eq!(convert(&["--post-url", &url, "--batch", "2"], input), "");
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, "/ingest");
+    assert_eq!(
+        requests[0].header("content-type"),
+        Some("application/x-ndjson")
+    );
+    assert_eq!(requests[0].body, b"{\"a\":1}\n{\"a\":2}\n");
+    assert_eq!(requests[1].body, b"{\"a\":3}\n");
+
+    let (addr, server) = http_server(vec![(200, vec![])]);
+    let url = format!("http://{addr}/ingest");
+    convert(&["--post-url", &url, "--post-body", "array"], input);
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].header("content-type"), Some("application/json"));
+    assert_eq!(requests[0].body, br#"[{"a":1},{"a":2},{"a":3}]"#);
+}
+
+#[test]
+fn clickhouse() {
+    let (addr, server) = http_server(vec![(200, vec![]); 2]);
+    let url = format!("http://{addr}/?user=u");
+    let args = [
+        "--to",
+        "clickhouse",
+        "--clickhouse-url",
+        &url,
+        "--table",
+        "db.t",
+        "--batch",
+        "2",
+    ];
+    assert_eq!(convert(&args, r#"[{"a":1},{"a":2},{"a":3}]"#), "");
+    let requests = server.join().unwrap();
+    assert_eq!(
+        requests[0].path,
+        "/?user=u&query=INSERT%20INTO%20db.t%20FORMAT%20JSONEachRow"
+    );
+    assert_eq!(requests[0].body, b"{\"a\":1}\n{\"a\":2}\n");
+    assert_eq!(requests[1].body, b"{\"a\":3}\n");
+}
+
+#[test]
+fn websocket() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("ws://{}/feed", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut socket = tungstenite::accept(stream).unwrap();
+        for message in [
+            Message::text("{\"a\":1}"),
+            Message::text(" [2, 3] "),
+            Message::binary(&b"[]"[..]),
+            Message::text("\"x\""),
+        ] {
+            socket.send(message).unwrap();
+        }
+        socket.close(None).unwrap();
+        // until the client sees the close
+        while socket.read().is_ok() {}
+    });
+    assert_eq!(convert(&[&url], ""), "{\"a\":1}\n2\n3\n\"x\"\n");
+    server.join().unwrap();
+}
+
+#[test]
+fn sse() {
+    let events = "retry: 10\n\nid: 1\nevent: x\ndata: {\"a\":1}\n\n: comment\nid: 2\ndata: [1,\ndata: 2]\n\n";
+    let (addr, server) = http_server(vec![
+        (200, events.as_bytes().to_vec()),
+        (200, b"data: \"b\"\n\n".to_vec()),
+        (204, vec![]),
+    ]);
+    let url = format!("http://{addr}/events");
+    assert_eq!(
+        convert(&["--from", "sse", &url], ""),
+        "{\"a\":1}\n[1,2]\n\"b\"\n"
+    );
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].header("accept"), Some("text/event-stream"));
+    assert_eq!(requests[0].header("last-event-id"), None);
+    // reconnected after the last event seen
+    assert_eq!(requests[1].header("last-event-id"), Some("2"));
+    assert_eq!(requests[2].header("last-event-id"), Some("2"));
+}
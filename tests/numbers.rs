@@ -0,0 +1,108 @@
+//! Numbers past the precision of f64 and i64 go through the conversions
+//! digit for digit.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Integers and decimals that f64 and i64 can't hold, the exponents of
+/// those out of the range of f64, the negative zeros and numbers written
+/// in more digits than they need.
+const NUMBERS: [&str; 11] = [
+    "123456789012345678901234567890",
+    "-9223372036854775809",
+    "18446744073709551616",
+    "0.10000000000000000000000000001",
+    "1.5E+400",
+    "1e-400",
+    "-0.0",
+    "-0",
+    "1.10",
+    "1e3",
+    "0.0e+00",
+];
+
+/// The output of json2jsonl run with `args` on `input`.
+fn convert(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_json2jsonl"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "json2jsonl {args:?} failed");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// A record with each of [`NUMBERS`] in a field of its own, nested in an
+/// array too, and written with `space` around them.
+fn record(space: &str) -> String {
+    record_of(space, &NUMBERS)
+}
+
+fn record_of(space: &str, numbers: &[&str]) -> String {
+    let fields: Vec<_> = (numbers.iter().enumerate())
+        .map(|(i, n)| format!("\"n{i}\":{space}{n}{space}"))
+        .collect();
+    format!("{{{},\"a\":[{}]}}", fields.join(","), numbers.join(","))
+}
+
+#[test]
+fn compacted() {
+    let input = format!("[\n  {} ,\n  {}\n]\n", record(" "), record("\n\t"));
+    let expected = record("");
+    assert_eq!(convert(&[], &input), format!("{expected}\n{expected}\n"));
+    let threads = convert(&["--threads", "2", "--unordered"], &input);
+    assert_eq!(threads, format!("{expected}\n{expected}\n"));
+}
+
+#[test]
+fn reencoded() {
+    // these parse the records and serialize them again
+    let input = format!("[{}]", record(" "));
+    let expected = record("");
+    let defaulted = format!(
+        "{},\"x\":{{\"y\":1}}}}",
+        expected.strip_suffix('}').unwrap()
+    );
+    // Ion has no negative zero integer, but a negative zero decimal
+    let ion_numbers = NUMBERS.map(|n| if n == "-0" { "-0d0" } else { n });
+    let ion = record_of("", &ion_numbers);
+    for (args, expected) in [
+        (&["--sanitize-keys", "warehouse"][..], &expected),
+        (
+            &["--sanitize-keys", "warehouse", "--threads", "2"],
+            &expected,
+        ),
+        (&["--trim-strings"], &expected),
+        (&["--default", "x.y=1"], &defaulted),
+        (&["--duplicate-keys", "first"], &expected),
+        (&["--preserve-number-text", "--empty-as-null"], &expected),
+        (&["--to", "ion", "--preserve-number-text"], &ion),
+    ] {
+        assert_eq!(convert(args, &input), format!("{expected}\n"), "{args:?}");
+    }
+}
+
+#[test]
+fn wrapped() {
+    let input = format!("[{}]", NUMBERS.join(","));
+    let output = convert(&["--wrap", "v"], &input);
+    let expected: Vec<_> = NUMBERS.iter().map(|n| format!("{{\"v\":{n}}}\n")).collect();
+    assert_eq!(output, expected.concat());
+}
+
+#[test]
+fn templated() {
+    let input = format!("[{}]", record(""));
+    assert_eq!(
+        convert(&["--template", "{n0} {n3} {a}"], &input),
+        format!("{} {} [{}]\n", NUMBERS[0], NUMBERS[3], NUMBERS.join(","))
+    );
+}
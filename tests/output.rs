@@ -0,0 +1,198 @@
+//! The formats and framings of the output, and where it goes.
+
+mod common;
+
+use common::{convert, run, stderr, temp_dir};
+use std::fs;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn json_seq() {
+    assert_eq!(
+        convert(&["--format", "json-seq"], "[{\"a\":1},{\"b\":\"x\\ny\"}]"),
+        "\x1e{\"a\":1}\n\x1e{\"b\":\"x\\ny\"}\n"
+    );
+}
+
+#[test]
+fn print0() {
+    for flag in ["-0", "--print0"] {
+        assert_eq!(convert(&[flag], "[{\"a\":1},2]\n"), "{\"a\":1}\x002\x00");
+    }
+}
+
+#[test]
+fn rate_limit() {
+    let input = format!("[{}0]", "0,".repeat(24));
+    for args in [["--rate-limit", "20/s"], ["--bandwidth", "20B/s"]] {
+        let start = Instant::now();
+        assert_eq!(convert(&args, &input), "0\n".repeat(25));
+        // a second's worth right away, then the other 5 at 20 a second (of one
+        // byte each, the newline aside)
+        let elapsed = start.elapsed();
+        assert!(
+            elapsed >= Duration::from_millis(200),
+            "{args:?}: {elapsed:?}"
+        );
+    }
+}
+
+#[test]
+fn timeout() {
+    let input = format!("[{}0]", "0,".repeat(29));
+    let start = Instant::now();
+    let output = run(&["--rate-limit", "10/s", "--timeout", "500ms"], &input);
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert_eq!(output.status.code(), Some(124));
+    let report = stderr(&output);
+    let written = String::from_utf8(output.stdout).unwrap().lines().count();
+    assert!((10..30).contains(&written), "{written}");
+    let reached = format!("timed out: {written} records written, input offset ");
+    assert!(report.starts_with(&reached), "{report}");
+}
+
+/// What json2jsonl has written with `--flush policy` once it has read
+/// `input`, the rest of the input still to come.
+fn flushed(policy: &str, input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_json2jsonl"))
+        .args(["--flush", policy])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .as_mut()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let written = Arc::new(Mutex::new(vec![]));
+    let reader = {
+        let written = written.clone();
+        thread::spawn(move || {
+            let mut buf = [0; 1024];
+            while let Ok(n @ 1..) = stdout.read(&mut buf) {
+                written.lock().unwrap().extend_from_slice(&buf[..n]);
+            }
+        })
+    };
+    thread::sleep(Duration::from_millis(500));
+    let flushed = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+    child.kill().unwrap();
+    child.wait().unwrap();
+    reader.join().unwrap();
+    flushed
+}
+
+#[test]
+fn flush() {
+    let input = "[1,2,3,4,5,";
+    assert_eq!(flushed("on-exit", input), "");
+    assert_eq!(flushed("every-n=3", input), "1\n2\n3\n");
+    assert!(flushed("every-record", input).starts_with("1\n2\n3\n4\n"));
+    assert!(flushed("auto", input).starts_with("1\n2\n3\n4\n"));
+    assert_eq!(run(&["--flush", "every-n=0"], "[]").status.code(), Some(2));
+}
+
+#[test]
+fn wrap() {
+    assert_eq!(
+        convert(&["--wrap", "value"], r#"["a",{"b":1},[2],null]"#),
+        "{\"value\":\"a\"}\n{\"b\":1}\n{\"value\":[2]}\n{\"value\":null}\n"
+    );
+    let output = run(&["--wrap", "v", "--require-objects"], "[1]");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn index() {
+    let dir = temp_dir("index");
+    let (out, index) = (dir.join("out"), dir.join("out.idx"));
+    let [out, index] = [&out, &index].map(|path| path.to_str().unwrap());
+    let records = [r#"{"a":1}"#, "2", r#"["x","y"]"#];
+    let input = format!("[{}]", records.join(", "));
+    for format in ["jsonl", "json-seq", "length-prefixed"] {
+        let args = ["--format", format, "--force", "-o", out, "--index", index];
+        convert(&args, &input);
+        let (output, index) = (fs::read(out).unwrap(), fs::read(index).unwrap());
+        assert_eq!(index.len(), 16 * records.len());
+        for (entry, record) in index.chunks(16).zip(records) {
+            let offset = u64::from_le_bytes(entry[..8].try_into().unwrap()) as usize;
+            let len = u64::from_le_bytes(entry[8..].try_into().unwrap()) as usize;
+            assert_eq!(&output[offset..offset + len], record.as_bytes(), "{format}");
+        }
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn pivot() {
+    let wide = "[{\"id\":1,\"m_a\":2,\"m_b\":3},{\"id\":2,\"m_a\":4}]";
+    let long = convert(&["--unpivot", "m_*", "--unpivot-names", "k,v"], wide);
+    assert_eq!(
+        long,
+        "{\"id\":1,\"k\":\"m_a\",\"v\":2}\n{\"id\":1,\"k\":\"m_b\",\"v\":3}\n\
+         {\"id\":2,\"k\":\"m_a\",\"v\":4}\n"
+    );
+    assert_eq!(
+        convert(
+            &["--pivot", "k,v"],
+            &format!("[{}]", long.trim_end().replace('\n', ","))
+        ),
+        "{\"id\":1,\"m_a\":2,\"m_b\":3}\n{\"id\":2,\"m_a\":4}\n"
+    );
+    let output = run(&["--pivot", "k"], "[]");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("--pivot and --unpivot-names take two fields"));
+}
+
+#[test]
+fn batch_lines() {
+    assert_eq!(
+        convert(&["--batch-lines", "2"], "[1, {\"a\": 2}, 3, 4, 5]"),
+        "[1,{\"a\":2}]\n[3,4]\n[5]\n"
+    );
+    assert_eq!(
+        convert(&["--batch-lines", "2", "--format", "json-seq"], "[1,2,3]"),
+        "\x1e[1,2]\n\x1e[3]\n"
+    );
+    assert_eq!(convert(&["--batch-lines", "2"], "[]"), "");
+    assert_eq!(run(&["--batch-lines", "0"], "[1]").status.code(), Some(2));
+}
+
+#[test]
+fn preview() {
+    let dir = temp_dir("preview");
+    let input = dir.join("input.json");
+    fs::write(&input, "[{\"a\":1},{\"a\":2},{\"a\":3}]").unwrap();
+    let input = input.to_str().unwrap();
+    let output = run(&["--preview=2", input], "y\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(output.stdout, b"{\"a\":1}\n{\"a\":2}\n{\"a\":3}\n");
+    let shown = stderr(&output);
+    assert!(
+        shown.contains(
+            "{\n  \"a\": 1\n}\n{\n  \"a\": 2\n}\n2 records shown. Convert the whole input? [y/N] "
+        ),
+        "{shown}"
+    );
+    assert!(!shown.contains("\"a\": 3"), "{shown}");
+
+    let output = run(&["--preview", input], "\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, b"");
+    let shown = stderr(&output);
+    assert!(shown.contains("3 records shown."), "{shown}");
+    assert!(shown.contains("not converting, as answered after the preview"));
+    assert_eq!(
+        run(&["--preview"], "[1]").status.code(),
+        Some(2),
+        "needs an input file"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
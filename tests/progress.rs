@@ -0,0 +1,62 @@
+//! Reports of how far the conversion got.
+
+mod common;
+
+use common::temp_dir;
+use serde_json::Value;
+use std::fs;
+use std::process::{Command, Stdio};
+
+/// The events written by `--progress-json` converting `stdin`.
+fn events(stdin: impl Into<Stdio>) -> Vec<Value> {
+    let output = Command::new(env!("CARGO_BIN_EXE_json2jsonl"))
+        .arg("--progress-json")
+        .stdin(stdin)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    (output.stderr.split(|&b| b == b'\n'))
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect()
+}
+
+// the size of stdin is only known on Unix
+#[cfg(unix)]
+#[test]
+fn stdin_size() {
+    use std::fs::File;
+    use std::io::{Read, Seek, SeekFrom};
+
+    let path = temp_dir("stdin-size").join("in.json");
+    fs::write(&path, "xxxxx[1,2,3]").unwrap();
+    // what is left to read of a file
+    let mut file = File::open(&path).unwrap();
+    file.seek(SeekFrom::Start(5)).unwrap();
+    let done = events(file).pop().unwrap();
+    assert_eq!(done["total_bytes"], 7);
+    assert_eq!(done["eta_secs"], 0);
+    // unknown from a pipe
+    let mut child = Command::new("cat")
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut pipe = child.stdout.take().unwrap();
+    pipe.read_exact(&mut [0; 5]).unwrap();
+    let done = events(pipe).pop().unwrap();
+    child.wait().unwrap();
+    assert_eq!(done["total_bytes"], Value::Null);
+}
+
+#[test]
+fn progress_json_file() {
+    let path = temp_dir("progress-json").join("events.jsonl");
+    let arg = format!("--progress-json={}", path.display());
+    assert_eq!(common::convert(&[&arg], "[1,2,3]"), "1\n2\n3\n");
+    let events = fs::read_to_string(&path).unwrap();
+    let done: Value = serde_json::from_str(events.lines().last().unwrap()).unwrap();
+    assert_eq!(done["event"], "done");
+    assert_eq!(done["records"], 3);
+    assert_eq!(done["bytes"], 6);
+}
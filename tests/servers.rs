@@ -0,0 +1,100 @@
+//! The modes serving conversions to other processes.
+
+mod common;
+
+use common::{Server, connect, free_addr, temp_dir};
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+#[test]
+fn listen() {
+    let addr = free_addr();
+    let _server = Server::start(&["--listen", &addr]);
+    for (input, expected) in [("[{\"a\":1},2]", "{\"a\":1}\n2\n"), ("[3]", "3\n")] {
+        let mut stream = connect(|| TcpStream::connect(&addr));
+        stream.write_all(input.as_bytes()).unwrap();
+        stream.shutdown(Shutdown::Write).unwrap();
+        let mut output = String::new();
+        stream.read_to_string(&mut output).unwrap();
+        assert_eq!(output, expected);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn listen_unix() {
+    let path = temp_dir("listen").join("j2j.sock");
+    let _server = Server::start(&["--listen", &format!("unix:{}", path.display())]);
+    let mut stream = connect(|| UnixStream::connect(&path));
+    stream.write_all(b"[{\"b\":true}]").unwrap();
+    stream.shutdown(Shutdown::Write).unwrap();
+    let mut output = String::new();
+    stream.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "{\"b\":true}\n");
+}
+
+#[test]
+fn serve() {
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    let addr = free_addr();
+    let _server = Server::start(&["--serve", &addr]);
+    let url = format!("http://{addr}/");
+    connect(|| TcpStream::connect(&addr));
+    let mut gz = GzEncoder::new(vec![], Compression::default());
+    gz.write_all(b"[3, [4]]").unwrap();
+    let bodies = [
+        (b"[{\"a\":1},2]".to_vec(), "{\"a\":1}\n2\n"),
+        (gz.finish().unwrap(), "3\n[4]\n"),
+    ];
+    for (body, expected) in bodies {
+        let mut response = ureq::post(&url).send(&body[..]).unwrap();
+        let content_type = response.headers()["content-type"].to_str().unwrap();
+        assert_eq!(content_type, "application/x-ndjson");
+        assert_eq!(response.body_mut().read_to_string().unwrap(), expected);
+    }
+    let agent = ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .build();
+    let mut response = ureq::Agent::new_with_config(agent)
+        .post(&url)
+        .send("[1,{]")
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let error = response.body_mut().read_to_string().unwrap();
+    assert!(error.contains("element starting at offset 3"), "{error}");
+}
+
+#[test]
+fn metrics() {
+    let (addr, metrics) = (free_addr(), free_addr());
+    let _server = Server::start(&["--serve", &addr, "--metrics", &metrics]);
+    connect(|| TcpStream::connect(&addr));
+    let agent = ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .build();
+    let agent = ureq::Agent::new_with_config(agent);
+    for body in ["[{\"a\":1},2]", "[1,{]"] {
+        let mut response = agent.post(format!("http://{addr}/")).send(body).unwrap();
+        response.body_mut().read_to_string().unwrap();
+    }
+    let mut response = agent
+        .get(format!("http://{metrics}/metrics"))
+        .call()
+        .unwrap();
+    let text = response.body_mut().read_to_string().unwrap();
+    // the 1 before the error was sent
+    for line in [
+        "# TYPE json2jsonl_records_total counter\njson2jsonl_records_total 3\n",
+        "json2jsonl_conversions_total 2\n",
+        "json2jsonl_errors_total 1\n",
+        "json2jsonl_output_bytes_total 9\n",
+    ] {
+        assert!(text.contains(line), "{text}");
+    }
+    let response = agent.get(format!("http://{metrics}/")).call().unwrap();
+    assert_eq!(response.status(), 404);
+}
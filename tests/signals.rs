@@ -0,0 +1,72 @@
+//! Signals sent to a running conversion.
+
+#![cfg(unix)]
+
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// json2jsonl converting its standard input, once it's read `input`.
+fn start(input: &str) -> Child {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_json2jsonl"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .as_mut()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    thread::sleep(Duration::from_millis(500));
+    child
+}
+
+fn kill(child: &Child, signal: &str) {
+    let status = Command::new("kill")
+        .args([signal, &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    thread::sleep(Duration::from_millis(200));
+}
+
+#[test]
+fn interrupt() {
+    let mut child = start("[1,{\"a\":");
+    kill(&child, "-INT");
+    // stops once the element being read is complete
+    let stdin = child.stdin.as_mut().unwrap();
+    stdin.write_all(b"2},").unwrap();
+    let _ = stdin.write_all(b"3,4]");
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    // the element being converted may be the first or the second
+    let report = match String::from_utf8(output.stdout).unwrap().as_str() {
+        "1\n" => "interrupted: 1 records written, input offset 2 reached\n",
+        stdout => {
+            assert_eq!(stdout, "1\n{\"a\":2}\n");
+            "interrupted: 2 records written, input offset 10 reached\n"
+        }
+    };
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), report);
+}
+
+#[test]
+fn status() {
+    let mut child = start("[1,2,");
+    kill(&child, "-USR1");
+    child.stdin.as_mut().unwrap().write_all(b"3]").unwrap();
+    drop(child.stdin.take());
+    let output = child.wait_with_output().unwrap();
+    // goes on converting
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1\n2\n3\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("status: input offset "), "{stderr}");
+    assert!(stderr.contains(" records written, "), "{stderr}");
+    assert!(stderr.contains(" MB/s over "), "{stderr}");
+}